@@ -1,7 +1,8 @@
-use std::env;
+use std::{env, sync::Arc};
 
 use askama::Template;
 use axum::{
+    extract::State,
     http::StatusCode,
     response::{Html, IntoResponse},
     routing::get,
@@ -9,14 +10,30 @@ use axum::{
 };
 use axum_extra::extract::CookieJar;
 use serde::Serialize;
+use token_verifier::{TokenVerifier, VerifyTokenError};
 use tower_http::services::ServeDir;
 
+mod token_verifier;
+
+#[derive(Clone)]
+struct AppState {
+    token_verifier: Arc<TokenVerifier>,
+}
+
 #[tokio::main]
 async fn main() {
+    let auth_hostname = env::var("AUTH_SERVICE_URL").unwrap_or("http://0.0.0.0:3000".to_owned());
+    let check_revocation = env::var("AUTH_REVOCATION_CHECK").is_ok_and(|value| value == "true");
+    let token_verifier =
+        TokenVerifier::new(reqwest::Client::new(), auth_hostname, check_revocation);
+
     let app = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(root))
-        .route("/protected", get(protected));
+        .route("/protected", get(protected))
+        .with_state(AppState {
+            token_verifier: Arc::new(token_verifier),
+        });
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
@@ -36,7 +53,7 @@ async fn root() -> impl IntoResponse {
     if address.is_empty() {
         address = "http://localhost:3000".to_owned();
     }
-    let login_link = format!("{}", address);
+    let login_link = address.clone();
     let logout_link = format!("{}/logout", address);
 
     let template = IndexTemplate {
@@ -46,7 +63,7 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
+async fn protected(State(state): State<AppState>, jar: CookieJar) -> impl IntoResponse {
     let jwt_cookie = match jar.get("jwt") {
         Some(cookie) => cookie,
         None => {
@@ -54,31 +71,15 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
         }
     };
 
-    let api_client = reqwest::Client::builder().build().unwrap();
-
-    let verify_token_body = serde_json::json!({
-        "token": &jwt_cookie.value(),
-    });
-
-    let auth_hostname = env::var("AUTH_SERVICE_URL").unwrap_or("http://0.0.0.0:3000".to_owned());
-    let url = format!("{}/verify-token", auth_hostname);
-
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
-        Ok(response) => response,
-        Err(_) => {
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
-        }
-    };
-
-    match response.status() {
-        reqwest::StatusCode::UNAUTHORIZED | reqwest::StatusCode::BAD_REQUEST => {
-            StatusCode::UNAUTHORIZED.into_response()
-        }
-        reqwest::StatusCode::OK => Json(ProtectedRouteResponse {
+    match state.token_verifier.verify(jwt_cookie.value()).await {
+        Ok(_) => Json(ProtectedRouteResponse {
             img_url: "https://i.ibb.co/YP90j68/Light-Live-Bootcamp-Certificate.png".to_owned(),
         })
         .into_response(),
-        _ => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Err(VerifyTokenError::InvalidToken) => StatusCode::UNAUTHORIZED.into_response(),
+        Err(VerifyTokenError::AuthServiceUnavailable) => {
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
     }
 }
 
@@ -0,0 +1,192 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+/// Errors from the auth service, one for each of its `AuthAPIError`s, plus
+/// failures to reach it or to make sense of what it sent back.
+#[derive(Debug)]
+pub enum AuthError {
+    UserAlreadyExists,
+    InvalidCredentials,
+    /// A password being set breaks the password policy, with the reason.
+    InvalidPassword(String),
+    /// An account can't be created at the email's domain, with the reason.
+    EmailNotAllowed(String),
+    /// A request field that isn't valid, with the reason.
+    InvalidInput(String),
+    IncorrectCredentials,
+    MissingToken,
+    InvalidToken,
+    /// A sign-in link bound to one browser was followed in another.
+    WrongBrowser,
+    TotpAlreadyEnrolled,
+    TotpNotEnrolled,
+    TwoFANotEnabled,
+    PasskeyAlreadyRegistered,
+    PasskeyNotRegistered,
+    SessionNotFound,
+    UserNotFound,
+    ServiceAccountNotFound,
+    AccountDisabled,
+    AccountLocked,
+    PasswordResetRequired,
+    Forbidden,
+    UnexpectedError,
+    /// The request didn't reach the auth service, or its response couldn't be read.
+    Transport(reqwest::Error),
+    /// A response that none of the above describe.
+    UnexpectedResponse { status: StatusCode, body: String },
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+impl AuthError {
+    // The service tells its errors apart by status and message, so both are
+    // matched, as they are worded in its `IntoResponse` for `AuthAPIError`.
+    pub(crate) fn from_response(status: StatusCode, body: String) -> Self {
+        let Ok(ErrorResponse { error }) = serde_json::from_str(&body) else {
+            return AuthError::UnexpectedResponse { status, body };
+        };
+        match (status.as_u16(), error.as_str()) {
+            (409, "User already exists") => AuthError::UserAlreadyExists,
+            (400, "Invalid credentials") => AuthError::InvalidCredentials,
+            (401, "Incorrect credentials") => AuthError::IncorrectCredentials,
+            (400, "Missing auth token") => AuthError::MissingToken,
+            (401, "Invalid auth token") => AuthError::InvalidToken,
+            (403, "Open the link in the browser that requested it") => AuthError::WrongBrowser,
+            (409, "Authenticator app already enrolled") => AuthError::TotpAlreadyEnrolled,
+            (400, "Authenticator app not enrolled") => AuthError::TotpNotEnrolled,
+            (400, "2FA is not enabled") => AuthError::TwoFANotEnabled,
+            (409, "Passkey already registered") => AuthError::PasskeyAlreadyRegistered,
+            (400, "No passkey registered") => AuthError::PasskeyNotRegistered,
+            (404, "Session not found") => AuthError::SessionNotFound,
+            (404, "User not found") => AuthError::UserNotFound,
+            (404, "Service account not found") => AuthError::ServiceAccountNotFound,
+            (403, "Account disabled") => AuthError::AccountDisabled,
+            (423, "Account locked") => AuthError::AccountLocked,
+            (403, "Password reset required") => AuthError::PasswordResetRequired,
+            (403, "Forbidden") => AuthError::Forbidden,
+            (500, "Unexpected error") => AuthError::UnexpectedError,
+            // Password and email policy errors carry the reason they were refused.
+            (400, _) if error.starts_with("Password ") => AuthError::InvalidPassword(error),
+            (400, "Email addresses at this domain are not allowed")
+            | (400, "Disposable email addresses are not allowed, use a permanent one") => {
+                AuthError::EmailNotAllowed(error)
+            }
+            (400, _) if error.starts_with("Invalid input: ") => {
+                AuthError::InvalidInput(error["Invalid input: ".len()..].to_owned())
+            }
+            _ => AuthError::UnexpectedResponse { status, body },
+        }
+    }
+}
+
+impl From<reqwest::Error> for AuthError {
+    fn from(e: reqwest::Error) -> Self {
+        AuthError::Transport(e)
+    }
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::UserAlreadyExists => write!(f, "user already exists"),
+            AuthError::InvalidCredentials => write!(f, "invalid credentials"),
+            AuthError::InvalidPassword(reason) => write!(f, "invalid password: {}", reason),
+            AuthError::EmailNotAllowed(reason) => write!(f, "email not allowed: {}", reason),
+            AuthError::InvalidInput(reason) => write!(f, "invalid input: {}", reason),
+            AuthError::IncorrectCredentials => write!(f, "incorrect credentials"),
+            AuthError::MissingToken => write!(f, "missing auth token"),
+            AuthError::InvalidToken => write!(f, "invalid auth token"),
+            AuthError::WrongBrowser => write!(f, "link opened in a browser that didn't request it"),
+            AuthError::TotpAlreadyEnrolled => write!(f, "authenticator app already enrolled"),
+            AuthError::TotpNotEnrolled => write!(f, "authenticator app not enrolled"),
+            AuthError::TwoFANotEnabled => write!(f, "2FA is not enabled"),
+            AuthError::PasskeyAlreadyRegistered => write!(f, "passkey already registered"),
+            AuthError::PasskeyNotRegistered => write!(f, "no passkey registered"),
+            AuthError::SessionNotFound => write!(f, "session not found"),
+            AuthError::UserNotFound => write!(f, "user not found"),
+            AuthError::ServiceAccountNotFound => write!(f, "service account not found"),
+            AuthError::AccountDisabled => write!(f, "account disabled"),
+            AuthError::AccountLocked => write!(f, "account locked"),
+            AuthError::PasswordResetRequired => write!(f, "password reset required"),
+            AuthError::Forbidden => write!(f, "forbidden"),
+            AuthError::UnexpectedError => write!(f, "unexpected error in the auth service"),
+            AuthError::Transport(e) => write!(f, "couldn't reach the auth service: {}", e),
+            AuthError::UnexpectedResponse { status, body } => {
+                write!(f, "unexpected response from the auth service: {} {}", status, body)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AuthError::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(status: u16, message: &str) -> AuthError {
+        AuthError::from_response(
+            StatusCode::from_u16(status).unwrap(),
+            serde_json::json!({ "error": message }).to_string(),
+        )
+    }
+
+    #[test]
+    fn maps_fixed_messages_to_their_errors() {
+        assert!(matches!(error(409, "User already exists"), AuthError::UserAlreadyExists));
+        assert!(matches!(error(401, "Incorrect credentials"), AuthError::IncorrectCredentials));
+        assert!(matches!(error(401, "Invalid auth token"), AuthError::InvalidToken));
+        assert!(matches!(error(423, "Account locked"), AuthError::AccountLocked));
+        assert!(matches!(error(500, "Unexpected error"), AuthError::UnexpectedError));
+    }
+
+    #[test]
+    fn keeps_the_reason_for_policy_errors() {
+        let AuthError::InvalidPassword(reason) =
+            error(400, "Password must contain at least one digit")
+        else {
+            panic!("expected InvalidPassword");
+        };
+        assert_eq!(reason, "Password must contain at least one digit");
+
+        assert!(matches!(
+            error(400, "Disposable email addresses are not allowed, use a permanent one"),
+            AuthError::EmailNotAllowed(_)
+        ));
+        assert!(matches!(
+            error(400, "Email addresses at this domain are not allowed"),
+            AuthError::EmailNotAllowed(_)
+        ));
+
+        let AuthError::InvalidInput(reason) = error(400, "Invalid input: invalid role 'x'") else {
+            panic!("expected InvalidInput");
+        };
+        assert_eq!(reason, "invalid role 'x'");
+    }
+
+    #[test]
+    fn keeps_responses_it_does_not_recognise() {
+        assert!(matches!(
+            error(403, "Something new"),
+            AuthError::UnexpectedResponse { .. }
+        ));
+
+        let e = AuthError::from_response(StatusCode::BAD_GATEWAY, "<html></html>".to_owned());
+        let AuthError::UnexpectedResponse { status, body } = e else {
+            panic!("expected UnexpectedResponse");
+        };
+        assert_eq!(status, StatusCode::BAD_GATEWAY);
+        assert_eq!(body, "<html></html>");
+    }
+}
@@ -0,0 +1,175 @@
+//! Typed client for the auth service, so that other services don't have to
+//! build its request bodies or interpret its status codes themselves.
+
+mod error;
+mod types;
+
+pub use error::AuthError;
+pub use types::{
+    AuthToken, LoginOutcome, SignupResponse, TwoFactorChallenge, TwoFactorVerified, VerifiedToken,
+};
+
+use reqwest::header::COOKIE;
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use types::{
+    LoginRequest, RecoveryCodeLoginResponse, SignupRequest, Verify2FARequest, VerifyTokenRequest,
+};
+
+// Matches the cookie the auth service keeps the session's JWT in.
+const JWT_COOKIE_NAME: &str = "jwt";
+
+/// Client for the account routes of an auth service at `base_url`.
+#[derive(Debug, Clone)]
+pub struct AuthClient {
+    http_client: reqwest::Client,
+    base_url: String,
+}
+
+impl AuthClient {
+    pub fn new(http_client: reqwest::Client, base_url: impl Into<String>) -> Self {
+        let mut base_url = base_url.into();
+        if base_url.ends_with('/') {
+            base_url.pop();
+        }
+        Self {
+            http_client,
+            base_url,
+        }
+    }
+
+    pub async fn signup(
+        &self,
+        email: &str,
+        password: &str,
+        requires_2fa: bool,
+    ) -> Result<SignupResponse, AuthError> {
+        let response = self
+            .http_client
+            .post(self.url("/signup"))
+            .json(&SignupRequest {
+                email,
+                password,
+                requires_2fa,
+            })
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::CREATED => json(response).await,
+            _ => Err(error(response).await),
+        }
+    }
+
+    pub async fn login(&self, email: &str, password: &str) -> Result<LoginOutcome, AuthError> {
+        let response = self
+            .http_client
+            .post(self.url("/login"))
+            .json(&LoginRequest { email, password })
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => auth_token(&response).map(LoginOutcome::Authenticated),
+            StatusCode::PARTIAL_CONTENT => json(response).await.map(LoginOutcome::TwoFactorRequired),
+            _ => Err(error(response).await),
+        }
+    }
+
+    /// Finishes a login that needed 2FA, with a code from the user's second
+    /// factor or one of their recovery codes.
+    pub async fn verify_2fa(
+        &self,
+        email: &str,
+        login_attempt_id: &str,
+        two_fa_code: &str,
+    ) -> Result<TwoFactorVerified, AuthError> {
+        let response = self
+            .http_client
+            .post(self.url("/verify-2fa"))
+            .json(&Verify2FARequest {
+                email,
+                login_attempt_id,
+                two_fa_code,
+            })
+            .send()
+            .await?;
+        if response.status() != StatusCode::OK {
+            return Err(error(response).await);
+        }
+
+        let token = auth_token(&response)?;
+        // Only logins with a recovery code have a body.
+        let body = response.bytes().await?;
+        let remaining_recovery_codes = if body.is_empty() {
+            None
+        } else {
+            let body: RecoveryCodeLoginResponse =
+                serde_json::from_slice(&body).map_err(|_| AuthError::UnexpectedResponse {
+                    status: StatusCode::OK,
+                    body: String::from_utf8_lossy(&body).into_owned(),
+                })?;
+            Some(body.remaining_recovery_codes)
+        };
+        Ok(TwoFactorVerified {
+            token,
+            remaining_recovery_codes,
+        })
+    }
+
+    /// Checks that the token is valid and its session hasn't been revoked,
+    /// returning who it was issued to.
+    pub async fn verify_token(&self, token: &str) -> Result<VerifiedToken, AuthError> {
+        let response = self
+            .http_client
+            .post(self.url("/verify-token"))
+            .json(&VerifyTokenRequest { token })
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => json(response).await,
+            _ => Err(error(response).await),
+        }
+    }
+
+    /// Revokes the token's session.
+    pub async fn logout(&self, token: &str) -> Result<(), AuthError> {
+        let response = self
+            .http_client
+            .delete(self.url("/logout"))
+            .header(COOKIE, format!("{}={}", JWT_COOKIE_NAME, token))
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::OK => Ok(()),
+            _ => Err(error(response).await),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
+
+async fn json<T: DeserializeOwned>(response: Response) -> Result<T, AuthError> {
+    let status = response.status();
+    let body = response.text().await?;
+    serde_json::from_str(&body).map_err(|_| AuthError::UnexpectedResponse { status, body })
+}
+
+async fn error(response: Response) -> AuthError {
+    let status = response.status();
+    match response.text().await {
+        Ok(body) => AuthError::from_response(status, body),
+        Err(e) => AuthError::Transport(e),
+    }
+}
+
+fn auth_token(response: &Response) -> Result<AuthToken, AuthError> {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .map(|cookie| AuthToken::new(cookie.value()))
+        .ok_or_else(|| AuthError::UnexpectedResponse {
+            status: response.status(),
+            body: "no auth cookie was set".to_owned(),
+        })
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+/// A session's JWT, as the auth service sets it in the `jwt` cookie.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthToken(String);
+
+impl AuthToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+}
+
+impl AsRef<str> for AuthToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct SignupRequest<'a> {
+    pub email: &'a str,
+    pub password: &'a str,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SignupResponse {
+    pub message: String,
+    /// One-time recovery codes, only issued when the account is created with
+    /// 2FA enabled.
+    #[serde(rename = "recoveryCodes", default)]
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct LoginRequest<'a> {
+    pub email: &'a str,
+    pub password: &'a str,
+}
+
+/// What a correct password leads to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoginOutcome {
+    /// The user is signed in.
+    Authenticated(AuthToken),
+    /// The user has to pass their second factor with `verify_2fa` first.
+    TwoFactorRequired(TwoFactorChallenge),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TwoFactorChallenge {
+    pub message: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    /// WebAuthn assertion options for users whose second factor is a passkey,
+    /// to be passed to the browser's `navigator.credentials.get`.
+    #[serde(rename = "publicKey", default)]
+    pub public_key: Option<serde_json::Value>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Verify2FARequest<'a> {
+    pub email: &'a str,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: &'a str,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: &'a str,
+}
+
+/// A user signed in with their second factor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoFactorVerified {
+    pub token: AuthToken,
+    /// How many recovery codes are left, when one was used in place of the
+    /// user's usual second factor.
+    pub remaining_recovery_codes: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RecoveryCodeLoginResponse {
+    #[serde(rename = "remainingRecoveryCodes")]
+    pub remaining_recovery_codes: usize,
+}
+
+#[derive(Serialize)]
+pub(crate) struct VerifyTokenRequest<'a> {
+    pub token: &'a str,
+}
+
+/// Who a valid token was issued to.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct VerifiedToken {
+    /// The user's email, or the client id of a service account.
+    pub sub: String,
+    /// The OpenID Connect client or service account the token was issued to;
+    /// `None` for the tokens of browser sessions.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Space separated scopes granted to an access token.
+    #[serde(default)]
+    pub scope: Option<String>,
+}
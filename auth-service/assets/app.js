@@ -1,3 +1,15 @@
+// Set when an OpenID Connect client sent the user here to sign in, so they
+// can be returned to the authorization request afterwards.
+const nextPath = new URLSearchParams(window.location.search).get("next");
+
+function completeLogin() {
+    if (nextPath !== null && nextPath.startsWith("/authorize?")) {
+        window.location.assign(nextPath);
+    } else {
+        alert("You have successfully logged in.");
+    }
+}
+
 const loginSection = document.getElementById("login-section");
 const twoFASection = document.getElementById("2fa-section");
 const signupSection = document.getElementById("signup-section");
@@ -66,7 +78,7 @@ loginButton.addEventListener("click", (e) => {
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            completeLogin();
         } else {
             response.json().then(data => {
                 let error_msg = data.error;
@@ -81,6 +93,35 @@ loginButton.addEventListener("click", (e) => {
     });
 });
 
+const magicLinkButton = document.getElementById("magic-link-submit");
+
+magicLinkButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = loginForm.email.value;
+    const sameBrowser = loginForm.sameBrowser.checked;
+
+    fetch('/login/magic-link', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email, sameBrowser }),
+    }).then(response => {
+        response.json().then(data => {
+            if (response.ok) {
+                loginErrAlter.style.display = "none";
+                alert(data.message);
+            } else if (data.error !== undefined && data.error !== null && data.error !== "") {
+                loginErrAlter.innerHTML = `<span><strong>Error: </strong>${data.error}</span>`;
+                loginErrAlter.style.display = "block";
+            } else {
+                loginErrAlter.style.display = "none";
+            }
+        });
+    });
+});
+
 const signupForm = document.getElementById("signup-form");
 const signupButton = document.getElementById("signup-form-submit");
 const signupErrAlter = document.getElementById("signup-err-alert");
@@ -145,7 +186,7 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            completeLogin();
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
@@ -161,4 +202,46 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+
+// Sign-in links from `/login/magic-link` point back at this page.
+const pageParams = new URLSearchParams(window.location.search);
+const magicLinkToken = pageParams.get("magicLinkToken");
+
+if (magicLinkToken !== null) {
+    // Keep the token out of the history once it's been used.
+    pageParams.delete("magicLinkToken");
+    const query = pageParams.toString();
+    window.history.replaceState(null, "", window.location.pathname + (query ? `?${query}` : ""));
+
+    fetch('/login/magic-link/verify', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token: magicLinkToken }),
+    }).then(response => {
+        if (response.status === 206) {
+            TwoFAForm.email.value = pageParams.get("email");
+            response.json().then(data => {
+                TwoFAForm.login_attempt_id.value = data.loginAttemptId;
+            });
+
+            loginSection.style.display = "none";
+            twoFASection.style.display = "block";
+            signupSection.style.display = "none";
+        } else if (response.status === 200) {
+            completeLogin();
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    loginErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    loginErrAlter.style.display = "block";
+                } else {
+                    loginErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,151 @@
+//! Throughput of the user store under concurrent signups and logins, compared
+//! with the same store behind a single `RwLock` as `AppState` used to hold it.
+//!
+//! Run with `cargo bench --bench user_store`.
+
+use auth_service::app_state::UserStoreType;
+use auth_service::domain::{Email, User, UserPage, UserQuery, UserStore, UserStoreError};
+use auth_service::services::HashmapUserStore;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use tokio::sync::RwLock;
+
+const TASKS: usize = 32;
+const USERS_PER_TASK: usize = 64;
+
+/// The store the way it was shared before: every change waits for the write
+/// lock, and every read waits for any change in progress.
+#[derive(Default)]
+struct GloballyLockedUserStore(RwLock<HashmapUserStore>);
+
+#[async_trait::async_trait]
+impl UserStore for GloballyLockedUserStore {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        self.0.write().await.add_user(user).await
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        self.0.read().await.get_user(email).await
+    }
+
+    async fn update_user(&self, user: User) -> Result<(), UserStoreError> {
+        self.0.write().await.update_user(user).await
+    }
+
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.0.write().await.delete_user(email).await
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        self.0.read().await.list_users(query).await
+    }
+}
+
+type NewStore = fn() -> UserStoreType;
+
+fn stores() -> [(&'static str, NewStore); 2] {
+    [
+        ("sharded", || Arc::new(HashmapUserStore::new())),
+        ("global_lock", || Arc::new(GloballyLockedUserStore::default())),
+    ]
+}
+
+// Hashing a password takes far longer than anything the store does, so every
+// user shares one hash.
+fn users() -> Vec<Vec<User>> {
+    let template = User::new("user@example.com".to_owned(), "passworD123!".to_owned(), false);
+    (0..TASKS)
+        .map(|task| {
+            (0..USERS_PER_TASK)
+                .map(|i| User {
+                    email: Email::parse(&format!("user{}-{}@example.com", task, i)).unwrap(),
+                    ..template.clone()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+async fn run_tasks<F, Fut>(store: &UserStoreType, users: Vec<Vec<User>>, task: F)
+where
+    F: Fn(UserStoreType, Vec<User>) -> Fut,
+    Fut: std::future::Future<Output = ()> + Send + 'static,
+{
+    let handles: Vec<_> = users
+        .into_iter()
+        .map(|users| tokio::spawn(task(store.clone(), users)))
+        .collect();
+    for handle in handles {
+        handle.await.unwrap();
+    }
+}
+
+fn signups(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let users = users();
+    let mut group = c.benchmark_group("signups");
+    group.throughput(Throughput::Elements((TASKS * USERS_PER_TASK) as u64));
+
+    for (name, new_store) in stores() {
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.to_async(&runtime).iter_batched(
+                || (new_store(), users.clone()),
+                |(store, users)| async move {
+                    run_tasks(&store, users, |store, users| async move {
+                        for user in users {
+                            store.add_user(user).await.unwrap();
+                        }
+                    })
+                    .await;
+                },
+                BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+// A login reads the user and then resets or bumps their failed attempts.
+fn logins(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let users = users();
+    let mut group = c.benchmark_group("logins");
+    group.throughput(Throughput::Elements((TASKS * USERS_PER_TASK) as u64));
+
+    for (name, new_store) in stores() {
+        let store = new_store();
+        runtime.block_on(async {
+            for user in users.iter().flatten() {
+                store.add_user(user.clone()).await.unwrap();
+            }
+        });
+
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.to_async(&runtime).iter_batched(
+                || users.clone(),
+                |users| {
+                    let store = store.clone();
+                    async move {
+                        run_tasks(&store, users, |store, users| async move {
+                            for (i, user) in users.iter().enumerate() {
+                                store.get_user(&user.email).await.unwrap();
+                                if i % 4 == 0 {
+                                    store.record_failed_login(&user.email).await.unwrap();
+                                } else {
+                                    store.clear_failed_logins(&user.email).await.unwrap();
+                                }
+                            }
+                        })
+                        .await;
+                    }
+                },
+                BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, signups, logins);
+criterion_main!(benches);
@@ -1,10 +1,68 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use crate::domain::UserStore;
+use crate::domain::{
+    AuditSink, AuthorizationCodeStore, BreachedPasswords, EmailClient, KeyRing, MagicLinkStore, OidcClientStore, ServiceAccountStore, SessionStore, TwoFACodeStore,
+    UserStore, WebAuthnChallengeStore,
+};
 
-pub type UserStoreType = Arc<RwLock<dyn UserStore>>;
+pub type UserStoreType = Arc<dyn UserStore>;
+pub type TwoFACodeStoreType = Arc<dyn TwoFACodeStore>;
+pub type WebAuthnChallengeStoreType = Arc<dyn WebAuthnChallengeStore>;
+pub type OidcClientStoreType = Arc<dyn OidcClientStore>;
+pub type ServiceAccountStoreType = Arc<dyn ServiceAccountStore>;
+pub type AuthorizationCodeStoreType = Arc<dyn AuthorizationCodeStore>;
+pub type MagicLinkStoreType = Arc<dyn MagicLinkStore>;
+pub type SessionStoreType = Arc<dyn SessionStore>;
+pub type AuditSinkType = Arc<dyn AuditSink>;
+pub type KeyRingType = Arc<RwLock<KeyRing>>;
+pub type EmailClientType = Arc<dyn EmailClient>;
+pub type BreachedPasswordsType = Arc<dyn BreachedPasswords>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
-}
\ No newline at end of file
+    pub two_fa_code_store: TwoFACodeStoreType,
+    pub webauthn_challenge_store: WebAuthnChallengeStoreType,
+    pub oidc_client_store: OidcClientStoreType,
+    pub service_account_store: ServiceAccountStoreType,
+    pub authorization_code_store: AuthorizationCodeStoreType,
+    pub magic_link_store: MagicLinkStoreType,
+    pub session_store: SessionStoreType,
+    pub audit_sink: AuditSinkType,
+    pub key_ring: KeyRingType,
+    pub email_client: EmailClientType,
+    pub breached_passwords: BreachedPasswordsType,
+}
+
+impl AppState {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        user_store: UserStoreType,
+        two_fa_code_store: TwoFACodeStoreType,
+        webauthn_challenge_store: WebAuthnChallengeStoreType,
+        oidc_client_store: OidcClientStoreType,
+        service_account_store: ServiceAccountStoreType,
+        authorization_code_store: AuthorizationCodeStoreType,
+        magic_link_store: MagicLinkStoreType,
+        session_store: SessionStoreType,
+        audit_sink: AuditSinkType,
+        key_ring: KeyRingType,
+        email_client: EmailClientType,
+        breached_passwords: BreachedPasswordsType,
+    ) -> Self {
+        Self {
+            user_store,
+            two_fa_code_store,
+            webauthn_challenge_store,
+            oidc_client_store,
+            service_account_store,
+            authorization_code_store,
+            magic_link_store,
+            session_store,
+            audit_sink,
+            key_ring,
+            email_client,
+            breached_passwords,
+        }
+    }
+}
@@ -0,0 +1,372 @@
+//! Command-line administration of the auth service.
+//!
+//! User commands edit the store in `USER_STORE_DIR` directly. The service only
+//! reads the store when it starts and locks it while it runs, so stop it first
+//! and start it again once done. `keys generate` creates the key file at
+//! `JWT_KEYS_PATH` before the service first starts; once it runs, the service
+//! rotates the keys itself, so the other `keys` commands go through its admin
+//! API instead.
+
+use auth_service::domain::{
+    AuditAction, AuditEvent, AuditOutcome, AuditQuery, AuditSink, AuditSinkError, ClientInfo,
+    Email, Password, PasswordHash, Permission, Role, User, UserQuery, UserStore,
+};
+use auth_service::routes::{ErrorResponse, KeyRotationResponse};
+use auth_service::services::{
+    HashmapUserStore, HibpPasswordList, JsonlAuditSink, UserStoreWalError,
+};
+use auth_service::utils::auth::open_key_ring;
+use auth_service::utils::constants::{
+    AUDIT_LOG_PATH, BREACHED_PASSWORDS_PATH, JWT_COOKIE_NAME, USER_STORE_DIR,
+    USER_STORE_SNAPSHOT_INTERVAL,
+};
+use clap::{Args, Parser, Subcommand};
+use jsonwebtoken::jwk::JwkSet;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "authctl", about = "Administer the auth service")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage the users in the store at USER_STORE_DIR
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Generate signing keys, or list or rotate the running service's keys
+    Keys(KeysArgs),
+    /// Check the audit log at AUDIT_LOG_PATH
+    #[command(subcommand)]
+    Audit(AuditCommand),
+}
+
+// Passwords are read from stdin rather than taken as arguments, which would
+// leave them in the shell history and process list.
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Create a user, reading their password from stdin
+    Create {
+        email: String,
+        #[arg(long = "role")]
+        roles: Vec<String>,
+        #[arg(long = "permission")]
+        permissions: Vec<String>,
+        #[arg(long = "requires-2fa")]
+        requires_2fa: bool,
+    },
+    /// List users, ordered by email
+    List {
+        /// Only list users whose email contains this
+        #[arg(long)]
+        search: Option<String>,
+    },
+    Disable {
+        email: String,
+    },
+    Enable {
+        email: String,
+    },
+    Delete {
+        email: String,
+    },
+    /// Set a user's password, reading the new one from stdin
+    ResetPassword {
+        email: String,
+        /// Also make the user choose a new password when they next sign in
+        #[arg(long)]
+        require_change: bool,
+    },
+    /// Turn off 2FA and remove the user's authenticator app, recovery codes and passkeys
+    #[command(name = "reset-2fa")]
+    Reset2FA {
+        email: String,
+    },
+}
+
+#[derive(Args)]
+struct KeysArgs {
+    /// Base URL of the running auth service
+    #[arg(long, env = "AUTH_SERVICE_URL", default_value = "http://localhost:3000")]
+    url: String,
+    #[command(subcommand)]
+    command: KeysCommand,
+}
+
+#[derive(Subcommand)]
+enum KeysCommand {
+    /// Create the signing key file for the service to load when it starts
+    Generate {
+        /// File to create, which the service reads from JWT_KEYS_PATH
+        #[arg(long, env = "JWT_KEYS_PATH")]
+        path: String,
+    },
+    /// List the published signing keys
+    List,
+    /// Publish a new signing key ahead of schedule
+    Rotate {
+        /// Auth token of a signed-in admin, as found in their `jwt` cookie
+        #[arg(long, env = "AUTHCTL_TOKEN", hide_env_values = true)]
+        token: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditCommand {
+    /// Check that no entries were modified, removed or reordered
+    Verify,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::User(command) => run_user_command(command).await,
+        Command::Keys(args) => run_keys_command(args).await,
+        Command::Audit(AuditCommand::Verify) => verify_audit_log().await,
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run_user_command(command: UserCommand) -> Result<(), String> {
+    let Some(dir) = USER_STORE_DIR.as_ref() else {
+        return Err("USER_STORE_DIR is not set, so users only live in the service's memory".into());
+    };
+    let store =
+        HashmapUserStore::open(dir, *USER_STORE_SNAPSHOT_INTERVAL).map_err(|e| match e {
+            UserStoreWalError::Locked => {
+                format!("{} is in use by the auth service, so stop it first", dir)
+            }
+            UserStoreWalError::DuplicateEmail { first, second } => {
+                format!("users stored as {} and {} now have the same email", first, second)
+            }
+            e => format!("failed to load users: {:?}", e),
+        })?;
+
+    let (email, detail) = match command {
+        UserCommand::List { search } => return list_users(&store, search).await,
+        UserCommand::Create {
+            email,
+            roles,
+            permissions,
+            requires_2fa,
+        } => {
+            let parsed = Email::parse_new(&email)?;
+            let password = read_password(&parsed)?;
+            let mut user = User::new(email, password.as_ref().to_owned(), requires_2fa);
+            user.roles = roles
+                .iter()
+                .map(|role| Role::parse(role))
+                .collect::<Result<_, _>>()?;
+            user.permissions = permissions
+                .iter()
+                .map(|permission| Permission::parse(permission))
+                .collect::<Result<_, _>>()?;
+            store.add_user(user).await.map_err(|e| format!("{:?}", e))?;
+            (parsed, "user create")
+        }
+        UserCommand::Disable { email } => {
+            let email = Email::parse(&email)?;
+            store.set_disabled(&email, true).await.map_err(|e| format!("{:?}", e))?;
+            (email, "user disable")
+        }
+        UserCommand::Enable { email } => {
+            let email = Email::parse(&email)?;
+            store.set_disabled(&email, false).await.map_err(|e| format!("{:?}", e))?;
+            (email, "user enable")
+        }
+        UserCommand::Delete { email } => {
+            let email = Email::parse(&email)?;
+            store.delete_user(&email).await.map_err(|e| format!("{:?}", e))?;
+            (email, "user delete")
+        }
+        UserCommand::ResetPassword {
+            email,
+            require_change,
+        } => {
+            let email = Email::parse(&email)?;
+            store.get_user(&email).await.map_err(|e| format!("{:?}", e))?;
+            let password = read_password(&email)?;
+            store
+                .set_password(&email, PasswordHash::hash(&password))
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+            if require_change {
+                store
+                    .set_password_reset_required(&email, true)
+                    .await
+                    .map_err(|e| format!("{:?}", e))?;
+            }
+            (email, "user reset-password")
+        }
+        UserCommand::Reset2FA { email } => {
+            let email = Email::parse(&email)?;
+            store.reset_two_fa(&email).await.map_err(|e| format!("{:?}", e))?;
+            (email, "user reset-2fa")
+        }
+    };
+
+    record_admin_action(&email, detail).await
+}
+
+async fn list_users(store: &HashmapUserStore, search: Option<String>) -> Result<(), String> {
+    let query = UserQuery {
+        search,
+        limit: usize::MAX,
+        ..UserQuery::default()
+    };
+    let page = store.list_users(&query).await.map_err(|e| format!("{:?}", e))?;
+
+    println!("{:<40} {:<8} {:<24} STATUS", "EMAIL", "2FA", "ROLES");
+    for user in &page.users {
+        let two_fa = if user.requires_2fa {
+            format!("{:?}", user.two_fa_method).to_lowercase()
+        } else {
+            "off".to_owned()
+        };
+        let roles: Vec<&str> = user.roles.iter().map(AsRef::as_ref).collect();
+        let status = if user.disabled {
+            "disabled"
+        } else if user.is_locked() {
+            "locked"
+        } else if user.password_reset_required {
+            "password reset required"
+        } else {
+            "active"
+        };
+        println!(
+            "{:<40} {:<8} {:<24} {}",
+            user.email.as_ref(),
+            two_fa,
+            roles.join(","),
+            status
+        );
+    }
+    Ok(())
+}
+
+fn read_password(email: &Email) -> Result<Password, String> {
+    let breached = match BREACHED_PASSWORDS_PATH.as_ref() {
+        Some(path) => HibpPasswordList::open(path)
+            .map_err(|e| format!("failed to open breached passwords: {}", e))?,
+        None => HibpPasswordList::empty(),
+    };
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Password: ");
+    }
+    let mut line = String::new();
+    stdin.read_line(&mut line).map_err(|e| e.to_string())?;
+    Password::parse_new(line.trim_end_matches(['\r', '\n']), email, &breached)
+        .map_err(|e| e.to_string())
+}
+
+// Changes made here bypass the admin API, so they're recorded the same way
+// its requests are, with authctl as the user agent.
+async fn record_admin_action(email: &Email, detail: &str) -> Result<(), String> {
+    let client = ClientInfo {
+        user_agent: Some("authctl".to_owned()),
+        ip_address: None,
+    };
+    let event = AuditEvent::new(AuditAction::AdminAction, AuditOutcome::Success, &client)
+        .subject(email.as_ref())
+        .detail(format!("authctl {}", detail));
+    let sink = JsonlAuditSink::open(AUDIT_LOG_PATH.as_str())
+        .map_err(|e| format!("failed to open audit log: {:?}", e))?;
+    sink.record(event, chrono::Utc::now().timestamp())
+        .await
+        .map_err(|e| format!("failed to record audit event: {:?}", e))?;
+    Ok(())
+}
+
+async fn run_keys_command(args: KeysArgs) -> Result<(), String> {
+    let client = reqwest::Client::new();
+    let base_url = args.url.trim_end_matches('/');
+
+    match args.command {
+        KeysCommand::Generate { path } => {
+            // The service rotates the keys in an existing file, and replacing
+            // them would invalidate every token signed with them.
+            if Path::new(&path).exists() {
+                return Err(format!("{} already exists", path));
+            }
+            let now = chrono::Utc::now().timestamp();
+            let key_ring = open_key_ring(Some(&path), now)?;
+            let key = key_ring
+                .signing_key(now)
+                .ok_or("the new key ring has no signing key")?;
+            println!("created {} with key {}", path, key.kid);
+        }
+        KeysCommand::List => {
+            let jwks: JwkSet = client
+                .get(format!("{}/.well-known/jwks.json", base_url))
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| e.to_string())?
+                .json()
+                .await
+                .map_err(|e| e.to_string())?;
+            for key in jwks.keys {
+                let algorithm = key
+                    .common
+                    .key_algorithm
+                    .map(|algorithm| algorithm.to_string())
+                    .unwrap_or_default();
+                println!("{}\t{}", key.common.key_id.unwrap_or_default(), algorithm);
+            }
+        }
+        KeysCommand::Rotate { token } => {
+            let response = client
+                .post(format!("{}/admin/keys/rotate", base_url))
+                .header(
+                    reqwest::header::COOKIE,
+                    format!("{}={}", JWT_COOKIE_NAME, token),
+                )
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            let status = response.status();
+            if !status.is_success() {
+                let error = response
+                    .json::<ErrorResponse>()
+                    .await
+                    .map(|body| body.error)
+                    .unwrap_or_default();
+                return Err(format!("{} {}", status, error));
+            }
+            let key: KeyRotationResponse = response.json().await.map_err(|e| e.to_string())?;
+            let activates_at = chrono::DateTime::from_timestamp(key.activates_at, 0)
+                .map(|time| time.to_rfc3339())
+                .unwrap_or_else(|| key.activates_at.to_string());
+            println!("published key {}, which signs new tokens from {}", key.kid, activates_at);
+        }
+    }
+    Ok(())
+}
+
+async fn verify_audit_log() -> Result<(), String> {
+    let sink = JsonlAuditSink::open(AUDIT_LOG_PATH.as_str())
+        .map_err(|e| format!("failed to open audit log: {:?}", e))?;
+    match sink.verify().await {
+        Ok(()) => {
+            let entries = sink
+                .query(&AuditQuery::default())
+                .await
+                .map_err(|e| format!("{:?}", e))?;
+            println!("audit log is intact ({} entries)", entries.len());
+            Ok(())
+        }
+        Err(AuditSinkError::Tampered(e)) => Err(format!("audit log was tampered with: {}", e)),
+        Err(e) => Err(format!("failed to read audit log: {:?}", e)),
+    }
+}
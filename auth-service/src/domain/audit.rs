@@ -0,0 +1,312 @@
+use crate::domain::ClientInfo;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// `prev_hash` of the first entry in a log.
+pub const AUDIT_GENESIS_HASH: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum AuditAction {
+    #[serde(rename = "signup")]
+    Signup,
+    /// Password or passkey sign-in. Users with 2FA get a `2fa_challenge`
+    /// instead, followed by a `2fa_verification`.
+    #[serde(rename = "login")]
+    Login,
+    /// A sign-in link was asked for. Following it is logged as a `login`
+    /// or `2fa_challenge`.
+    #[serde(rename = "magic_link_request")]
+    MagicLinkRequest,
+    #[serde(rename = "2fa_challenge")]
+    TwoFAChallenge,
+    #[serde(rename = "2fa_verification")]
+    TwoFAVerification,
+    #[serde(rename = "logout")]
+    Logout,
+    #[serde(rename = "admin_action")]
+    AdminAction,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+/// Something that happened, before it's given a place in the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+    /// Email of whoever made the request, if known.
+    pub actor: Option<String>,
+    /// Email of the account acted on, when that isn't the actor's own, or
+    /// client id of the service account acted on.
+    pub subject: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    /// Extra context, such as why a login failed or which admin route was called.
+    pub detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction, outcome: AuditOutcome, client: &ClientInfo) -> Self {
+        Self {
+            action,
+            outcome,
+            actor: None,
+            subject: None,
+            ip_address: client.ip_address.clone(),
+            user_agent: client.user_agent.clone(),
+            detail: None,
+        }
+    }
+
+    pub fn actor(mut self, email: impl Into<String>) -> Self {
+        self.actor = Some(email.into());
+        self
+    }
+
+    pub fn subject(mut self, email: impl Into<String>) -> Self {
+        self.subject = Some(email.into());
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// An event as written to the log. Each entry's hash covers the one before
+/// it, so editing, removing or reordering entries breaks the chain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEntry {
+    /// Starts at 1 and increases by one per entry.
+    pub seq: u64,
+    /// Unix timestamp.
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub event: AuditEvent,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+// Every field except `hash`, in the order they're hashed.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct HashedFields<'a> {
+    seq: u64,
+    timestamp: i64,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+    prev_hash: &'a str,
+}
+
+impl AuditEntry {
+    pub fn new(seq: u64, timestamp: i64, event: AuditEvent, prev_hash: String) -> Self {
+        let mut entry = Self {
+            seq,
+            timestamp,
+            event,
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+        entry
+    }
+
+    pub fn compute_hash(&self) -> String {
+        let fields = HashedFields {
+            seq: self.seq,
+            timestamp: self.timestamp,
+            event: &self.event,
+            prev_hash: &self.prev_hash,
+        };
+        let bytes = serde_json::to_vec(&fields).expect("audit entries always serialize");
+        format!("{:x}", Sha256::digest(bytes))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditChainError {
+    /// The line couldn't be parsed as an entry. Lines count from 1.
+    Malformed { line: usize },
+    /// An entry is missing or out of order before `seq`.
+    SequenceGap { seq: u64 },
+    /// The entry doesn't point at the hash of the one before it.
+    BrokenLink { seq: u64 },
+    /// The entry was modified after it was written.
+    HashMismatch { seq: u64 },
+}
+
+impl fmt::Display for AuditChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditChainError::Malformed { line } => write!(f, "line {} is not a valid entry", line),
+            AuditChainError::SequenceGap { seq } => {
+                write!(f, "entries are missing or out of order before entry {}", seq)
+            }
+            AuditChainError::BrokenLink { seq } => {
+                write!(f, "entry {} does not follow the previous entry", seq)
+            }
+            AuditChainError::HashMismatch { seq } => {
+                write!(f, "entry {} was modified after it was written", seq)
+            }
+        }
+    }
+}
+
+/// Checks that `entries` form an unbroken chain from the start of the log.
+///
+/// Entries dropped from the end can't be detected from the log alone; compare
+/// the last hash against one recorded elsewhere to catch truncation.
+pub fn verify_audit_chain(entries: &[AuditEntry]) -> Result<(), AuditChainError> {
+    let mut prev_hash = AUDIT_GENESIS_HASH;
+    for (index, entry) in entries.iter().enumerate() {
+        if entry.seq != index as u64 + 1 {
+            return Err(AuditChainError::SequenceGap { seq: entry.seq });
+        }
+        if entry.prev_hash != prev_hash {
+            return Err(AuditChainError::BrokenLink { seq: entry.seq });
+        }
+        if entry.hash != entry.compute_hash() {
+            return Err(AuditChainError::HashMismatch { seq: entry.seq });
+        }
+        prev_hash = &entry.hash;
+    }
+    Ok(())
+}
+
+/// Filters for reading the log back. Bounds are inclusive Unix timestamps.
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    /// Matches entries where the user is either the actor or the subject.
+    pub email: Option<String>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        let email_matches = self.email.as_deref().is_none_or(|email| {
+            [&entry.event.actor, &entry.event.subject]
+                .into_iter()
+                .flatten()
+                .any(|e| e.eq_ignore_ascii_case(email))
+        });
+        email_matches
+            && self.from.is_none_or(|from| entry.timestamp >= from)
+            && self.to.is_none_or(|to| entry.timestamp <= to)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuditSinkError {
+    Tampered(AuditChainError),
+    UnexpectedError,
+}
+
+/// Append-only destination for audit events.
+#[async_trait::async_trait]
+pub trait AuditSink: Send + Sync {
+    async fn record(
+        &self,
+        event: AuditEvent,
+        timestamp: i64,
+    ) -> Result<AuditEntry, AuditSinkError>;
+    /// Matching entries, oldest first.
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, AuditSinkError>;
+    async fn verify(&self) -> Result<(), AuditSinkError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(count: u64) -> Vec<AuditEntry> {
+        let mut entries: Vec<AuditEntry> = Vec::new();
+        for seq in 1..=count {
+            let prev_hash = entries
+                .last()
+                .map_or(AUDIT_GENESIS_HASH.to_owned(), |entry| entry.hash.clone());
+            let event = AuditEvent::new(AuditAction::Login, AuditOutcome::Success, &ClientInfo::default())
+                .actor(format!("user{}@example.com", seq));
+            entries.push(AuditEntry::new(seq, 1_000 + seq as i64, event, prev_hash));
+        }
+        entries
+    }
+
+    #[test]
+    fn test_intact_chain_verifies() {
+        assert_eq!(verify_audit_chain(&chain(3)), Ok(()));
+        assert_eq!(verify_audit_chain(&[]), Ok(()));
+    }
+
+    #[test]
+    fn test_modified_entry_is_detected() {
+        let mut entries = chain(3);
+        entries[1].event.outcome = AuditOutcome::Failure;
+        assert_eq!(
+            verify_audit_chain(&entries),
+            Err(AuditChainError::HashMismatch { seq: 2 })
+        );
+    }
+
+    #[test]
+    fn test_rehashed_entry_breaks_the_next_link() {
+        let mut entries = chain(3);
+        entries[1].event.actor = Some("someone-else@example.com".to_owned());
+        entries[1].hash = entries[1].compute_hash();
+        assert_eq!(
+            verify_audit_chain(&entries),
+            Err(AuditChainError::BrokenLink { seq: 3 })
+        );
+    }
+
+    #[test]
+    fn test_removed_or_reordered_entries_are_detected() {
+        let mut entries = chain(3);
+        entries.remove(1);
+        assert_eq!(
+            verify_audit_chain(&entries),
+            Err(AuditChainError::SequenceGap { seq: 3 })
+        );
+
+        let mut entries = chain(3);
+        entries.swap(0, 1);
+        assert_eq!(
+            verify_audit_chain(&entries),
+            Err(AuditChainError::SequenceGap { seq: 2 })
+        );
+    }
+
+    #[test]
+    fn test_query_filters_by_user_and_time() {
+        let entries = chain(3);
+        let query = AuditQuery {
+            email: Some("USER2@example.com".to_owned()),
+            ..AuditQuery::default()
+        };
+        assert!(!query.matches(&entries[0]));
+        assert!(query.matches(&entries[1]));
+
+        let query = AuditQuery {
+            email: None,
+            from: Some(1_002),
+            to: Some(1_002),
+        };
+        let matching: Vec<u64> = entries
+            .iter()
+            .filter(|entry| query.matches(entry))
+            .map(|entry| entry.seq)
+            .collect();
+        assert_eq!(matching, [2]);
+    }
+}
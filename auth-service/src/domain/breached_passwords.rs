@@ -0,0 +1,8 @@
+use crate::domain::Password;
+
+/// Passwords known from data breaches, which shouldn't be chosen again
+/// however well they meet the password rules.
+pub trait BreachedPasswords: Send + Sync {
+    /// How often the password appears in breaches, or 0 if it never has.
+    fn occurrences(&self, password: &Password) -> u64;
+}
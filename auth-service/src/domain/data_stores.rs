@@ -1,4 +1,9 @@
-use crate::domain::{Email, User};
+use crate::domain::{
+    AuthorizationCode, AuthorizationGrant, Email, HashedRecoveryCode, MagicLink, MagicLinkId, OidcClient,
+    PasskeyCredential, PasswordHash, Permission, Role, ServiceAccount, Session, SessionId, TotpCredential, TwoFAMethod, User, WebAuthnChallenge,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::Rng;
 
 #[derive(Debug, PartialEq)]
 pub enum UserStoreError {
@@ -8,10 +13,557 @@ pub enum UserStoreError {
     UnexpectedError,
 }
 
+/// Where a user listing carries on from: just after the user with this email.
+/// Clients get it as an opaque string, so its form can change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserCursor(Email);
+
+impl UserCursor {
+    pub fn after(email: &Email) -> Self {
+        UserCursor(email.clone())
+    }
+
+    pub fn email(&self) -> &Email {
+        &self.0
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(self.0.as_ref())
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, String> {
+        let email = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or("invalid cursor")?;
+        Email::parse(&email)
+            .map(UserCursor)
+            .map_err(|_| "invalid cursor".to_owned())
+    }
+}
+
+/// A page of a user listing, ordered by email. Unset filters match everyone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserQuery {
+    /// Case-insensitive substring of the email.
+    pub search: Option<String>,
+    pub role: Option<Role>,
+    pub disabled: Option<bool>,
+    pub requires_2fa: Option<bool>,
+    /// Continues a listing from the `next_cursor` of its previous page.
+    pub after: Option<UserCursor>,
+    pub limit: usize,
+}
+
+impl UserQuery {
+    /// Whether the user passes the filters, regardless of the page.
+    pub fn matches(&self, user: &User) -> bool {
+        let search_matches = self.search.as_deref().is_none_or(|search| {
+            user.email
+                .as_ref()
+                .to_lowercase()
+                .contains(&search.to_lowercase())
+        });
+        search_matches
+            && self.role.as_ref().is_none_or(|role| user.roles.contains(role))
+            && self.disabled.is_none_or(|disabled| user.disabled == disabled)
+            && self
+                .requires_2fa
+                .is_none_or(|requires_2fa| user.requires_2fa == requires_2fa)
+    }
+
+    /// Whether the user comes after the cursor, if there is one.
+    pub fn is_after_cursor(&self, user: &User) -> bool {
+        self.after
+            .as_ref()
+            .is_none_or(|cursor| user.email.as_ref() > cursor.email().as_ref())
+    }
+}
+
+pub struct UserPage {
+    pub users: Vec<User>,
+    /// Number of users matching the filters across all pages.
+    pub total: usize,
+    /// Set when there are more users after this page.
+    pub next_cursor: Option<UserCursor>,
+}
+
+/// A change to a stored user. Returning an error leaves the user as it was.
+pub type UserChange<'a> = Box<dyn FnOnce(&mut User) -> Result<(), UserStoreError> + Send + 'a>;
+
+/// Users keyed by email. Implementations only have to store and list whole
+/// users; the narrower changes are built on `modify_user`, and can be
+/// overridden where a backend does them better.
+///
+/// Stores are shared between requests without a lock around them, so they
+/// handle concurrent calls themselves. Every implementation should pass
+/// `services::user_store_conformance`.
 #[async_trait::async_trait]
 pub trait UserStore: Send + Sync {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
-    async fn get_user(&self, email: &Email) -> Result<&User, UserStoreError>;
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError>;
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
+    /// Replaces the stored user with the same email.
+    async fn update_user(&self, user: User) -> Result<(), UserStoreError>;
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError>;
+    /// Users matching the query, ordered by email.
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError>;
+
+    /// Applies `change` to the stored user and returns the result. The default
+    /// reads the user and writes it back, so a change made in between is lost;
+    /// stores override it to apply changes atomically.
+    async fn modify_user(&self, email: &Email, change: UserChange<'_>) -> Result<User, UserStoreError> {
+        let mut user = self.get_user(email).await?;
+        change(&mut user)?;
+        self.update_user(user.clone()).await?;
+        Ok(user)
+    }
+
+    async fn validate_user(&self, email: &Email, password: &str) -> Result<(), UserStoreError> {
+        if self.get_user(email).await?.password_hash.verify(password) {
+            Ok(())
+        } else {
+            Err(UserStoreError::InvalidCredentials)
+        }
+    }
+
+    async fn update_two_fa(
+        &self,
+        email: &Email,
+        requires_2fa: bool,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.requires_2fa = requires_2fa;
+                user.two_fa_method = method;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    /// Turns 2FA off and removes every second factor the user has set up.
+    async fn reset_two_fa(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(|user| {
+                user.requires_2fa = false;
+                user.two_fa_method = TwoFAMethod::default();
+                user.totp = None;
+                user.recovery_codes.clear();
+                user.passkeys.clear();
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    async fn set_totp_credential(
+        &self,
+        email: &Email,
+        credential: Option<TotpCredential>,
+    ) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.totp = credential;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    async fn set_recovery_codes(
+        &self,
+        email: &Email,
+        codes: Vec<HashedRecoveryCode>,
+    ) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.recovery_codes = codes;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    async fn add_passkey(
+        &self,
+        email: &Email,
+        passkey: PasskeyCredential,
+    ) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.passkeys.push(passkey);
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    async fn update_passkey_sign_count(
+        &self,
+        email: &Email,
+        credential_id: &[u8],
+        sign_count: u32,
+    ) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                let passkey = user
+                    .passkeys
+                    .iter_mut()
+                    .find(|passkey| passkey.id == credential_id)
+                    .ok_or(UserStoreError::InvalidCredentials)?;
+                passkey.sign_count = sign_count;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn set_disabled(&self, email: &Email, disabled: bool) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.disabled = disabled;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    /// Replaces the password and clears any pending reset.
+    async fn set_password(
+        &self,
+        email: &Email,
+        password_hash: PasswordHash,
+    ) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.password_hash = password_hash;
+                user.password_reset_required = false;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    /// Replaces the hash of an unchanged password, e.g. to upgrade an imported one.
+    async fn update_password_hash(
+        &self,
+        email: &Email,
+        password_hash: PasswordHash,
+    ) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.password_hash = password_hash;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    async fn set_password_reset_required(
+        &self,
+        email: &Email,
+        required: bool,
+    ) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.password_reset_required = required;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    /// Returns the number of consecutive failures including this one.
+    async fn record_failed_login(&self, email: &Email) -> Result<u32, UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(|user| {
+                user.failed_login_attempts += 1;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|user| user.failed_login_attempts)
+    }
+    async fn clear_failed_logins(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(|user| {
+                user.failed_login_attempts = 0;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    async fn set_roles(&self, email: &Email, roles: Vec<Role>) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.roles = roles;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+    async fn set_permissions(
+        &self,
+        email: &Email,
+        permissions: Vec<Permission>,
+    ) -> Result<(), UserStoreError> {
+        self.modify_user(
+            email,
+            Box::new(move |user| {
+                user.permissions = permissions;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TwoFACodeStoreError {
+    LoginAttemptIdNotFound,
+    UnexpectedError,
+}
+
+/// Wrong second factor codes a login attempt survives before it is dropped.
+pub const MAX_2FA_CODE_ATTEMPTS: u32 = 5;
+
+#[async_trait::async_trait]
+pub trait TwoFACodeStore: Send + Sync {
+    /// `expires_at` is a Unix timestamp after which the attempt can no longer
+    /// be completed.
+    async fn add_code(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+        expires_at: i64,
+    ) -> Result<(), TwoFACodeStoreError>;
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    async fn get_code(
+        &self,
+        email: &Email,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    /// Counts a wrong code against the login attempt, removing the attempt
+    /// once `MAX_2FA_CODE_ATTEMPTS` have been made.
+    async fn record_failed_attempt(&self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    async fn remove_expired_codes(&self, now: i64) -> Result<(), TwoFACodeStoreError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WebAuthnCeremony {
+    Registration,
+    Authentication,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnChallengeStoreError {
+    ChallengeNotFound,
+    UnexpectedError,
+}
+
+/// Holds the challenge of each in-flight WebAuthn ceremony until the
+/// authenticator's response comes back. Challenges are single use, and one
+/// taken after `expires_at` (a Unix timestamp) is treated as not found.
+#[async_trait::async_trait]
+pub trait WebAuthnChallengeStore: Send + Sync {
+    async fn add_challenge(
+        &self,
+        email: Email,
+        ceremony: WebAuthnCeremony,
+        challenge: WebAuthnChallenge,
+        expires_at: i64,
+    ) -> Result<(), WebAuthnChallengeStoreError>;
+    async fn take_challenge(
+        &self,
+        email: &Email,
+        ceremony: WebAuthnCeremony,
+        now: i64,
+    ) -> Result<WebAuthnChallenge, WebAuthnChallengeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OidcClientStoreError {
+    ClientAlreadyExists,
+    ClientNotFound,
+    UnexpectedError,
+}
+
+/// Registry of the applications allowed to use the auth service as their
+/// OpenID Connect provider.
+#[async_trait::async_trait]
+pub trait OidcClientStore: Send + Sync {
+    async fn add_client(&self, client: OidcClient) -> Result<(), OidcClientStoreError>;
+    async fn get_client(&self, client_id: &str) -> Result<OidcClient, OidcClientStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ServiceAccountStoreError {
+    AccountAlreadyExists,
+    AccountNotFound,
+    UnexpectedError,
+}
+
+/// Service accounts keyed by client id.
+#[async_trait::async_trait]
+pub trait ServiceAccountStore: Send + Sync {
+    async fn add_account(&self, account: ServiceAccount) -> Result<(), ServiceAccountStoreError>;
+    async fn get_account(&self, client_id: &str) -> Result<ServiceAccount, ServiceAccountStoreError>;
+    /// Replaces the stored account with the same client id.
+    async fn update_account(&self, account: ServiceAccount) -> Result<(), ServiceAccountStoreError>;
+    async fn delete_account(&self, client_id: &str) -> Result<(), ServiceAccountStoreError>;
+    /// All accounts, ordered by client id.
+    async fn list_accounts(&self) -> Result<Vec<ServiceAccount>, ServiceAccountStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthorizationCodeStoreError {
+    CodeNotFound,
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore: Send + Sync {
+    async fn add_code(
+        &self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError>;
+    /// Removes the code as it is read so it can only be redeemed once.
+    async fn take_code(
+        &self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MagicLinkStoreError {
+    LinkNotFound,
+    UnexpectedError,
+}
+
+#[async_trait::async_trait]
+pub trait MagicLinkStore: Send + Sync {
+    async fn add_link(&self, id: MagicLinkId, link: MagicLink) -> Result<(), MagicLinkStoreError>;
+    /// Removes the link as it is read so it can only be followed once.
+    async fn take_link(&self, id: &MagicLinkId) -> Result<MagicLink, MagicLinkStoreError>;
+    async fn remove_expired_links(&self, now: i64) -> Result<(), MagicLinkStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionStoreError {
+    SessionNotFound,
+    UnexpectedError,
+}
 
-    async fn validate_user(&mut self, user: &User) -> Result<(), UserStoreError>;
-}
\ No newline at end of file
+#[async_trait::async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError>;
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError>;
+    /// Records that the session's token was just used.
+    async fn touch_session(&self, id: &SessionId, now: i64) -> Result<(), SessionStoreError>;
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+    async fn remove_session(&self, id: &SessionId) -> Result<(), SessionStoreError>;
+    async fn remove_sessions(&self, email: &Email) -> Result<(), SessionStoreError>;
+    async fn remove_expired_sessions(&self, now: i64) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoginAttemptId(String);
+
+impl LoginAttemptId {
+    pub fn parse(id: String) -> Result<Self, String> {
+        match uuid::Uuid::parse_str(&id) {
+            Ok(_) => Ok(LoginAttemptId(id)),
+            Err(_) => Err("invalid login attempt id".to_string()),
+        }
+    }
+}
+
+impl Default for LoginAttemptId {
+    fn default() -> Self {
+        LoginAttemptId(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for LoginAttemptId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwoFACode(String);
+
+impl TwoFACode {
+    pub fn parse(code: String) -> Result<Self, String> {
+        if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+            Ok(TwoFACode(code))
+        } else {
+            Err("invalid 2FA code".to_string())
+        }
+    }
+}
+
+impl Default for TwoFACode {
+    fn default() -> Self {
+        let code = rand::thread_rng().gen_range(0..1_000_000);
+        TwoFACode(format!("{:06}", code))
+    }
+}
+
+impl AsRef<str> for TwoFACode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_valid_login_attempt_id() {
+        let id = uuid::Uuid::new_v4().to_string();
+        let result = LoginAttemptId::parse(id.clone());
+        assert_eq!(result.unwrap().as_ref(), id);
+    }
+
+    #[test]
+    fn test_parse_invalid_login_attempt_id() {
+        let result = LoginAttemptId::parse("not-a-uuid".to_string());
+        assert_eq!(result.unwrap_err(), "invalid login attempt id");
+    }
+
+    #[test]
+    fn test_default_two_fa_code_is_valid() {
+        let code = TwoFACode::default();
+        assert!(TwoFACode::parse(code.as_ref().to_string()).is_ok());
+    }
+
+    #[test]
+    fn test_parse_invalid_two_fa_codes() {
+        for code in ["", "12345", "1234567", "12a456", " 12345"] {
+            let result = TwoFACode::parse(code.to_string());
+            assert_eq!(result.unwrap_err(), "invalid 2FA code", "Code '{}' should be invalid", code);
+        }
+    }
+}
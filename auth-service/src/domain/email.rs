@@ -1,15 +1,120 @@
-use validator::validate_email;
+use crate::domain::EmailPolicy;
+use crate::utils::constants::EMAIL_POLICY;
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+// RFC 5321 section 4.5.3.1.
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+const MAX_DOMAIN_LENGTH: usize = 255;
+const MAX_LABEL_LENGTH: usize = 63;
+// A path is at most 256 octets, two of which are its angle brackets.
+const MAX_EMAIL_LENGTH: usize = 254;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmailError {
+    Invalid,
+    DomainNotAllowed,
+    Disposable,
+}
+
+impl std::error::Error for EmailError {}
+
+impl std::fmt::Display for EmailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmailError::Invalid => write!(f, "invalid email"),
+            EmailError::DomainNotAllowed => write!(f, "email addresses at this domain are not allowed"),
+            EmailError::Disposable => write!(f, "disposable email addresses are not allowed, use a permanent one"),
+        }
+    }
+}
+
+impl From<EmailError> for String {
+    fn from(e: EmailError) -> Self {
+        e.to_string()
+    }
+}
+
+/// A syntactically valid email address, normalized so that each mailbox has
+/// one spelling: trimmed, with the domain lowercased and IDNs in punycode,
+/// and the local part case folded unless the policy says otherwise.
+#[derive(Debug, Clone, PartialEq, Hash, Eq, Serialize)]
+#[serde(transparent)]
 pub struct Email(String);
 
 impl Email {
-    pub fn parse(email: &str) -> Result<Email, String> {
-        if validate_email(email) {
-            Ok(Email(email.to_string()))
+    pub fn parse(email: &str) -> Result<Email, EmailError> {
+        Self::parse_with(email, &EMAIL_POLICY)
+    }
+
+    pub fn parse_with(email: &str, policy: &EmailPolicy) -> Result<Email, EmailError> {
+        let email = email.trim();
+        let (local_part, domain) = email.rsplit_once('@').ok_or(EmailError::Invalid)?;
+        if !is_valid_local_part(local_part) {
+            return Err(EmailError::Invalid);
+        }
+        let domain = normalize_domain(domain).ok_or(EmailError::Invalid)?;
+        let local_part = if policy.fold_local_part_case {
+            local_part.to_lowercase()
         } else {
-            Err("invalid email".to_string())
+            local_part.to_owned()
+        };
+
+        let email = format!("{}@{}", local_part, domain);
+        if email.len() > MAX_EMAIL_LENGTH {
+            return Err(EmailError::Invalid);
         }
+        Ok(Email(email))
+    }
+
+    /// Parses the email of an account being created, which must also be at a
+    /// domain the policy accepts.
+    pub fn parse_new(email: &str) -> Result<Email, EmailError> {
+        let email = Self::parse(email)?;
+        EMAIL_POLICY.check_domain(email.domain())?;
+        Ok(email)
+    }
+
+    pub fn domain(&self) -> &str {
+        self.0.rsplit_once('@').map_or("", |(_, domain)| domain)
+    }
+}
+
+/// Lowercases and converts the domain to punycode, returning `None` unless
+/// it's a valid hostname with at least two labels.
+pub(crate) fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = idna::domain_to_ascii(domain).ok()?;
+    let valid = domain.len() <= MAX_DOMAIN_LENGTH
+        && domain.contains('.')
+        && domain.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= MAX_LABEL_LENGTH
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    valid.then_some(domain)
+}
+
+// A dot-string: atoms of letters, digits and the specials RFC 5322 allows,
+// separated by single dots. Quoted local parts are technically valid but
+// not accepted, since hardly anyone has one and they're a common way to
+// smuggle odd characters through. Non-ASCII letters are allowed, per RFC 6531.
+fn is_valid_local_part(local_part: &str) -> bool {
+    local_part.len() <= MAX_LOCAL_PART_LENGTH
+        && local_part.split('.').all(|atom| {
+            !atom.is_empty()
+                && atom
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "!#$%&'*+-/=?^_`{|}~".contains(c))
+        })
+}
+
+// Stored emails may predate normalization, so they're normalized as they're
+// read back, and kept as they were if they no longer parse.
+impl<'de> Deserialize<'de> for Email {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let email = String::deserialize(deserializer)?;
+        Ok(Email::parse(&email).unwrap_or(Email(email)))
     }
 }
 
@@ -60,7 +165,7 @@ mod tests {
         let email = "invalid-email";
         let result = Email::parse(email);
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "invalid email");
+        assert_eq!(result.unwrap_err(), EmailError::Invalid);
     }
 
     #[test]
@@ -70,20 +175,68 @@ mod tests {
             "withoutatsign.domain.com",
             "",
             " ",
-            "spaces in@email.com",  // Note: This would actually be valid with the current implementation
-            "user@",                // Note: This would actually be valid with the current implementation
-            "@example.com",         // Note: This would actually be valid with the current implementation
+            "spaces in@email.com",
+            "user@",
+            "@example.com",
+            "user@localhost",
+            ".leading@example.com",
+            "trailing.@example.com",
+            "double..dot@example.com",
+            "\"quoted\"@example.com",
+            "user@-example.com",
+            "user@example..com",
+            "user@exa_mple.com",
         ];
 
         for email in invalid_emails {
-            if email.contains('@') {
-                // Skip emails that contain '@' since they would pass the current validation
-                continue;
-            }
             let result = Email::parse(email);
             assert!(result.is_err(), "Email '{}' should be invalid", email);
-            assert_eq!(result.unwrap_err(), "invalid email");
+            assert_eq!(result.unwrap_err(), EmailError::Invalid);
+        }
+    }
+
+    #[test]
+    fn test_length_limits() {
+        let local_part = "a".repeat(64);
+        assert!(Email::parse(&format!("{}@example.com", local_part)).is_ok());
+        assert!(Email::parse(&format!("a{}@example.com", local_part)).is_err());
+
+        let label = "b".repeat(64);
+        assert!(Email::parse(&format!("user@{}.com", label)).is_err());
+        let domain = vec!["c".repeat(60); 4].join(".");
+        assert!(Email::parse(&format!("{}@{}", local_part, domain)).is_err());
+    }
+
+    #[test]
+    fn test_parse_normalizes() {
+        let cases = [
+            ("  Bob.Smith@Example.COM ", "bob.smith@example.com"),
+            ("user@BÜCHER.de", "user@xn--bcher-kva.de"),
+            ("José@example.com", "josé@example.com"),
+        ];
+        for (email, normalized) in cases {
+            assert_eq!(Email::parse(email).unwrap().as_ref(), normalized);
         }
+        assert_eq!(Email::parse("Bob@example.com"), Email::parse("bob@EXAMPLE.com"));
+    }
+
+    #[test]
+    fn test_local_part_case_folding_is_optional() {
+        let policy = EmailPolicy {
+            fold_local_part_case: false,
+            ..EmailPolicy::default()
+        };
+        let email = Email::parse_with("Bob@Example.com", &policy).unwrap();
+        assert_eq!(email.as_ref(), "Bob@example.com");
+    }
+
+    #[test]
+    fn test_stored_emails_are_normalized_when_read() {
+        let email: Email = serde_json::from_str("\"Bob@Example.com\"").unwrap();
+        assert_eq!(email.as_ref(), "bob@example.com");
+        // Ones that no longer parse are kept, so their users can still be found.
+        let email: Email = serde_json::from_str("\"odd..user@example.com\"").unwrap();
+        assert_eq!(email.as_ref(), "odd..user@example.com");
     }
 
     #[test]
@@ -91,7 +244,7 @@ mod tests {
         let email = "plainaddress";
         let result = Email::parse(email);
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "invalid email");
+        assert_eq!(result.unwrap_err().to_string(), "invalid email");
     }
 
     #[test]
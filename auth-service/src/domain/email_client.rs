@@ -0,0 +1,6 @@
+use crate::domain::Email;
+
+#[async_trait::async_trait]
+pub trait EmailClient: Send + Sync {
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<(), String>;
+}
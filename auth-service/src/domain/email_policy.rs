@@ -0,0 +1,120 @@
+use crate::domain::{normalize_domain, EmailError};
+
+const DISPOSABLE_DOMAINS: &str = include_str!("disposable_domains.txt");
+
+/// How email addresses are normalized, and which domains accounts may be
+/// created at. Lists hold normalized domains, and each also covers its
+/// subdomains.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailPolicy {
+    /// Treat local parts as case-insensitive, as nearly every mail server
+    /// does, although RFC 5321 leaves it up to them.
+    pub fold_local_part_case: bool,
+    /// When not empty, only these domains are accepted.
+    pub allowed_domains: Vec<String>,
+    pub denied_domains: Vec<String>,
+    /// Refuse domains on the bundled list of disposable email services.
+    pub block_disposable: bool,
+}
+
+impl Default for EmailPolicy {
+    fn default() -> Self {
+        Self {
+            fold_local_part_case: true,
+            allowed_domains: Vec::new(),
+            denied_domains: Vec::new(),
+            block_disposable: true,
+        }
+    }
+}
+
+impl EmailPolicy {
+    /// Normalizes a list of domains the way `Email::parse` normalizes the
+    /// domains it checks against them.
+    pub fn parse_domains(domains: &str) -> Result<Vec<String>, String> {
+        domains
+            .split(',')
+            .map(str::trim)
+            .filter(|domain| !domain.is_empty())
+            .map(|domain| normalize_domain(domain).ok_or(format!("invalid domain: {}", domain)))
+            .collect()
+    }
+
+    pub fn check_domain(&self, domain: &str) -> Result<(), EmailError> {
+        if !self.allowed_domains.is_empty() && !is_listed(domain, &self.allowed_domains) {
+            return Err(EmailError::DomainNotAllowed);
+        }
+        if is_listed(domain, &self.denied_domains) {
+            return Err(EmailError::DomainNotAllowed);
+        }
+        let disposable = DISPOSABLE_DOMAINS
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        if self.block_disposable && is_listed(domain, disposable) {
+            return Err(EmailError::Disposable);
+        }
+        Ok(())
+    }
+}
+
+fn is_listed<'a>(domain: &str, list: impl IntoIterator<Item = impl AsRef<str> + 'a>) -> bool {
+    list.into_iter().any(|entry| {
+        let entry = entry.as_ref();
+        domain == entry
+            || domain
+                .strip_suffix(entry)
+                .is_some_and(|subdomain| subdomain.ends_with('.'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowed_domains_limit_signups() {
+        let policy = EmailPolicy {
+            allowed_domains: EmailPolicy::parse_domains("example.com, Corp.Example.ORG").unwrap(),
+            ..EmailPolicy::default()
+        };
+        assert!(policy.check_domain("example.com").is_ok());
+        assert!(policy.check_domain("mail.example.com").is_ok());
+        assert!(policy.check_domain("corp.example.org").is_ok());
+        assert_eq!(policy.check_domain("badexample.com"), Err(EmailError::DomainNotAllowed));
+        assert_eq!(policy.check_domain("example.org"), Err(EmailError::DomainNotAllowed));
+    }
+
+    #[test]
+    fn test_denied_domains_are_refused() {
+        let policy = EmailPolicy {
+            denied_domains: vec!["competitor.com".to_owned()],
+            ..EmailPolicy::default()
+        };
+        assert_eq!(policy.check_domain("competitor.com"), Err(EmailError::DomainNotAllowed));
+        assert_eq!(policy.check_domain("eu.competitor.com"), Err(EmailError::DomainNotAllowed));
+        assert!(policy.check_domain("example.com").is_ok());
+    }
+
+    #[test]
+    fn test_disposable_domains_are_blocked_by_default() {
+        let policy = EmailPolicy::default();
+        assert_eq!(policy.check_domain("mailinator.com"), Err(EmailError::Disposable));
+        assert_eq!(policy.check_domain("xyz.yopmail.com"), Err(EmailError::Disposable));
+
+        let policy = EmailPolicy {
+            block_disposable: false,
+            ..policy
+        };
+        assert!(policy.check_domain("mailinator.com").is_ok());
+    }
+
+    #[test]
+    fn test_parse_domains_rejects_invalid_entries() {
+        assert_eq!(
+            EmailPolicy::parse_domains("BÜCHER.de,,").unwrap(),
+            vec!["xn--bcher-kva.de".to_owned()]
+        );
+        assert!(EmailPolicy::parse_domains("not a domain").is_err());
+    }
+}
@@ -1,5 +1,82 @@
+use crate::domain::{EmailError, PasswordError};
+
+#[derive(Debug)]
 pub enum AuthAPIError {
     UserAlreadyExists,
     InvalidCredentials,
+    /// A password being set breaks the password policy.
+    InvalidPassword(PasswordError),
+    /// An account can't be created at the email's domain.
+    EmailNotAllowed(EmailError),
+    /// A request field that isn't valid, with the reason.
+    InvalidInput(String),
+    IncorrectCredentials,
+    MissingToken,
+    InvalidToken,
+    /// A sign-in link bound to one browser was followed in another.
+    WrongBrowser,
+    TotpAlreadyEnrolled,
+    TotpNotEnrolled,
+    TwoFANotEnabled,
+    PasskeyAlreadyRegistered,
+    PasskeyNotRegistered,
+    SessionNotFound,
+    UserNotFound,
+    ServiceAccountNotFound,
+    AccountDisabled,
+    AccountLocked,
+    PasswordResetRequired,
+    Forbidden,
     UnexpectedError,
-}
\ No newline at end of file
+}
+
+impl From<EmailError> for AuthAPIError {
+    fn from(e: EmailError) -> Self {
+        match e {
+            EmailError::Invalid => AuthAPIError::InvalidCredentials,
+            e => AuthAPIError::EmailNotAllowed(e),
+        }
+    }
+}
+
+impl From<PasswordError> for AuthAPIError {
+    fn from(e: PasswordError) -> Self {
+        AuthAPIError::InvalidPassword(e)
+    }
+}
+
+/// Error codes from RFC 6749 section 5.2, RFC 6750 section 3.1 and OpenID
+/// Connect Core section 3.1.2.6,
+/// returned by the OAuth endpoints in place of `AuthAPIError`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OAuthError {
+    InvalidRequest,
+    InvalidClient,
+    InvalidGrant,
+    UnauthorizedClient,
+    UnsupportedGrantType,
+    UnsupportedResponseType,
+    InvalidScope,
+    LoginRequired,
+    InvalidToken,
+    InsufficientScope,
+    ServerError,
+}
+
+impl OAuthError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            OAuthError::InvalidRequest => "invalid_request",
+            OAuthError::InvalidClient => "invalid_client",
+            OAuthError::InvalidGrant => "invalid_grant",
+            OAuthError::UnauthorizedClient => "unauthorized_client",
+            OAuthError::UnsupportedGrantType => "unsupported_grant_type",
+            OAuthError::UnsupportedResponseType => "unsupported_response_type",
+            OAuthError::InvalidScope => "invalid_scope",
+            OAuthError::LoginRequired => "login_required",
+            OAuthError::InvalidToken => "invalid_token",
+            OAuthError::InsufficientScope => "insufficient_scope",
+            OAuthError::ServerError => "server_error",
+        }
+    }
+}
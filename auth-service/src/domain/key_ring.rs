@@ -0,0 +1,638 @@
+use base64::{
+    engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD},
+    Engine,
+};
+use ed25519_dalek::pkcs8::{DecodePrivateKey, EncodePrivateKey};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use rand::rngs::OsRng;
+use rsa::pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey};
+use rsa::traits::PublicKeyParts;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const RSA_KEY_BITS: usize = 2048;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SigningAlgorithm {
+    RS256,
+    EdDSA,
+}
+
+impl SigningAlgorithm {
+    pub fn parse(algorithm: &str) -> Result<Self, String> {
+        match algorithm {
+            "RS256" => Ok(SigningAlgorithm::RS256),
+            "EdDSA" => Ok(SigningAlgorithm::EdDSA),
+            _ => Err(format!("unsupported signing algorithm: {}", algorithm)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigningAlgorithm::RS256 => "RS256",
+            SigningAlgorithm::EdDSA => "EdDSA",
+        }
+    }
+
+    pub fn jwt_algorithm(&self) -> Algorithm {
+        match self {
+            SigningAlgorithm::RS256 => Algorithm::RS256,
+            SigningAlgorithm::EdDSA => Algorithm::EdDSA,
+        }
+    }
+}
+
+/// Key pair used to sign tokens, identified in token headers by its `kid`.
+#[derive(Clone)]
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: SigningAlgorithm,
+    /// Unix timestamp from which the key signs new tokens. Until then it is
+    /// only published, so verifiers can fetch it before they first see it.
+    pub activates_at: i64,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+    /// PKCS#1 DER for RS256 and PKCS#8 DER for EdDSA, kept to persist the key.
+    private_key_der: Vec<u8>,
+}
+
+impl SigningKey {
+    pub fn generate(algorithm: SigningAlgorithm, activates_at: i64) -> Result<Self, String> {
+        let der = match algorithm {
+            SigningAlgorithm::RS256 => {
+                let private_key = rsa::RsaPrivateKey::new(&mut OsRng, RSA_KEY_BITS)
+                    .map_err(|e| e.to_string())?;
+                private_key.to_pkcs1_der().map_err(|e| e.to_string())?.as_bytes().to_vec()
+            }
+            SigningAlgorithm::EdDSA => ed25519_dalek::SigningKey::generate(&mut OsRng)
+                .to_pkcs8_der()
+                .map_err(|e| e.to_string())?
+                .as_bytes()
+                .to_vec(),
+        };
+        let kid = uuid::Uuid::new_v4().simple().to_string();
+        Self::from_der(kid, algorithm, activates_at, der)
+    }
+
+    fn from_der(
+        kid: String,
+        algorithm: SigningAlgorithm,
+        activates_at: i64,
+        der: Vec<u8>,
+    ) -> Result<Self, String> {
+        let (encoding_key, parameters) = match algorithm {
+            SigningAlgorithm::RS256 => {
+                let private_key =
+                    rsa::RsaPrivateKey::from_pkcs1_der(&der).map_err(|e| e.to_string())?;
+                let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+                    e: URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+                });
+                (EncodingKey::from_rsa_der(&der), parameters)
+            }
+            SigningAlgorithm::EdDSA => {
+                let private_key =
+                    ed25519_dalek::SigningKey::from_pkcs8_der(&der).map_err(|e| e.to_string())?;
+                let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(private_key.verifying_key().as_bytes()),
+                });
+                (EncodingKey::from_ed_der(&der), parameters)
+            }
+        };
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(match algorithm {
+                    SigningAlgorithm::RS256 => KeyAlgorithm::RS256,
+                    SigningAlgorithm::EdDSA => KeyAlgorithm::EdDSA,
+                }),
+                key_id: Some(kid.clone()),
+                ..CommonParameters::default()
+            },
+            algorithm: parameters,
+        };
+        let decoding_key = DecodingKey::from_jwk(&jwk).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            kid,
+            algorithm,
+            activates_at,
+            encoding_key,
+            decoding_key,
+            jwk,
+            private_key_der: der,
+        })
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    /// Public half of the key, as published in the JWKS.
+    pub fn jwk(&self) -> &Jwk {
+        &self.jwk
+    }
+}
+
+/// Timings, in seconds, of the key rotation schedule.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyRotationPolicy {
+    /// How long each key signs tokens before the next one takes over.
+    pub rotation_interval: i64,
+    /// How long a new key is published before it starts signing. Must be
+    /// longer than verifiers cache the JWKS for.
+    pub publish_ahead: i64,
+    /// How long a superseded key stays verifiable. Must cover the lifetime
+    /// of the last tokens it signed.
+    pub retired_key_ttl: i64,
+}
+
+/// The set of keys the service signs and verifies tokens with. Keys are
+/// ordered by activation time; the newest active key signs, while pending
+/// and recently retired keys are published for verification.
+///
+/// A key ring opened from a file writes itself back whenever its keys change,
+/// so tokens survive restarts.
+#[derive(Clone)]
+pub struct KeyRing {
+    algorithm: SigningAlgorithm,
+    policy: KeyRotationPolicy,
+    keys: Vec<SigningKey>,
+    path: Option<PathBuf>,
+}
+
+// How a key ring is written to its file. The rotation policy comes from the
+// configuration instead, so it can change between restarts.
+#[derive(Serialize, Deserialize)]
+struct KeyRingFile {
+    algorithm: String,
+    keys: Vec<KeyFileEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyFileEntry {
+    kid: String,
+    activates_at: i64,
+    /// Base64 of the private key's DER.
+    private_key: String,
+}
+
+impl KeyRing {
+    pub fn new(
+        algorithm: SigningAlgorithm,
+        policy: KeyRotationPolicy,
+        now: i64,
+    ) -> Result<Self, String> {
+        check_policy(&policy)?;
+        Ok(Self {
+            algorithm,
+            policy,
+            keys: vec![SigningKey::generate(algorithm, now)?],
+            path: None,
+        })
+    }
+
+    /// Loads the keys saved at `path`, or creates a key ring there if the
+    /// file doesn't exist yet. A file that can't be read, or that holds keys
+    /// for another algorithm, is an error rather than a reason to start over.
+    pub fn open(
+        path: impl Into<PathBuf>,
+        algorithm: SigningAlgorithm,
+        policy: KeyRotationPolicy,
+        now: i64,
+    ) -> Result<Self, String> {
+        check_policy(&policy)?;
+        let path = path.into();
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key_ring = Self {
+                    path: Some(path),
+                    ..Self::new(algorithm, policy, now)?
+                };
+                key_ring.save()?;
+                return Ok(key_ring);
+            }
+            Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+        };
+
+        let file: KeyRingFile = serde_json::from_slice(&contents)
+            .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+        if file.algorithm != algorithm.as_str() {
+            return Err(format!(
+                "{} holds {} keys, but {} is configured",
+                path.display(),
+                file.algorithm,
+                algorithm.as_str()
+            ));
+        }
+        let mut keys = file
+            .keys
+            .into_iter()
+            .map(|entry| {
+                let der = STANDARD
+                    .decode(&entry.private_key)
+                    .map_err(|e| format!("key {} is not valid base64: {}", entry.kid, e))?;
+                SigningKey::from_der(entry.kid.clone(), algorithm, entry.activates_at, der)
+                    .map_err(|e| format!("key {} can't be loaded: {}", entry.kid, e))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if keys.is_empty() {
+            return Err(format!("{} holds no keys", path.display()));
+        }
+        keys.sort_by_key(|key| key.activates_at);
+
+        Ok(Self {
+            algorithm,
+            policy,
+            keys,
+            path: Some(path),
+        })
+    }
+
+    // Replaces the file atomically, readable by the owner only.
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let file = KeyRingFile {
+            algorithm: self.algorithm.as_str().to_owned(),
+            keys: self
+                .keys
+                .iter()
+                .map(|key| KeyFileEntry {
+                    kid: key.kid.clone(),
+                    activates_at: key.activates_at,
+                    private_key: STANDARD.encode(&key.private_key_der),
+                })
+                .collect(),
+        };
+        let contents = serde_json::to_vec_pretty(&file).expect("key rings always serialize");
+        write_private_file(path, &contents)
+            .map_err(|e| format!("failed to save signing keys to {}: {}", path.display(), e))
+    }
+
+    pub fn algorithm(&self) -> SigningAlgorithm {
+        self.algorithm
+    }
+
+    pub fn signing_key(&self, now: i64) -> Option<&SigningKey> {
+        self.keys.iter().rev().find(|key| key.activates_at <= now)
+    }
+
+    /// Looks up the key a token names in its header. Keys that have been
+    /// published but not activated yet can't have signed anything.
+    pub fn verification_key(&self, kid: &str, now: i64) -> Option<&SigningKey> {
+        self.keys
+            .iter()
+            .find(|key| key.kid == kid && key.activates_at <= now)
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().map(|key| key.jwk.clone()).collect(),
+        }
+    }
+
+    /// When `rotate` at `now` would publish a successor to the signing key,
+    /// the time that successor activates.
+    pub fn successor_due(&self, now: i64) -> Option<i64> {
+        if self.keys.iter().any(|key| key.activates_at > now) {
+            return None;
+        }
+        let due_at = self.signing_key(now)?.activates_at + self.policy.rotation_interval;
+        (now >= due_at - self.policy.publish_ahead)
+            .then(|| due_at.max(now + self.policy.publish_ahead))
+    }
+
+    /// Drops keys whose retirement period is over and, once the signing key
+    /// is due for replacement, publishes its successor.
+    pub fn rotate(&mut self, now: i64) -> Result<(), String> {
+        let successor = self
+            .successor_due(now)
+            .map(|activates_at| SigningKey::generate(self.algorithm, activates_at))
+            .transpose()?;
+        self.rotate_with(now, successor)
+    }
+
+    /// Like `rotate`, but publishes `successor` rather than generating one.
+    /// RSA keys take a while to generate, so callers that share the key ring
+    /// generate the key before locking it, once `successor_due` says so. The
+    /// key is given the activation time due by the time it's published, and
+    /// dropped if none is due any more.
+    pub fn rotate_with(&mut self, now: i64, successor: Option<SigningKey>) -> Result<(), String> {
+        // A key retires when its successor activates. Keys are ordered, so
+        // only the oldest ones can have outlived their retirement period.
+        let key_count = self.keys.len();
+        while self.keys.len() > 1
+            && self.keys[1].activates_at + self.policy.retired_key_ttl <= now
+        {
+            self.keys.remove(0);
+        }
+
+        if self.signing_key(now).is_none() {
+            return Err("no signing key".to_owned());
+        }
+        if let Some(activates_at) = self.successor_due(now) {
+            let successor = match successor.filter(|key| key.algorithm == self.algorithm) {
+                Some(key) => SigningKey { activates_at, ..key },
+                None => SigningKey::generate(self.algorithm, activates_at)?,
+            };
+            self.keys.push(successor);
+        }
+        if self.keys.len() != key_count {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Publishes a successor to the signing key ahead of schedule, e.g. when
+    /// the current key may have leaked. It still waits `publish_ahead` before
+    /// signing, and a successor that's already published is returned as is.
+    pub fn rotate_now(&mut self, now: i64) -> Result<&SigningKey, String> {
+        let successor = SigningKey::generate(self.algorithm, now)?;
+        self.rotate_now_with(now, successor)
+    }
+
+    /// Like `rotate_now`, but publishes `successor`, generated before the key
+    /// ring was locked, rather than generating one.
+    pub fn rotate_now_with(
+        &mut self,
+        now: i64,
+        successor: SigningKey,
+    ) -> Result<&SigningKey, String> {
+        if !self.keys.iter().any(|key| key.activates_at > now) {
+            if successor.algorithm != self.algorithm {
+                return Err("the successor is for another algorithm".to_owned());
+            }
+            self.keys.push(SigningKey {
+                activates_at: now + self.policy.publish_ahead,
+                ..successor
+            });
+            self.save()?;
+        }
+        Ok(self.keys.last().expect("key rings always hold a key"))
+    }
+}
+
+fn check_policy(policy: &KeyRotationPolicy) -> Result<(), String> {
+    if policy.publish_ahead >= policy.rotation_interval {
+        return Err("keys must be published for less than the rotation interval".to_owned());
+    }
+    Ok(())
+}
+
+/// Replaces the file at `path` in one step, readable only by its owner, so a
+/// crash leaves either the old contents or the new.
+pub(crate) fn write_private_file(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut temp = options.open(&temp_path)?;
+    temp.write_all(contents)?;
+    temp.sync_all()?;
+    drop(temp);
+    fs::rename(&temp_path, path)?;
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+    const POLICY: KeyRotationPolicy = KeyRotationPolicy {
+        rotation_interval: 1000,
+        publish_ahead: 100,
+        retired_key_ttl: 50,
+    };
+
+    fn kids(key_ring: &KeyRing) -> Vec<String> {
+        key_ring.keys.iter().map(|key| key.kid.clone()).collect()
+    }
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("keys-{}.json", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn test_parse_signing_algorithm() {
+        assert_eq!(SigningAlgorithm::parse("RS256"), Ok(SigningAlgorithm::RS256));
+        assert_eq!(SigningAlgorithm::parse("EdDSA"), Ok(SigningAlgorithm::EdDSA));
+        assert!(SigningAlgorithm::parse("HS256").is_err());
+    }
+
+    #[test]
+    fn test_generated_keys_sign_and_verify() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Claims {
+            sub: String,
+            exp: usize,
+        }
+
+        for algorithm in [SigningAlgorithm::EdDSA, SigningAlgorithm::RS256] {
+            let key = SigningKey::generate(algorithm, NOW).unwrap();
+            let mut header = jsonwebtoken::Header::new(algorithm.jwt_algorithm());
+            header.kid = Some(key.kid.clone());
+            let claims = Claims {
+                sub: "test@example.com".to_owned(),
+                exp: usize::MAX,
+            };
+
+            let token = jsonwebtoken::encode(&header, &claims, key.encoding_key()).unwrap();
+            let decoding_key = DecodingKey::from_jwk(key.jwk()).unwrap();
+            let result = jsonwebtoken::decode::<Claims>(
+                &token,
+                &decoding_key,
+                &jsonwebtoken::Validation::new(algorithm.jwt_algorithm()),
+            );
+            assert!(result.is_ok(), "Failed for {:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_new_key_ring_signs_immediately() {
+        let key_ring = KeyRing::new(SigningAlgorithm::EdDSA, POLICY, NOW).unwrap();
+        let key = key_ring.signing_key(NOW).unwrap();
+
+        assert_eq!(key_ring.verification_key(&key.kid, NOW).unwrap().kid, key.kid);
+        assert_eq!(key_ring.jwks().keys.len(), 1);
+    }
+
+    #[test]
+    fn test_new_key_ring_rejects_invalid_policy() {
+        let policy = KeyRotationPolicy {
+            publish_ahead: POLICY.rotation_interval,
+            ..POLICY
+        };
+        assert!(KeyRing::new(SigningAlgorithm::EdDSA, policy, NOW).is_err());
+    }
+
+    #[test]
+    fn test_rotate_publishes_next_key_before_it_signs() {
+        let mut key_ring = KeyRing::new(SigningAlgorithm::EdDSA, POLICY, NOW).unwrap();
+        let first = key_ring.signing_key(NOW).unwrap().kid.clone();
+
+        key_ring.rotate(NOW + 899).unwrap();
+        assert_eq!(kids(&key_ring), vec![first.clone()]);
+
+        key_ring.rotate(NOW + 900).unwrap();
+        let [_, second] = &kids(&key_ring)[..] else {
+            panic!("Expected the next key to be published");
+        };
+        assert_eq!(key_ring.keys[1].activates_at, NOW + 1000);
+
+        // Published but not yet signing, nor accepted for verification.
+        assert_eq!(key_ring.signing_key(NOW + 999).unwrap().kid, first);
+        assert!(key_ring.verification_key(second, NOW + 999).is_none());
+        assert_eq!(key_ring.jwks().keys.len(), 2);
+
+        assert_eq!(&key_ring.signing_key(NOW + 1000).unwrap().kid, second);
+    }
+
+    #[test]
+    fn test_rotate_with_publishes_the_key_generated_beforehand() {
+        let mut key_ring = KeyRing::new(SigningAlgorithm::EdDSA, POLICY, NOW).unwrap();
+        assert_eq!(key_ring.successor_due(NOW + 899), None);
+        let unneeded = SigningKey::generate(SigningAlgorithm::EdDSA, NOW).unwrap();
+        key_ring.rotate_with(NOW + 899, Some(unneeded)).unwrap();
+        assert_eq!(key_ring.keys.len(), 1);
+
+        assert_eq!(key_ring.successor_due(NOW + 900), Some(NOW + 1000));
+        let successor = SigningKey::generate(SigningAlgorithm::EdDSA, NOW).unwrap();
+        let kid = successor.kid.clone();
+        key_ring.rotate_with(NOW + 900, Some(successor)).unwrap();
+        assert_eq!(key_ring.keys[1].kid, kid);
+        assert_eq!(key_ring.keys[1].activates_at, NOW + 1000);
+        assert_eq!(key_ring.successor_due(NOW + 900), None);
+    }
+
+    #[test]
+    fn test_rotate_keeps_retired_key_until_its_tokens_expire() {
+        let mut key_ring = KeyRing::new(SigningAlgorithm::EdDSA, POLICY, NOW).unwrap();
+        let first = key_ring.signing_key(NOW).unwrap().kid.clone();
+        key_ring.rotate(NOW + 900).unwrap();
+
+        key_ring.rotate(NOW + 1049).unwrap();
+        assert!(key_ring.verification_key(&first, NOW + 1049).is_some());
+
+        key_ring.rotate(NOW + 1050).unwrap();
+        assert!(key_ring.verification_key(&first, NOW + 1050).is_none());
+        assert_eq!(key_ring.jwks().keys.len(), 1);
+    }
+
+    #[test]
+    fn test_rotate_now_publishes_successor_early() {
+        let mut key_ring = KeyRing::new(SigningAlgorithm::EdDSA, POLICY, NOW).unwrap();
+        let first = key_ring.signing_key(NOW).unwrap().kid.clone();
+
+        let second = key_ring.rotate_now(NOW + 10).unwrap().clone();
+        assert_eq!(second.activates_at, NOW + 110);
+        assert_eq!(key_ring.signing_key(NOW + 109).unwrap().kid, first);
+        assert_eq!(key_ring.signing_key(NOW + 110).unwrap().kid, second.kid);
+
+        // Until it activates, asking again returns the same successor.
+        assert_eq!(key_ring.rotate_now(NOW + 20).unwrap().kid, second.kid);
+        assert_eq!(key_ring.jwks().keys.len(), 2);
+
+        // The regular schedule carries on from the new key.
+        key_ring.rotate(NOW + 110 + 899).unwrap();
+        assert_eq!(key_ring.jwks().keys.len(), 1);
+    }
+
+    #[test]
+    fn test_open_keeps_keys_across_restarts() {
+        for algorithm in [SigningAlgorithm::EdDSA, SigningAlgorithm::RS256] {
+            let path = temp_path();
+            let mut key_ring = KeyRing::open(&path, algorithm, POLICY, NOW).unwrap();
+            key_ring.rotate_now(NOW + 10).unwrap();
+
+            let reopened = KeyRing::open(&path, algorithm, POLICY, NOW + 20).unwrap();
+            assert_eq!(kids(&reopened), kids(&key_ring));
+            assert_eq!(reopened.jwks(), key_ring.jwks());
+            assert_eq!(reopened.keys[1].activates_at, NOW + 110);
+
+            // Tokens signed before the restart still verify.
+            let key = key_ring.signing_key(NOW + 20).unwrap();
+            let mut header = jsonwebtoken::Header::new(algorithm.jwt_algorithm());
+            header.kid = Some(key.kid.clone());
+            let token =
+                jsonwebtoken::encode(&header, &serde_json::json!({ "exp": usize::MAX }), key.encoding_key())
+                    .unwrap();
+            let verification_key = reopened.verification_key(&key.kid, NOW + 20).unwrap();
+            let result = jsonwebtoken::decode::<serde_json::Value>(
+                &token,
+                verification_key.decoding_key(),
+                &jsonwebtoken::Validation::new(algorithm.jwt_algorithm()),
+            );
+            assert!(result.is_ok(), "Failed for {:?}", algorithm);
+            fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_rotate_saves_key_changes() {
+        let path = temp_path();
+        let mut key_ring = KeyRing::open(&path, SigningAlgorithm::EdDSA, POLICY, NOW).unwrap();
+
+        key_ring.rotate(NOW + 900).unwrap();
+        let reopened = KeyRing::open(&path, SigningAlgorithm::EdDSA, POLICY, NOW + 900).unwrap();
+        assert_eq!(kids(&reopened), kids(&key_ring));
+
+        key_ring.rotate(NOW + 1050).unwrap();
+        let reopened = KeyRing::open(&path, SigningAlgorithm::EdDSA, POLICY, NOW + 1050).unwrap();
+        assert_eq!(kids(&reopened), kids(&key_ring));
+        assert_eq!(reopened.keys.len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_rejects_keys_it_cannot_load() {
+        let path = temp_path();
+        KeyRing::open(&path, SigningAlgorithm::EdDSA, POLICY, NOW).unwrap();
+        assert!(KeyRing::open(&path, SigningAlgorithm::RS256, POLICY, NOW).is_err());
+
+        fs::write(&path, "not json").unwrap();
+        assert!(KeyRing::open(&path, SigningAlgorithm::EdDSA, POLICY, NOW).is_err());
+
+        let file = serde_json::json!({
+            "algorithm": "EdDSA",
+            "keys": [{ "kid": "broken", "activates_at": NOW, "private_key": "AAAA" }],
+        });
+        fs::write(&path, file.to_string()).unwrap();
+        assert!(KeyRing::open(&path, SigningAlgorithm::EdDSA, POLICY, NOW).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_after_downtime_still_publishes_ahead() {
+        let mut key_ring = KeyRing::new(SigningAlgorithm::EdDSA, POLICY, NOW).unwrap();
+        let first = key_ring.signing_key(NOW).unwrap().kid.clone();
+
+        key_ring.rotate(NOW + 5000).unwrap();
+
+        assert_eq!(key_ring.keys[1].activates_at, NOW + 5100);
+        assert_eq!(key_ring.signing_key(NOW + 5099).unwrap().kid, first);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::domain::Email;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn is_token(value: &str) -> bool {
+    URL_SAFE_NO_PAD.decode(value).is_ok_and(|bytes| bytes.len() == 32)
+}
+
+/// Names a sign-in link, as its token's `jti`. The link is only honoured
+/// while its id is stored, which it stops being once followed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MagicLinkId(String);
+
+impl MagicLinkId {
+    pub fn generate() -> Self {
+        MagicLinkId(random_token())
+    }
+
+    pub fn parse(id: String) -> Result<Self, String> {
+        if is_token(&id) {
+            Ok(MagicLinkId(id))
+        } else {
+            Err("invalid magic link id".to_string())
+        }
+    }
+}
+
+impl AsRef<str> for MagicLinkId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A sign-in link that was sent and hasn't been followed yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MagicLink {
+    pub email: Email,
+    pub expires_at: i64,
+}
+
+impl MagicLink {
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+/// Secret kept in a cookie of the browser that asked for a link, so that the
+/// link only works there. The link carries just its digest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowserBinding(String);
+
+impl BrowserBinding {
+    pub fn generate() -> Self {
+        BrowserBinding(random_token())
+    }
+
+    pub fn parse(binding: &str) -> Result<Self, String> {
+        if is_token(binding) {
+            Ok(BrowserBinding(binding.to_owned()))
+        } else {
+            Err("invalid browser binding".to_string())
+        }
+    }
+
+    pub fn digest(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.0.as_bytes()))
+    }
+}
+
+impl AsRef<str> for BrowserBinding {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_round_trip_and_reject_garbage() {
+        let id = MagicLinkId::generate();
+        assert_eq!(MagicLinkId::parse(id.as_ref().to_owned()), Ok(id));
+        assert!(MagicLinkId::parse("".to_owned()).is_err());
+        assert!(MagicLinkId::parse("not-32-bytes".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_binding_digest_identifies_binding() {
+        let binding = BrowserBinding::generate();
+        let parsed = BrowserBinding::parse(binding.as_ref()).unwrap();
+        assert_eq!(parsed.digest(), binding.digest());
+        assert_ne!(BrowserBinding::generate().digest(), binding.digest());
+    }
+}
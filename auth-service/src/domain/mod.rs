@@ -1,11 +1,45 @@
 mod user;
 mod error;
+mod audit;
+mod breached_passwords;
 mod data_stores;
 mod email;
+mod email_client;
+mod email_policy;
+mod key_ring;
+mod magic_link;
+mod oidc;
 mod password;
+mod password_hash;
+mod password_policy;
+mod password_strength;
+mod recovery_code;
+mod role;
+mod service_account;
+mod session;
+mod totp;
+mod user_export;
+mod webauthn;
 
 pub use data_stores::*;
 pub use error::*;
+pub use audit::*;
+pub use breached_passwords::*;
 pub use user::*;
 pub use email::*;
+pub use email_client::*;
+pub use email_policy::*;
+pub use key_ring::*;
+pub use magic_link::*;
+pub use oidc::*;
 pub use password::*;
+pub use password_hash::*;
+pub use password_policy::*;
+pub use password_strength::*;
+pub use recovery_code::*;
+pub use role::*;
+pub use service_account::*;
+pub use session::*;
+pub use totp::*;
+pub use user_export::*;
+pub use webauthn::*;
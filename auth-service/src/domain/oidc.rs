@@ -0,0 +1,261 @@
+use crate::domain::Email;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const OIDC_SCOPE: &str = "openid";
+pub const PKCE_METHOD_S256: &str = "S256";
+
+// RFC 7636 section 4.1 bounds on the length of a code verifier.
+const PKCE_VERIFIER_MIN_LENGTH: usize = 43;
+const PKCE_VERIFIER_MAX_LENGTH: usize = 128;
+
+/// An application allowed to sign users in through the auth service.
+/// Clients without a secret are public (e.g. single page apps) and rely on
+/// PKCE alone to bind the authorization code to the caller.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OidcClient {
+    pub client_id: String,
+    pub name: String,
+    pub client_secret: Option<HashedClientSecret>,
+    pub redirect_uris: Vec<String>,
+}
+
+impl OidcClient {
+    pub fn new(
+        client_id: String,
+        name: String,
+        client_secret: Option<&str>,
+        redirect_uris: Vec<String>,
+    ) -> Self {
+        Self {
+            client_id,
+            name,
+            client_secret: client_secret.map(HashedClientSecret::new),
+            redirect_uris,
+        }
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.client_secret.is_none()
+    }
+
+    /// Redirect URIs are compared exactly, as required by OAuth 2.1.
+    pub fn allows_redirect_uri(&self, redirect_uri: &str) -> bool {
+        self.redirect_uris.iter().any(|uri| uri == redirect_uri)
+    }
+
+    /// Public clients authenticate by omitting a secret; confidential clients
+    /// must present the one they were registered with.
+    pub fn authenticate(&self, secret: Option<&str>) -> bool {
+        match (&self.client_secret, secret) {
+            (None, None) => true,
+            (Some(hash), Some(secret)) => hash.matches(secret),
+            _ => false,
+        }
+    }
+}
+
+/// Entry of the client registry file loaded at startup. Secrets are given in
+/// plain text here and only their hash is kept once loaded.
+#[derive(Debug, Deserialize)]
+pub struct OidcClientConfig {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub name: String,
+    #[serde(rename = "clientSecret", default)]
+    pub client_secret: Option<String>,
+    #[serde(rename = "redirectUris")]
+    pub redirect_uris: Vec<String>,
+}
+
+impl From<OidcClientConfig> for OidcClient {
+    fn from(config: OidcClientConfig) -> Self {
+        OidcClient::new(
+            config.client_id,
+            config.name,
+            config.client_secret.as_deref(),
+            config.redirect_uris,
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HashedClientSecret(String);
+
+impl HashedClientSecret {
+    pub fn new(secret: &str) -> Self {
+        HashedClientSecret(format!("{:x}", Sha256::digest(secret.as_bytes())))
+    }
+
+    pub fn matches(&self, secret: &str) -> bool {
+        *self == Self::new(secret)
+    }
+}
+
+impl AsRef<str> for HashedClientSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Short-lived, single use code handed to the client's redirect URI.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AuthorizationCode(String);
+
+impl AuthorizationCode {
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        AuthorizationCode(URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    pub fn parse(code: String) -> Result<Self, String> {
+        if !code.is_empty() && code.bytes().all(is_unreserved) {
+            Ok(AuthorizationCode(code))
+        } else {
+            Err("invalid authorization code".to_string())
+        }
+    }
+}
+
+impl AsRef<str> for AuthorizationCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// S256 code challenge from RFC 7636. The `plain` method is not supported
+/// since it offers no protection if the authorization request leaks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PkceChallenge(String);
+
+impl PkceChallenge {
+    pub fn parse(challenge: &str, method: &str) -> Result<Self, String> {
+        if method != PKCE_METHOD_S256 {
+            return Err("unsupported code challenge method".to_string());
+        }
+        match URL_SAFE_NO_PAD.decode(challenge) {
+            Ok(digest) if digest.len() == 32 => Ok(PkceChallenge(challenge.to_owned())),
+            _ => Err("invalid code challenge".to_string()),
+        }
+    }
+
+    pub fn from_verifier(verifier: &str) -> Self {
+        PkceChallenge(URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())))
+    }
+
+    pub fn verify(&self, verifier: &str) -> bool {
+        (PKCE_VERIFIER_MIN_LENGTH..=PKCE_VERIFIER_MAX_LENGTH).contains(&verifier.len())
+            && verifier.bytes().all(is_unreserved)
+            && *self == Self::from_verifier(verifier)
+    }
+}
+
+impl AsRef<str> for PkceChallenge {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Everything the token endpoint needs to know about an authorization
+/// request once the user has signed in and the code comes back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizationGrant {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub email: Email,
+    pub scope: String,
+    pub nonce: Option<String>,
+    pub code_challenge: PkceChallenge,
+    /// Unix timestamp after which the code can no longer be redeemed.
+    pub expires_at: i64,
+}
+
+impl AuthorizationGrant {
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+// The unreserved characters of RFC 3986, which is the alphabet of PKCE
+// verifiers and of the codes we hand out.
+fn is_unreserved(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, b'-' | b'.' | b'_' | b'~')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Example from RFC 7636 appendix B.
+    const VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+    const CHALLENGE: &str = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+    fn client(secret: Option<&str>) -> OidcClient {
+        OidcClient::new(
+            "app".to_owned(),
+            "App".to_owned(),
+            secret,
+            vec!["https://app.example.com/callback".to_owned()],
+        )
+    }
+
+    #[test]
+    fn test_pkce_challenge_matches_rfc_example() {
+        assert_eq!(PkceChallenge::from_verifier(VERIFIER).as_ref(), CHALLENGE);
+
+        let challenge = PkceChallenge::parse(CHALLENGE, PKCE_METHOD_S256).unwrap();
+        assert!(challenge.verify(VERIFIER));
+        assert!(!challenge.verify(&VERIFIER.replace('d', "e")));
+    }
+
+    #[test]
+    fn test_pkce_rejects_plain_and_malformed_challenges() {
+        assert!(PkceChallenge::parse(CHALLENGE, "plain").is_err());
+        assert!(PkceChallenge::parse("short", PKCE_METHOD_S256).is_err());
+        assert!(PkceChallenge::parse("not base64!", PKCE_METHOD_S256).is_err());
+    }
+
+    #[test]
+    fn test_pkce_rejects_verifiers_outside_rfc_alphabet_or_length() {
+        for verifier in ["a".repeat(42), "a".repeat(129), format!("{}!", "a".repeat(43))] {
+            let challenge = PkceChallenge::from_verifier(&verifier);
+            assert!(!challenge.verify(&verifier), "Verifier '{}' should be invalid", verifier);
+        }
+    }
+
+    #[test]
+    fn test_redirect_uris_match_exactly() {
+        let client = client(None);
+
+        assert!(client.allows_redirect_uri("https://app.example.com/callback"));
+        assert!(!client.allows_redirect_uri("https://app.example.com/callback/"));
+        assert!(!client.allows_redirect_uri("https://app.example.com/callback?next=/"));
+        assert!(!client.allows_redirect_uri("https://evil.example.com/callback"));
+    }
+
+    #[test]
+    fn test_client_authentication() {
+        let public = client(None);
+        assert!(public.is_public());
+        assert!(public.authenticate(None));
+        assert!(!public.authenticate(Some("secret")));
+
+        let confidential = client(Some("secret"));
+        assert!(!confidential.is_public());
+        assert!(confidential.authenticate(Some("secret")));
+        assert!(!confidential.authenticate(Some("wrong")));
+        assert!(!confidential.authenticate(None));
+        assert_ne!(confidential.client_secret.unwrap().as_ref(), "secret");
+    }
+
+    #[test]
+    fn test_generated_authorization_code_can_be_parsed() {
+        let code = AuthorizationCode::generate();
+        assert_eq!(AuthorizationCode::parse(code.as_ref().to_owned()), Ok(code));
+        assert!(AuthorizationCode::parse("".to_owned()).is_err());
+        assert!(AuthorizationCode::parse("a b".to_owned()).is_err());
+    }
+}
@@ -1,11 +1,22 @@
+use crate::domain::{BreachedPasswords, Email, PasswordPolicy};
+use crate::utils::constants::PASSWORD_POLICY;
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
 #[derive(Debug, PartialEq)]
 pub enum PasswordError {
     Empty,
     TooShort(usize),
+    TooLong(usize),
     MissingUppercase,
     MissingLowercase,
     MissingDigit,
     MissingSpecialChar,
+    SameAsEmail,
+    /// The password appears in a known data breach.
+    Compromised,
+    /// Scored below the policy's minimum strength, with a hint on what to change.
+    TooWeak(&'static str),
 }
 
 impl std::error::Error for PasswordError {}
@@ -15,64 +26,56 @@ impl std::fmt::Display for PasswordError {
         match self {
             PasswordError::Empty => write!(f, "password cannot be empty"),
             PasswordError::TooShort(min_length) => write!(f, "password must be at least {} characters long", min_length),
+            PasswordError::TooLong(max_length) => write!(f, "password must be at most {} characters long", max_length),
             PasswordError::MissingUppercase => write!(f, "password must contain at least one uppercase letter"),
             PasswordError::MissingLowercase => write!(f, "password must contain at least one lowercase letter"),
             PasswordError::MissingDigit => write!(f, "password must contain at least one digit"),
             PasswordError::MissingSpecialChar => write!(f, "password must contain at least one special character"),
+            PasswordError::SameAsEmail => write!(f, "password must not be your email address"),
+            PasswordError::Compromised => write!(f, "password has appeared in a data breach, choose another"),
+            PasswordError::TooWeak(hint) => write!(f, "password is too easy to guess, {}", hint),
         }
     }
 }
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Password(String);
 
 impl Password {
+    /// Parses a password under the configured `PASSWORD_POLICY`, after NFKC
+    /// normalization so that it matches however it's typed in later.
     pub fn parse(password: &str) -> Result<Password, PasswordError> {
-        match Self::is_valid(password) {
-            Ok(_) => {
-                Ok(Password(password.to_string()))
-            }
-            Err(err) => Err(err),
-        }
+        Self::parse_with(password, &PASSWORD_POLICY)
     }
-    
-    fn is_valid(password: &str) -> Result<(), PasswordError> {
-        // Check if password is empty
-        if password.is_empty() {
-            return Err(PasswordError::Empty);
-        }
-
-        // Check if password is at least 8 characters long
-        const MIN_LENGTH: usize = 8;
-        if password.len() < MIN_LENGTH {
-            return Err(PasswordError::TooShort(MIN_LENGTH)); 
-        }
-
-        // Check for at least one uppercase letter
-        let has_uppercase = password.chars().any(|c| c.is_ascii_uppercase());
-        if !has_uppercase {
-            return Err(PasswordError::MissingUppercase);
-        }
 
-        // Check for at least one lowercase letter
-        let has_lowercase = password.chars().any(|c| c.is_ascii_lowercase());
-        if !has_lowercase {
-            return Err(PasswordError::MissingLowercase);
-        }
+    pub fn parse_with(password: &str, policy: &PasswordPolicy) -> Result<Password, PasswordError> {
+        let password = Self::normalize(password);
+        policy.check(password.as_ref())?;
+        Ok(password)
+    }
 
-        // Check for at least one digit
-        let has_digit = password.chars().any(|c| c.is_ascii_digit());
-        if !has_digit {
-            return Err(PasswordError::MissingDigit);
-        }
+    /// Normalizes a password given at login without checking it against any
+    /// policy, since it may have been set under a laxer one.
+    pub fn normalize(password: &str) -> Password {
+        Password(password.nfkc().collect())
+    }
 
-        // Check for at least one special character
-        let has_special = password.chars().any(|c| !c.is_alphanumeric());
-        if !has_special {
-            return Err(PasswordError::MissingSpecialChar);
+    /// Parses a password that's being set for `email`, which unlike one given
+    /// at login must also not be the email itself, known from a data breach,
+    /// or too easy to guess.
+    pub fn parse_new(
+        password: &str,
+        email: &Email,
+        breached: &dyn BreachedPasswords,
+    ) -> Result<Password, PasswordError> {
+        let password = Self::parse(password)?;
+        if breached.occurrences(&password) > 0 {
+            return Err(PasswordError::Compromised);
         }
-        Ok(())
+        PASSWORD_POLICY.check_for_user(password.as_ref(), email)?;
+        Ok(password)
     }
 }
 
@@ -86,6 +89,13 @@ impl AsRef<str> for Password {
 mod tests {
     use super::*;
 
+    fn composition_rules() -> PasswordPolicy {
+        PasswordPolicy {
+            composition_rules: true,
+            ..PasswordPolicy::default()
+        }
+    }
+
     #[test]
     fn test_parse_valid_password() {
         let valid_password = "Passw0rd!";
@@ -110,10 +120,29 @@ mod tests {
         assert_eq!(result.unwrap_err(), PasswordError::TooShort(8));
     }
 
+    #[test]
+    fn test_long_password_error() {
+        let result = Password::parse(&"a".repeat(65));
+        assert_eq!(result.unwrap_err(), PasswordError::TooLong(64));
+    }
+
+    #[test]
+    fn test_password_is_nfkc_normalized() {
+        // A decomposed "é" and a full-width "Ａ" both have simpler equivalents.
+        let password = Password::parse("caf\u{65}\u{301}\u{FF21}bcdefg").unwrap();
+        assert_eq!(password.as_ref(), "caf\u{e9}Abcdefg");
+    }
+
+    #[test]
+    fn test_normalize_ignores_the_policy() {
+        let password = Password::normalize("P\u{FF57}1!");
+        assert_eq!(password.as_ref(), "Pw1!");
+    }
+
     #[test]
     fn test_password_without_uppercase_error() {
         let no_uppercase = "password1!";
-        let result = Password::parse(no_uppercase);
+        let result = Password::parse_with(no_uppercase, &composition_rules());
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), PasswordError::MissingUppercase);
     }
@@ -121,7 +150,7 @@ mod tests {
     #[test]
     fn test_password_without_lowercase_error() {
         let no_lowercase = "PASSWORD1!";
-        let result = Password::parse(no_lowercase);
+        let result = Password::parse_with(no_lowercase, &composition_rules());
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), PasswordError::MissingLowercase);
     }
@@ -129,7 +158,7 @@ mod tests {
     #[test]
     fn test_password_without_digit_error() {
         let no_digit = "Password!";
-        let result = Password::parse(no_digit);
+        let result = Password::parse_with(no_digit, &composition_rules());
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), PasswordError::MissingDigit);
     }
@@ -137,7 +166,7 @@ mod tests {
     #[test]
     fn test_password_without_special_char_error() {
         let no_special = "Password1";
-        let result = Password::parse(no_special);
+        let result = Password::parse_with(no_special, &composition_rules());
         assert!(result.is_err());
         assert_eq!(result.unwrap_err(), PasswordError::MissingSpecialChar);
     }
@@ -153,7 +182,7 @@ mod tests {
         ];
 
         for password in valid_passwords.iter() {
-            assert!(Password::parse(password).is_ok(), "Password '{}' should be valid", password);
+            assert!(Password::parse_with(password, &composition_rules()).is_ok(), "Password '{}' should be valid", password);
         }
     }
 
@@ -161,10 +190,40 @@ mod tests {
     fn test_error_display_messages() {
         assert_eq!(PasswordError::Empty.to_string(), "password cannot be empty");
         assert_eq!(PasswordError::TooShort(8).to_string(), "password must be at least 8 characters long");
+        assert_eq!(PasswordError::TooLong(64).to_string(), "password must be at most 64 characters long");
         assert_eq!(PasswordError::MissingUppercase.to_string(), "password must contain at least one uppercase letter");
         assert_eq!(PasswordError::MissingLowercase.to_string(), "password must contain at least one lowercase letter");
         assert_eq!(PasswordError::MissingDigit.to_string(), "password must contain at least one digit");
         assert_eq!(PasswordError::MissingSpecialChar.to_string(), "password must contain at least one special character");
+        assert_eq!(PasswordError::SameAsEmail.to_string(), "password must not be your email address");
+        assert_eq!(PasswordError::Compromised.to_string(), "password has appeared in a data breach, choose another");
+        assert_eq!(
+            PasswordError::TooWeak("avoid years").to_string(),
+            "password is too easy to guess, avoid years"
+        );
     }
-}
 
+    struct Breached(&'static str);
+
+    impl BreachedPasswords for Breached {
+        fn occurrences(&self, password: &Password) -> u64 {
+            if password.as_ref() == self.0 { 42 } else { 0 }
+        }
+    }
+
+    #[test]
+    fn test_parse_new_rejects_breached_passwords() {
+        let email = Email::parse("user@example.com").unwrap();
+        let breached = Breached("tumble-ostrich-velvet");
+        assert_eq!(
+            Password::parse_new("tumble-ostrich-velvet", &email, &breached).unwrap_err(),
+            PasswordError::Compromised
+        );
+        assert_eq!(Password::parse_new("short", &email, &breached).unwrap_err(), PasswordError::TooShort(8));
+        assert!(matches!(
+            Password::parse_new("password1", &email, &breached),
+            Err(PasswordError::TooWeak(_))
+        ));
+        assert!(Password::parse_new("velvet-ostrich-tumble", &email, &breached).is_ok());
+    }
+}
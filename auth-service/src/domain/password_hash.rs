@@ -0,0 +1,175 @@
+use crate::domain::Password;
+use argon2::password_hash::{
+    rand_core::OsRng, PasswordHash as PhcHash, PasswordHasher, PasswordVerifier, SaltString,
+};
+use argon2::{Algorithm, Argon2, Params};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PasswordHashAlgorithm {
+    /// What the service hashes new passwords with, as Argon2id.
+    Argon2,
+    Bcrypt,
+    Pbkdf2,
+}
+
+/// A stored password: a PHC string for Argon2 and PBKDF2, or bcrypt's own
+/// `$2b$` format. Only Argon2id hashes are created here; the others come from
+/// users imported from other systems.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PasswordHash(String);
+
+impl PasswordHash {
+    pub fn hash(password: &Password) -> Self {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = Argon2::default()
+            .hash_password(password.as_ref().as_bytes(), &salt)
+            .expect("Argon2 accepts any password with a generated salt");
+        PasswordHash(hash.to_string())
+    }
+
+    pub fn parse(hash: &str) -> Result<Self, String> {
+        let valid = match algorithm_of(hash) {
+            Some(PasswordHashAlgorithm::Bcrypt) => hash.len() == 60,
+            Some(PasswordHashAlgorithm::Argon2) => PhcHash::new(hash).is_ok_and(|phc| {
+                phc.hash.is_some() && Algorithm::try_from(phc.algorithm).is_ok()
+            }),
+            Some(PasswordHashAlgorithm::Pbkdf2) => PhcHash::new(hash).is_ok_and(|phc| {
+                phc.hash.is_some() && pbkdf2::Algorithm::try_from(phc.algorithm).is_ok()
+            }),
+            None => false,
+        };
+        if valid {
+            Ok(PasswordHash(hash.to_owned()))
+        } else {
+            Err("unsupported password hash".to_owned())
+        }
+    }
+
+    pub fn algorithm(&self) -> PasswordHashAlgorithm {
+        algorithm_of(&self.0).expect("parsed hashes have a known algorithm")
+    }
+
+    // Takes the raw password, since imported passwords may predate the
+    // current password rules.
+    pub fn verify(&self, password: &str) -> bool {
+        let password = password.as_bytes();
+        match self.algorithm() {
+            PasswordHashAlgorithm::Bcrypt => bcrypt::verify(password, &self.0).unwrap_or(false),
+            PasswordHashAlgorithm::Argon2 => PhcHash::new(&self.0)
+                .is_ok_and(|phc| Argon2::default().verify_password(password, &phc).is_ok()),
+            PasswordHashAlgorithm::Pbkdf2 => PhcHash::new(&self.0)
+                .is_ok_and(|phc| pbkdf2::Pbkdf2.verify_password(password, &phc).is_ok()),
+        }
+    }
+
+    /// Whether the hash was made by anything other than the current Argon2id
+    /// settings, so should be replaced the next time the password is known.
+    pub fn needs_rehash(&self) -> bool {
+        let Ok(phc) = PhcHash::new(&self.0) else {
+            return true;
+        };
+        let Ok(params) = Params::try_from(&phc) else {
+            return true;
+        };
+        let current = Params::default();
+        phc.algorithm != Algorithm::Argon2id.ident()
+            || params.m_cost() != current.m_cost()
+            || params.t_cost() != current.t_cost()
+            || params.p_cost() != current.p_cost()
+    }
+}
+
+impl TryFrom<String> for PasswordHash {
+    type Error = String;
+
+    fn try_from(hash: String) -> Result<Self, Self::Error> {
+        PasswordHash::parse(&hash)
+    }
+}
+
+impl From<PasswordHash> for String {
+    fn from(hash: PasswordHash) -> Self {
+        hash.0
+    }
+}
+
+impl AsRef<str> for PasswordHash {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+fn algorithm_of(hash: &str) -> Option<PasswordHashAlgorithm> {
+    if ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix)) {
+        Some(PasswordHashAlgorithm::Bcrypt)
+    } else if hash.starts_with("$argon2") {
+        Some(PasswordHashAlgorithm::Argon2)
+    } else if hash.starts_with("$pbkdf2") {
+        Some(PasswordHashAlgorithm::Pbkdf2)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWORD: &str = "passworD123!";
+
+    #[test]
+    fn test_native_hash_verifies_and_is_current() {
+        let hash = PasswordHash::hash(&Password::parse(PASSWORD).unwrap());
+        assert!(hash.as_ref().starts_with("$argon2id$"));
+        assert_eq!(hash.algorithm(), PasswordHashAlgorithm::Argon2);
+        assert!(hash.verify(PASSWORD));
+        assert!(!hash.verify("wrongPassword1!"));
+        assert!(!hash.needs_rehash());
+        assert_eq!(PasswordHash::parse(hash.as_ref()), Ok(hash));
+    }
+
+    #[test]
+    fn test_imported_hashes_verify_and_need_rehash() {
+        let bcrypt = bcrypt::hash(PASSWORD, 4).unwrap();
+        let pbkdf2 = pbkdf2::Pbkdf2
+            .hash_password_customized(
+                PASSWORD.as_bytes(),
+                None,
+                None,
+                pbkdf2::Params {
+                    rounds: 1_000,
+                    output_length: 32,
+                },
+                &SaltString::generate(&mut OsRng),
+            )
+            .unwrap()
+            .to_string();
+        let argon2i = Argon2::new(Algorithm::Argon2i, Default::default(), Params::default())
+            .hash_password(PASSWORD.as_bytes(), &SaltString::generate(&mut OsRng))
+            .unwrap()
+            .to_string();
+
+        for (hash, algorithm) in [
+            (bcrypt, PasswordHashAlgorithm::Bcrypt),
+            (pbkdf2, PasswordHashAlgorithm::Pbkdf2),
+            (argon2i, PasswordHashAlgorithm::Argon2),
+        ] {
+            let hash = PasswordHash::parse(&hash).unwrap();
+            assert_eq!(hash.algorithm(), algorithm);
+            assert!(hash.verify(PASSWORD), "{:?} should verify", algorithm);
+            assert!(!hash.verify("wrongPassword1!"));
+            assert!(hash.needs_rehash(), "{:?} should need rehashing", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_unknown_hashes() {
+        for hash in ["", PASSWORD, "$1$abc$def", "$2b$10$tooshort", "$argon2id$garbage"] {
+            assert!(PasswordHash::parse(hash).is_err(), "'{}' should be rejected", hash);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::domain::{estimate_strength, Email, PasswordError};
+
+/// The rules passwords are held to when they're set. The defaults follow
+/// NIST SP 800-63B: a minimum length and a guessability check, without
+/// composition rules, which mostly lead to `Password1!`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PasswordPolicy {
+    /// In Unicode characters, after NFKC normalization.
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Require an uppercase letter, a lowercase letter, a digit and a special character.
+    pub composition_rules: bool,
+    /// Minimum zxcvbn-style score, from 0 (anything goes) to 4.
+    pub min_strength: u8,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: 64,
+            composition_rules: false,
+            min_strength: 2,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Checks the rules that apply to any password, on its own.
+    pub fn check(&self, password: &str) -> Result<(), PasswordError> {
+        if password.is_empty() {
+            return Err(PasswordError::Empty);
+        }
+        let length = password.chars().count();
+        if length < self.min_length {
+            return Err(PasswordError::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            return Err(PasswordError::TooLong(self.max_length));
+        }
+        if self.composition_rules {
+            if !password.chars().any(char::is_uppercase) {
+                return Err(PasswordError::MissingUppercase);
+            }
+            if !password.chars().any(char::is_lowercase) {
+                return Err(PasswordError::MissingLowercase);
+            }
+            if !password.chars().any(char::is_numeric) {
+                return Err(PasswordError::MissingDigit);
+            }
+            if !password.chars().any(|c| !c.is_alphanumeric()) {
+                return Err(PasswordError::MissingSpecialChar);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a password being set for `email` isn't the address itself
+    /// and would take long enough to guess.
+    pub fn check_for_user(&self, password: &str, email: &Email) -> Result<(), PasswordError> {
+        let email = email.as_ref().to_lowercase();
+        let local_part = email.split('@').next().unwrap_or_default();
+        let lowercase = password.to_lowercase();
+        if lowercase == email || lowercase == local_part {
+            return Err(PasswordError::SameAsEmail);
+        }
+
+        if self.min_strength == 0 {
+            return Ok(());
+        }
+        let mut user_inputs = vec![email.as_str(), local_part];
+        user_inputs.extend(email.split(|c: char| !c.is_alphanumeric()));
+        let strength = estimate_strength(password, &user_inputs);
+        if strength.score < self.min_strength {
+            return Err(PasswordError::TooWeak(strength.hint));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse("jane.doe@example.com").unwrap()
+    }
+
+    #[test]
+    fn test_length_is_measured_in_characters() {
+        let policy = PasswordPolicy {
+            min_length: 4,
+            max_length: 6,
+            ..PasswordPolicy::default()
+        };
+        // Four characters, but twelve bytes.
+        assert!(policy.check("日本語語").is_ok());
+        assert_eq!(policy.check("abc"), Err(PasswordError::TooShort(4)));
+        assert_eq!(policy.check("abcdefg"), Err(PasswordError::TooLong(6)));
+    }
+
+    #[test]
+    fn test_composition_rules_are_optional() {
+        let policy = PasswordPolicy::default();
+        assert!(policy.check("alllowercase").is_ok());
+
+        let policy = PasswordPolicy {
+            composition_rules: true,
+            ..policy
+        };
+        assert_eq!(policy.check("alllowercase"), Err(PasswordError::MissingUppercase));
+        // Letters outside ASCII count too.
+        assert!(policy.check("Ünïcödé1!").is_ok());
+    }
+
+    #[test]
+    fn test_rejects_email_as_password() {
+        let policy = PasswordPolicy::default();
+        for password in ["jane.doe@example.com", "Jane.Doe"] {
+            assert_eq!(
+                policy.check_for_user(password, &email()),
+                Err(PasswordError::SameAsEmail)
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_guessable_passwords() {
+        let policy = PasswordPolicy::default();
+        assert_eq!(
+            policy.check_for_user("password1", &email()),
+            Err(PasswordError::TooWeak("avoid common passwords and words"))
+        );
+        assert_eq!(
+            policy.check_for_user("Jane.Doe2024", &email()),
+            Err(PasswordError::TooWeak("avoid parts of your email address"))
+        );
+        assert!(policy.check_for_user("tumble-ostrich-velvet", &email()).is_ok());
+
+        let policy = PasswordPolicy {
+            min_strength: 0,
+            ..policy
+        };
+        assert!(policy.check_for_user("password1", &email()).is_ok());
+    }
+}
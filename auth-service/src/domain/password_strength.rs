@@ -0,0 +1,387 @@
+use chrono::Datelike;
+
+/// How hard a password is to guess, on zxcvbn's 0 to 4 scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PasswordStrength {
+    pub score: u8,
+    pub guesses: f64,
+    /// What to change to make the password stronger.
+    pub hint: &'static str,
+}
+
+// Ordered roughly by how common each is, since that's how they're guessed.
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "123456", "qwerty", "letmein", "welcome", "admin", "iloveyou", "monkey",
+    "dragon", "football", "baseball", "abc123", "sunshine", "princess", "master", "shadow",
+    "superman", "trustno1", "michael", "jennifer", "hunter", "ranger", "login", "starwars",
+    "secret", "summer", "winter", "spring", "autumn", "freedom", "whatever", "qazwsx",
+    "asdfgh", "asdf", "zxcvbn", "zxcvbnm", "qwertyuiop", "jordan", "harley", "pepper",
+    "hello", "charlie", "thomas", "george", "soccer", "hockey", "killer", "batman", "access",
+    "flower", "lovely", "cheese", "computer", "internet", "service", "server", "changeme",
+    "default", "root", "user", "guest", "test", "pass", "love", "angel", "blink", "matrix",
+    "orange", "purple", "banana", "cookie", "chocolate", "mustang", "corvette", "ferrari",
+    "london", "paris", "america", "canada", "google", "apple", "samsung", "family",
+    "friend", "forever", "happy", "lucky", "money", "power", "secure", "security", "account",
+    "company", "office", "spider", "tigger", "buster", "ginger", "maggie", "daniel",
+    "andrew", "joshua", "robert", "william", "jessica", "ashley", "nicole", "amanda",
+];
+
+// Analysing longer passwords costs more than it's worth: they're already
+// far beyond any score threshold unless they're one long pattern.
+const MAX_ANALYSED_CHARS: usize = 64;
+// Each extra pattern an attacker has to combine multiplies their work.
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10_000.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+const MIN_YEAR_SPACE: f64 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pattern {
+    Dictionary,
+    UserInput,
+    Sequence,
+    Repeat,
+    Year,
+    Bruteforce,
+}
+
+impl Pattern {
+    fn hint(self) -> &'static str {
+        match self {
+            Pattern::Dictionary => "avoid common passwords and words",
+            Pattern::UserInput => "avoid parts of your email address",
+            Pattern::Sequence => "avoid sequences like abc or 123",
+            Pattern::Repeat => "avoid repeated characters like aaa",
+            Pattern::Year => "avoid years",
+            Pattern::Bruteforce => "make it longer, for example with a few uncommon words",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Match {
+    start: usize,
+    // Exclusive.
+    end: usize,
+    guesses: f64,
+    pattern: Pattern,
+}
+
+/// Estimates how many guesses an attacker would need, the way zxcvbn does:
+/// the password is split into the sequence of known patterns (common
+/// passwords, sequences, repeats, years, and the `user_inputs`) that's
+/// cheapest to guess, with anything else guessed character by character.
+pub fn estimate_strength(password: &str, user_inputs: &[&str]) -> PasswordStrength {
+    let chars: Vec<char> = password.chars().take(MAX_ANALYSED_CHARS).collect();
+    let mut matches = dictionary_matches(&chars, COMMON_PASSWORDS, Pattern::Dictionary);
+    matches.extend(dictionary_matches(&chars, user_inputs, Pattern::UserInput));
+    matches.extend(sequence_matches(&chars));
+    matches.extend(repeat_matches(&chars));
+    matches.extend(year_matches(&chars));
+
+    let (guesses, sequence) = most_guessable_sequence(&chars, &matches);
+    let score = match guesses {
+        g if g < 1e3 + 5.0 => 0,
+        g if g < 1e6 + 5.0 => 1,
+        g if g < 1e8 + 5.0 => 2,
+        g if g < 1e10 + 5.0 => 3,
+        _ => 4,
+    };
+    // The longest pattern found is the one most worth changing.
+    let hint = sequence
+        .iter()
+        .filter(|m| m.pattern != Pattern::Bruteforce)
+        .max_by_key(|m| m.end - m.start)
+        .map_or(Pattern::Bruteforce, |m| m.pattern)
+        .hint();
+    PasswordStrength {
+        score,
+        guesses,
+        hint,
+    }
+}
+
+fn dictionary_matches(chars: &[char], words: &[&str], pattern: Pattern) -> Vec<Match> {
+    let words: Vec<String> = words.iter().map(|word| word.to_lowercase()).collect();
+    let mut matches = Vec::new();
+    for start in 0..chars.len() {
+        for end in start + 3..=chars.len() {
+            let token = &chars[start..end];
+            let lower: String = token.iter().flat_map(|c| c.to_lowercase()).collect();
+            // '1' and '!' could stand for either 'i' or 'l', so both are tried.
+            let candidates = [
+                (lower.clone(), false),
+                (unleet(&lower, 'i'), true),
+                (unleet(&lower, 'l'), true),
+            ];
+            let rank = candidates.iter().find_map(|(candidate, leet)| {
+                let rank = words.iter().position(|word| word == candidate)?;
+                Some((rank + 1, *leet && *candidate != lower))
+            });
+            if let Some((rank, leet)) = rank {
+                let leet_variations = if leet { 2.0 } else { 1.0 };
+                matches.push(Match {
+                    start,
+                    end,
+                    guesses: rank as f64 * uppercase_variations(token) * leet_variations,
+                    pattern,
+                });
+            }
+        }
+    }
+    matches
+}
+
+fn unleet(word: &str, one: char) -> String {
+    word.chars()
+        .map(|c| match c {
+            '4' | '@' => 'a',
+            '8' => 'b',
+            '(' => 'c',
+            '3' => 'e',
+            '6' | '9' => 'g',
+            '1' | '!' | '|' => one,
+            '0' => 'o',
+            '$' | '5' => 's',
+            '7' | '+' => 't',
+            '2' => 'z',
+            c => c,
+        })
+        .collect()
+}
+
+// All lowercase is tried first, then capitalised, upper and lowercase at
+// the end, then every other way of mixing the same number of capitals.
+fn uppercase_variations(token: &[char]) -> f64 {
+    let upper = token.iter().filter(|c| c.is_uppercase()).count();
+    let lower = token.iter().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+    let first_or_last_only = upper == 1
+        && (token[0].is_uppercase() || token[token.len() - 1].is_uppercase());
+    if lower == 0 || first_or_last_only {
+        return 2.0;
+    }
+    (1..=upper.min(lower))
+        .map(|k| binomial(upper + lower, k))
+        .sum()
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (1..=k).fold(1.0, |acc, i| acc * (n + 1 - i) as f64 / i as f64)
+}
+
+// Runs of at least three characters going up or down by one, like "abc" or "987".
+fn sequence_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let delta = chars[start + 1] as i64 - chars[start] as i64;
+        let mut end = start + 1;
+        while end < chars.len() && chars[end] as i64 - chars[end - 1] as i64 == delta {
+            end += 1;
+        }
+        if delta.abs() == 1 && end - start >= 3 {
+            let first = chars[start];
+            let base = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            let direction = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(Match {
+                start,
+                end,
+                guesses: base * (end - start) as f64 * direction,
+                pattern: Pattern::Sequence,
+            });
+            start = end - 1;
+        } else {
+            start += 1;
+        }
+    }
+    matches
+}
+
+// Runs of at least three of the same character.
+fn repeat_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let end = chars[start..]
+            .iter()
+            .position(|&c| c != chars[start])
+            .map_or(chars.len(), |i| start + i);
+        if end - start >= 3 {
+            matches.push(Match {
+                start,
+                end,
+                guesses: cardinality(chars[start]) * (end - start) as f64,
+                pattern: Pattern::Repeat,
+            });
+        }
+        start = end;
+    }
+    matches
+}
+
+fn year_matches(chars: &[char]) -> Vec<Match> {
+    let this_year = chrono::Utc::now().year();
+    chars
+        .windows(4)
+        .enumerate()
+        .filter_map(|(start, window)| {
+            let year: i32 = window.iter().collect::<String>().parse().ok()?;
+            (1900..=2099).contains(&year).then(|| Match {
+                start,
+                end: start + 4,
+                guesses: ((year - this_year).abs() as f64).max(MIN_YEAR_SPACE),
+                pattern: Pattern::Year,
+            })
+        })
+        .collect()
+}
+
+fn cardinality(c: char) -> f64 {
+    if c.is_ascii_digit() {
+        10.0
+    } else if c.is_alphabetic() {
+        26.0
+    } else {
+        33.0
+    }
+}
+
+fn bruteforce(start: usize, end: usize) -> Match {
+    let length = end - start;
+    let min_guesses = if length == 1 {
+        MIN_SUBMATCH_GUESSES_SINGLE_CHAR + 1.0
+    } else {
+        MIN_SUBMATCH_GUESSES_MULTI_CHAR + 1.0
+    };
+    Match {
+        start,
+        end,
+        guesses: 10f64.powi(length as i32).max(min_guesses),
+        pattern: Pattern::Bruteforce,
+    }
+}
+
+// Finds the sequence of matches covering the password that minimises
+// `l! * product(guesses) + MIN_GUESSES_BEFORE_GROWING_SEQUENCE^(l - 1)`,
+// where `l` is its length, by dynamic programming over prefixes and lengths.
+fn most_guessable_sequence(chars: &[char], matches: &[Match]) -> (f64, Vec<Match>) {
+    let n = chars.len();
+    if n == 0 {
+        return (1.0, Vec::new());
+    }
+    // best[end][l]: the smallest product of guesses, and the last match, of
+    // `l` matches covering `chars[..end]`.
+    let mut best: Vec<Vec<Option<(f64, Match)>>> = vec![vec![None; n + 1]; n + 1];
+    for end in 1..=n {
+        let candidates = matches
+            .iter()
+            .filter(|m| m.end == end)
+            .copied()
+            .chain((0..end).map(|start| bruteforce(start, end)));
+        for candidate in candidates {
+            let min_guesses = if candidate.end - candidate.start == 1 {
+                MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+            } else {
+                MIN_SUBMATCH_GUESSES_MULTI_CHAR
+            };
+            let guesses = candidate.guesses.max(min_guesses);
+            let candidate = Match { guesses, ..candidate };
+            if candidate.start == 0 {
+                update(&mut best[end][1], guesses, candidate);
+                continue;
+            }
+            for l in 1..=candidate.start {
+                if let Some((product, _)) = best[candidate.start][l] {
+                    update(&mut best[end][l + 1], product * guesses, candidate);
+                }
+            }
+        }
+    }
+
+    let (length, guesses) = (1..=n)
+        .filter_map(|l| {
+            let (product, _) = best[n][l]?;
+            let total = factorial(l) * product
+                + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(l as i32 - 1);
+            Some((l, total))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .expect("a single bruteforce match always covers the password");
+
+    let mut sequence = Vec::with_capacity(length);
+    let (mut end, mut l) = (n, length);
+    while l > 0 {
+        let (_, m) = best[end][l].expect("every step of the best sequence was recorded");
+        sequence.push(m);
+        end = m.start;
+        l -= 1;
+    }
+    sequence.reverse();
+    (guesses, sequence)
+}
+
+fn update(slot: &mut Option<(f64, Match)>, product: f64, candidate: Match) {
+    if slot.is_none_or(|(best, _)| product < best) {
+        *slot = Some((product, candidate));
+    }
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).map(|i| i as f64).product()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(password: &str) -> u8 {
+        estimate_strength(password, &[]).score
+    }
+
+    #[test]
+    fn test_common_patterns_score_low() {
+        for password in ["password", "P@ssw0rd", "qwerty123", "aaaaaaaaaa", "abcdefgh", "1987"] {
+            assert!(score(password) <= 1, "{} should be weak", password);
+        }
+    }
+
+    #[test]
+    fn test_long_uncommon_passwords_score_high() {
+        for password in ["correct horse battery staple", "vR8#qLz2!mWp", "tumble-ostrich-velvet"] {
+            assert_eq!(score(password), 4, "{} should be strong", password);
+        }
+    }
+
+    #[test]
+    fn test_user_inputs_count_as_words() {
+        let without = estimate_strength("jsmithxq", &[]);
+        let with = estimate_strength("jsmithxq", &["jsmith"]);
+        assert!(with.guesses < without.guesses);
+        assert_eq!(with.hint, "avoid parts of your email address");
+    }
+
+    #[test]
+    fn test_hint_names_the_longest_pattern() {
+        assert_eq!(estimate_strength("Password1", &[]).hint, "avoid common passwords and words");
+        assert_eq!(estimate_strength("abcdefg!", &[]).hint, "avoid sequences like abc or 123");
+        assert_eq!(estimate_strength("zzzzzzz9", &[]).hint, "avoid repeated characters like aaa");
+    }
+
+    #[test]
+    fn test_uppercase_variations() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        assert_eq!(uppercase_variations(&chars("password")), 1.0);
+        assert_eq!(uppercase_variations(&chars("Password")), 2.0);
+        assert_eq!(uppercase_variations(&chars("PASSWORD")), 2.0);
+        assert_eq!(uppercase_variations(&chars("paSsword")), 8.0);
+    }
+}
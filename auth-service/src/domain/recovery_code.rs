@@ -0,0 +1,144 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Number of recovery codes issued each time a set is generated.
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+const RECOVERY_CODE_LENGTH: usize = 12;
+const RECOVERY_CODE_GROUP_SIZE: usize = 4;
+// Lowercase letters and digits without the easily confused 0/o, 1/l/i.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Single-use code a user can give in place of their second factor,
+/// formatted as `xxxx-xxxx-xxxx`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecoveryCode(String);
+
+impl RecoveryCode {
+    pub fn generate() -> Self {
+        let mut rng = rand::thread_rng();
+        let chars: String = (0..RECOVERY_CODE_LENGTH)
+            .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+            .collect();
+        RecoveryCode(Self::format(&chars))
+    }
+
+    /// Generates a fresh set of codes along with the hashes to store for them.
+    pub fn generate_set() -> (Vec<RecoveryCode>, Vec<HashedRecoveryCode>) {
+        let codes: Vec<RecoveryCode> = (0..RECOVERY_CODE_COUNT).map(|_| Self::generate()).collect();
+        let hashes = codes.iter().map(RecoveryCode::hash).collect();
+        (codes, hashes)
+    }
+
+    /// Accepts codes regardless of case, separators or surrounding whitespace.
+    pub fn parse(code: &str) -> Result<RecoveryCode, String> {
+        let chars: String = code
+            .trim()
+            .chars()
+            .filter(|c| *c != '-' && *c != ' ')
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+
+        if chars.len() == RECOVERY_CODE_LENGTH
+            && chars.bytes().all(|c| RECOVERY_CODE_ALPHABET.contains(&c))
+        {
+            Ok(RecoveryCode(Self::format(&chars)))
+        } else {
+            Err("invalid recovery code".to_string())
+        }
+    }
+
+    pub fn hash(&self) -> HashedRecoveryCode {
+        // Codes carry ~60 bits of entropy, so a fast hash is enough to keep
+        // them from being read back out of the store.
+        HashedRecoveryCode(format!("{:x}", Sha256::digest(self.0.as_bytes())))
+    }
+
+    fn format(chars: &str) -> String {
+        chars
+            .as_bytes()
+            .chunks(RECOVERY_CODE_GROUP_SIZE)
+            .map(|group| std::str::from_utf8(group).expect("recovery codes are ASCII"))
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+}
+
+impl AsRef<str> for RecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct HashedRecoveryCode(String);
+
+impl HashedRecoveryCode {
+    pub fn matches(&self, code: &RecoveryCode) -> bool {
+        *self == code.hash()
+    }
+}
+
+impl AsRef<str> for HashedRecoveryCode {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_code_format() {
+        let code = RecoveryCode::generate();
+        let groups: Vec<&str> = code.as_ref().split('-').collect();
+
+        assert_eq!(groups.len(), 3);
+        assert!(groups.iter().all(|group| group.len() == RECOVERY_CODE_GROUP_SIZE));
+        assert_eq!(RecoveryCode::parse(code.as_ref()), Ok(code));
+    }
+
+    #[test]
+    fn test_generate_set() {
+        let (codes, hashes) = RecoveryCode::generate_set();
+
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(hashes.len(), RECOVERY_CODE_COUNT);
+        for (code, hash) in codes.iter().zip(hashes.iter()) {
+            assert!(hash.matches(code));
+            assert_ne!(hash.as_ref(), code.as_ref());
+        }
+    }
+
+    #[test]
+    fn test_parse_normalizes_input() {
+        let code = RecoveryCode::parse("abcd-efgh-jkmn").unwrap();
+
+        for input in [" ABCD-EFGH-JKMN ", "abcdefghjkmn", "abcd efgh jkmn"] {
+            assert_eq!(RecoveryCode::parse(input), Ok(code.clone()), "Failed for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_codes() {
+        for input in ["", "123456", "abcd-efgh-jkm", "abcd-efgh-jkmnp", "abcd-efgh-jk0n"] {
+            assert_eq!(
+                RecoveryCode::parse(input),
+                Err("invalid recovery code".to_string()),
+                "Code '{}' should be invalid",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_hash_does_not_match_other_codes() {
+        let code = RecoveryCode::parse("abcd-efgh-jkmn").unwrap();
+        let other = RecoveryCode::parse("abcd-efgh-jkmp").unwrap();
+
+        assert!(!code.hash().matches(&other));
+    }
+}
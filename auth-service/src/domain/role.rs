@@ -0,0 +1,123 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const ADMIN_ROLE: &str = "admin";
+pub const USERS_READ_PERMISSION: &str = "users:read";
+pub const USERS_WRITE_PERMISSION: &str = "users:write";
+pub const AUDIT_READ_PERMISSION: &str = "audit:read";
+
+const MAX_NAME_LENGTH: usize = 64;
+
+/// A named group of users, e.g. `admin` or an application specific role
+/// such as `premium`. Only built-in roles imply permissions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(transparent)]
+pub struct Role(String);
+
+impl Role {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        parse_name(name)
+            .map(Role)
+            .ok_or_else(|| format!("invalid role '{}'", name))
+    }
+
+    pub fn admin() -> Self {
+        Role(ADMIN_ROLE.to_owned())
+    }
+
+    pub fn permissions(&self) -> Vec<Permission> {
+        match self.0.as_str() {
+            ADMIN_ROLE => [USERS_READ_PERMISSION, USERS_WRITE_PERMISSION, AUDIT_READ_PERMISSION]
+                .into_iter()
+                .map(|name| Permission(name.to_owned()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl AsRef<str> for Role {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Something a user is allowed to do, written `resource:action`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(transparent)]
+pub struct Permission(String);
+
+impl Permission {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        parse_name(name)
+            .map(Permission)
+            .ok_or_else(|| format!("invalid permission '{}'", name))
+    }
+}
+
+impl AsRef<str> for Permission {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// Names are lowercase so that checks can compare them exactly.
+fn parse_name(name: &str) -> Option<String> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LENGTH
+        && name
+            .bytes()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || b":-_.".contains(&c));
+    valid.then(|| name.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::User;
+
+    #[test]
+    fn test_parse_names() {
+        assert!(Role::parse("premium").is_ok());
+        assert!(Permission::parse("reports:export").is_ok());
+
+        for name in ["", "Admin", "has space", &"a".repeat(65)] {
+            assert!(Role::parse(name).is_err(), "Role '{}' should be invalid", name);
+            assert!(Permission::parse(name).is_err(), "Permission '{}' should be invalid", name);
+        }
+    }
+
+    #[test]
+    fn test_only_built_in_roles_imply_permissions() {
+        let permissions = Role::admin().permissions();
+        assert!(permissions.contains(&Permission::parse(USERS_READ_PERMISSION).unwrap()));
+        assert!(permissions.contains(&Permission::parse(USERS_WRITE_PERMISSION).unwrap()));
+        assert!(permissions.contains(&Permission::parse(AUDIT_READ_PERMISSION).unwrap()));
+
+        assert!(Role::parse("premium").unwrap().permissions().is_empty());
+    }
+
+    #[test]
+    fn test_effective_permissions_combine_roles_and_grants() {
+        let mut user = User::new(
+            "test@example.com".to_owned(),
+            "passworD123!".to_owned(),
+            false,
+        );
+        user.roles = vec![Role::parse("premium").unwrap(), Role::admin()];
+        user.permissions = vec![
+            Permission::parse("reports:export").unwrap(),
+            Permission::parse(USERS_READ_PERMISSION).unwrap(),
+        ];
+
+        let permissions: Vec<String> = user
+            .effective_permissions()
+            .iter()
+            .map(|permission| permission.as_ref().to_owned())
+            .collect();
+        assert_eq!(
+            permissions,
+            ["audit:read", "reports:export", "users:read", "users:write"]
+        );
+    }
+}
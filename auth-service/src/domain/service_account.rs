@@ -0,0 +1,147 @@
+use crate::domain::HashedClientSecret;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+/// A caller without a human user behind it, such as a batch job. It gets
+/// access tokens with the client credentials grant, limited to its scopes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceAccount {
+    pub client_id: String,
+    pub name: String,
+    pub client_secret: HashedClientSecret,
+    pub scopes: Vec<String>,
+    /// Unix timestamps.
+    pub created_at: i64,
+    pub secret_rotated_at: i64,
+}
+
+impl ServiceAccount {
+    /// Creates an account with a new client id and secret. The secret is
+    /// returned in plain text, as this is the only time it is known.
+    pub fn new(name: String, scopes: Vec<String>, now: i64) -> (Self, ClientSecret) {
+        let secret = ClientSecret::generate();
+        let account = Self {
+            client_id: uuid::Uuid::new_v4().to_string(),
+            name,
+            client_secret: HashedClientSecret::new(secret.as_ref()),
+            scopes,
+            created_at: now,
+            secret_rotated_at: now,
+        };
+        (account, secret)
+    }
+
+    /// Replaces the secret, which also revokes the tokens issued for the old one.
+    pub fn rotate_secret(&mut self, now: i64) -> ClientSecret {
+        let secret = ClientSecret::generate();
+        self.client_secret = HashedClientSecret::new(secret.as_ref());
+        self.secret_rotated_at = now;
+        secret
+    }
+
+    pub fn authenticate(&self, secret: &str) -> bool {
+        self.client_secret.matches(secret)
+    }
+
+    /// The scopes a token request gets: all of the account's when it asks for
+    /// none, or those it asks for if the account has every one of them.
+    pub fn grant_scope(&self, requested: Option<&str>) -> Result<String, String> {
+        let Some(requested) = requested else {
+            return Ok(self.scopes.join(" "));
+        };
+        let requested: Vec<&str> = requested.split_whitespace().collect();
+        match requested
+            .iter()
+            .find(|scope| !self.scopes.iter().any(|s| s == *scope))
+        {
+            Some(scope) => Err(format!("scope '{}' is not granted", scope)),
+            None => Ok(requested.join(" ")),
+        }
+    }
+
+    // Timestamps are in whole seconds, so a token issued in the second the
+    // secret was rotated is still accepted.
+    pub fn accepts_token_issued_at(&self, iat: usize) -> bool {
+        i64::try_from(iat).is_ok_and(|iat| iat >= self.secret_rotated_at)
+    }
+}
+
+/// Checks a scope against the scope-token grammar of RFC 6749 section 3.3.
+pub fn parse_scope(scope: &str) -> Result<String, String> {
+    let valid = !scope.is_empty()
+        && scope
+            .bytes()
+            .all(|c| c.is_ascii_graphic() && c != b'"' && c != b'\\');
+    if valid {
+        Ok(scope.to_owned())
+    } else {
+        Err(format!("invalid scope '{}'", scope))
+    }
+}
+
+/// A service account's secret in plain text, as handed to its owner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientSecret(String);
+
+impl ClientSecret {
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        ClientSecret(URL_SAFE_NO_PAD.encode(bytes))
+    }
+}
+
+impl AsRef<str> for ClientSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> (ServiceAccount, ClientSecret) {
+        ServiceAccount::new(
+            "Nightly report".to_owned(),
+            vec!["reports:read".to_owned(), "reports:write".to_owned()],
+            1000,
+        )
+    }
+
+    #[test]
+    fn test_new_account_authenticates_with_its_secret() {
+        let (account, secret) = account();
+        assert!(account.authenticate(secret.as_ref()));
+        assert!(!account.authenticate("wrong"));
+        assert_ne!(account.client_secret.as_ref(), secret.as_ref());
+    }
+
+    #[test]
+    fn test_rotate_secret() {
+        let (mut account, old_secret) = account();
+        let new_secret = account.rotate_secret(2000);
+
+        assert!(account.authenticate(new_secret.as_ref()));
+        assert!(!account.authenticate(old_secret.as_ref()));
+        assert!(!account.accepts_token_issued_at(1999));
+        assert!(account.accepts_token_issued_at(2000));
+    }
+
+    #[test]
+    fn test_grant_scope() {
+        let (account, _) = account();
+        assert_eq!(account.grant_scope(None), Ok("reports:read reports:write".to_owned()));
+        assert_eq!(account.grant_scope(Some("reports:read")), Ok("reports:read".to_owned()));
+        assert!(account.grant_scope(Some("reports:read users:write")).is_err());
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        assert!(parse_scope("reports:read").is_ok());
+        assert!(parse_scope("").is_err());
+        assert!(parse_scope("two words").is_err());
+        assert!(parse_scope("quote\"").is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::domain::Email;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionId(String);
+
+impl SessionId {
+    pub fn parse(id: String) -> Result<Self, String> {
+        match uuid::Uuid::parse_str(&id) {
+            Ok(_) => Ok(SessionId(id)),
+            Err(_) => Err("invalid session id".to_string()),
+        }
+    }
+}
+
+impl Default for SessionId {
+    fn default() -> Self {
+        SessionId(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+impl AsRef<str> for SessionId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The device a session was started from, as far as the request tells us.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+/// A signed-in device. Every auth token names the session it belongs to, so
+/// removing the session revokes the token before it expires.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: SessionId,
+    pub email: Email,
+    pub client: ClientInfo,
+    /// Unix timestamps.
+    pub created_at: i64,
+    pub last_seen_at: i64,
+    pub expires_at: i64,
+}
+
+impl Session {
+    pub fn new(email: Email, client: ClientInfo, now: i64, ttl_seconds: i64) -> Self {
+        Self {
+            id: SessionId::default(),
+            email,
+            client,
+            created_at: now,
+            last_seen_at: now,
+            expires_at: now + ttl_seconds,
+        }
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_session_id() {
+        let id = SessionId::default();
+        assert_eq!(SessionId::parse(id.as_ref().to_owned()), Ok(id));
+        assert!(SessionId::parse("not-a-uuid".to_owned()).is_err());
+    }
+
+    #[test]
+    fn test_session_expires_after_ttl() {
+        let email = Email::parse("test@example.com").unwrap();
+        let session = Session::new(email, ClientInfo::default(), 1_000, 600);
+
+        assert_eq!(session.last_seen_at, 1_000);
+        assert!(!session.is_expired(1_599));
+        assert!(session.is_expired(1_600));
+    }
+}
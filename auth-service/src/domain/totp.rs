@@ -0,0 +1,197 @@
+use crate::domain::Email;
+use crate::utils::constants::TOTP_ISSUER;
+use image::{ImageFormat, Luma};
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+// Number of steps either side of the current one that are still accepted,
+// to tolerate clock drift between the server and the authenticator app.
+const TOTP_ALLOWED_DRIFT: u64 = 1;
+
+/// Base32 encoded shared secret for an RFC 6238 authenticator app.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TotpSecret(String);
+
+impl TotpSecret {
+    pub fn generate() -> Self {
+        match Secret::generate_secret().to_encoded() {
+            Secret::Encoded(secret) => TotpSecret(secret),
+            Secret::Raw(_) => unreachable!("to_encoded always returns an encoded secret"),
+        }
+    }
+
+    pub fn parse(secret: &str) -> Result<TotpSecret, String> {
+        let bytes = Secret::Encoded(secret.to_string())
+            .to_bytes()
+            .map_err(|_| "invalid TOTP secret".to_string())?;
+        // RFC 4226 requires shared secrets of at least 128 bits.
+        if bytes.len() < 16 {
+            return Err("invalid TOTP secret".to_string());
+        }
+        Ok(TotpSecret(secret.to_string()))
+    }
+}
+
+impl AsRef<str> for TotpSecret {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TotpCredential {
+    pub secret: TotpSecret,
+    /// Set once the user has proven their authenticator app produces valid codes.
+    pub confirmed: bool,
+    /// Last time step a code was accepted for, used to reject replayed codes.
+    pub last_used_step: Option<u64>,
+}
+
+impl TotpCredential {
+    pub fn new(secret: TotpSecret) -> Self {
+        Self {
+            secret,
+            confirmed: false,
+            last_used_step: None,
+        }
+    }
+
+    pub fn provisioning_uri(&self, email: &Email) -> Result<String, String> {
+        Ok(self.totp(email)?.get_url())
+    }
+
+    pub fn qr_code_png(&self, email: &Email) -> Result<Vec<u8>, String> {
+        let uri = self.provisioning_uri(email)?;
+        let image = QrCode::new(uri.as_bytes())
+            .map_err(|e| e.to_string())?
+            .render::<Luma<u8>>()
+            .build();
+
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| e.to_string())?;
+        Ok(png)
+    }
+
+    pub fn code_at(&self, email: &Email, unix_time: u64) -> Result<String, String> {
+        Ok(self.totp(email)?.generate(unix_time))
+    }
+
+    /// Checks `code` against the time step for `unix_time` and its neighbours,
+    /// returning the step that matched. Steps at or before `last_used_step` are
+    /// never accepted so an intercepted code can't be used a second time.
+    pub fn verify(&self, email: &Email, code: &str, unix_time: u64) -> Option<u64> {
+        let totp = self.totp(email).ok()?;
+        let current_step = unix_time / TOTP_STEP_SECONDS;
+        let first_step = current_step.saturating_sub(TOTP_ALLOWED_DRIFT);
+
+        (first_step..=current_step + TOTP_ALLOWED_DRIFT)
+            .filter(|step| self.last_used_step.is_none_or(|last| *step > last))
+            .find(|step| totp.check(code, step * TOTP_STEP_SECONDS))
+    }
+
+    fn totp(&self, email: &Email) -> Result<TOTP, String> {
+        let secret = Secret::Encoded(self.secret.as_ref().to_string())
+            .to_bytes()
+            .map_err(|_| "invalid TOTP secret".to_string())?;
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            0,
+            TOTP_STEP_SECONDS,
+            secret,
+            Some(TOTP_ISSUER.to_string()),
+            email.as_ref().to_string(),
+        )
+        .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn credential() -> (Email, TotpCredential) {
+        let email = Email::parse("test@example.com").unwrap();
+        (email, TotpCredential::new(TotpSecret::generate()))
+    }
+
+    #[test]
+    fn test_generated_secret_can_be_parsed() {
+        let secret = TotpSecret::generate();
+        assert_eq!(TotpSecret::parse(secret.as_ref()), Ok(secret));
+    }
+
+    #[test]
+    fn test_parse_invalid_secret() {
+        for secret in ["", "not base32!", "JBSWY3DP"] {
+            assert!(TotpSecret::parse(secret).is_err(), "Secret '{}' should be invalid", secret);
+        }
+    }
+
+    #[test]
+    fn test_provisioning_uri() {
+        let (email, credential) = credential();
+        let uri = credential.provisioning_uri(&email).unwrap();
+
+        assert!(uri.starts_with("otpauth://totp/"));
+        assert!(uri.contains(&format!("secret={}", credential.secret.as_ref())));
+    }
+
+    #[test]
+    fn test_qr_code_is_png() {
+        let (email, credential) = credential();
+        let png = credential.qr_code_png(&email).unwrap();
+
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+
+    #[test]
+    fn test_verify_accepts_current_code() {
+        let (email, credential) = credential();
+        let code = credential.code_at(&email, NOW).unwrap();
+
+        assert_eq!(credential.verify(&email, &code, NOW), Some(NOW / TOTP_STEP_SECONDS));
+    }
+
+    #[test]
+    fn test_verify_accepts_one_step_of_drift() {
+        let (email, credential) = credential();
+        let previous = credential.code_at(&email, NOW - TOTP_STEP_SECONDS).unwrap();
+        let next = credential.code_at(&email, NOW + TOTP_STEP_SECONDS).unwrap();
+
+        assert!(credential.verify(&email, &previous, NOW).is_some());
+        assert!(credential.verify(&email, &next, NOW).is_some());
+    }
+
+    #[test]
+    fn test_verify_rejects_codes_outside_drift_window() {
+        let (email, credential) = credential();
+        let stale = credential.code_at(&email, NOW - 2 * TOTP_STEP_SECONDS).unwrap();
+        let early = credential.code_at(&email, NOW + 2 * TOTP_STEP_SECONDS).unwrap();
+
+        assert_eq!(credential.verify(&email, &stale, NOW), None);
+        assert_eq!(credential.verify(&email, &early, NOW), None);
+    }
+
+    #[test]
+    fn test_verify_rejects_replayed_code() {
+        let (email, mut credential) = credential();
+        let code = credential.code_at(&email, NOW).unwrap();
+
+        credential.last_used_step = credential.verify(&email, &code, NOW);
+        assert!(credential.last_used_step.is_some());
+        assert_eq!(credential.verify(&email, &code, NOW), None);
+
+        let previous = credential.code_at(&email, NOW - TOTP_STEP_SECONDS).unwrap();
+        assert_eq!(credential.verify(&email, &previous, NOW), None);
+    }
+}
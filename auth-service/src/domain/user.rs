@@ -1,17 +1,79 @@
 use crate::domain::email::Email;
 use crate::domain::password::Password;
+use crate::domain::password_hash::PasswordHash;
+use crate::domain::recovery_code::HashedRecoveryCode;
+use crate::domain::role::{Permission, Role};
+use crate::domain::totp::TotpCredential;
+use crate::domain::webauthn::PasskeyCredential;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Clone)]
+/// Second factor a user is challenged with when `requires_2fa` is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TwoFAMethod {
+    #[default]
+    Email,
+    Totp,
+    WebAuthn,
+}
+
+/// Failed password logins after which the account is locked until an admin unlocks it.
+pub const MAX_FAILED_LOGIN_ATTEMPTS: u32 = 5;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct User {
     pub email: Email,
-    pub password: Password,
-    pub requires_2fa: bool
+    pub password_hash: PasswordHash,
+    pub requires_2fa: bool,
+    pub two_fa_method: TwoFAMethod,
+    pub totp: Option<TotpCredential>,
+    pub recovery_codes: Vec<HashedRecoveryCode>,
+    pub passkeys: Vec<PasskeyCredential>,
+    pub roles: Vec<Role>,
+    /// Granted directly, on top of those implied by `roles`.
+    pub permissions: Vec<Permission>,
+    /// Disabled users can't sign in by any method.
+    pub disabled: bool,
+    /// Set by an admin to make the user choose a new password before signing in again.
+    pub password_reset_required: bool,
+    pub failed_login_attempts: u32,
 }
 
 impl User {
     pub fn new(email: String, password: String, requires_2fa: bool) -> Self {
         let email = Email::parse(email.as_str()).expect("Invalid email");
         let password = Password::parse(password.as_str()).expect("Invalid password");
-        Self { email, password, requires_2fa }
+        Self {
+            email,
+            password_hash: PasswordHash::hash(&password),
+            requires_2fa,
+            two_fa_method: TwoFAMethod::default(),
+            totp: None,
+            recovery_codes: Vec::new(),
+            passkeys: Vec::new(),
+            roles: Vec::new(),
+            permissions: Vec::new(),
+            disabled: false,
+            password_reset_required: false,
+            failed_login_attempts: 0,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.failed_login_attempts >= MAX_FAILED_LOGIN_ATTEMPTS
+    }
+
+    /// Direct permissions together with those implied by the user's roles.
+    pub fn effective_permissions(&self) -> Vec<Permission> {
+        let mut permissions: Vec<Permission> = self
+            .roles
+            .iter()
+            .flat_map(Role::permissions)
+            .chain(self.permissions.iter().cloned())
+            .collect();
+        permissions.sort();
+        permissions.dedup();
+        permissions
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,464 @@
+use crate::domain::{
+    Email, PasswordHash, PasswordHashAlgorithm, Permission, Role, TotpCredential, TotpSecret,
+    TwoFAMethod, User,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Version written to exports. Imports of any other version are refused.
+pub const USER_EXPORT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UserExportFormat {
+    #[default]
+    Json,
+    /// One user per row, with roles and permissions separated by spaces.
+    Csv,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UserExportError {
+    UnsupportedVersion(u32),
+    Malformed(String),
+}
+
+impl std::fmt::Display for UserExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserExportError::UnsupportedVersion(version) => {
+                write!(f, "unsupported export version {}", version)
+            }
+            UserExportError::Malformed(reason) => write!(f, "malformed import: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UserExport {
+    pub version: u32,
+    pub users: Vec<ExportedUser>,
+}
+
+/// A user as moved between systems. Passkeys, recovery codes and login state
+/// are left out: passkeys only work for the origin they were registered
+/// with, and the rest belongs to this service.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedUser {
+    pub email: String,
+    pub password_algorithm: PasswordHashAlgorithm,
+    pub password_hash: String,
+    #[serde(rename = "requires2FA", default)]
+    pub requires_2fa: bool,
+    #[serde(rename = "twoFAMethod", default)]
+    pub two_fa_method: TwoFAMethod,
+    /// Base32 secret of a confirmed authenticator app.
+    #[serde(default)]
+    pub totp_secret: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+impl From<&User> for ExportedUser {
+    fn from(user: &User) -> Self {
+        Self {
+            email: user.email.as_ref().to_owned(),
+            password_algorithm: user.password_hash.algorithm(),
+            password_hash: user.password_hash.as_ref().to_owned(),
+            requires_2fa: user.requires_2fa,
+            two_fa_method: user.two_fa_method,
+            totp_secret: user
+                .totp
+                .as_ref()
+                .filter(|totp| totp.confirmed)
+                .map(|totp| totp.secret.as_ref().to_owned()),
+            roles: user.roles.iter().map(|role| role.as_ref().to_owned()).collect(),
+            permissions: user
+                .permissions
+                .iter()
+                .map(|permission| permission.as_ref().to_owned())
+                .collect(),
+            disabled: user.disabled,
+        }
+    }
+}
+
+impl ExportedUser {
+    /// Validates the exported fields. Users who signed in with a passkey as
+    /// their second factor fall back to email codes, since passkeys aren't
+    /// exported.
+    pub fn into_user(self) -> Result<User, String> {
+        let email = Email::parse(&self.email)?;
+        let password_hash = PasswordHash::parse(&self.password_hash)?;
+        if password_hash.algorithm() != self.password_algorithm {
+            return Err("password hash does not match passwordAlgorithm".to_owned());
+        }
+        let totp = self
+            .totp_secret
+            .map(|secret| {
+                TotpSecret::parse(&secret).map(|secret| TotpCredential {
+                    confirmed: true,
+                    ..TotpCredential::new(secret)
+                })
+            })
+            .transpose()?;
+        let two_fa_method = match self.two_fa_method {
+            TwoFAMethod::Totp if totp.is_none() => {
+                return Err("TOTP 2FA requires a totpSecret".to_owned())
+            }
+            TwoFAMethod::WebAuthn => TwoFAMethod::Email,
+            method => method,
+        };
+        let roles = self
+            .roles
+            .iter()
+            .map(|role| Role::parse(role))
+            .collect::<Result<Vec<_>, _>>()?;
+        let permissions = self
+            .permissions
+            .iter()
+            .map(|permission| Permission::parse(permission))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(User {
+            email,
+            password_hash,
+            requires_2fa: self.requires_2fa,
+            two_fa_method,
+            totp,
+            recovery_codes: Vec::new(),
+            passkeys: Vec::new(),
+            roles,
+            permissions,
+            disabled: self.disabled,
+            password_reset_required: false,
+            failed_login_attempts: 0,
+        })
+    }
+}
+
+// CSV has no lists, so every row repeats the version and joins roles and
+// permissions with spaces, which neither may contain.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CsvRow {
+    version: u32,
+    email: String,
+    password_algorithm: PasswordHashAlgorithm,
+    password_hash: String,
+    #[serde(rename = "requires2FA")]
+    requires_2fa: bool,
+    #[serde(rename = "twoFAMethod")]
+    two_fa_method: TwoFAMethod,
+    totp_secret: Option<String>,
+    roles: String,
+    permissions: String,
+    disabled: bool,
+}
+
+impl From<ExportedUser> for CsvRow {
+    fn from(user: ExportedUser) -> Self {
+        Self {
+            version: USER_EXPORT_VERSION,
+            email: user.email,
+            password_algorithm: user.password_algorithm,
+            password_hash: user.password_hash,
+            requires_2fa: user.requires_2fa,
+            two_fa_method: user.two_fa_method,
+            totp_secret: user.totp_secret,
+            roles: user.roles.join(" "),
+            permissions: user.permissions.join(" "),
+            disabled: user.disabled,
+        }
+    }
+}
+
+impl From<CsvRow> for ExportedUser {
+    fn from(row: CsvRow) -> Self {
+        let split = |list: &str| list.split_whitespace().map(str::to_owned).collect();
+        Self {
+            email: row.email,
+            password_algorithm: row.password_algorithm,
+            password_hash: row.password_hash,
+            requires_2fa: row.requires_2fa,
+            two_fa_method: row.two_fa_method,
+            totp_secret: row.totp_secret.filter(|secret| !secret.is_empty()),
+            roles: split(&row.roles),
+            permissions: split(&row.permissions),
+            disabled: row.disabled,
+        }
+    }
+}
+
+impl ExportedUser {
+    /// Drops the TOTP secret. Users who used an authenticator app as their
+    /// second factor are exported with email codes instead, so the export
+    /// still imports.
+    fn without_totp_secret(self) -> Self {
+        let two_fa_method = match self.two_fa_method {
+            TwoFAMethod::Totp => TwoFAMethod::Email,
+            method => method,
+        };
+        Self {
+            two_fa_method,
+            totp_secret: None,
+            ..self
+        }
+    }
+}
+
+/// Writes an export a page of users at a time, so that exports of large
+/// stores never have to be held in memory whole.
+pub struct UserExportWriter {
+    format: UserExportFormat,
+    include_totp_secrets: bool,
+    users_written: usize,
+}
+
+impl UserExportWriter {
+    pub fn new(format: UserExportFormat, include_totp_secrets: bool) -> Self {
+        Self {
+            format,
+            include_totp_secrets,
+            users_written: 0,
+        }
+    }
+
+    /// Everything before the first user.
+    pub fn start(&self) -> String {
+        match self.format {
+            UserExportFormat::Json => format!("{{\"version\":{},\"users\":[", USER_EXPORT_VERSION),
+            UserExportFormat::Csv => String::new(),
+        }
+    }
+
+    /// The next users of the export. The CSV header row comes with the first
+    /// user, so an export without users is empty.
+    pub fn write(&mut self, users: &[User]) -> String {
+        let written = self.users_written;
+        self.users_written += users.len();
+        let users = users.iter().map(|user| {
+            let user = ExportedUser::from(user);
+            if self.include_totp_secrets {
+                user
+            } else {
+                user.without_totp_secret()
+            }
+        });
+        let chunk = match self.format {
+            UserExportFormat::Json => {
+                let mut chunk = String::new();
+                for (i, user) in users.enumerate() {
+                    if written + i > 0 {
+                        chunk.push(',');
+                    }
+                    chunk.push_str(
+                        &serde_json::to_string(&user).expect("exports always serialize"),
+                    );
+                }
+                chunk
+            }
+            UserExportFormat::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(written == 0)
+                    .from_writer(Vec::new());
+                for user in users {
+                    writer
+                        .serialize(CsvRow::from(user))
+                        .expect("writing to memory can't fail");
+                }
+                let bytes = writer.into_inner().expect("writing to memory can't fail");
+                String::from_utf8(bytes).expect("CSV of strings is UTF-8")
+            }
+        };
+        chunk
+    }
+
+    /// Everything after the last user.
+    pub fn finish(&self) -> String {
+        match self.format {
+            UserExportFormat::Json => "]}".to_owned(),
+            UserExportFormat::Csv => String::new(),
+        }
+    }
+}
+
+pub fn export_users(users: &[User], format: UserExportFormat) -> String {
+    let mut writer = UserExportWriter::new(format, true);
+    let mut export = writer.start();
+    export.push_str(&writer.write(users));
+    export.push_str(&writer.finish());
+    export
+}
+/// Reads an export produced by `export_users`, or by hand in the same format.
+/// Only the overall shape is checked here; each user is validated by
+/// `ExportedUser::into_user` so that one bad user doesn't fail the rest.
+pub fn parse_user_import(
+    body: &str,
+    format: UserExportFormat,
+) -> Result<Vec<ExportedUser>, UserExportError> {
+    match format {
+        UserExportFormat::Json => {
+            #[derive(Deserialize)]
+            struct Versioned {
+                version: u32,
+            }
+            let Versioned { version } = serde_json::from_str(body)
+                .map_err(|e| UserExportError::Malformed(e.to_string()))?;
+            if version != USER_EXPORT_VERSION {
+                return Err(UserExportError::UnsupportedVersion(version));
+            }
+            serde_json::from_str::<UserExport>(body)
+                .map(|export| export.users)
+                .map_err(|e| UserExportError::Malformed(e.to_string()))
+        }
+        UserExportFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(body.as_bytes());
+            let mut users = Vec::new();
+            for row in reader.deserialize::<CsvRow>() {
+                let row = row.map_err(|e| UserExportError::Malformed(e.to_string()))?;
+                if row.version != USER_EXPORT_VERSION {
+                    return Err(UserExportError::UnsupportedVersion(row.version));
+                }
+                users.push(row.into());
+            }
+            Ok(users)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> Vec<User> {
+        let mut admin = User::new(
+            "admin@example.com".to_owned(),
+            "passworD123!".to_owned(),
+            true,
+        );
+        admin.roles = vec![Role::admin(), Role::parse("premium").unwrap()];
+        admin.permissions = vec![Permission::parse("reports:export").unwrap()];
+        admin.two_fa_method = TwoFAMethod::Totp;
+        admin.totp = Some(TotpCredential {
+            confirmed: true,
+            ..TotpCredential::new(TotpSecret::generate())
+        });
+        let user = User::new(
+            "user@example.com".to_owned(),
+            "passworD123!".to_owned(),
+            false,
+        );
+        vec![admin, user]
+    }
+
+    #[test]
+    fn test_export_round_trips_in_both_formats() {
+        let users = users();
+        for format in [UserExportFormat::Json, UserExportFormat::Csv] {
+            let exported = export_users(&users, format);
+            let imported = parse_user_import(&exported, format).unwrap();
+            assert_eq!(
+                imported,
+                users.iter().map(ExportedUser::from).collect::<Vec<_>>(),
+                "{:?} export should round trip",
+                format
+            );
+
+            let admin = imported[0].clone().into_user().unwrap();
+            assert_eq!(admin.roles, users[0].roles);
+            assert_eq!(admin.totp, users[0].totp);
+            assert!(admin.password_hash.verify("passworD123!"));
+        }
+    }
+
+    #[test]
+    fn test_writing_pages_matches_a_single_export() {
+        let users = users();
+        for format in [UserExportFormat::Json, UserExportFormat::Csv] {
+            let mut writer = UserExportWriter::new(format, true);
+            let mut export = writer.start();
+            for page in users.chunks(1) {
+                export.push_str(&writer.write(page));
+            }
+            export.push_str(&writer.write(&[]));
+            export.push_str(&writer.finish());
+            assert_eq!(export, export_users(&users, format), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_totp_secrets_are_left_out_unless_asked_for() {
+        let users = users();
+        for format in [UserExportFormat::Json, UserExportFormat::Csv] {
+            let mut writer = UserExportWriter::new(format, false);
+            let export = writer.start() + &writer.write(&users) + &writer.finish();
+            let admin = parse_user_import(&export, format).unwrap().remove(0);
+            assert_eq!(admin.totp_secret, None);
+            assert_eq!(admin.two_fa_method, TwoFAMethod::Email);
+            assert!(admin.into_user().is_ok());
+        }
+    }
+
+    #[test]
+    fn test_import_refuses_other_versions() {
+        let body = r#"{"version": 2, "users": "whatever"}"#;
+        assert_eq!(
+            parse_user_import(body, UserExportFormat::Json),
+            Err(UserExportError::UnsupportedVersion(2))
+        );
+
+        let body = export_users(&users(), UserExportFormat::Csv).replace("\n1,", "\n2,");
+        assert_eq!(
+            parse_user_import(&body, UserExportFormat::Csv),
+            Err(UserExportError::UnsupportedVersion(2))
+        );
+    }
+
+    #[test]
+    fn test_into_user_validates_fields() {
+        let valid = ExportedUser::from(&users()[1]);
+        assert!(valid.clone().into_user().is_ok());
+
+        let invalid = [
+            ExportedUser {
+                email: "not an email".to_owned(),
+                ..valid.clone()
+            },
+            ExportedUser {
+                password_hash: "plaintext".to_owned(),
+                ..valid.clone()
+            },
+            ExportedUser {
+                password_algorithm: PasswordHashAlgorithm::Bcrypt,
+                ..valid.clone()
+            },
+            ExportedUser {
+                two_fa_method: TwoFAMethod::Totp,
+                ..valid.clone()
+            },
+            ExportedUser {
+                roles: vec!["Not A Role".to_owned()],
+                ..valid.clone()
+            },
+        ];
+        for user in invalid {
+            assert!(user.clone().into_user().is_err(), "{:?} should be invalid", user);
+        }
+    }
+
+    #[test]
+    fn test_passkey_users_fall_back_to_email_codes() {
+        let user = ExportedUser {
+            requires_2fa: true,
+            two_fa_method: TwoFAMethod::WebAuthn,
+            ..ExportedUser::from(&users()[1])
+        };
+        assert_eq!(user.into_user().unwrap().two_fa_method, TwoFAMethod::Email);
+    }
+}
@@ -0,0 +1,531 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::value::Value;
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// COSE algorithm identifier for ECDSA with P-256 and SHA-256.
+pub const COSE_ALG_ES256: i64 = -7;
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+const RP_ID_HASH_LENGTH: usize = 32;
+const AAGUID_LENGTH: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub enum WebAuthnError {
+    MalformedClientData,
+    MalformedAuthenticatorData,
+    MalformedAttestation,
+    UnsupportedAttestation,
+    UnsupportedAlgorithm,
+    CeremonyMismatch,
+    ChallengeMismatch,
+    OriginMismatch,
+    RelyingPartyMismatch,
+    UserNotPresent,
+    UserNotVerified,
+    InvalidSignature,
+    SignCountRegression,
+}
+
+impl std::error::Error for WebAuthnError {}
+
+impl std::fmt::Display for WebAuthnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebAuthnError::MalformedClientData => write!(f, "client data is malformed"),
+            WebAuthnError::MalformedAuthenticatorData => write!(f, "authenticator data is malformed"),
+            WebAuthnError::MalformedAttestation => write!(f, "attestation object is malformed"),
+            WebAuthnError::UnsupportedAttestation => write!(f, "attestation format is not supported"),
+            WebAuthnError::UnsupportedAlgorithm => write!(f, "credential algorithm is not supported"),
+            WebAuthnError::CeremonyMismatch => write!(f, "client data is for a different ceremony"),
+            WebAuthnError::ChallengeMismatch => write!(f, "challenge does not match"),
+            WebAuthnError::OriginMismatch => write!(f, "origin does not match"),
+            WebAuthnError::RelyingPartyMismatch => write!(f, "relying party id does not match"),
+            WebAuthnError::UserNotPresent => write!(f, "user presence was not confirmed"),
+            WebAuthnError::UserNotVerified => write!(f, "user was not verified"),
+            WebAuthnError::InvalidSignature => write!(f, "signature is not valid"),
+            WebAuthnError::SignCountRegression => write!(f, "signature counter did not increase"),
+        }
+    }
+}
+
+/// Random challenge the authenticator must sign to complete a ceremony.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebAuthnChallenge(Vec<u8>);
+
+impl WebAuthnChallenge {
+    pub fn generate() -> Self {
+        let mut bytes = vec![0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        WebAuthnChallenge(bytes)
+    }
+
+    pub fn to_base64(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.0)
+    }
+}
+
+/// Whether the authenticator must verify the user, with a PIN or biometric,
+/// rather than only check that someone is present.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UserVerification {
+    Required,
+    Preferred,
+}
+
+impl UserVerification {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserVerification::Required => "required",
+            UserVerification::Preferred => "preferred",
+        }
+    }
+}
+
+/// Public key credential registered by one of a user's authenticators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PasskeyCredential {
+    pub id: Vec<u8>,
+    /// SEC1 encoded P-256 public key.
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+impl PasskeyCredential {
+    pub fn id_base64(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.id)
+    }
+}
+
+/// Where the relying party expects ceremonies to come from.
+pub struct RelyingParty<'a> {
+    pub id: &'a str,
+    pub origin: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    ceremony: String,
+    challenge: String,
+    origin: String,
+}
+
+struct AuthenticatorData<'a> {
+    rp_id_hash: &'a [u8],
+    flags: u8,
+    sign_count: u32,
+    attested_credential: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Verifies the response to a `navigator.credentials.create()` call and
+/// returns the credential to store for the user.
+pub fn verify_registration(
+    rp: &RelyingParty,
+    challenge: &WebAuthnChallenge,
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+) -> Result<PasskeyCredential, WebAuthnError> {
+    verify_client_data(rp, "webauthn.create", challenge, client_data_json)?;
+
+    let attestation: Value = ciborium::from_reader(attestation_object)
+        .map_err(|_| WebAuthnError::MalformedAttestation)?;
+    let attestation = attestation.as_map().ok_or(WebAuthnError::MalformedAttestation)?;
+
+    let fmt = map_get_text(attestation, "fmt")
+        .and_then(Value::as_text)
+        .ok_or(WebAuthnError::MalformedAttestation)?;
+    // Only unattested credentials are accepted, which is what browsers
+    // return when the relying party asks for `attestation: "none"`.
+    if fmt != "none" {
+        return Err(WebAuthnError::UnsupportedAttestation);
+    }
+
+    let auth_data = map_get_text(attestation, "authData")
+        .and_then(Value::as_bytes)
+        .ok_or(WebAuthnError::MalformedAttestation)?;
+    let auth_data = parse_authenticator_data(auth_data)?;
+    verify_authenticator_data(rp, &auth_data)?;
+
+    let (id, public_key) = auth_data
+        .attested_credential
+        .ok_or(WebAuthnError::MalformedAuthenticatorData)?;
+
+    Ok(PasskeyCredential {
+        id,
+        public_key,
+        sign_count: auth_data.sign_count,
+    })
+}
+
+/// Verifies the response to a `navigator.credentials.get()` call and
+/// returns the authenticator's new signature counter.
+pub fn verify_assertion(
+    rp: &RelyingParty,
+    challenge: &WebAuthnChallenge,
+    credential: &PasskeyCredential,
+    client_data_json: &[u8],
+    authenticator_data: &[u8],
+    signature: &[u8],
+    user_verification: UserVerification,
+) -> Result<u32, WebAuthnError> {
+    verify_client_data(rp, "webauthn.get", challenge, client_data_json)?;
+
+    let auth_data = parse_authenticator_data(authenticator_data)?;
+    verify_authenticator_data(rp, &auth_data)?;
+    // "preferred" leaves it to the authenticator, so only "required" is enforced.
+    if user_verification == UserVerification::Required
+        && auth_data.flags & FLAG_USER_VERIFIED == 0
+    {
+        return Err(WebAuthnError::UserNotVerified);
+    }
+
+    let key = VerifyingKey::from_sec1_bytes(&credential.public_key)
+        .map_err(|_| WebAuthnError::UnsupportedAlgorithm)?;
+    let signature = Signature::from_der(signature).map_err(|_| WebAuthnError::InvalidSignature)?;
+    let mut signed = authenticator_data.to_vec();
+    signed.extend_from_slice(&Sha256::digest(client_data_json));
+    key.verify(&signed, &signature)
+        .map_err(|_| WebAuthnError::InvalidSignature)?;
+
+    // Authenticators that don't implement a counter always report zero.
+    // Otherwise a counter that fails to move forward means the credential
+    // may have been cloned.
+    if (auth_data.sign_count != 0 || credential.sign_count != 0)
+        && auth_data.sign_count <= credential.sign_count
+    {
+        return Err(WebAuthnError::SignCountRegression);
+    }
+
+    Ok(auth_data.sign_count)
+}
+
+fn verify_client_data(
+    rp: &RelyingParty,
+    ceremony: &str,
+    challenge: &WebAuthnChallenge,
+    client_data_json: &[u8],
+) -> Result<(), WebAuthnError> {
+    let client_data: ClientData =
+        serde_json::from_slice(client_data_json).map_err(|_| WebAuthnError::MalformedClientData)?;
+
+    if client_data.ceremony != ceremony {
+        return Err(WebAuthnError::CeremonyMismatch);
+    }
+    if client_data.challenge != challenge.to_base64() {
+        return Err(WebAuthnError::ChallengeMismatch);
+    }
+    if client_data.origin != rp.origin {
+        return Err(WebAuthnError::OriginMismatch);
+    }
+    Ok(())
+}
+
+fn verify_authenticator_data(
+    rp: &RelyingParty,
+    auth_data: &AuthenticatorData,
+) -> Result<(), WebAuthnError> {
+    if auth_data.rp_id_hash != Sha256::digest(rp.id.as_bytes()).as_slice() {
+        return Err(WebAuthnError::RelyingPartyMismatch);
+    }
+    if auth_data.flags & FLAG_USER_PRESENT == 0 {
+        return Err(WebAuthnError::UserNotPresent);
+    }
+    Ok(())
+}
+
+fn parse_authenticator_data(data: &[u8]) -> Result<AuthenticatorData<'_>, WebAuthnError> {
+    let malformed = || WebAuthnError::MalformedAuthenticatorData;
+
+    let (rp_id_hash, rest) = split(data, RP_ID_HASH_LENGTH).ok_or_else(malformed)?;
+    let (flags, rest) = split(rest, 1).ok_or_else(malformed)?;
+    let (sign_count, rest) = split(rest, 4).ok_or_else(malformed)?;
+    let flags = flags[0];
+    let sign_count = u32::from_be_bytes(sign_count.try_into().map_err(|_| malformed())?);
+
+    let attested_credential = if flags & FLAG_ATTESTED_CREDENTIAL_DATA != 0 {
+        let (_aaguid, rest) = split(rest, AAGUID_LENGTH).ok_or_else(malformed)?;
+        let (id_length, rest) = split(rest, 2).ok_or_else(malformed)?;
+        let id_length = u16::from_be_bytes([id_length[0], id_length[1]]) as usize;
+        let (id, mut rest) = split(rest, id_length).ok_or_else(malformed)?;
+
+        let cose_key: Value = ciborium::from_reader(&mut rest).map_err(|_| malformed())?;
+        Some((id.to_vec(), cose_key_to_sec1(&cose_key)?))
+    } else {
+        None
+    };
+
+    Ok(AuthenticatorData {
+        rp_id_hash,
+        flags,
+        sign_count,
+        attested_credential,
+    })
+}
+
+// Converts an EC2 P-256 COSE_Key into an uncompressed SEC1 point.
+fn cose_key_to_sec1(key: &Value) -> Result<Vec<u8>, WebAuthnError> {
+    let key = key.as_map().ok_or(WebAuthnError::MalformedAuthenticatorData)?;
+    let int = |label: i64| map_get_int(key, label).and_then(Value::as_integer).map(i128::from);
+
+    // kty = EC2, alg = ES256, crv = P-256
+    if int(1) != Some(2) || int(3) != Some(COSE_ALG_ES256.into()) || int(-1) != Some(1) {
+        return Err(WebAuthnError::UnsupportedAlgorithm);
+    }
+
+    let x = map_get_int(key, -2).and_then(Value::as_bytes);
+    let y = map_get_int(key, -3).and_then(Value::as_bytes);
+    let (Some(x), Some(y)) = (x, y) else {
+        return Err(WebAuthnError::MalformedAuthenticatorData);
+    };
+
+    let mut point = vec![0x04];
+    point.extend_from_slice(x);
+    point.extend_from_slice(y);
+    VerifyingKey::from_sec1_bytes(&point).map_err(|_| WebAuthnError::UnsupportedAlgorithm)?;
+    Ok(point)
+}
+
+fn split(data: &[u8], at: usize) -> Option<(&[u8], &[u8])> {
+    (data.len() >= at).then(|| data.split_at(at))
+}
+
+fn map_get_text<'a>(map: &'a [(Value, Value)], key: &str) -> Option<&'a Value> {
+    map.iter()
+        .find(|(k, _)| k.as_text() == Some(key))
+        .map(|(_, v)| v)
+}
+
+fn map_get_int(map: &[(Value, Value)], key: i64) -> Option<&Value> {
+    map.iter()
+        .find(|(k, _)| k.as_integer().map(i128::from) == Some(key.into()))
+        .map(|(_, v)| v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::{signature::Signer, SigningKey};
+    use rand::rngs::OsRng;
+
+    const RP: RelyingParty = RelyingParty {
+        id: "localhost",
+        origin: "http://localhost:3000",
+    };
+
+    fn client_data(ceremony: &str, challenge: &WebAuthnChallenge, origin: &str) -> Vec<u8> {
+        serde_json::json!({
+            "type": ceremony,
+            "challenge": challenge.to_base64(),
+            "origin": origin,
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    fn authenticator_data(rp_id: &str, flags: u8, sign_count: u32) -> Vec<u8> {
+        let mut data = Sha256::digest(rp_id.as_bytes()).to_vec();
+        data.push(flags);
+        data.extend_from_slice(&sign_count.to_be_bytes());
+        data
+    }
+
+    fn attestation_object(key: &SigningKey, credential_id: &[u8], sign_count: u32) -> Vec<u8> {
+        let point = key.verifying_key().to_encoded_point(false);
+        let cose_key = Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(COSE_ALG_ES256)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+
+        let mut auth_data = authenticator_data(
+            RP.id,
+            FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL_DATA,
+            sign_count,
+        );
+        auth_data.extend_from_slice(&[0u8; AAGUID_LENGTH]);
+        auth_data.extend_from_slice(&(credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(credential_id);
+        ciborium::into_writer(&cose_key, &mut auth_data).unwrap();
+
+        let attestation = Value::Map(vec![
+            (Value::from("fmt"), Value::from("none")),
+            (Value::from("attStmt"), Value::Map(vec![])),
+            (Value::from("authData"), Value::Bytes(auth_data)),
+        ]);
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&attestation, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn register(key: &SigningKey) -> PasskeyCredential {
+        let challenge = WebAuthnChallenge::generate();
+        verify_registration(
+            &RP,
+            &challenge,
+            &client_data("webauthn.create", &challenge, RP.origin),
+            &attestation_object(key, b"credential-id", 0),
+        )
+        .unwrap()
+    }
+
+    fn assert_with(
+        key: &SigningKey,
+        credential: &PasskeyCredential,
+        sign_count: u32,
+    ) -> Result<u32, WebAuthnError> {
+        assert_with_flags(key, credential, sign_count, FLAG_USER_PRESENT, UserVerification::Preferred)
+    }
+
+    fn assert_with_flags(
+        key: &SigningKey,
+        credential: &PasskeyCredential,
+        sign_count: u32,
+        flags: u8,
+        user_verification: UserVerification,
+    ) -> Result<u32, WebAuthnError> {
+        let challenge = WebAuthnChallenge::generate();
+        let client_data = client_data("webauthn.get", &challenge, RP.origin);
+        let auth_data = authenticator_data(RP.id, flags, sign_count);
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&client_data));
+        let signature: Signature = key.sign(&signed);
+
+        verify_assertion(
+            &RP,
+            &challenge,
+            credential,
+            &client_data,
+            &auth_data,
+            signature.to_der().as_bytes(),
+            user_verification,
+        )
+    }
+
+    #[test]
+    fn test_verify_registration() {
+        let key = SigningKey::random(&mut OsRng);
+        let credential = register(&key);
+
+        assert_eq!(credential.id, b"credential-id");
+        assert_eq!(
+            credential.public_key,
+            key.verifying_key().to_encoded_point(false).as_bytes()
+        );
+        assert_eq!(credential.sign_count, 0);
+    }
+
+    #[test]
+    fn test_verify_registration_rejects_mismatched_client_data() {
+        let key = SigningKey::random(&mut OsRng);
+        let challenge = WebAuthnChallenge::generate();
+        let attestation = attestation_object(&key, b"credential-id", 0);
+
+        let test_cases = [
+            (
+                client_data("webauthn.get", &challenge, RP.origin),
+                WebAuthnError::CeremonyMismatch,
+            ),
+            (
+                client_data("webauthn.create", &WebAuthnChallenge::generate(), RP.origin),
+                WebAuthnError::ChallengeMismatch,
+            ),
+            (
+                client_data("webauthn.create", &challenge, "https://evil.example"),
+                WebAuthnError::OriginMismatch,
+            ),
+            (b"not json".to_vec(), WebAuthnError::MalformedClientData),
+        ];
+
+        for (client_data, expected) in test_cases {
+            let result = verify_registration(&RP, &challenge, &client_data, &attestation);
+            assert_eq!(result.err(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_verify_registration_rejects_other_relying_party() {
+        let key = SigningKey::random(&mut OsRng);
+        let challenge = WebAuthnChallenge::generate();
+        let rp = RelyingParty {
+            id: "example.com",
+            origin: RP.origin,
+        };
+
+        let result = verify_registration(
+            &rp,
+            &challenge,
+            &client_data("webauthn.create", &challenge, RP.origin),
+            &attestation_object(&key, b"credential-id", 0),
+        );
+        assert_eq!(result.err(), Some(WebAuthnError::RelyingPartyMismatch));
+    }
+
+    #[test]
+    fn test_verify_assertion() {
+        let key = SigningKey::random(&mut OsRng);
+        let credential = register(&key);
+
+        assert_eq!(assert_with(&key, &credential, 1), Ok(1));
+    }
+
+    #[test]
+    fn test_verify_assertion_requires_user_verification_when_asked() {
+        let key = SigningKey::random(&mut OsRng);
+        let credential = register(&key);
+
+        assert_eq!(
+            assert_with_flags(&key, &credential, 1, FLAG_USER_PRESENT, UserVerification::Required),
+            Err(WebAuthnError::UserNotVerified)
+        );
+        assert_eq!(
+            assert_with_flags(
+                &key,
+                &credential,
+                2,
+                FLAG_USER_PRESENT | FLAG_USER_VERIFIED,
+                UserVerification::Required
+            ),
+            Ok(2)
+        );
+    }
+
+    #[test]
+    fn test_verify_assertion_rejects_other_key() {
+        let key = SigningKey::random(&mut OsRng);
+        let credential = register(&key);
+        let other_key = SigningKey::random(&mut OsRng);
+
+        assert_eq!(
+            assert_with(&other_key, &credential, 1),
+            Err(WebAuthnError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_verify_assertion_rejects_sign_count_regression() {
+        let key = SigningKey::random(&mut OsRng);
+        let mut credential = register(&key);
+        credential.sign_count = 5;
+
+        assert_eq!(
+            assert_with(&key, &credential, 5),
+            Err(WebAuthnError::SignCountRegression)
+        );
+        assert_eq!(assert_with(&key, &credential, 6), Ok(6));
+    }
+
+    #[test]
+    fn test_verify_assertion_allows_authenticators_without_counter() {
+        let key = SigningKey::random(&mut OsRng);
+        let credential = register(&key);
+
+        assert_eq!(assert_with(&key, &credential, 0), Ok(0));
+    }
+}
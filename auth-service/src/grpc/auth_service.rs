@@ -0,0 +1,146 @@
+use crate::app_state::AppState;
+use crate::domain::{AuthAPIError, Email, TwoFAMethod, User, UserStoreError};
+use crate::grpc::proto;
+use crate::grpc::proto::auth_service_server::AuthService;
+use crate::routes::{introspect_token, IntrospectedSession, IntrospectionResponse};
+use crate::utils::auth::validate_access_token;
+use tonic::{Request, Response, Status};
+
+/// Scope a service account's token needs to look users up.
+pub const USERS_READ_SCOPE: &str = "users:read";
+/// Scope a service account's token needs to describe other tokens.
+pub const TOKENS_INTROSPECT_SCOPE: &str = "tokens:introspect";
+
+/// Answers the gRPC calls from the same stores and signing keys as the HTTP routes.
+pub struct GrpcAuthService {
+    state: AppState,
+}
+
+impl GrpcAuthService {
+    pub fn new(state: AppState) -> Self {
+        Self { state }
+    }
+
+
+    // Callers send a service account's access token as `authorization:
+    // Bearer <token>` metadata, granted `scope`.
+    async fn authorize_caller<T>(&self, request: &Request<T>, scope: &str) -> Result<(), Status> {
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("Missing service account token"))?;
+        let (claims, session) = validate_access_token(token, &self.state)
+            .await
+            .map_err(|e| match e {
+                AuthAPIError::InvalidToken => Status::unauthenticated("Invalid service account token"),
+                _ => Status::internal("Unexpected error"),
+            })?;
+        // Users' tokens all belong to a session; service account tokens don't.
+        if session.is_some() {
+            return Err(Status::unauthenticated("Not a service account token"));
+        }
+        let granted = claims.scope.as_deref().unwrap_or_default();
+        if !granted.split_whitespace().any(|granted| granted == scope) {
+            return Err(Status::permission_denied(format!("Missing scope {}", scope)));
+        }
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl AuthService for GrpcAuthService {
+    async fn verify_token(
+        &self,
+        request: Request<proto::VerifyTokenRequest>,
+    ) -> Result<Response<proto::VerifyTokenResponse>, Status> {
+        let (claims, _) = validate_access_token(&request.into_inner().token, &self.state)
+            .await
+            .map_err(|e| match e {
+                AuthAPIError::InvalidToken => Status::unauthenticated("Invalid auth token"),
+                _ => Status::internal("Unexpected error"),
+            })?;
+        Ok(Response::new(proto::VerifyTokenResponse {
+            sub: claims.sub,
+            client_id: claims.client_id.unwrap_or_default(),
+            scope: claims.scope.unwrap_or_default(),
+        }))
+    }
+
+    async fn introspect_token(
+        &self,
+        request: Request<proto::IntrospectTokenRequest>,
+    ) -> Result<Response<proto::IntrospectTokenResponse>, Status> {
+        self.authorize_caller(&request, TOKENS_INTROSPECT_SCOPE).await?;
+        let response = introspect_token(&request.into_inner().token, &self.state)
+            .await
+            .map_err(|_| Status::internal("Unexpected error"))?;
+        Ok(Response::new(response.into()))
+    }
+
+    async fn get_user(
+        &self,
+        request: Request<proto::GetUserRequest>,
+    ) -> Result<Response<proto::User>, Status> {
+        self.authorize_caller(&request, USERS_READ_SCOPE).await?;
+        let email = Email::parse(&request.into_inner().email)
+            .map_err(|_| Status::invalid_argument("Invalid email"))?;
+        match self.state.user_store.get_user(&email).await {
+            Ok(user) => Ok(Response::new(proto::User::from(&user))),
+            Err(UserStoreError::UserNotFound) => Err(Status::not_found("User not found")),
+            Err(_) => Err(Status::internal("Unexpected error")),
+        }
+    }
+}
+
+impl From<IntrospectionResponse> for proto::IntrospectTokenResponse {
+    fn from(response: IntrospectionResponse) -> Self {
+        Self {
+            active: response.active,
+            sub: response.sub.unwrap_or_default(),
+            exp: response.exp.unwrap_or_default() as i64,
+            scope: response.scope.unwrap_or_default(),
+            client_id: response.client_id.unwrap_or_default(),
+            token_type: response.token_type.unwrap_or_default(),
+            iat: response.iat.unwrap_or_default() as i64,
+            iss: response.iss.unwrap_or_default(),
+            session: response.session.map(proto::IntrospectedSession::from),
+        }
+    }
+}
+
+impl From<IntrospectedSession> for proto::IntrospectedSession {
+    fn from(session: IntrospectedSession) -> Self {
+        Self {
+            id: session.id,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+impl From<&User> for proto::User {
+    fn from(user: &User) -> Self {
+        let two_fa_method = match user.two_fa_method {
+            TwoFAMethod::Email => proto::TwoFaMethod::Email,
+            TwoFAMethod::Totp => proto::TwoFaMethod::Totp,
+            TwoFAMethod::WebAuthn => proto::TwoFaMethod::Webauthn,
+        };
+        Self {
+            email: user.email.as_ref().to_owned(),
+            roles: user.roles.iter().map(|role| role.as_ref().to_owned()).collect(),
+            permissions: user
+                .permissions
+                .iter()
+                .map(|permission| permission.as_ref().to_owned())
+                .collect(),
+            requires_2fa: user.requires_2fa,
+            two_fa_method: two_fa_method.into(),
+            disabled: user.disabled,
+            locked: user.is_locked(),
+            password_reset_required: user.password_reset_required,
+        }
+    }
+}
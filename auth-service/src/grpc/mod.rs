@@ -0,0 +1,10 @@
+//! gRPC interface for services inside the mesh, served next to the HTTP API.
+
+mod auth_service;
+
+pub use auth_service::*;
+
+/// Types and client generated from `proto/auth.proto`.
+pub mod proto {
+    tonic::include_proto!("auth.v1");
+}
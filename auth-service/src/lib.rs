@@ -2,48 +2,177 @@ pub mod routes;
 pub mod domain;
 pub mod services;
 pub mod app_state;
+pub mod grpc;
+pub mod utils;
 
 use crate::app_state::AppState;
-use crate::domain::UserStore;
 use crate::routes::{
-    login_route, logout_route, signup_route, verify_2fa_route, verify_token_route,
+    admin_audit_log_route, admin_create_service_account_route, admin_delete_service_account_route,
+    admin_disable_user_route, admin_enable_user_route,
+    admin_export_users_route, admin_force_password_reset_route, admin_get_user_route,
+    admin_import_users_route, admin_list_service_accounts_route, admin_list_users_route,
+    admin_rotate_keys_route, admin_rotate_service_account_secret_route,
+    admin_set_2fa_route, admin_set_roles_route, admin_unlock_user_route,
+    admin_verify_audit_log_route, ApiDoc,
+    audit_admin_requests,
+    authorize_route, change_password_route, introspect_route, jwks_route, list_sessions_route,
+    login_route, logout_route,
+    magic_link_route, verify_magic_link_route,
+    openid_configuration_route, recovery_codes_status_route, regenerate_recovery_codes_route,
+    revoke_all_sessions_route, revoke_session_route, signup_route, token_route,
+    totp_confirm_route, totp_enroll_route, two_fa_method_route, userinfo_route,
+    verify_2fa_route, verify_token_route, webauthn_login_finish_route,
+    webauthn_login_start_route, webauthn_register_finish_route, webauthn_register_start_route,
+    webauthn_verify_2fa_route,
 };
 use axum::{
-    routing::{delete, post},
+    extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo},
+    middleware::{self, AddExtension},
+    routing::{delete, get, post, put},
     serve::Serve,
     Router,
 };
+use grpc::proto::auth_service_server::AuthServiceServer;
+use grpc::GrpcAuthService;
 use std::error::Error;
+use std::future::IntoFuture;
+use std::net::SocketAddr;
+use tonic::transport::server::{Router as GrpcRouter, TcpIncoming};
 use tower_http::services::ServeDir;
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 pub struct Application {
-    server: Serve<Router, Router>,
+    // Connection info is kept so sessions can record the client's IP address.
+    server: Serve<
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
+    // Served on a port of its own, so that it can stay inside the cluster
+    // while the HTTP API is exposed.
+    grpc_server: GrpcRouter,
+    grpc_incoming: TcpIncoming,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
+    pub grpc_address: String,
 }
 
 impl Application {
-    pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
+    pub async fn build(
+        app_state: AppState,
+        address: &str,
+        grpc_address: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Every admin request is written to the audit log, whatever its outcome.
+        let admin_router = Router::new()
+            .route("/admin/users", get(admin_list_users_route))
+            .route("/admin/users/export", get(admin_export_users_route))
+            .route("/admin/users/import", post(admin_import_users_route))
+            .route("/admin/users/:email", get(admin_get_user_route))
+            .route("/admin/users/:email/disable", post(admin_disable_user_route))
+            .route("/admin/users/:email/enable", post(admin_enable_user_route))
+            .route(
+                "/admin/users/:email/force-password-reset",
+                post(admin_force_password_reset_route),
+            )
+            .route("/admin/users/:email/2fa", put(admin_set_2fa_route))
+            .route("/admin/users/:email/roles", put(admin_set_roles_route))
+            .route("/admin/users/:email/unlock", post(admin_unlock_user_route))
+            .route("/admin/audit", get(admin_audit_log_route))
+            .route("/admin/audit/verify", get(admin_verify_audit_log_route))
+            .route("/admin/keys/rotate", post(admin_rotate_keys_route))
+            .route(
+                "/admin/service-accounts",
+                get(admin_list_service_accounts_route).post(admin_create_service_account_route),
+            )
+            .route(
+                "/admin/service-accounts/:client_id",
+                delete(admin_delete_service_account_route),
+            )
+            .route(
+                "/admin/service-accounts/:client_id/rotate-secret",
+                post(admin_rotate_service_account_secret_route),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                audit_admin_requests,
+            ));
+
         let router = Router::new()
             .nest_service("/", ServeDir::new("assets"))
             .route("/login", post(login_route))
+            .route("/login/magic-link", post(magic_link_route))
+            .route("/login/magic-link/verify", post(verify_magic_link_route))
             .route("/logout", delete(logout_route))
             .route("/signup", post(signup_route))
             .route("/verify-token", post(verify_token_route))
             .route("/verify-2fa", post(verify_2fa_route))
-            .with_state(app_state);
+            .route("/change-password", post(change_password_route))
+            .route(
+                "/sessions",
+                get(list_sessions_route).delete(revoke_all_sessions_route),
+            )
+            .route("/sessions/:id", delete(revoke_session_route))
+            .route("/2fa/method", put(two_fa_method_route))
+            .route("/2fa/totp/enroll", post(totp_enroll_route))
+            .route("/2fa/totp/confirm", post(totp_confirm_route))
+            .route(
+                "/2fa/recovery-codes",
+                get(recovery_codes_status_route).post(regenerate_recovery_codes_route),
+            )
+            .route("/webauthn/register/start", post(webauthn_register_start_route))
+            .route("/webauthn/register/finish", post(webauthn_register_finish_route))
+            .route("/webauthn/login/start", post(webauthn_login_start_route))
+            .route("/webauthn/login/finish", post(webauthn_login_finish_route))
+            .route("/webauthn/verify-2fa", post(webauthn_verify_2fa_route))
+            .route(
+                "/.well-known/openid-configuration",
+                get(openid_configuration_route),
+            )
+            .route("/.well-known/jwks.json", get(jwks_route))
+            .route("/authorize", get(authorize_route))
+            .route("/token", post(token_route))
+            .route("/userinfo", get(userinfo_route).post(userinfo_route))
+            .route("/introspect", post(introspect_route))
+            .merge(admin_router)
+            .merge(SwaggerUi::new("/swagger-ui").url("/openapi.json", ApiDoc::openapi()))
+            .with_state(app_state.clone());
 
         let listener = tokio::net::TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
+
+        let grpc_listener = tokio::net::TcpListener::bind(grpc_address).await?;
+        let grpc_address = grpc_listener.local_addr()?.to_string();
+        let grpc_incoming =
+            TcpIncoming::from_listener(grpc_listener, true, None).map_err(|e| e as Box<dyn Error>)?;
+        let grpc_server = tonic::transport::Server::builder()
+            .add_service(AuthServiceServer::new(GrpcAuthService::new(app_state)));
 
         // Create a new Application instance and return it
-        Ok(Application { server, address })
+        Ok(Application {
+            server,
+            grpc_server,
+            grpc_incoming,
+            address,
+            grpc_address,
+        })
     }
 
     pub async fn run(self) -> Result<(), std::io::Error> {
         println!("listening on {}", &self.address);
-        self.server.await
+        println!("gRPC listening on {}", &self.grpc_address);
+        let grpc_server = async {
+            self.grpc_server
+                .serve_with_incoming(self.grpc_incoming)
+                .await
+                .map_err(std::io::Error::other)
+        };
+        tokio::try_join!(self.server.into_future(), grpc_server)?;
+        Ok(())
     }
 }
@@ -1,10 +1,111 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use auth_service::{Application, services::HashmapUserStore, app_state::AppState};
+use auth_service::{
+    app_state::AppState,
+    domain::{
+        OidcClient, OidcClientConfig, OidcClientStore,
+    },
+    services::{
+        HashmapAuthorizationCodeStore, HashmapMagicLinkStore, HashmapOidcClientStore,
+        HashmapServiceAccountStore, HashmapSessionStore,
+        HashmapTwoFACodeStore,
+        HashmapUserStore, HashmapWebAuthnChallengeStore, HibpPasswordList, JsonlAuditSink,
+        MockEmailClient, SERVICE_ACCOUNTS_FILE_NAME,
+    },
+    utils::{
+        auth::{open_key_ring, rotate_signing_keys},
+        constants::{
+            env::OIDC_CLIENTS_FILE_ENV_VAR, AUDIT_LOG_PATH, BREACHED_PASSWORDS_PATH, GRPC_ADDRESS,
+            JWT_KEYS_PATH, USER_STORE_DIR, USER_STORE_SNAPSHOT_INTERVAL,
+        },
+    },
+    Application,
+};
 
 #[tokio::main]
 async fn main() {
-    let user_store = Arc::new(RwLock::new(HashmapUserStore::new()));
-    let app = Application::build(AppState { user_store }, "0.0.0.0:3000").await.expect("failed to build server");
+    let user_store = Arc::new(build_user_store());
+    let two_fa_code_store = Arc::new(HashmapTwoFACodeStore::new());
+    let webauthn_challenge_store = Arc::new(HashmapWebAuthnChallengeStore::new());
+    let oidc_client_store = Arc::new(load_oidc_clients().await);
+    let service_account_store = Arc::new(build_service_account_store());
+    let authorization_code_store = Arc::new(HashmapAuthorizationCodeStore::new());
+    let magic_link_store = Arc::new(HashmapMagicLinkStore::new());
+    let session_store = Arc::new(HashmapSessionStore::new());
+    let audit_sink = Arc::new(
+        JsonlAuditSink::open(AUDIT_LOG_PATH.as_str()).expect("failed to open audit log"),
+    );
+    let key_ring = Arc::new(RwLock::new(
+        open_key_ring(JWT_KEYS_PATH.as_deref(), chrono::Utc::now().timestamp())
+            .expect("failed to load signing keys"),
+    ));
+    let email_client = Arc::new(MockEmailClient);
+    let breached_passwords = Arc::new(build_breached_passwords());
+    tokio::spawn(rotate_signing_keys(key_ring.clone()));
+    let app_state = AppState::new(
+        user_store,
+        two_fa_code_store,
+        webauthn_challenge_store,
+        oidc_client_store,
+        service_account_store,
+        authorization_code_store,
+        magic_link_store,
+        session_store,
+        audit_sink,
+        key_ring,
+        email_client,
+        breached_passwords,
+    );
+
+    let app = Application::build(app_state, "0.0.0.0:3000", &GRPC_ADDRESS)
+        .await
+        .expect("failed to build server");
     app.run().await.expect("failed to run server");
 }
+
+fn build_user_store() -> HashmapUserStore {
+    match USER_STORE_DIR.as_ref() {
+        Some(dir) => HashmapUserStore::open(dir, *USER_STORE_SNAPSHOT_INTERVAL)
+            .expect("failed to load users"),
+        None => HashmapUserStore::new(),
+    }
+}
+
+// Service accounts are saved next to the users, and like them only live in
+// memory without USER_STORE_DIR.
+fn build_service_account_store() -> HashmapServiceAccountStore {
+    match USER_STORE_DIR.as_ref() {
+        Some(dir) => HashmapServiceAccountStore::open(
+            std::path::Path::new(dir).join(SERVICE_ACCOUNTS_FILE_NAME),
+        )
+        .expect("failed to load service accounts"),
+        None => HashmapServiceAccountStore::new(),
+    }
+}
+
+fn build_breached_passwords() -> HibpPasswordList {
+    match BREACHED_PASSWORDS_PATH.as_ref() {
+        Some(path) => HibpPasswordList::open(path).expect("failed to open breached passwords"),
+        None => HibpPasswordList::empty(),
+    }
+}
+
+// Registers the OpenID Connect clients listed in the JSON file named by
+// `OIDC_CLIENTS_FILE`, if it is set.
+async fn load_oidc_clients() -> HashmapOidcClientStore {
+    let store = HashmapOidcClientStore::new();
+    let Ok(path) = std::env::var(OIDC_CLIENTS_FILE_ENV_VAR) else {
+        return store;
+    };
+
+    let contents = std::fs::read_to_string(&path).expect("failed to read OIDC clients file");
+    let clients: Vec<OidcClientConfig> =
+        serde_json::from_str(&contents).expect("failed to parse OIDC clients file");
+    for client in clients {
+        store
+            .add_client(OidcClient::from(client))
+            .await
+            .expect("duplicate OIDC client id");
+    }
+    store
+}
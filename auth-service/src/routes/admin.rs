@@ -0,0 +1,1031 @@
+use crate::app_state::AppState;
+use crate::domain::{
+    AuditAction, AuditEntry, AuditEvent, AuditOutcome, AuditQuery, AuditSinkError, AuthAPIError,
+    ClientInfo, ClientSecret, Email, Permission, Role, ServiceAccount, ServiceAccountStoreError,
+    TwoFAMethod, User, UserCursor, UserExport, UserExportFormat, UserExportWriter, UserQuery,
+    UserStoreError,
+};
+use crate::domain::{parse_scope, parse_user_import};
+use crate::routes::ErrorResponse;
+use crate::utils::audit::record_audit_event;
+use crate::utils::auth::{generate_signing_key, validate_token};
+use crate::utils::constants::{
+    ADMIN_USERS_DEFAULT_PAGE_SIZE, ADMIN_USERS_MAX_PAGE_SIZE, JWT_COOKIE_NAME,
+    USER_EXPORT_PAGE_SIZE,
+};
+use crate::utils::guards::{
+    Admin, AuditRead, RequirePermission, RequireRole, UsersRead, UsersWrite,
+};
+use axum::body::Body;
+use axum::extract::{MatchedPath, Path, Query, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::CookieJar;
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListUsersQuery {
+    /// Case-insensitive substring of the email.
+    pub search: Option<String>,
+    /// Only users with this role.
+    pub role: Option<String>,
+    pub disabled: Option<bool>,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: Option<bool>,
+    /// The `nextCursor` of the previous page.
+    pub cursor: Option<String>,
+    #[serde(rename = "perPage")]
+    pub per_page: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ListUsersResponse {
+    pub users: Vec<AdminUserResponse>,
+    #[serde(rename = "perPage")]
+    pub per_page: usize,
+    /// Number of matching users across all pages.
+    pub total: usize,
+    /// Only returned when there are more users.
+    #[serde(rename = "nextCursor", default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct AdminUserResponse {
+    pub email: String,
+    pub roles: Vec<Role>,
+    /// Granted directly, not counting those implied by `roles`.
+    pub permissions: Vec<Permission>,
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: TwoFAMethod,
+    pub disabled: bool,
+    pub locked: bool,
+    #[serde(rename = "failedLoginAttempts")]
+    pub failed_login_attempts: u32,
+    #[serde(rename = "passwordResetRequired")]
+    pub password_reset_required: bool,
+}
+
+impl From<&User> for AdminUserResponse {
+    fn from(user: &User) -> Self {
+        Self {
+            email: user.email.as_ref().to_owned(),
+            roles: user.roles.clone(),
+            permissions: user.permissions.clone(),
+            requires_2fa: user.requires_2fa,
+            two_fa_method: user.two_fa_method,
+            disabled: user.disabled,
+            locked: user.is_locked(),
+            failed_login_attempts: user.failed_login_attempts,
+            password_reset_required: user.password_reset_required,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AdminSetRolesRequest {
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AdminSet2FARequest {
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+}
+
+/// List users
+///
+/// Users are ordered by email.
+///
+/// Requires the `users:read` permission.
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    tag = "admin",
+    params(ListUsersQuery),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Users ordered by email", body = ListUsersResponse),
+        (status = 400, description = "Invalid query or missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user lacks the required permission", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_list_users_route(
+    State(state): State<AppState>,
+    _: RequirePermission<UsersRead>,
+    Query(query): Query<ListUsersQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let per_page = query
+        .per_page
+        .unwrap_or(ADMIN_USERS_DEFAULT_PAGE_SIZE)
+        .clamp(1, ADMIN_USERS_MAX_PAGE_SIZE);
+    let user_query = UserQuery {
+        search: query.search.filter(|search| !search.is_empty()),
+        role: query
+            .role
+            .map(|role| Role::parse(&role))
+            .transpose()
+            .map_err(AuthAPIError::InvalidInput)?,
+        disabled: query.disabled,
+        requires_2fa: query.requires_2fa,
+        after: query
+            .cursor
+            .map(|cursor| UserCursor::decode(&cursor))
+            .transpose()
+            .map_err(AuthAPIError::InvalidInput)?,
+        limit: per_page,
+    };
+
+    let result = state
+        .user_store
+        .list_users(&user_query)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(ListUsersResponse {
+        users: result.users.iter().map(AdminUserResponse::from).collect(),
+        per_page,
+        total: result.total,
+        next_cursor: result.next_cursor.map(|cursor| cursor.encode()),
+    }))
+}
+
+/// Get a user
+///
+/// Requires the `users:read` permission.
+#[utoipa::path(
+    get,
+    path = "/admin/users/{email}",
+    tag = "admin",
+    params(("email" = String, Path, description = "Email of the user")),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "The user", body = AdminUserResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user lacks the required permission", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_get_user_route(
+    State(state): State<AppState>,
+    _: RequirePermission<UsersRead>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&email).map_err(|_| AuthAPIError::UserNotFound)?;
+    user_response(&state, &email).await
+}
+
+/// Disable a user
+///
+/// The user can no longer sign in, and their sessions are revoked so they are
+/// signed out at once.
+///
+/// Requires the `users:write` permission.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{email}/disable",
+    tag = "admin",
+    params(("email" = String, Path, description = "Email of the user")),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "The updated user", body = AdminUserResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user lacks the required permission", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_disable_user_route(
+    State(state): State<AppState>,
+    _: RequirePermission<UsersWrite>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&email).map_err(|_| AuthAPIError::UserNotFound)?;
+
+    state
+        .user_store
+        .set_disabled(&email, true)
+        .await
+        .map_err(|_| AuthAPIError::UserNotFound)?;
+    revoke_sessions(&state, &email).await?;
+
+    user_response(&state, &email).await
+}
+
+/// Enable a disabled user
+///
+/// Lets the user sign in again.
+///
+/// Requires the `users:write` permission.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{email}/enable",
+    tag = "admin",
+    params(("email" = String, Path, description = "Email of the user")),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "The updated user", body = AdminUserResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user lacks the required permission", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_enable_user_route(
+    State(state): State<AppState>,
+    _: RequirePermission<UsersWrite>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&email).map_err(|_| AuthAPIError::UserNotFound)?;
+
+    state
+        .user_store
+        .set_disabled(&email, false)
+        .await
+        .map_err(|_| AuthAPIError::UserNotFound)?;
+
+    user_response(&state, &email).await
+}
+
+/// Force a password reset
+///
+/// The user is signed out and has to choose a new password via `/change-password`.
+///
+/// Requires the `users:write` permission.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{email}/force-password-reset",
+    tag = "admin",
+    params(("email" = String, Path, description = "Email of the user")),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "The updated user", body = AdminUserResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user lacks the required permission", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_force_password_reset_route(
+    State(state): State<AppState>,
+    _: RequirePermission<UsersWrite>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&email).map_err(|_| AuthAPIError::UserNotFound)?;
+
+    state
+        .user_store
+        .set_password_reset_required(&email, true)
+        .await
+        .map_err(|_| AuthAPIError::UserNotFound)?;
+    revoke_sessions(&state, &email).await?;
+
+    user_response(&state, &email).await
+}
+
+/// Turn 2FA on or off for a user
+///
+/// Turning it off also removes the user's authenticator app, recovery codes
+/// and passkeys.
+///
+/// Requires the `users:write` permission.
+#[utoipa::path(
+    put,
+    path = "/admin/users/{email}/2fa",
+    tag = "admin",
+    params(("email" = String, Path, description = "Email of the user")),
+    request_body = AdminSet2FARequest,
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "The updated user", body = AdminUserResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user lacks the required permission", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_set_2fa_route(
+    State(state): State<AppState>,
+    _: RequirePermission<UsersWrite>,
+    Path(email): Path<String>,
+    Json(request): Json<AdminSet2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&email).map_err(|_| AuthAPIError::UserNotFound)?;
+
+    let user_store = &state.user_store;
+    let method = user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::UserNotFound)?
+        .two_fa_method;
+    // Like `authctl user reset-2fa`, turning 2FA off also removes the user's
+    // second factors, so a lost or leaked one doesn't come back with 2FA.
+    let result = if request.requires_2fa {
+        user_store.update_two_fa(&email, true, method).await
+    } else {
+        user_store.reset_two_fa(&email).await
+    };
+    result.map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    user_response(&state, &email).await
+}
+
+/// Unlock a user
+///
+/// Clears the failed login count of a user locked out by too many failed logins.
+///
+/// Requires the `users:write` permission.
+#[utoipa::path(
+    post,
+    path = "/admin/users/{email}/unlock",
+    tag = "admin",
+    params(("email" = String, Path, description = "Email of the user")),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "The updated user", body = AdminUserResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user lacks the required permission", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_unlock_user_route(
+    State(state): State<AppState>,
+    _: RequirePermission<UsersWrite>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&email).map_err(|_| AuthAPIError::UserNotFound)?;
+
+    state
+        .user_store
+        .clear_failed_logins(&email)
+        .await
+        .map_err(|_| AuthAPIError::UserNotFound)?;
+
+    user_response(&state, &email).await
+}
+
+/// Replace a user's roles and directly granted permissions
+///
+/// Tokens carry the user's roles, so their sessions are revoked for the
+/// change to take effect straight away.
+///
+/// Requires the `admin` role, since `users:write` alone would let a user
+/// grant themselves or others more than they hold.
+#[utoipa::path(
+    put,
+    path = "/admin/users/{email}/roles",
+    tag = "admin",
+    params(("email" = String, Path, description = "Email of the user")),
+    request_body = AdminSetRolesRequest,
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "The updated user", body = AdminUserResponse),
+        (status = 400, description = "Invalid role or permission name, or missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user is not an admin", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_set_roles_route(
+    State(state): State<AppState>,
+    _: RequireRole<Admin>,
+    Path(email): Path<String>,
+    Json(request): Json<AdminSetRolesRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&email).map_err(|_| AuthAPIError::UserNotFound)?;
+    let roles = request
+        .roles
+        .iter()
+        .map(|role| Role::parse(role))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AuthAPIError::InvalidInput)?;
+    let permissions = request
+        .permissions
+        .iter()
+        .map(|permission| Permission::parse(permission))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AuthAPIError::InvalidInput)?;
+
+    let user_store = &state.user_store;
+    user_store
+        .set_roles(&email, roles)
+        .await
+        .map_err(|_| AuthAPIError::UserNotFound)?;
+    user_store
+        .set_permissions(&email, permissions)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    revoke_sessions(&state, &email).await?;
+
+    user_response(&state, &email).await
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserExportQuery {
+    #[serde(default)]
+    pub format: UserExportFormat,
+    /// Include the secrets of authenticator apps. Without them, users who
+    /// sign in with an authenticator app are exported with email codes.
+    #[serde(rename = "includeTotpSecrets", default)]
+    pub include_totp_secrets: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UserImportQuery {
+    #[serde(default)]
+    pub format: UserExportFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct UserImportResponse {
+    pub imported: usize,
+    pub skipped: Vec<SkippedUser>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct SkippedUser {
+    pub email: String,
+    pub reason: String,
+}
+
+/// Export all users
+///
+/// Exports carry password hashes, and TOTP secrets when asked for, so they
+/// take the `admin` role. Users are fetched from the store a page at a time
+/// while the export streams, so an error part way through cuts the response
+/// short rather than changing its status.
+///
+/// Requires the `admin` role.
+#[utoipa::path(
+    get,
+    path = "/admin/users/export",
+    tag = "admin",
+    params(UserExportQuery),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Every user, ordered by email. CSV exports have a header row followed by one row per user, with the fields of ExportedUser plus version", content(
+            (UserExport = "application/json"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user is not an admin", body = ErrorResponse),
+    )
+)]
+pub async fn admin_export_users_route(
+    State(state): State<AppState>,
+    _: RequireRole<Admin>,
+    Query(query): Query<UserExportQuery>,
+) -> impl IntoResponse {
+    let content_type = match query.format {
+        UserExportFormat::Json => "application/json",
+        UserExportFormat::Csv => "text/csv",
+    };
+    let writer = UserExportWriter::new(query.format, query.include_totp_secrets);
+    let start = writer.start();
+
+    // `None` once the last page has been written.
+    let pages = stream::unfold(Some((writer, None)), move |next| {
+        let user_store = state.user_store.clone();
+        async move {
+            let (mut writer, after) = next?;
+            let query = UserQuery {
+                after,
+                limit: USER_EXPORT_PAGE_SIZE,
+                ..UserQuery::default()
+            };
+            let page = match user_store.list_users(&query).await {
+                Ok(page) => page,
+                Err(_) => {
+                    let error = std::io::Error::other("failed to list users");
+                    return Some((Err(error), None));
+                }
+            };
+            let mut chunk = writer.write(&page.users);
+            match page.next_cursor {
+                Some(cursor) => Some((Ok(chunk), Some((writer, Some(cursor))))),
+                None => {
+                    chunk.push_str(&writer.finish());
+                    Some((Ok(chunk), None))
+                }
+            }
+        }
+    });
+    let body = stream::once(async { Ok(start) }).chain(pages);
+
+    ([(header::CONTENT_TYPE, content_type)], Body::from_stream(body))
+}
+
+/// Import users
+///
+/// Adds users from an export. Users that already exist or fail validation are
+/// skipped and reported, without stopping the rest of the import. Imported
+/// bcrypt, PBKDF2 and Argon2 hashes are replaced with the service's own
+/// Argon2id hash on the user's first login.
+///
+/// Requires the `admin` role, as imported users keep their roles and
+/// permissions.
+#[utoipa::path(
+    post,
+    path = "/admin/users/import",
+    tag = "admin",
+    params(UserImportQuery),
+    request_body(content(
+        (UserExport = "application/json"),
+        (String = "text/csv"),
+    )),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Import finished", body = UserImportResponse),
+        (status = 400, description = "Malformed body, unsupported version or missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user is not an admin", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_import_users_route(
+    State(state): State<AppState>,
+    _: RequireRole<Admin>,
+    Query(query): Query<UserImportQuery>,
+    body: String,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let exported = parse_user_import(&body, query.format)
+        .map_err(|e| AuthAPIError::InvalidInput(e.to_string()))?;
+
+    let mut response = UserImportResponse {
+        imported: 0,
+        skipped: Vec::new(),
+    };
+    let user_store = &state.user_store;
+    for user in exported {
+        let email = user.email.clone();
+        let result = match user.into_user() {
+            Ok(user) => user_store.add_user(user).await.map_err(|e| match e {
+                UserStoreError::UserAlreadyExists => "user already exists".to_owned(),
+                _ => "unexpected error".to_owned(),
+            }),
+            Err(reason) => Err(reason),
+        };
+        match result {
+            Ok(()) => response.imported += 1,
+            Err(reason) => response.skipped.push(SkippedUser { email, reason }),
+        }
+    }
+
+    Ok(Json(response))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogQuery {
+    /// Only entries where the user is the actor or the subject.
+    pub email: Option<String>,
+    /// Inclusive Unix timestamps.
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct AuditLogResponse {
+    pub entries: Vec<AuditEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct AuditVerifyResponse {
+    pub valid: bool,
+    /// Where the chain first breaks, if it does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Query the audit log
+///
+/// Entries are returned oldest first.
+///
+/// Requires the `audit:read` permission.
+#[utoipa::path(
+    get,
+    path = "/admin/audit",
+    tag = "admin",
+    params(AuditLogQuery),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Matching audit log entries", body = AuditLogResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user lacks the required permission", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_audit_log_route(
+    State(state): State<AppState>,
+    _: RequirePermission<AuditRead>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let audit_query = AuditQuery {
+        email: query.email.filter(|email| !email.is_empty()),
+        from: query.from,
+        to: query.to,
+    };
+
+    let entries = state
+        .audit_sink
+        .query(&audit_query)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(AuditLogResponse { entries }))
+}
+
+/// Check the audit log for tampering
+///
+/// Recomputes the hash chain, which breaks if an entry was edited, removed or
+/// reordered.
+///
+/// Requires the `audit:read` permission.
+#[utoipa::path(
+    get,
+    path = "/admin/audit/verify",
+    tag = "admin",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Result of the check", body = AuditVerifyResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user lacks the required permission", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_verify_audit_log_route(
+    State(state): State<AppState>,
+    _: RequirePermission<AuditRead>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let response = match state.audit_sink.verify().await {
+        Ok(()) => AuditVerifyResponse {
+            valid: true,
+            error: None,
+        },
+        Err(AuditSinkError::Tampered(e)) => AuditVerifyResponse {
+            valid: false,
+            error: Some(e.to_string()),
+        },
+        Err(AuditSinkError::UnexpectedError) => return Err(AuthAPIError::UnexpectedError),
+    };
+
+    Ok(Json(response))
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct KeyRotationResponse {
+    pub kid: String,
+    /// Unix timestamp from which the key signs new tokens.
+    #[serde(rename = "activatesAt")]
+    pub activates_at: i64,
+}
+
+/// Rotate the signing key ahead of schedule
+///
+/// Publishes a new signing key, which takes over once verifiers have had time
+/// to fetch it. If a new key is already published, that key is returned instead.
+///
+/// Requires the `admin` role.
+#[utoipa::path(
+    post,
+    path = "/admin/keys/rotate",
+    tag = "admin",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "The key that will sign new tokens next", body = KeyRotationResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user is not an admin", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_rotate_keys_route(
+    State(state): State<AppState>,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let algorithm = state.key_ring.read().await.algorithm();
+    let successor = generate_signing_key(algorithm, chrono::Utc::now().timestamp())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let mut key_ring = state.key_ring.write().await;
+    let key = key_ring
+        .rotate_now_with(chrono::Utc::now().timestamp(), successor)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(KeyRotationResponse {
+        kid: key.kid.clone(),
+        activates_at: key.activates_at,
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AdminCreateServiceAccountRequest {
+    pub name: String,
+    /// Scopes the account's tokens may be granted.
+    pub scopes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ServiceAccountResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// Unix timestamp.
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    /// Unix timestamp before which the account's tokens are no longer accepted.
+    #[serde(rename = "secretRotatedAt")]
+    pub secret_rotated_at: i64,
+}
+
+impl From<&ServiceAccount> for ServiceAccountResponse {
+    fn from(account: &ServiceAccount) -> Self {
+        Self {
+            client_id: account.client_id.clone(),
+            name: account.name.clone(),
+            scopes: account.scopes.clone(),
+            created_at: account.created_at,
+            secret_rotated_at: account.secret_rotated_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ServiceAccountsResponse {
+    #[serde(rename = "serviceAccounts")]
+    pub service_accounts: Vec<ServiceAccountResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ServiceAccountCredentialsResponse {
+    #[serde(rename = "serviceAccount")]
+    pub service_account: ServiceAccountResponse,
+    /// Only shown this once; the auth service keeps a hash of it.
+    #[serde(rename = "clientSecret")]
+    pub client_secret: String,
+}
+
+impl ServiceAccountCredentialsResponse {
+    fn new(account: &ServiceAccount, secret: ClientSecret) -> Self {
+        Self {
+            service_account: ServiceAccountResponse::from(account),
+            client_secret: secret.as_ref().to_owned(),
+        }
+    }
+}
+
+/// List service accounts
+///
+/// Accounts are ordered by client id.
+///
+/// Requires the `admin` role.
+#[utoipa::path(
+    get,
+    path = "/admin/service-accounts",
+    tag = "admin",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Service accounts ordered by client id", body = ServiceAccountsResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user is not an admin", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_list_service_accounts_route(
+    State(state): State<AppState>,
+    _: RequireRole<Admin>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let accounts = state
+        .service_account_store
+        .list_accounts()
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(ServiceAccountsResponse {
+        service_accounts: accounts.iter().map(ServiceAccountResponse::from).collect(),
+    }))
+}
+
+/// Create a service account
+///
+/// The new account gets its tokens from `/token` with the
+/// `client_credentials` grant, using the returned client id and secret.
+///
+/// Requires the `admin` role.
+#[utoipa::path(
+    post,
+    path = "/admin/service-accounts",
+    tag = "admin",
+    request_body = AdminCreateServiceAccountRequest,
+    security(("jwt" = [])),
+    responses(
+        (status = 201, description = "The new account and its secret", body = ServiceAccountCredentialsResponse),
+        (status = 400, description = "Invalid name or scope, or missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user is not an admin", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_create_service_account_route(
+    State(state): State<AppState>,
+    _: RequireRole<Admin>,
+    Json(request): Json<AdminCreateServiceAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(AuthAPIError::InvalidInput("name must not be empty".to_owned()));
+    }
+    let scopes = request
+        .scopes
+        .iter()
+        .map(|scope| parse_scope(scope))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(AuthAPIError::InvalidInput)?;
+
+    let (account, secret) =
+        ServiceAccount::new(name.to_owned(), scopes, chrono::Utc::now().timestamp());
+    state
+        .service_account_store
+        .add_account(account.clone())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ServiceAccountCredentialsResponse::new(&account, secret)),
+    ))
+}
+
+/// Rotate a service account's secret
+///
+/// The old secret stops working straight away, and tokens issued with it are
+/// no longer accepted.
+///
+/// Requires the `admin` role.
+#[utoipa::path(
+    post,
+    path = "/admin/service-accounts/{client_id}/rotate-secret",
+    tag = "admin",
+    params(("client_id" = String, Path, description = "Client id of the service account")),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "The account and its new secret", body = ServiceAccountCredentialsResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user is not an admin", body = ErrorResponse),
+        (status = 404, description = "Service account not found", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_rotate_service_account_secret_route(
+    State(state): State<AppState>,
+    _: RequireRole<Admin>,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let store = &state.service_account_store;
+    let mut account = store
+        .get_account(&client_id)
+        .await
+        .map_err(service_account_error)?;
+    let secret = account.rotate_secret(chrono::Utc::now().timestamp());
+    store
+        .update_account(account.clone())
+        .await
+        .map_err(service_account_error)?;
+
+    Ok(Json(ServiceAccountCredentialsResponse::new(&account, secret)))
+}
+
+/// Delete a service account
+///
+/// Revokes its secret and every token issued to it.
+///
+/// Requires the `admin` role.
+#[utoipa::path(
+    delete,
+    path = "/admin/service-accounts/{client_id}",
+    tag = "admin",
+    params(("client_id" = String, Path, description = "Client id of the service account")),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Service account deleted"),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 403, description = "The signed-in user is not an admin", body = ErrorResponse),
+        (status = 404, description = "Service account not found", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn admin_delete_service_account_route(
+    State(state): State<AppState>,
+    _: RequireRole<Admin>,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    state
+        .service_account_store
+        .delete_account(&client_id)
+        .await
+        .map_err(service_account_error)?;
+    Ok(StatusCode::OK)
+}
+
+/// Records every request to the admin routes, including those the guards
+/// turn away. The actor is taken from the token alone so that requests with
+/// revoked sessions are still attributed.
+pub async fn audit_admin_requests(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    matched_path: MatchedPath,
+    path_params: Option<Path<HashMap<String, String>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let actor = match jar.get(JWT_COOKIE_NAME) {
+        Some(cookie) => validate_token(cookie.value(), &*state.key_ring.read().await)
+            .ok()
+            .map(|claims| claims.sub),
+        None => None,
+    };
+    let route = format!("{} {}", request.method(), matched_path.as_str());
+
+    let response = next.run(request).await;
+
+    let outcome = if response.status().is_success() {
+        AuditOutcome::Success
+    } else {
+        AuditOutcome::Failure
+    };
+    let mut event = AuditEvent::new(AuditAction::AdminAction, outcome, &client)
+        .detail(format!("{} ({})", route, response.status()));
+    if let Some(actor) = actor {
+        event = event.actor(actor);
+    }
+    let subject = path_params.and_then(|Path(mut params)| {
+        params.remove("email").or_else(|| params.remove("client_id"))
+    });
+    if let Some(subject) = subject {
+        event = event.subject(subject);
+    }
+    record_audit_event(&state, event).await;
+
+    response
+}
+
+fn service_account_error(e: ServiceAccountStoreError) -> AuthAPIError {
+    match e {
+        ServiceAccountStoreError::AccountNotFound => AuthAPIError::ServiceAccountNotFound,
+        _ => AuthAPIError::UnexpectedError,
+    }
+}
+
+async fn revoke_sessions(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    state
+        .session_store
+        .remove_sessions(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+async fn user_response(
+    state: &AppState,
+    email: &Email,
+) -> Result<Json<AdminUserResponse>, AuthAPIError> {
+    let user_store = &state.user_store;
+    let user = user_store
+        .get_user(email)
+        .await
+        .map_err(|_| AuthAPIError::UserNotFound)?;
+    Ok(Json(AdminUserResponse::from(&user)))
+}
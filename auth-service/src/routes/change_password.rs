@@ -0,0 +1,66 @@
+use crate::app_state::AppState;
+use crate::domain::{AuthAPIError, Email, Password, PasswordHash};
+use crate::routes::{check_password, ErrorResponse};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub email: String,
+    pub password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+/// Change password
+///
+/// Takes the current password rather than a session, so users whose password
+/// reset was forced by an admin can still get back in. Revokes all of the
+/// user's sessions.
+#[utoipa::path(
+    post,
+    path = "/change-password",
+    tag = "account",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed"),
+        (status = 400, description = "Invalid input, or a password that breaks the password policy, with the reason in `error`", body = ErrorResponse),
+        (status = 401, description = "Incorrect credentials", body = ErrorResponse),
+        (status = 403, description = "Account disabled", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 423, description = "Account locked after too many failed logins", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn change_password_route(
+    State(state): State<AppState>,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let new_password =
+        Password::parse_new(&request.new_password, &email, &*state.breached_passwords)?;
+
+    let user = check_password(&state, &email, &request.password).await?;
+    if user.disabled {
+        return Err(AuthAPIError::AccountDisabled);
+    }
+
+    state
+        .user_store
+        .set_password(&email, PasswordHash::hash(&new_password))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Sessions started with the old password are no longer trusted.
+    state
+        .session_store
+        .remove_sessions(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,62 @@
+use crate::app_state::AppState;
+use crate::utils::constants::JWKS_MAX_AGE_SECONDS;
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::Json;
+use std::borrow::Cow;
+use utoipa::openapi::schema::{ArrayBuilder, ObjectBuilder, Schema, Type};
+use utoipa::openapi::RefOr;
+use utoipa::{PartialSchema, ToSchema};
+
+/// Documents the `JwkSet` served at `/.well-known/jwks.json`, which comes from
+/// `jsonwebtoken` and so can't derive `ToSchema`.
+pub struct JwkSetSchema;
+
+impl PartialSchema for JwkSetSchema {
+    fn schema() -> RefOr<Schema> {
+        let string = || ObjectBuilder::new().schema_type(Type::String);
+        let key = ObjectBuilder::new()
+            .property("kty", string())
+            .property("use", string())
+            .property("alg", string())
+            .property("kid", string())
+            .required("kty")
+            .required("kid")
+            .description(Some("Key parameters beyond these depend on kty"));
+        ObjectBuilder::new()
+            .property("keys", ArrayBuilder::new().items(key))
+            .required("keys")
+            .into()
+    }
+}
+
+impl ToSchema for JwkSetSchema {
+    fn name() -> Cow<'static, str> {
+        Cow::Borrowed("JwkSet")
+    }
+}
+
+/// Public keys for verifying tokens issued by the auth service
+///
+/// Includes keys that will start signing soon and retired keys whose tokens
+/// may still be in use. Tokens name their key in the `kid` header.
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "openid-connect",
+    responses(
+        (status = 200, description = "JSON Web Key Set", body = JwkSetSchema, headers(("Cache-Control" = String))),
+    )
+)]
+pub async fn jwks_route(State(state): State<AppState>) -> impl IntoResponse {
+    let jwks = state.key_ring.read().await.jwks();
+
+    (
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", JWKS_MAX_AGE_SECONDS),
+        )],
+        Json(jwks),
+    )
+}
@@ -1,6 +1,228 @@
+use crate::app_state::AppState;
+use crate::domain::{
+    AuditAction, AuditEvent, AuditOutcome, AuthAPIError, ClientInfo, Email, LoginAttemptId,
+    Password, PasswordHash, TwoFACode, TwoFAMethod, User, UserVerification,
+};
+use crate::routes::{start_authentication, ErrorResponse, PublicKeyCredentialRequestOptions};
+use crate::utils::audit::record_audit_event;
+use crate::utils::auth::{check_can_sign_in, start_session};
+use crate::utils::constants::TWO_FA_CODE_TTL_SECONDS;
+use axum::extract::State;
 use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-pub async fn login_route() -> impl IntoResponse {
-    StatusCode::OK.into_response()
-}
\ No newline at end of file
+#[derive(Deserialize, ToSchema)]
+pub struct LoginRequest {
+    #[schema(format = "email")]
+    pub email: String,
+    #[schema(format = "password")]
+    pub password: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LoginResponse {
+    RegularAuth,
+    TwoFactorAuth(TwoFactorAuthResponse),
+}
+
+// Signing in without 2FA only sets the auth cookie, so there is no body.
+impl IntoResponse for LoginResponse {
+    fn into_response(self) -> Response {
+        match self {
+            LoginResponse::RegularAuth => StatusCode::OK.into_response(),
+            LoginResponse::TwoFactorAuth(response) => {
+                (StatusCode::PARTIAL_CONTENT, Json(response)).into_response()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct TwoFactorAuthResponse {
+    pub message: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    /// Assertion options for users whose second factor is a passkey.
+    #[serde(rename = "publicKey", default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<PublicKeyCredentialRequestOptions>,
+}
+
+/// Authenticate user and return JWT
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "account",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, description = "Login successful", headers(("Set-Cookie" = String, description = "The session's JWT"))),
+        (status = 206, description = "Login requires 2FA", body = TwoFactorAuthResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 401, description = "Authentication failed", body = ErrorResponse),
+        (status = 403, description = "Account disabled, or the user must change their password first", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 423, description = "Account locked after too many failed logins", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn login_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<LoginRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (jar, result) = login(&state, jar, client.clone(), &request).await;
+
+    let event = match &result {
+        Ok(LoginResponse::TwoFactorAuth(_)) => {
+            AuditEvent::new(AuditAction::TwoFAChallenge, AuditOutcome::Success, &client)
+        }
+        Ok(_) => AuditEvent::new(AuditAction::Login, AuditOutcome::Success, &client),
+        Err(e) => AuditEvent::new(AuditAction::Login, AuditOutcome::Failure, &client)
+            .detail(format!("{:?}", e)),
+    };
+    record_audit_event(&state, event.actor(request.email)).await;
+
+    (jar, result)
+}
+
+async fn login(
+    state: &AppState,
+    jar: CookieJar,
+    client: ClientInfo,
+    request: &LoginRequest,
+) -> (CookieJar, Result<LoginResponse, AuthAPIError>) {
+    let Ok(email) = Email::parse(&request.email) else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+
+    let user = match check_password(state, &email, &request.password).await {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
+    if let Err(e) = check_can_sign_in(&user) {
+        return (jar, Err(e));
+    }
+
+    if user.requires_2fa {
+        handle_2fa(&user.email, user.two_fa_method, state, jar).await
+    } else {
+        handle_no_2fa(&user, state, jar, client).await
+    }
+}
+
+/// Checks the user's password, locking the account after too many failures.
+/// Imported password hashes are upgraded to the service's own once they match.
+pub(crate) async fn check_password(
+    state: &AppState,
+    email: &Email,
+    password: &str,
+) -> Result<User, AuthAPIError> {
+    if password.is_empty() {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+    // The password policy only applies when a password is set, so that
+    // tightening it doesn't lock out anyone whose password predates it.
+    let normalized = Password::normalize(password);
+    let user_store = &state.user_store;
+    let user = user_store
+        .get_user(email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    if user.is_locked() {
+        return Err(AuthAPIError::AccountLocked);
+    }
+
+    // Passwords are hashed once normalized, except those set before they
+    // were, or imported from elsewhere.
+    let verified = user.password_hash.verify(normalized.as_ref())
+        || (normalized.as_ref() != password && user.password_hash.verify(password));
+    if !verified {
+        user_store
+            .record_failed_login(email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+    if user.failed_login_attempts > 0 {
+        user_store
+            .clear_failed_logins(email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+    if user.password_hash.needs_rehash() {
+        user_store
+            .update_password_hash(email, PasswordHash::hash(&normalized))
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(user)
+}
+
+pub(crate) async fn handle_2fa(
+    email: &Email,
+    method: TwoFAMethod,
+    state: &AppState,
+    jar: CookieJar,
+) -> (CookieJar, Result<LoginResponse, AuthAPIError>) {
+    let login_attempt_id = LoginAttemptId::default();
+    let two_fa_code = TwoFACode::default();
+    let expires_at = chrono::Utc::now().timestamp() + TWO_FA_CODE_TTL_SECONDS;
+
+    if state
+        .two_fa_code_store
+        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone(), expires_at)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // Authenticator app users generate their own code, so there is nothing to send.
+    let mut public_key = None;
+    match method {
+        TwoFAMethod::Email => {
+            if state
+                .email_client
+                .send_email(email, "2FA Code", two_fa_code.as_ref())
+                .await
+                .is_err()
+            {
+                return (jar, Err(AuthAPIError::UnexpectedError));
+            }
+        }
+        TwoFAMethod::Totp => {}
+        TwoFAMethod::WebAuthn => match start_authentication(state, email, UserVerification::Preferred).await {
+            Ok(options) => public_key = Some(options),
+            Err(e) => return (jar, Err(e)),
+        },
+    }
+
+    let response = LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
+        message: "2FA required".to_owned(),
+        login_attempt_id: login_attempt_id.as_ref().to_owned(),
+        public_key,
+    });
+
+    (jar, Ok(response))
+}
+
+pub(crate) async fn handle_no_2fa(
+    user: &User,
+    state: &AppState,
+    jar: CookieJar,
+    client: ClientInfo,
+) -> (CookieJar, Result<LoginResponse, AuthAPIError>) {
+    let auth_cookie = match start_session(state, user, client).await {
+        Ok(cookie) => cookie,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let updated_jar = jar.add(auth_cookie);
+
+    (updated_jar, Ok(LoginResponse::RegularAuth))
+}
@@ -1,6 +1,53 @@
+use crate::app_state::AppState;
+use crate::domain::{AuditAction, AuditEvent, AuditOutcome, AuthAPIError, ClientInfo};
+use crate::routes::ErrorResponse;
+use crate::utils::{
+    audit::record_audit_event, auth::authenticated_session, constants::JWT_COOKIE_NAME,
+};
+use axum::extract::State;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
+use axum_extra::extract::{cookie::Cookie, CookieJar};
 
-pub async fn logout_route () -> impl IntoResponse {
-    StatusCode::OK.into_response()
-}
\ No newline at end of file
+/// Logout user
+#[utoipa::path(
+    delete,
+    path = "/logout",
+    tag = "account",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Logout successful", headers(("Set-Cookie" = String, description = "Clears the JWT cookie"))),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn logout_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let claims = match authenticated_session(&jar, &state).await {
+        Ok(claims) => claims,
+        Err(e) => {
+            let event = AuditEvent::new(AuditAction::Logout, AuditOutcome::Failure, &client)
+                .detail(format!("{:?}", e));
+            record_audit_event(&state, event).await;
+            return (jar, Err(e));
+        }
+    };
+
+    // Revoke the session so the token can't be replayed before it expires.
+    if let Some(session_id) = claims.session_id() {
+        let _ = state
+            .session_store
+            .remove_session(&session_id)
+            .await;
+    }
+
+    let jar = jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/"));
+    let event = AuditEvent::new(AuditAction::Logout, AuditOutcome::Success, &client).actor(claims.sub);
+    record_audit_event(&state, event).await;
+
+    (jar, Ok(StatusCode::OK))
+}
@@ -0,0 +1,277 @@
+use crate::app_state::AppState;
+use crate::domain::{
+    AuditAction, AuditEvent, AuditOutcome, AuthAPIError, BrowserBinding, ClientInfo, Email,
+    MagicLink, MagicLinkId, MagicLinkStoreError,
+};
+use crate::routes::{handle_2fa, handle_no_2fa, ErrorResponse, LoginResponse, TwoFactorAuthResponse};
+use crate::utils::audit::record_audit_event;
+use crate::utils::auth::{
+    check_can_sign_in, generate_magic_link_token, validate_magic_link_token, MagicLinkClaims,
+};
+use crate::utils::constants::{
+    MAGIC_LINK_BINDING_COOKIE_NAME, MAGIC_LINK_TTL_SECONDS, MAGIC_LINK_URL,
+};
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::Json;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use url::Url;
+use utoipa::ToSchema;
+
+// The binding cookie is only needed by the verify endpoint.
+const BINDING_COOKIE_PATH: &str = "/login/magic-link";
+
+#[derive(Deserialize, ToSchema)]
+pub struct MagicLinkRequest {
+    #[schema(format = "email")]
+    pub email: String,
+    /// Only let the link sign in from the browser that asked for it, which is
+    /// given a binding cookie.
+    #[serde(rename = "sameBrowser", default)]
+    pub same_browser: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct MagicLinkResponse {
+    pub message: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyMagicLinkRequest {
+    pub token: String,
+}
+
+/// Email the user a single-use sign-in link
+///
+/// The link points at `MAGIC_LINK_URL` with `magicLinkToken` and `email` query
+/// parameters. The response is the same whether or not the account exists.
+#[utoipa::path(
+    post,
+    path = "/login/magic-link",
+    tag = "account",
+    request_body = MagicLinkRequest,
+    responses(
+        (status = 200, description = "Link sent, if the account exists and can sign in", body = MagicLinkResponse, headers(("Set-Cookie" = String, description = "Binds the link to this browser when `sameBrowser` is set"))),
+        (status = 400, description = "Invalid email", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn magic_link_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<MagicLinkRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (jar, result) = send_magic_link(&state, jar, &request).await;
+
+    let event = match &result {
+        Ok(()) => AuditEvent::new(AuditAction::MagicLinkRequest, AuditOutcome::Success, &client),
+        Err(e) => AuditEvent::new(AuditAction::MagicLinkRequest, AuditOutcome::Failure, &client)
+            .detail(format!("{:?}", e)),
+    };
+    record_audit_event(&state, event.actor(request.email)).await;
+
+    // The response is the same whether or not a link was sent, so that it
+    // doesn't tell anyone which emails have accounts.
+    let result = match result {
+        Ok(())
+        | Err(
+            AuthAPIError::UserNotFound
+            | AuthAPIError::AccountDisabled
+            | AuthAPIError::PasswordResetRequired,
+        ) => Ok(Json(MagicLinkResponse {
+            message: "If an account exists for this email, a sign-in link is on its way".to_owned(),
+        })),
+        Err(e) => Err(e),
+    };
+    (jar, result)
+}
+
+async fn send_magic_link(
+    state: &AppState,
+    jar: CookieJar,
+    request: &MagicLinkRequest,
+) -> (CookieJar, Result<(), AuthAPIError>) {
+    let email = match Email::parse(&request.email) {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e.into())),
+    };
+
+    // The cookie is set whether or not the account exists, for the same
+    // reason the response doesn't differ.
+    let (jar, binding) = if request.same_browser {
+        let binding = BrowserBinding::generate();
+        (jar.add(binding_cookie(&binding)), Some(binding))
+    } else {
+        (jar, None)
+    };
+
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::UserNotFound)),
+    };
+    if let Err(e) = check_can_sign_in(&user) {
+        return (jar, Err(e));
+    }
+
+    let now = Utc::now().timestamp();
+    let id = MagicLinkId::generate();
+    let link = MagicLink {
+        email: user.email.clone(),
+        expires_at: now + MAGIC_LINK_TTL_SECONDS,
+    };
+    let token = match generate_magic_link_token(
+        &link.email,
+        &id,
+        binding.as_ref(),
+        link.expires_at,
+        &*state.key_ring.read().await,
+    ) {
+        Ok(token) => token,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+    let Ok(mut url) = Url::parse(&MAGIC_LINK_URL) else {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    };
+    url.query_pairs_mut()
+        .append_pair("magicLinkToken", &token)
+        .append_pair("email", link.email.as_ref());
+
+    {
+        let magic_link_store = &state.magic_link_store;
+        if magic_link_store.remove_expired_links(now).await.is_err()
+            || magic_link_store.add_link(id, link).await.is_err()
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+    }
+
+    let content = format!(
+        "Follow this link to sign in. It works once, within {} minutes: {}",
+        MAGIC_LINK_TTL_SECONDS / 60,
+        url
+    );
+    if state
+        .email_client
+        .send_email(&user.email, "Your sign-in link", &content)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    (jar, Ok(()))
+}
+
+fn binding_cookie(binding: &BrowserBinding) -> Cookie<'static> {
+    Cookie::build((MAGIC_LINK_BINDING_COOKIE_NAME, binding.as_ref().to_owned()))
+        .path(BINDING_COOKIE_PATH)
+        .http_only(true)
+        // The link is opened by navigating to the page, but the page then
+        // posts the token itself, which is a same-site request.
+        .same_site(SameSite::Strict)
+        .build()
+}
+
+/// Sign in with the token from a sign-in link
+#[utoipa::path(
+    post,
+    path = "/login/magic-link/verify",
+    tag = "account",
+    request_body = VerifyMagicLinkRequest,
+    responses(
+        (status = 200, description = "Login successful", headers(("Set-Cookie" = String, description = "The session's JWT"))),
+        (status = 206, description = "Login requires 2FA", body = TwoFactorAuthResponse),
+        (status = 401, description = "Token invalid, expired or already used", body = ErrorResponse),
+        (status = 403, description = "Link bound to another browser, account disabled, or the user must change their password first", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn verify_magic_link_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<VerifyMagicLinkRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let claims = validate_magic_link_token(&request.token, &*state.key_ring.read().await).ok();
+    let actor = claims.as_ref().map(|claims| claims.sub.clone());
+    let (jar, result) = verify_magic_link(&state, jar, client.clone(), claims).await;
+
+    let event = match &result {
+        Ok(LoginResponse::TwoFactorAuth(_)) => {
+            AuditEvent::new(AuditAction::TwoFAChallenge, AuditOutcome::Success, &client)
+        }
+        Ok(_) => AuditEvent::new(AuditAction::Login, AuditOutcome::Success, &client),
+        Err(e) => AuditEvent::new(AuditAction::Login, AuditOutcome::Failure, &client)
+            .detail(format!("magic link: {:?}", e)),
+    };
+    // Tells these apart from password sign-ins in the log.
+    let event = if result.is_ok() { event.detail("magic link") } else { event };
+    let event = match actor {
+        Some(actor) => event.actor(actor),
+        None => event,
+    };
+    record_audit_event(&state, event).await;
+
+    (jar, result)
+}
+
+async fn verify_magic_link(
+    state: &AppState,
+    jar: CookieJar,
+    client: ClientInfo,
+    claims: Option<MagicLinkClaims>,
+) -> (CookieJar, Result<LoginResponse, AuthAPIError>) {
+    let Some(claims) = claims else {
+        return (jar, Err(AuthAPIError::InvalidToken));
+    };
+
+    // Checked before the link is used up, so that opening it in the wrong
+    // browser doesn't stop it working in the right one.
+    if let Some(digest) = &claims.bnd {
+        let bound = jar
+            .get(MAGIC_LINK_BINDING_COOKIE_NAME)
+            .and_then(|cookie| BrowserBinding::parse(cookie.value()).ok())
+            .is_some_and(|binding| binding.digest() == *digest);
+        if !bound {
+            return (jar, Err(AuthAPIError::WrongBrowser));
+        }
+    }
+
+    let (Ok(id), Ok(email)) = (MagicLinkId::parse(claims.jti), Email::parse(&claims.sub)) else {
+        return (jar, Err(AuthAPIError::InvalidToken));
+    };
+    let link = match state.magic_link_store.take_link(&id).await {
+        Ok(link) => link,
+        Err(MagicLinkStoreError::LinkNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+    if link.email != email || link.is_expired(Utc::now().timestamp()) {
+        return (jar, Err(AuthAPIError::InvalidToken));
+    }
+
+    let jar = if claims.bnd.is_some() {
+        jar.remove(Cookie::build(MAGIC_LINK_BINDING_COOKIE_NAME).path(BINDING_COOKIE_PATH))
+    } else {
+        jar
+    };
+
+    let user = match state.user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+    if let Err(e) = check_can_sign_in(&user) {
+        return (jar, Err(e));
+    }
+
+    if user.requires_2fa {
+        handle_2fa(&user.email, user.two_fa_method, state, jar).await
+    } else {
+        handle_no_2fa(&user, state, jar, client).await
+    }
+}
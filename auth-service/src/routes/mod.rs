@@ -1,11 +1,33 @@
+mod admin;
+mod change_password;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod oidc;
+mod openapi;
+mod recovery_codes;
+mod sessions;
 mod signup;
+mod totp;
+mod two_fa_method;
 mod verify_token;
 mod verify_2fa;
+mod webauthn;
 
+pub use admin::*;
+pub use change_password::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use magic_link::*;
+pub use oidc::*;
+pub use openapi::*;
+pub use recovery_codes::*;
+pub use sessions::*;
 pub use signup::*;
+pub use totp::*;
+pub use two_fa_method::*;
 pub use verify_2fa::*;
-pub use verify_token::*;
\ No newline at end of file
+pub use verify_token::*;
+pub use webauthn::*;
@@ -0,0 +1,655 @@
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError, AuthorizationCode, AuthorizationGrant, ClientInfo, Email, OAuthError, OidcClient,
+    PkceChallenge, OIDC_SCOPE, PKCE_METHOD_S256,
+};
+use crate::utils::auth::{
+    authenticated_email, check_can_sign_in, create_session, generate_access_token,
+    generate_id_token, generate_service_account_token, validate_access_token, TOKEN_TTL_SECONDS,
+};
+use crate::utils::constants::{AUTHORIZATION_CODE_TTL_SECONDS, OIDC_ISSUER};
+use axum::extract::{OriginalUri, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum::{Form, Json};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use url::Url;
+use utoipa::{IntoParams, ToSchema};
+
+const EMAIL_SCOPE: &str = "email";
+const SUPPORTED_SCOPES: [&str; 2] = [OIDC_SCOPE, EMAIL_SCOPE];
+const GRANT_TYPE_AUTHORIZATION_CODE: &str = "authorization_code";
+const GRANT_TYPE_CLIENT_CREDENTIALS: &str = "client_credentials";
+// The login page served from `assets/`, which sends the user back to
+// `next` once they have signed in.
+const LOGIN_PAGE_PATH: &str = "/";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub introspection_endpoint: String,
+    pub jwks_uri: String,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<String>,
+    pub scopes_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub introspection_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+}
+
+// Parameters are optional so that missing ones are reported with OAuth error
+// codes instead of being rejected by the extractor.
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuthorizeRequest {
+    /// Must be `code`.
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    /// One of the client's registered redirect URIs.
+    pub redirect_uri: Option<String>,
+    /// Must include `openid`.
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    /// Must be `S256`.
+    pub code_challenge_method: Option<String>,
+    /// `none` to fail with `login_required` instead of showing the login page.
+    pub prompt: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TokenRequest {
+    /// `authorization_code`, or `client_credentials` for service accounts.
+    pub grant_type: Option<String>,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub code_verifier: Option<String>,
+    /// Scopes a service account asks for, defaulting to all of its own.
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    /// Only issued to users, not to service accounts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    pub scope: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct IntrospectionRequest {
+    pub token: Option<String>,
+    /// Accepted and ignored, as only access tokens can be introspected.
+    pub token_type_hint: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+}
+
+/// Token metadata as defined by RFC 7662. Only `active` is present for
+/// tokens that are not valid.
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct IntrospectionResponse {
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// Absent for the tokens of browser sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    /// Unix timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    /// Unix timestamp.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    /// Absent for service account tokens.
+    pub session: Option<IntrospectedSession>,
+}
+
+/// The session a token was issued to.
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct IntrospectedSession {
+    pub id: String,
+    /// Unix timestamp.
+    pub created_at: i64,
+    /// Unix timestamp of the last request made with the session's token.
+    pub last_seen_at: i64,
+    /// Unix timestamp.
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct UserInfoResponse {
+    pub sub: String,
+    /// Only with the `email` scope.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct OAuthErrorResponse {
+    pub error: String,
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            OAuthError::InvalidClient | OAuthError::InvalidToken => StatusCode::UNAUTHORIZED,
+            OAuthError::InsufficientScope => StatusCode::FORBIDDEN,
+            OAuthError::ServerError => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        let body = Json(OAuthErrorResponse {
+            error: self.code().to_owned(),
+        });
+
+        if matches!(self, OAuthError::InvalidToken | OAuthError::InsufficientScope) {
+            // RFC 6750 section 3: bearer token failures carry a challenge.
+            let challenge = format!("Bearer error=\"{}\"", self.code());
+            return (status, [(header::WWW_AUTHENTICATE, challenge)], body).into_response();
+        }
+        (status, body).into_response()
+    }
+}
+
+/// OpenID Connect discovery document
+#[utoipa::path(
+    get,
+    path = "/.well-known/openid-configuration",
+    tag = "openid-connect",
+    responses(
+        (status = 200, description = "Provider metadata as defined by OpenID Connect Discovery 1.0", body = OpenIdConfiguration),
+    )
+)]
+pub async fn openid_configuration_route(State(state): State<AppState>) -> impl IntoResponse {
+    let algorithm = state.key_ring.read().await.algorithm();
+    let issuer = OIDC_ISSUER.trim_end_matches('/');
+    let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+
+    Json(OpenIdConfiguration {
+        issuer: issuer.to_owned(),
+        authorization_endpoint: format!("{}/authorize", issuer),
+        token_endpoint: format!("{}/token", issuer),
+        userinfo_endpoint: format!("{}/userinfo", issuer),
+        introspection_endpoint: format!("{}/introspect", issuer),
+        jwks_uri: format!("{}/.well-known/jwks.json", issuer),
+        response_types_supported: strings(&["code"]),
+        grant_types_supported: strings(&[
+            GRANT_TYPE_AUTHORIZATION_CODE,
+            GRANT_TYPE_CLIENT_CREDENTIALS,
+        ]),
+        subject_types_supported: strings(&["public"]),
+        id_token_signing_alg_values_supported: strings(&[algorithm.as_str()]),
+        scopes_supported: strings(&SUPPORTED_SCOPES),
+        claims_supported: strings(&["iss", "sub", "aud", "exp", "iat", "nonce", "email"]),
+        token_endpoint_auth_methods_supported: strings(&[
+            "client_secret_basic",
+            "client_secret_post",
+            "none",
+        ]),
+        introspection_endpoint_auth_methods_supported: strings(&[
+            "client_secret_basic",
+            "client_secret_post",
+        ]),
+        code_challenge_methods_supported: strings(&[PKCE_METHOD_S256]),
+    })
+}
+
+/// Start the OpenID Connect authorization code flow
+///
+/// Users without a valid session are sent to the regular login page, which
+/// brings them back here once they have signed in (including any 2FA step).
+/// PKCE with S256 is required.
+#[utoipa::path(
+    get,
+    path = "/authorize",
+    tag = "openid-connect",
+    params(AuthorizeRequest),
+    responses(
+        (status = 303, description = "Redirect to the client's redirect_uri with a code (or an error) and the state, or to the login page when the user is not signed in", headers(("Location" = String))),
+        (status = 400, description = "Missing or unregistered redirect_uri", body = OAuthErrorResponse),
+        (status = 401, description = "Unknown client", body = OAuthErrorResponse),
+    )
+)]
+pub async fn authorize_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    OriginalUri(uri): OriginalUri,
+    Query(request): Query<AuthorizeRequest>,
+) -> Response {
+    let Some(client_id) = request.client_id else {
+        return OAuthError::InvalidRequest.into_response();
+    };
+    let Ok(client) = state.oidc_client_store.get_client(&client_id).await else {
+        return OAuthError::InvalidClient.into_response();
+    };
+
+    // Until the redirect URI is known to belong to the client, errors must be
+    // shown here rather than sent to a possibly attacker controlled URI.
+    let Some(redirect_uri) = request
+        .redirect_uri
+        .filter(|uri| client.allows_redirect_uri(uri))
+    else {
+        return OAuthError::InvalidRequest.into_response();
+    };
+    let state_param = request.state.as_deref();
+    let error_redirect = |error: OAuthError| {
+        redirect_to_client(&redirect_uri, &[("error", error.code())], state_param)
+    };
+
+    if request.response_type.as_deref() != Some("code") {
+        return error_redirect(OAuthError::UnsupportedResponseType);
+    }
+
+    let requested_scopes = request.scope.unwrap_or_default();
+    let scopes: Vec<&str> = requested_scopes
+        .split_whitespace()
+        .filter(|scope| SUPPORTED_SCOPES.contains(scope))
+        .collect();
+    if !scopes.contains(&OIDC_SCOPE) {
+        return error_redirect(OAuthError::InvalidScope);
+    }
+
+    let code_challenge = match (request.code_challenge, request.code_challenge_method) {
+        (Some(challenge), Some(method)) => match PkceChallenge::parse(&challenge, &method) {
+            Ok(challenge) => challenge,
+            Err(_) => return error_redirect(OAuthError::InvalidRequest),
+        },
+        _ => return error_redirect(OAuthError::InvalidRequest),
+    };
+
+    let Some(email) = signed_in_user(&state, &jar).await else {
+        if request.prompt.as_deref() == Some("none") {
+            return error_redirect(OAuthError::LoginRequired);
+        }
+        return redirect_to_login(uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/"));
+    };
+
+    let code = AuthorizationCode::generate();
+    let grant = AuthorizationGrant {
+        client_id: client.client_id,
+        redirect_uri: redirect_uri.clone(),
+        email,
+        scope: scopes.join(" "),
+        nonce: request.nonce,
+        code_challenge,
+        expires_at: Utc::now().timestamp() + AUTHORIZATION_CODE_TTL_SECONDS,
+    };
+    if state
+        .authorization_code_store
+        .add_code(code.clone(), grant)
+        .await
+        .is_err()
+    {
+        return error_redirect(OAuthError::ServerError);
+    }
+
+    redirect_to_client(&redirect_uri, &[("code", code.as_ref())], state_param)
+}
+
+/// Exchange an authorization code or client credentials for tokens
+///
+/// Confidential clients authenticate with HTTP Basic or `client_secret`;
+/// public clients send `client_id` only. Service accounts use the
+/// `client_credentials` grant with their own client id and secret, and get
+/// an access token for their scopes without an ID token.
+#[utoipa::path(
+    post,
+    path = "/token",
+    tag = "openid-connect",
+    request_body(content = TokenRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Tokens issued", body = TokenResponse, headers(("Cache-Control" = String), ("Pragma" = String))),
+        (status = 400, description = "OAuth error such as invalid_grant, invalid_scope or invalid_request", body = OAuthErrorResponse),
+        (status = 401, description = "Client authentication failed", body = OAuthErrorResponse),
+        (status = 500, description = "Unexpected error", body = OAuthErrorResponse),
+    )
+)]
+pub async fn token_route(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    client_info: ClientInfo,
+    Form(request): Form<TokenRequest>,
+) -> Result<Response, OAuthError> {
+    if request.grant_type.as_deref() == Some(GRANT_TYPE_CLIENT_CREDENTIALS) {
+        return client_credentials_grant(&state, &headers, request).await;
+    }
+
+    let client = authenticate_client(
+        &state,
+        &headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+    )
+    .await?;
+
+    match request.grant_type.as_deref() {
+        Some(GRANT_TYPE_AUTHORIZATION_CODE) => {}
+        Some(_) => return Err(OAuthError::UnsupportedGrantType),
+        None => return Err(OAuthError::InvalidRequest),
+    }
+
+    let (Some(code), Some(redirect_uri), Some(code_verifier)) =
+        (request.code, request.redirect_uri, request.code_verifier)
+    else {
+        return Err(OAuthError::InvalidRequest);
+    };
+    let code = AuthorizationCode::parse(code).map_err(|_| OAuthError::InvalidGrant)?;
+
+    let grant = state
+        .authorization_code_store
+        .take_code(&code)
+        .await
+        .map_err(|_| OAuthError::InvalidGrant)?;
+
+    if grant.client_id != client.client_id
+        || grant.redirect_uri != redirect_uri
+        || grant.is_expired(Utc::now().timestamp())
+        || !grant.code_challenge.verify(&code_verifier)
+    {
+        return Err(OAuthError::InvalidGrant);
+    }
+
+    // The user may have been disabled since the code was issued.
+    let user = state
+        .user_store
+        .get_user(&grant.email)
+        .await
+        .map_err(|_| OAuthError::InvalidGrant)?;
+    check_can_sign_in(&user).map_err(|_| OAuthError::InvalidGrant)?;
+
+    // The access token gets its own session, so it can be revoked like any other login.
+    let session_id = create_session(&state, &grant.email, client_info)
+        .await
+        .map_err(|_| OAuthError::ServerError)?;
+    let key_ring = state.key_ring.read().await;
+    let access_token =
+        generate_access_token(&user, &session_id, &client.client_id, &grant.scope, &key_ring)
+            .map_err(|_| OAuthError::ServerError)?;
+    let id_token = generate_id_token(&grant.email, &client.client_id, grant.nonce, &key_ring)
+        .map_err(|_| OAuthError::ServerError)?;
+    drop(key_ring);
+
+    Ok(token_response(access_token, Some(id_token), grant.scope))
+}
+
+// Service accounts have no user to sign in or session to revoke, so the
+// token is issued straight away and revoked through the account.
+async fn client_credentials_grant(
+    state: &AppState,
+    headers: &HeaderMap,
+    request: TokenRequest,
+) -> Result<Response, OAuthError> {
+    let (client_id, client_secret) = client_credentials(
+        headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+    )?;
+    let account = state
+        .service_account_store
+        .get_account(&client_id)
+        .await
+        .map_err(|_| OAuthError::InvalidClient)?;
+    if !client_secret.is_some_and(|secret| account.authenticate(&secret)) {
+        return Err(OAuthError::InvalidClient);
+    }
+
+    let scope = account
+        .grant_scope(request.scope.as_deref())
+        .map_err(|_| OAuthError::InvalidScope)?;
+    let access_token =
+        generate_service_account_token(&account, &scope, &*state.key_ring.read().await)
+            .map_err(|_| OAuthError::ServerError)?;
+
+    Ok(token_response(access_token, None, scope))
+}
+
+fn token_response(access_token: String, id_token: Option<String>, scope: String) -> Response {
+    (
+        [(header::CACHE_CONTROL, "no-store"), (header::PRAGMA, "no-cache")],
+        Json(TokenResponse {
+            access_token,
+            token_type: "Bearer".to_owned(),
+            expires_in: TOKEN_TTL_SECONDS,
+            id_token,
+            scope,
+        }),
+    )
+        .into_response()
+}
+
+/// Claims about the user an access token was issued to
+///
+/// Needs an access token from the token endpoint with the `openid` scope.
+/// The email is only returned if the `email` scope was granted as well.
+#[utoipa::path(
+    method(get, post),
+    path = "/userinfo",
+    tag = "openid-connect",
+    security(("access_token" = [])),
+    responses(
+        (status = 200, description = "User claims", body = UserInfoResponse),
+        (status = 401, description = "Access token is missing or not valid", body = OAuthErrorResponse, headers(("WWW-Authenticate" = String))),
+        (status = 403, description = "Access token was not granted the `openid` scope", body = OAuthErrorResponse, headers(("WWW-Authenticate" = String))),
+    )
+)]
+pub async fn userinfo_route(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, OAuthError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(OAuthError::InvalidToken)?;
+    let (claims, session) = validate_access_token(token, &state)
+        .await
+        .map_err(|_| OAuthError::InvalidToken)?;
+    // Session cookies have no client, and service account tokens no session.
+    if claims.client_id.is_none() || session.is_none() {
+        return Err(OAuthError::InvalidToken);
+    }
+    let scopes: Vec<&str> = claims.scope.as_deref().unwrap_or_default().split_whitespace().collect();
+    if !scopes.contains(&OIDC_SCOPE) {
+        return Err(OAuthError::InsufficientScope);
+    }
+    let email = Email::parse(&claims.sub).map_err(|_| OAuthError::InvalidToken)?;
+
+    state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| OAuthError::InvalidToken)?;
+
+    Ok(Json(UserInfoResponse {
+        sub: claims.sub,
+        email: scopes.contains(&EMAIL_SCOPE).then(|| email.as_ref().to_owned()),
+    }))
+}
+
+/// Introspect a token
+///
+/// Tells resource servers whether a token is active and, if it is, who and
+/// what it was issued for, as defined by RFC 7662. Tokens whose session has
+/// been revoked or has expired, and service account tokens whose account has
+/// been deleted or whose secret has been rotated, are inactive. Only confidential clients may
+/// introspect, authenticating with HTTP Basic or `client_secret`.
+#[utoipa::path(
+    post,
+    path = "/introspect",
+    tag = "openid-connect",
+    request_body(content = IntrospectionRequest, content_type = "application/x-www-form-urlencoded"),
+    responses(
+        (status = 200, description = "Token metadata, or only `active: false` for tokens that are not valid", body = IntrospectionResponse, headers(("Cache-Control" = String), ("Pragma" = String))),
+        (status = 400, description = "The token is missing", body = OAuthErrorResponse),
+        (status = 401, description = "Client authentication failed or the client is public", body = OAuthErrorResponse),
+        (status = 500, description = "Unexpected error", body = OAuthErrorResponse),
+    )
+)]
+pub async fn introspect_route(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(request): Form<IntrospectionRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let client = authenticate_client(
+        &state,
+        &headers,
+        request.client_id.as_deref(),
+        request.client_secret.as_deref(),
+    )
+    .await?;
+    if client.is_public() {
+        return Err(OAuthError::InvalidClient);
+    }
+    let token = request.token.ok_or(OAuthError::InvalidRequest)?;
+    let response = introspect_token(&token, &state)
+        .await
+        .map_err(|_| OAuthError::ServerError)?;
+
+    Ok((
+        [(header::CACHE_CONTROL, "no-store"), (header::PRAGMA, "no-cache")],
+        Json(response),
+    ))
+}
+
+/// Describes a token for `/introspect` and the gRPC `IntrospectToken` call,
+/// once the caller has been authenticated.
+pub async fn introspect_token(
+    token: &str,
+    state: &AppState,
+) -> Result<IntrospectionResponse, AuthAPIError> {
+    match validate_access_token(token, state).await {
+        Ok((claims, session)) => Ok(IntrospectionResponse {
+            active: true,
+            scope: claims.scope,
+            client_id: claims.client_id,
+            token_type: Some("Bearer".to_owned()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            sub: Some(claims.sub),
+            iss: Some(OIDC_ISSUER.trim_end_matches('/').to_owned()),
+            session: session.map(|session| IntrospectedSession {
+                id: session.id.as_ref().to_owned(),
+                created_at: session.created_at,
+                last_seen_at: session.last_seen_at,
+                expires_at: session.expires_at,
+            }),
+        }),
+        Err(AuthAPIError::InvalidToken) => Ok(IntrospectionResponse::default()),
+        Err(e) => Err(e),
+    }
+}
+
+async fn signed_in_user(state: &AppState, jar: &CookieJar) -> Option<Email> {
+    let email = authenticated_email(jar, state).await.ok()?;
+    // The cookie may outlive the account it was issued for.
+    state.user_store.get_user(&email).await.ok()?;
+    Some(email)
+}
+
+async fn authenticate_client(
+    state: &AppState,
+    headers: &HeaderMap,
+    form_client_id: Option<&str>,
+    form_client_secret: Option<&str>,
+) -> Result<OidcClient, OAuthError> {
+    let (client_id, client_secret) =
+        client_credentials(headers, form_client_id, form_client_secret)?;
+    let client = state
+        .oidc_client_store
+        .get_client(&client_id)
+        .await
+        .map_err(|_| OAuthError::InvalidClient)?;
+    if !client.authenticate(client_secret.as_deref()) {
+        return Err(OAuthError::InvalidClient);
+    }
+    Ok(client)
+}
+
+// Clients authenticate with HTTP Basic or, for public clients and those that
+// can't set headers, with `client_id`/`client_secret` form fields.
+fn client_credentials(
+    headers: &HeaderMap,
+    form_client_id: Option<&str>,
+    form_client_secret: Option<&str>,
+) -> Result<(String, Option<String>), OAuthError> {
+    let Some(authorization) = headers.get(header::AUTHORIZATION) else {
+        let client_id = form_client_id.ok_or(OAuthError::InvalidClient)?;
+        return Ok((client_id.to_owned(), form_client_secret.map(str::to_owned)));
+    };
+
+    // Using more than one authentication method is not allowed.
+    if form_client_secret.is_some() {
+        return Err(OAuthError::InvalidRequest);
+    }
+
+    let credentials = authorization
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .ok_or(OAuthError::InvalidClient)?;
+    let (client_id, client_secret) = credentials
+        .split_once(':')
+        .ok_or(OAuthError::InvalidClient)?;
+    // RFC 6749 section 2.3.1 has both parts form-urlencoded before they're
+    // joined, so that either may contain a colon.
+    let client_id = form_urldecode(client_id).ok_or(OAuthError::InvalidClient)?;
+    let client_secret = form_urldecode(client_secret).ok_or(OAuthError::InvalidClient)?;
+
+    if form_client_id.is_some_and(|id| id != client_id) {
+        return Err(OAuthError::InvalidRequest);
+    }
+    Ok((client_id, Some(client_secret)))
+}
+
+fn form_urldecode(value: &str) -> Option<String> {
+    percent_decode_str(&value.replace('+', " "))
+        .decode_utf8()
+        .ok()
+        .map(String::from)
+}
+
+fn redirect_to_client(redirect_uri: &str, params: &[(&str, &str)], state: Option<&str>) -> Response {
+    let Ok(mut url) = Url::parse(redirect_uri) else {
+        return OAuthError::InvalidRequest.into_response();
+    };
+    {
+        let mut query = url.query_pairs_mut();
+        query.extend_pairs(params);
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+    }
+    Redirect::to(url.as_str()).into_response()
+}
+
+fn redirect_to_login(next: &str) -> Response {
+    let query = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("next", next)
+        .finish();
+    Redirect::to(&format!("{}?{}", LOGIN_PAGE_PATH, query)).into_response()
+}
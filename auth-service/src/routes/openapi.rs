@@ -0,0 +1,99 @@
+use super::*;
+use crate::utils::constants::JWT_COOKIE_NAME;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+
+/// The OpenAPI document for every route, generated from the handlers and
+/// their request and response types.
+///
+/// It is served at `/openapi.json`, and `api_schema.yml` is a checked-in copy
+/// that a test keeps in step with it.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Authentication Service API",
+        description = "This is an API for an authentication service using JWT and optional 2FA.",
+        version = "1.0.0",
+    ),
+    paths(
+        signup_route,
+        login_route,
+        magic_link_route,
+        verify_magic_link_route,
+        logout_route,
+        verify_token_route,
+        verify_2fa_route,
+        change_password_route,
+        list_sessions_route,
+        revoke_all_sessions_route,
+        revoke_session_route,
+        two_fa_method_route,
+        totp_enroll_route,
+        totp_confirm_route,
+        recovery_codes_status_route,
+        regenerate_recovery_codes_route,
+        webauthn_register_start_route,
+        webauthn_register_finish_route,
+        webauthn_login_start_route,
+        webauthn_login_finish_route,
+        webauthn_verify_2fa_route,
+        openid_configuration_route,
+        jwks_route,
+        authorize_route,
+        token_route,
+        userinfo_route,
+        introspect_route,
+        admin_list_users_route,
+        admin_export_users_route,
+        admin_import_users_route,
+        admin_get_user_route,
+        admin_disable_user_route,
+        admin_enable_user_route,
+        admin_force_password_reset_route,
+        admin_set_2fa_route,
+        admin_set_roles_route,
+        admin_unlock_user_route,
+        admin_audit_log_route,
+        admin_verify_audit_log_route,
+        admin_rotate_keys_route,
+        admin_list_service_accounts_route,
+        admin_create_service_account_route,
+        admin_rotate_service_account_secret_route,
+        admin_delete_service_account_route,
+    ),
+    tags(
+        (name = "account", description = "Signing up, signing in and out, and passwords"),
+        (name = "sessions", description = "The signed-in user's sessions"),
+        (name = "two-factor", description = "Second factors and recovery codes"),
+        (name = "passkeys", description = "WebAuthn passkeys"),
+        (name = "openid-connect", description = "OpenID Connect provider for other services"),
+        (name = "admin", description = "User and key management"),
+    ),
+    modifiers(&SecuritySchemes),
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        // The crate has no license, which would otherwise show up as an empty one.
+        openapi.info.license = None;
+        let components = openapi.components.get_or_insert_with(Default::default);
+        // Browser routes read the JWT from the session cookie.
+        components.add_security_scheme(
+            "jwt",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(JWT_COOKIE_NAME))),
+        );
+        // OpenID Connect clients send the access token from `/token`.
+        components.add_security_scheme(
+            "access_token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
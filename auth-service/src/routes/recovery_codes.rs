@@ -0,0 +1,103 @@
+use crate::app_state::AppState;
+use crate::domain::{AuthAPIError, RecoveryCode, RECOVERY_CODE_COUNT};
+use crate::routes::ErrorResponse;
+use crate::utils::auth::authenticated_email;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+impl RecoveryCodesResponse {
+    pub fn new(codes: &[RecoveryCode]) -> Self {
+        Self {
+            recovery_codes: codes.iter().map(|code| code.as_ref().to_owned()).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct RecoveryCodesStatusResponse {
+    /// Codes that haven't been used yet.
+    pub remaining: usize,
+    /// Codes in a freshly issued set.
+    pub total: usize,
+}
+
+/// Count remaining recovery codes
+#[utoipa::path(
+    get,
+    path = "/2fa/recovery-codes",
+    tag = "two-factor",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Recovery code usage", body = RecoveryCodesStatusResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn recovery_codes_status_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user_store = &state.user_store;
+    let user = user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    Ok(Json(RecoveryCodesStatusResponse {
+        remaining: user.recovery_codes.len(),
+        total: RECOVERY_CODE_COUNT,
+    }))
+}
+
+/// Regenerate recovery codes
+///
+/// Issues a new set of codes, invalidating any that are left from the previous one.
+#[utoipa::path(
+    post,
+    path = "/2fa/recovery-codes",
+    tag = "two-factor",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "New recovery codes", body = RecoveryCodesResponse),
+        (status = 400, description = "Missing JWT or 2FA is not enabled", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn regenerate_recovery_codes_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user_store = &state.user_store;
+    let user = user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if !user.requires_2fa {
+        return Err(AuthAPIError::TwoFANotEnabled);
+    }
+
+    let (codes, hashes) = RecoveryCode::generate_set();
+    user_store
+        .set_recovery_codes(&email, hashes)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(RecoveryCodesResponse::new(&codes)))
+}
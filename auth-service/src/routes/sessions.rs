@@ -0,0 +1,201 @@
+use crate::app_state::AppState;
+use crate::domain::{AuthAPIError, ClientInfo, Email, Session, SessionId};
+use crate::routes::ErrorResponse;
+use crate::utils::auth::authenticated_session;
+use crate::utils::constants::JWT_COOKIE_NAME;
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRequestParts, Path, State};
+use axum::http::{header, request::Parts, StatusCode};
+use axum::response::IntoResponse;
+use axum::Json;
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use utoipa::ToSchema;
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        let ip_address = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string());
+
+        Ok(ClientInfo {
+            user_agent,
+            ip_address,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct SessionResponse {
+    pub id: String,
+    #[serde(rename = "userAgent")]
+    pub user_agent: Option<String>,
+    #[serde(rename = "ipAddress")]
+    pub ip_address: Option<String>,
+    /// Unix timestamp.
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    /// Unix timestamp of the last request made with the session's token.
+    #[serde(rename = "lastSeenAt")]
+    pub last_seen_at: i64,
+    /// Whether this is the session making the request.
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current: &SessionId) -> Self {
+        Self {
+            current: &session.id == current,
+            id: session.id.as_ref().to_owned(),
+            user_agent: session.client.user_agent,
+            ip_address: session.client.ip_address,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at,
+        }
+    }
+}
+
+/// List the user's active sessions
+#[utoipa::path(
+    get,
+    path = "/sessions",
+    tag = "sessions",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Sessions of the signed-in user, oldest first", body = SessionsResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn list_sessions_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (email, current) = current_session(&jar, &state).await?;
+
+    let now = chrono::Utc::now().timestamp();
+    let sessions = state
+        .session_store
+        .get_sessions(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?
+        .into_iter()
+        .filter(|session| !session.is_expired(now))
+        .map(|session| SessionResponse::new(session, &current))
+        .collect();
+
+    Ok(Json(SessionsResponse { sessions }))
+}
+
+/// Revoke a session
+///
+/// Tokens issued to the session stop being accepted. Revoking the current
+/// session also clears the cookie.
+#[utoipa::path(
+    delete,
+    path = "/sessions/{id}",
+    tag = "sessions",
+    params(("id" = String, Path, description = "Id of the session")),
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Session revoked", headers(("Set-Cookie" = String, description = "Clears the JWT cookie when the revoked session is the current one"))),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 404, description = "The user has no session with this id", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn revoke_session_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Path(id): Path<String>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (email, current) = match current_session(&jar, &state).await {
+        Ok(session) => session,
+        Err(e) => return (jar, Err(e)),
+    };
+    let Ok(id) = SessionId::parse(id) else {
+        return (jar, Err(AuthAPIError::SessionNotFound));
+    };
+
+    let session_store = &state.session_store;
+    // Sessions of other users are reported as missing rather than forbidden.
+    match session_store.get_session(&id).await {
+        Ok(session) if session.email == email => {}
+        Ok(_) | Err(_) => return (jar, Err(AuthAPIError::SessionNotFound)),
+    }
+    if session_store.remove_session(&id).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    let jar = if id == current {
+        jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/"))
+    } else {
+        jar
+    };
+    (jar, Ok(StatusCode::OK))
+}
+
+/// Log out everywhere
+///
+/// Revokes every session of the user, including the one making the request.
+#[utoipa::path(
+    delete,
+    path = "/sessions",
+    tag = "sessions",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "All sessions revoked", headers(("Set-Cookie" = String, description = "Clears the JWT cookie"))),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn revoke_all_sessions_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (email, _) = match current_session(&jar, &state).await {
+        Ok(session) => session,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    if state
+        .session_store
+        .remove_sessions(&email)
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    let jar = jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/"));
+    (jar, Ok(StatusCode::OK))
+}
+
+async fn current_session(
+    jar: &CookieJar,
+    state: &AppState,
+) -> Result<(Email, SessionId), AuthAPIError> {
+    let claims = authenticated_session(jar, state).await?;
+    let email = Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+    let session_id = claims.session_id().ok_or(AuthAPIError::InvalidToken)?;
+    Ok((email, session_id))
+}
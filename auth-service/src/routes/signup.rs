@@ -1,53 +1,108 @@
 use crate::app_state::AppState;
-use crate::domain::{AuthAPIError, User, UserStoreError};
+use crate::domain::{
+    AuditAction, AuditEvent, AuditOutcome, AuthAPIError, ClientInfo, Email, Password, RecoveryCode,
+    User, UserStoreError,
+};
+use crate::utils::audit::record_audit_event;
 use axum::{
     extract::State,
     http::StatusCode,
     response::{IntoResponse, Json, Response},
 };
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SignupRequest {
+    #[schema(format = "email")]
     pub email: String,
+    #[schema(format = "password")]
     pub password: String,
+    /// Flag to enable two-factor authentication
     #[serde(rename = "requires2FA")]
     pub requires_2fa: bool,
 }
 
 impl SignupRequest {
-    pub fn is_valid(&self) -> bool {
-        match self {
-            user if user.email.contains('@')
-                && user.password.len() >= 8
-                && !user.email.is_empty() =>
-            {
-                true
-            }
-            _ => false,
-        }
-    }
-
-    pub fn to_user(&self) -> User {
-        User::new(self.email.clone(), self.password.clone(), self.requires_2fa)
+    pub fn to_user(&self, email: &Email, password: &Password) -> User {
+        User::new(
+            email.as_ref().to_owned(),
+            password.as_ref().to_owned(),
+            self.requires_2fa,
+        )
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, ToSchema)]
 pub struct SignupResponse {
+    #[schema(example = "User created successfully!")]
     pub message: String,
+    /// One-time recovery codes, only issued when the account is created with
+    /// 2FA enabled.
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct ErrorResponse {
     pub error: String,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
+        let message;
         let (status, error_message) = match self {
             AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
+            AuthAPIError::InvalidPassword(e) => {
+                message = capitalize(&e.to_string());
+                (StatusCode::BAD_REQUEST, message.as_str())
+            }
+            AuthAPIError::EmailNotAllowed(e) => {
+                message = capitalize(&e.to_string());
+                (StatusCode::BAD_REQUEST, message.as_str())
+            }
+            AuthAPIError::InvalidInput(reason) => {
+                message = format!("Invalid input: {}", reason);
+                (StatusCode::BAD_REQUEST, message.as_str())
+            }
+            AuthAPIError::IncorrectCredentials => {
+                (StatusCode::UNAUTHORIZED, "Incorrect credentials")
+            }
+            AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token"),
+            AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
+            AuthAPIError::WrongBrowser => (
+                StatusCode::FORBIDDEN,
+                "Open the link in the browser that requested it",
+            ),
+            AuthAPIError::TotpAlreadyEnrolled => {
+                (StatusCode::CONFLICT, "Authenticator app already enrolled")
+            }
+            AuthAPIError::TotpNotEnrolled => {
+                (StatusCode::BAD_REQUEST, "Authenticator app not enrolled")
+            }
+            AuthAPIError::TwoFANotEnabled => (StatusCode::BAD_REQUEST, "2FA is not enabled"),
+            AuthAPIError::PasskeyAlreadyRegistered => {
+                (StatusCode::CONFLICT, "Passkey already registered")
+            }
+            AuthAPIError::PasskeyNotRegistered => {
+                (StatusCode::BAD_REQUEST, "No passkey registered")
+            }
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
+            AuthAPIError::ServiceAccountNotFound => {
+                (StatusCode::NOT_FOUND, "Service account not found")
+            }
+            AuthAPIError::AccountDisabled => (StatusCode::FORBIDDEN, "Account disabled"),
+            AuthAPIError::AccountLocked => (StatusCode::LOCKED, "Account locked"),
+            AuthAPIError::PasswordResetRequired => {
+                (StatusCode::FORBIDDEN, "Password reset required")
+            }
+            AuthAPIError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden"),
             AuthAPIError::UnexpectedError => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
@@ -59,29 +114,69 @@ impl IntoResponse for AuthAPIError {
     }
 }
 
+fn capitalize(message: &str) -> String {
+    let mut chars = message.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+/// Register a new user
+#[utoipa::path(
+    post,
+    path = "/signup",
+    tag = "account",
+    request_body = SignupRequest,
+    responses(
+        (status = 201, description = "User created successfully", body = SignupResponse),
+        (status = 400, description = "Invalid input, an email at a domain that isn't accepted, or a password that breaks the password policy, with the reason in `error`", body = ErrorResponse),
+        (status = 409, description = "Email already exists", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
 pub async fn signup_route(
     State(state): State<AppState>,
+    client: ClientInfo,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    let mut store = state.user_store.write().await;
+    let result = signup(&state, &request).await;
 
-    if !request.is_valid() {
-        return Ok(AuthAPIError::InvalidCredentials.into_response());
+    let event = match &result {
+        Ok(_) => AuditEvent::new(AuditAction::Signup, AuditOutcome::Success, &client),
+        Err(e) => AuditEvent::new(AuditAction::Signup, AuditOutcome::Failure, &client)
+            .detail(format!("{:?}", e)),
+    };
+    record_audit_event(&state, event.actor(request.email.clone())).await;
+
+    match result {
+        Ok(response) => Ok((StatusCode::CREATED, response).into_response()),
+        Err(e) => Ok(e.into_response()),
     }
+}
 
-    let user = request.to_user();
-    let result = store.add_user(user).await;
+async fn signup(
+    state: &AppState,
+    request: &SignupRequest,
+) -> Result<Json<SignupResponse>, AuthAPIError> {
+    let email = Email::parse_new(&request.email)?;
+    let password = Password::parse_new(&request.password, &email, &*state.breached_passwords)?;
 
-    if result.is_ok() {
-        let response = Json(SignupResponse {
+    let mut user = request.to_user(&email, &password);
+    let mut recovery_codes = None;
+    if user.requires_2fa {
+        let (codes, hashes) = RecoveryCode::generate_set();
+        user.recovery_codes = hashes;
+        recovery_codes = Some(codes.iter().map(|code| code.as_ref().to_owned()).collect());
+    }
+
+    match state.user_store.add_user(user).await {
+        Ok(()) => Ok(Json(SignupResponse {
             message: "User created successfully!".to_string(),
-        });
-        Ok((StatusCode::CREATED, response).into_response())
-    } else {
-        let err = result.err().unwrap();
-        match err {
-            UserStoreError::UserAlreadyExists => Ok(AuthAPIError::UserAlreadyExists.into_response()),
-            _ => Ok(AuthAPIError::UnexpectedError.into_response()),
-        }
+            recovery_codes,
+        })),
+        Err(UserStoreError::UserAlreadyExists) => Err(AuthAPIError::UserAlreadyExists),
+        Err(_) => Err(AuthAPIError::UnexpectedError),
     }
 }
@@ -0,0 +1,168 @@
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError, RecoveryCode, TotpCredential, TotpSecret, TwoFACode, TwoFAMethod,
+};
+use crate::routes::ErrorResponse;
+use crate::utils::auth::authenticated_email;
+use axum::extract::State;
+use axum::response::IntoResponse;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct TotpEnrollResponse {
+    pub secret: String,
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+    /// Base64 encoded PNG of the QR code for `otpauthUri`.
+    #[serde(rename = "qrCodePng")]
+    pub qr_code_png: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TotpConfirmRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct TotpConfirmResponse {
+    pub message: String,
+    /// Only issued when confirming the app is what turns 2FA on.
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+/// Start enrolling an authenticator app
+///
+/// Generates a new TOTP secret which must be confirmed before it is used at login.
+#[utoipa::path(
+    post,
+    path = "/2fa/totp/enroll",
+    tag = "two-factor",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Secret generated", body = TotpEnrollResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 409, description = "An authenticator app is already in use", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn totp_enroll_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user_store = &state.user_store;
+    let user = user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Replacing the secret of an authenticator that is in use would lock the
+    // user out until the new one is confirmed.
+    if user.requires_2fa && user.two_fa_method == TwoFAMethod::Totp {
+        return Err(AuthAPIError::TotpAlreadyEnrolled);
+    }
+
+    let credential = TotpCredential::new(TotpSecret::generate());
+    let otpauth_uri = credential
+        .provisioning_uri(&email)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let qr_code_png = credential
+        .qr_code_png(&email)
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let secret = credential.secret.as_ref().to_owned();
+
+    user_store
+        .set_totp_credential(&email, Some(credential))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(TotpEnrollResponse {
+        secret,
+        otpauth_uri,
+        qr_code_png: STANDARD.encode(qr_code_png),
+    }))
+}
+
+/// Confirm authenticator app enrollment
+///
+/// Verifies a code from the enrolled app and makes it the user's second factor.
+#[utoipa::path(
+    post,
+    path = "/2fa/totp/confirm",
+    tag = "two-factor",
+    request_body = TotpConfirmRequest,
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Authenticator app enabled", body = TotpConfirmResponse),
+        (status = 400, description = "Invalid input, missing JWT or no pending enrollment", body = ErrorResponse),
+        (status = 401, description = "JWT or code is not valid", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn totp_confirm_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<TotpConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let user_store = &state.user_store;
+    let user = user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let mut credential = match &user.totp {
+        Some(credential) if !credential.confirmed => credential.clone(),
+        _ => return Err(AuthAPIError::TotpNotEnrolled),
+    };
+
+    let now = chrono::Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    let step = credential
+        .verify(&email, code.as_ref(), now)
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    credential.confirmed = true;
+    credential.last_used_step = Some(step);
+    let enables_2fa = !user.requires_2fa;
+
+    user_store
+        .set_totp_credential(&email, Some(credential))
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    user_store
+        .update_two_fa(&email, true, TwoFAMethod::Totp)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let mut recovery_codes = None;
+    if enables_2fa {
+        let (codes, hashes) = RecoveryCode::generate_set();
+        user_store
+            .set_recovery_codes(&email, hashes)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        recovery_codes = Some(codes.iter().map(|code| code.as_ref().to_owned()).collect());
+    }
+
+    Ok(Json(TotpConfirmResponse {
+        message: "Authenticator app enabled".to_owned(),
+        recovery_codes,
+    }))
+}
@@ -0,0 +1,81 @@
+use crate::app_state::AppState;
+use crate::domain::{AuthAPIError, RecoveryCode, TwoFAMethod};
+use crate::routes::{ErrorResponse, RecoveryCodesResponse};
+use crate::utils::auth::authenticated_email;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+#[derive(Deserialize, ToSchema)]
+pub struct TwoFAMethodRequest {
+    #[serde(rename = "requires2FA")]
+    pub requires_2fa: bool,
+    pub method: TwoFAMethod,
+}
+
+/// Select the second factor used at login
+#[utoipa::path(
+    put,
+    path = "/2fa/method",
+    tag = "two-factor",
+    request_body = TwoFAMethodRequest,
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "2FA settings updated. New recovery codes are only returned when the request turns 2FA on", body = Option<RecoveryCodesResponse>),
+        (status = 400, description = "Invalid input, missing JWT, or the chosen method has not been set up", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn two_fa_method_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(request): Json<TwoFAMethodRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user_store = &state.user_store;
+    let user = user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let totp_confirmed = user.totp.as_ref().is_some_and(|credential| credential.confirmed);
+    if request.method == TwoFAMethod::Totp && !totp_confirmed {
+        return Err(AuthAPIError::TotpNotEnrolled);
+    }
+    if request.method == TwoFAMethod::WebAuthn && user.passkeys.is_empty() {
+        return Err(AuthAPIError::PasskeyNotRegistered);
+    }
+
+    let enables_2fa = request.requires_2fa && !user.requires_2fa;
+
+    user_store
+        .update_two_fa(&email, request.requires_2fa, request.method)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    // Recovery codes are issued whenever 2FA is switched on and dropped when
+    // it is switched off, so a stale set can't outlive the factor it backs up.
+    if enables_2fa {
+        let (codes, hashes) = RecoveryCode::generate_set();
+        user_store
+            .set_recovery_codes(&email, hashes)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        return Ok(Json(RecoveryCodesResponse::new(&codes)).into_response());
+    }
+    if !request.requires_2fa {
+        user_store
+            .set_recovery_codes(&email, Vec::new())
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(StatusCode::OK.into_response())
+}
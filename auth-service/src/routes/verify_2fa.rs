@@ -1,6 +1,239 @@
+use crate::app_state::AppState;
+use crate::domain::{
+    AuditAction, AuditEvent, AuditOutcome, AuthAPIError, ClientInfo, Email, LoginAttemptId,
+    RecoveryCode, TotpCredential, TwoFACode, TwoFACodeStoreError, TwoFAMethod, User,
+    UserStoreError,
+};
+use crate::routes::ErrorResponse;
+use crate::utils::audit::record_audit_event;
+use crate::utils::auth::start_session;
+use axum::extract::State;
 use axum::http::StatusCode;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-pub async fn verify_2fa_route() -> impl IntoResponse {
-    StatusCode::OK.into_response()
-}
\ No newline at end of file
+#[derive(Deserialize, ToSchema)]
+pub struct Verify2FARequest {
+    #[schema(format = "email")]
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    /// Code from the user's second factor, or one of their recovery codes.
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct RecoveryCodeLoginResponse {
+    pub message: String,
+    #[serde(rename = "remainingRecoveryCodes")]
+    pub remaining_recovery_codes: usize,
+}
+
+// A recovery code can be given in place of the user's usual second factor.
+enum SecondFactor {
+    Code(TwoFACode),
+    Recovery(RecoveryCode),
+}
+
+impl SecondFactor {
+    fn parse(code: String) -> Result<Self, String> {
+        match TwoFACode::parse(code.clone()) {
+            Ok(code) => Ok(SecondFactor::Code(code)),
+            Err(_) => RecoveryCode::parse(&code).map(SecondFactor::Recovery),
+        }
+    }
+}
+
+/// Verify 2FA token
+#[utoipa::path(
+    post,
+    path = "/verify-2fa",
+    tag = "two-factor",
+    request_body = Verify2FARequest,
+    responses(
+        (status = 200, description = "2FA token verified successfully. The body is only returned when a recovery code was used", body = Option<RecoveryCodeLoginResponse>, headers(("Set-Cookie" = String, description = "The session's JWT"))),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 401, description = "Authentication failed", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn verify_2fa_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<Verify2FARequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let actor = request.email.clone();
+    let (jar, result) = verify_2fa(&state, jar, client.clone(), request).await;
+    record_2fa_verification(&state, &client, actor, result.as_ref().err()).await;
+    (jar, result)
+}
+
+async fn verify_2fa(
+    state: &AppState,
+    jar: CookieJar,
+    client: ClientInfo,
+    request: Verify2FARequest,
+) -> (CookieJar, Result<Response, AuthAPIError>) {
+    let Ok(email) = Email::parse(&request.email) else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+    let Ok(login_attempt_id) = LoginAttemptId::parse(request.login_attempt_id) else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+    let Ok(second_factor) = SecondFactor::parse(request.two_fa_code) else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+
+    let two_fa_code_store = &state.two_fa_code_store;
+    if two_fa_code_store
+        .remove_expired_codes(chrono::Utc::now().timestamp())
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    let (expected_attempt_id, expected_code) = match two_fa_code_store.get_code(&email).await {
+        Ok(entry) => entry,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+    if expected_attempt_id != login_attempt_id {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+
+    let user_store = &state.user_store;
+    let user = match user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+
+    let remaining_recovery_codes =
+        match check_second_factor(state, &user, second_factor, &expected_code).await {
+            Ok(remaining_recovery_codes) => remaining_recovery_codes,
+            Err(AuthAPIError::IncorrectCredentials) => {
+                // Codes are short, so each login attempt only gets a few guesses.
+                // A request racing this one may already have used it up.
+                match two_fa_code_store.record_failed_attempt(&email).await {
+                    Ok(()) | Err(TwoFACodeStoreError::LoginAttemptIdNotFound) => {}
+                    Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+                }
+                return (jar, Err(AuthAPIError::IncorrectCredentials));
+            }
+            Err(e) => return (jar, Err(e)),
+        };
+
+    if two_fa_code_store.remove_code(&email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    let auth_cookie = match start_session(state, &user, client).await {
+        Ok(cookie) => cookie,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let response = match remaining_recovery_codes {
+        Some(remaining_recovery_codes) => Json(RecoveryCodeLoginResponse {
+            message: "Recovery code accepted".to_owned(),
+            remaining_recovery_codes,
+        })
+        .into_response(),
+        None => StatusCode::OK.into_response(),
+    };
+
+    (jar.add(auth_cookie), Ok(response))
+}
+
+// Checks the code against the user's second factor, using it up. Returns how
+// many recovery codes are left when one was used. Codes are checked and used
+// up in one change to the stored user, so that requests racing each other
+// can't both redeem the same one.
+async fn check_second_factor(
+    state: &AppState,
+    user: &User,
+    second_factor: SecondFactor,
+    expected_code: &TwoFACode,
+) -> Result<Option<usize>, AuthAPIError> {
+    let user_store = &state.user_store;
+    match (second_factor, user.two_fa_method) {
+        (SecondFactor::Code(two_fa_code), TwoFAMethod::Email) => {
+            if *expected_code != two_fa_code {
+                return Err(AuthAPIError::IncorrectCredentials);
+            }
+            Ok(None)
+        }
+        (SecondFactor::Code(two_fa_code), TwoFAMethod::Totp) => {
+            user_store
+                .modify_user(
+                    &user.email,
+                    Box::new(move |user| {
+                        let credential = verify_totp(user, &two_fa_code)
+                            .ok_or(UserStoreError::InvalidCredentials)?;
+                        user.totp = Some(credential);
+                        Ok(())
+                    }),
+                )
+                .await
+                .map_err(second_factor_error)?;
+            Ok(None)
+        }
+        // Passkey assertions are verified by `/webauthn/verify-2fa`.
+        (SecondFactor::Code(_), TwoFAMethod::WebAuthn) => Err(AuthAPIError::IncorrectCredentials),
+        (SecondFactor::Recovery(recovery_code), _) => {
+            let user = user_store
+                .modify_user(
+                    &user.email,
+                    Box::new(move |user| {
+                        let index = user
+                            .recovery_codes
+                            .iter()
+                            .position(|hash| hash.matches(&recovery_code))
+                            .ok_or(UserStoreError::InvalidCredentials)?;
+                        user.recovery_codes.remove(index);
+                        Ok(())
+                    }),
+                )
+                .await
+                .map_err(second_factor_error)?;
+            Ok(Some(user.recovery_codes.len()))
+        }
+    }
+}
+
+fn second_factor_error(e: UserStoreError) -> AuthAPIError {
+    match e {
+        UserStoreError::InvalidCredentials | UserStoreError::UserNotFound => {
+            AuthAPIError::IncorrectCredentials
+        }
+        _ => AuthAPIError::UnexpectedError,
+    }
+}
+
+/// Records the outcome of a second factor check, whichever route made it.
+pub(crate) async fn record_2fa_verification(
+    state: &AppState,
+    client: &ClientInfo,
+    actor: String,
+    error: Option<&AuthAPIError>,
+) {
+    let event = match error {
+        None => AuditEvent::new(AuditAction::TwoFAVerification, AuditOutcome::Success, client),
+        Some(e) => AuditEvent::new(AuditAction::TwoFAVerification, AuditOutcome::Failure, client)
+            .detail(format!("{:?}", e)),
+    };
+    record_audit_event(state, event.actor(actor)).await;
+}
+
+// Returns the user's TOTP credential with the accepted step recorded,
+// or `None` if the code doesn't match an unused step in the drift window.
+fn verify_totp(user: &User, code: &TwoFACode) -> Option<TotpCredential> {
+    let mut credential = user.totp.clone().filter(|credential| credential.confirmed)?;
+    let now = chrono::Utc::now().timestamp().try_into().ok()?;
+    let step = credential.verify(&user.email, code.as_ref(), now)?;
+    credential.last_used_step = Some(step);
+    Some(credential)
+}
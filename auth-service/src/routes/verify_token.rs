@@ -1,6 +1,57 @@
-use axum::http::StatusCode;
+use crate::app_state::AppState;
+use crate::domain::AuthAPIError;
+use crate::routes::ErrorResponse;
+use crate::utils::auth::validate_access_token;
+use axum::extract::State;
 use axum::response::IntoResponse;
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-pub async fn verify_token_route() -> impl IntoResponse {
-    StatusCode::OK.into_response()
-}
\ No newline at end of file
+#[derive(Deserialize, ToSchema)]
+pub struct VerifyTokenRequest {
+    pub token: String,
+}
+
+/// Who a valid token was issued to.
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct VerifyTokenResponse {
+    /// The user's email, or the client id of a service account.
+    pub sub: String,
+    /// The OpenID Connect client or service account the token was issued to;
+    /// absent for the tokens of browser sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Space separated scopes granted to an access token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+/// Verify JWT
+///
+/// Verifies if a JWT is valid and its session has not been revoked. Service
+/// account tokens are valid until the account is deleted or its secret is
+/// rotated, and carry the scopes they were granted in their `scope` claim.
+#[utoipa::path(
+    post,
+    path = "/verify-token",
+    tag = "account",
+    request_body = VerifyTokenRequest,
+    responses(
+        (status = 200, description = "Token is valid", body = VerifyTokenResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn verify_token_route(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyTokenRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (claims, _) = validate_access_token(&request.token, &state).await?;
+    Ok(Json(VerifyTokenResponse {
+        sub: claims.sub,
+        client_id: claims.client_id,
+        scope: claims.scope,
+    }))
+}
@@ -0,0 +1,560 @@
+use crate::app_state::AppState;
+use crate::domain::{
+    verify_assertion, verify_registration, AuditAction, AuditEvent, AuditOutcome, AuthAPIError,
+    ClientInfo, Email, LoginAttemptId, RelyingParty, User, UserVerification, WebAuthnCeremony,
+    WebAuthnChallenge, COSE_ALG_ES256,
+};
+use crate::routes::{record_2fa_verification, ErrorResponse};
+use crate::utils::audit::record_audit_event;
+use crate::utils::auth::{authenticated_email, start_session};
+use crate::utils::constants::{
+    WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID, WEBAUTHN_RP_NAME, WEBAUTHN_TIMEOUT_MS,
+};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::ToSchema;
+
+const PUBLIC_KEY_CREDENTIAL_TYPE: &str = "public-key";
+
+lazy_static! {
+    // Keys the made-up credential ids handed out for emails without passkeys.
+    static ref DECOY_CREDENTIAL_KEY: [u8; 32] = rand::random();
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct CredentialDescriptor {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct CredentialParameters {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub alg: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct RelyingPartyEntity {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct UserEntity {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+/// Options for `navigator.credentials.create()`, with binary fields base64url encoded.
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct PublicKeyCredentialCreationOptions {
+    pub challenge: String,
+    pub rp: RelyingPartyEntity,
+    pub user: UserEntity,
+    #[serde(rename = "pubKeyCredParams")]
+    pub pub_key_cred_params: Vec<CredentialParameters>,
+    pub timeout: u64,
+    pub attestation: String,
+    #[serde(rename = "excludeCredentials")]
+    pub exclude_credentials: Vec<CredentialDescriptor>,
+}
+
+/// Options for `navigator.credentials.get()`, with binary fields base64url encoded.
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct PublicKeyCredentialRequestOptions {
+    pub challenge: String,
+    #[serde(rename = "rpId")]
+    pub rp_id: String,
+    #[serde(rename = "allowCredentials")]
+    pub allow_credentials: Vec<CredentialDescriptor>,
+    pub timeout: u64,
+    #[serde(rename = "userVerification")]
+    pub user_verification: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct RegistrationOptionsResponse {
+    #[serde(rename = "publicKey")]
+    pub public_key: PublicKeyCredentialCreationOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct AuthenticationOptionsResponse {
+    #[serde(rename = "publicKey")]
+    pub public_key: PublicKeyCredentialRequestOptions,
+}
+
+/// An `AuthenticatorAttestationResponse`, with binary fields base64url encoded.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AttestationResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "attestationObject")]
+    pub attestation_object: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RegistrationCredential {
+    #[serde(rename = "rawId")]
+    pub raw_id: String,
+    pub response: AttestationResponse,
+}
+
+/// An `AuthenticatorAssertionResponse`, with binary fields base64url encoded.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AssertionResponse {
+    #[serde(rename = "clientDataJSON")]
+    pub client_data_json: String,
+    #[serde(rename = "authenticatorData")]
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AssertionCredential {
+    #[serde(rename = "rawId")]
+    pub raw_id: String,
+    pub response: AssertionResponse,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WebAuthnLoginStartRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WebAuthnLoginFinishRequest {
+    pub email: String,
+    pub credential: AssertionCredential,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WebAuthnVerify2FARequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    pub credential: AssertionCredential,
+}
+
+/// Begin passkey registration
+///
+/// Returns options for `navigator.credentials.create()`.
+#[utoipa::path(
+    post,
+    path = "/webauthn/register/start",
+    tag = "passkeys",
+    security(("jwt" = [])),
+    responses(
+        (status = 200, description = "Credential creation options", body = RegistrationOptionsResponse),
+        (status = 400, description = "Missing JWT", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid", body = ErrorResponse),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn webauthn_register_start_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+
+    let user = state
+        .user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    let exclude_credentials = credential_descriptors(&user);
+
+    let challenge = WebAuthnChallenge::generate();
+    let public_key = PublicKeyCredentialCreationOptions {
+        challenge: challenge.to_base64(),
+        rp: RelyingPartyEntity {
+            id: WEBAUTHN_RP_ID.clone(),
+            name: WEBAUTHN_RP_NAME.to_owned(),
+        },
+        user: UserEntity {
+            // The user handle is stored on the authenticator, so it must not be the email itself.
+            id: URL_SAFE_NO_PAD.encode(Sha256::digest(email.as_ref().as_bytes())),
+            name: email.as_ref().to_owned(),
+            display_name: email.as_ref().to_owned(),
+        },
+        pub_key_cred_params: vec![CredentialParameters {
+            kind: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+            alg: COSE_ALG_ES256,
+        }],
+        timeout: WEBAUTHN_TIMEOUT_MS,
+        attestation: "none".to_owned(),
+        exclude_credentials,
+    };
+
+    state
+        .webauthn_challenge_store
+        .add_challenge(email, WebAuthnCeremony::Registration, challenge, challenge_expires_at())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(Json(RegistrationOptionsResponse { public_key }))
+}
+
+/// Complete passkey registration
+#[utoipa::path(
+    post,
+    path = "/webauthn/register/finish",
+    tag = "passkeys",
+    request_body = RegistrationCredential,
+    security(("jwt" = [])),
+    responses(
+        (status = 201, description = "Passkey registered"),
+        (status = 400, description = "Missing JWT or invalid input", body = ErrorResponse),
+        (status = 401, description = "JWT is not valid or the attestation could not be verified", body = ErrorResponse),
+        (status = 409, description = "Passkey already registered", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn webauthn_register_finish_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Json(credential): Json<RegistrationCredential>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&jar, &state).await?;
+    let client_data_json = decode(&credential.response.client_data_json)?;
+    let attestation_object = decode(&credential.response.attestation_object)?;
+
+    let challenge = state
+        .webauthn_challenge_store
+        .take_challenge(&email, WebAuthnCeremony::Registration, chrono::Utc::now().timestamp())
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let passkey = verify_registration(
+        &relying_party(),
+        &challenge,
+        &client_data_json,
+        &attestation_object,
+    )
+    .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let user_store = &state.user_store;
+    let user = user_store
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    if user.passkeys.iter().any(|existing| existing.id == passkey.id) {
+        return Err(AuthAPIError::PasskeyAlreadyRegistered);
+    }
+
+    user_store
+        .add_passkey(&email, passkey)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::CREATED)
+}
+
+/// Begin passwordless login with a passkey
+///
+/// The passkey stands in for both the password and the second factor, so the
+/// authenticator must verify the user. Emails without passkeys get options
+/// that look the same but can never be completed.
+#[utoipa::path(
+    post,
+    path = "/webauthn/login/start",
+    tag = "passkeys",
+    request_body = WebAuthnLoginStartRequest,
+    responses(
+        (status = 200, description = "Credential request options for `navigator.credentials.get()`", body = AuthenticationOptionsResponse),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn webauthn_login_start_route(
+    State(state): State<AppState>,
+    Json(request): Json<WebAuthnLoginStartRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(&request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let public_key =
+        match start_authentication(&state, &email, UserVerification::Required).await {
+            Err(AuthAPIError::IncorrectCredentials) => decoy_authentication_options(&email),
+            result => result?,
+        };
+
+    Ok(Json(AuthenticationOptionsResponse { public_key }))
+}
+
+/// Complete passwordless login with a passkey
+#[utoipa::path(
+    post,
+    path = "/webauthn/login/finish",
+    tag = "passkeys",
+    request_body = WebAuthnLoginFinishRequest,
+    responses(
+        (status = 200, description = "Login successful", headers(("Set-Cookie" = String, description = "The session's JWT"))),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 401, description = "Assertion could not be verified", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn webauthn_login_finish_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<WebAuthnLoginFinishRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (jar, result) = login_finish(&state, jar, client.clone(), &request).await;
+
+    let event = match &result {
+        Ok(_) => AuditEvent::new(AuditAction::Login, AuditOutcome::Success, &client),
+        Err(e) => AuditEvent::new(AuditAction::Login, AuditOutcome::Failure, &client)
+            .detail(format!("{:?}", e)),
+    };
+    record_audit_event(&state, event.actor(request.email).detail("passkey")).await;
+
+    (jar, result)
+}
+
+async fn login_finish(
+    state: &AppState,
+    jar: CookieJar,
+    client: ClientInfo,
+    request: &WebAuthnLoginFinishRequest,
+) -> (CookieJar, Result<StatusCode, AuthAPIError>) {
+    let Ok(email) = Email::parse(&request.email) else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+
+    let user = match finish_authentication(
+        state,
+        &email,
+        &request.credential,
+        UserVerification::Required,
+    )
+    .await
+    {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    match start_session(state, &user, client).await {
+        Ok(cookie) => (jar.add(cookie), Ok(StatusCode::OK)),
+        Err(e) => (jar, Err(e)),
+    }
+}
+
+/// Verify a passkey assertion as the second factor of a login
+///
+/// The second step of a password login for users whose 2FA method is a passkey.
+#[utoipa::path(
+    post,
+    path = "/webauthn/verify-2fa",
+    tag = "passkeys",
+    request_body = WebAuthnVerify2FARequest,
+    responses(
+        (status = 200, description = "2FA verified successfully", headers(("Set-Cookie" = String, description = "The session's JWT"))),
+        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 401, description = "Authentication failed", body = ErrorResponse),
+        (status = 422, description = "Unprocessable content", body = String, content_type = "text/plain"),
+        (status = 500, description = "Unexpected error", body = ErrorResponse),
+    )
+)]
+pub async fn webauthn_verify_2fa_route(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<WebAuthnVerify2FARequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let (jar, result) = verify_2fa_assertion(&state, jar, client.clone(), &request).await;
+    record_2fa_verification(&state, &client, request.email, result.as_ref().err()).await;
+    (jar, result)
+}
+
+async fn verify_2fa_assertion(
+    state: &AppState,
+    jar: CookieJar,
+    client: ClientInfo,
+    request: &WebAuthnVerify2FARequest,
+) -> (CookieJar, Result<StatusCode, AuthAPIError>) {
+    let Ok(email) = Email::parse(&request.email) else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+    let Ok(login_attempt_id) = LoginAttemptId::parse(request.login_attempt_id.clone()) else {
+        return (jar, Err(AuthAPIError::InvalidCredentials));
+    };
+
+    let two_fa_code_store = &state.two_fa_code_store;
+    if two_fa_code_store
+        .remove_expired_codes(chrono::Utc::now().timestamp())
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+    match two_fa_code_store.get_code(&email).await {
+        Ok((expected_attempt_id, _)) if expected_attempt_id == login_attempt_id => {}
+        _ => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    }
+
+    // The password was already checked, so the passkey only has to show presence.
+    let user = match finish_authentication(
+        state,
+        &email,
+        &request.credential,
+        UserVerification::Preferred,
+    )
+    .await
+    {
+        Ok(user) => user,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    if two_fa_code_store.remove_code(&email).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    match start_session(state, &user, client).await {
+        Ok(cookie) => (jar.add(cookie), Ok(StatusCode::OK)),
+        Err(e) => (jar, Err(e)),
+    }
+}
+
+/// Issues an authentication challenge for the user's registered passkeys.
+pub(crate) async fn start_authentication(
+    state: &AppState,
+    email: &Email,
+    user_verification: UserVerification,
+) -> Result<PublicKeyCredentialRequestOptions, AuthAPIError> {
+    let allow_credentials = match state.user_store.get_user(email).await {
+        Ok(user) if !user.passkeys.is_empty() => credential_descriptors(&user),
+        _ => return Err(AuthAPIError::IncorrectCredentials),
+    };
+
+    let challenge = WebAuthnChallenge::generate();
+    let options = PublicKeyCredentialRequestOptions {
+        challenge: challenge.to_base64(),
+        rp_id: WEBAUTHN_RP_ID.clone(),
+        allow_credentials,
+        timeout: WEBAUTHN_TIMEOUT_MS,
+        user_verification: user_verification.as_str().to_owned(),
+    };
+
+    state
+        .webauthn_challenge_store
+        .add_challenge(
+            email.clone(),
+            WebAuthnCeremony::Authentication,
+            challenge,
+            challenge_expires_at(),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(options)
+}
+
+// Stands in for `start_authentication` when the email has no passkeys. The
+// credential id is derived from the email so repeated requests agree, and no
+// challenge is stored, so the ceremony can't be finished.
+fn decoy_authentication_options(email: &Email) -> PublicKeyCredentialRequestOptions {
+    let mut hasher = Sha256::new();
+    hasher.update(DECOY_CREDENTIAL_KEY.as_slice());
+    hasher.update(email.as_ref().as_bytes());
+    let id = &hasher.finalize()[..16];
+
+    PublicKeyCredentialRequestOptions {
+        challenge: WebAuthnChallenge::generate().to_base64(),
+        rp_id: WEBAUTHN_RP_ID.clone(),
+        allow_credentials: vec![CredentialDescriptor {
+            kind: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+            id: URL_SAFE_NO_PAD.encode(id),
+        }],
+        timeout: WEBAUTHN_TIMEOUT_MS,
+        user_verification: UserVerification::Required.as_str().to_owned(),
+    }
+}
+
+async fn finish_authentication(
+    state: &AppState,
+    email: &Email,
+    credential: &AssertionCredential,
+    user_verification: UserVerification,
+) -> Result<User, AuthAPIError> {
+    let credential_id = decode(&credential.raw_id)?;
+    let client_data_json = decode(&credential.response.client_data_json)?;
+    let authenticator_data = decode(&credential.response.authenticator_data)?;
+    let signature = decode(&credential.response.signature)?;
+
+    let challenge = state
+        .webauthn_challenge_store
+        .take_challenge(email, WebAuthnCeremony::Authentication, chrono::Utc::now().timestamp())
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    let user_store = &state.user_store;
+    let user = user_store
+        .get_user(email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+    let passkey = user
+        .passkeys
+        .iter()
+        .find(|passkey| passkey.id == credential_id)
+        .cloned()
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    let sign_count = verify_assertion(
+        &relying_party(),
+        &challenge,
+        &passkey,
+        &client_data_json,
+        &authenticator_data,
+        &signature,
+        user_verification,
+    )
+    .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    user_store
+        .update_passkey_sign_count(email, &passkey.id, sign_count)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(user)
+}
+
+fn challenge_expires_at() -> i64 {
+    chrono::Utc::now().timestamp() + (WEBAUTHN_TIMEOUT_MS / 1000) as i64
+}
+
+fn relying_party() -> RelyingParty<'static> {
+    RelyingParty {
+        id: &WEBAUTHN_RP_ID,
+        origin: &WEBAUTHN_ORIGIN,
+    }
+}
+
+fn credential_descriptors(user: &User) -> Vec<CredentialDescriptor> {
+    user.passkeys
+        .iter()
+        .map(|passkey| CredentialDescriptor {
+            kind: PUBLIC_KEY_CREDENTIAL_TYPE.to_owned(),
+            id: passkey.id_base64(),
+        })
+        .collect()
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, AuthAPIError> {
+    URL_SAFE_NO_PAD
+        .decode(value)
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+}
@@ -0,0 +1,66 @@
+use crate::domain::{
+    AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError, AuthorizationGrant,
+};
+use dashmap::DashMap;
+
+#[derive(Default)]
+pub struct HashmapAuthorizationCodeStore {
+    codes: DashMap<AuthorizationCode, AuthorizationGrant>,
+}
+
+impl HashmapAuthorizationCodeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for HashmapAuthorizationCodeStore {
+    async fn add_code(
+        &self,
+        code: AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        self.codes.insert(code, grant);
+        Ok(())
+    }
+
+    async fn take_code(
+        &self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        self.codes
+            .remove(code)
+            .map(|(_, grant)| grant)
+            .ok_or(AuthorizationCodeStoreError::CodeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Email, PkceChallenge};
+
+    #[tokio::test]
+    async fn take_code_is_single_use() {
+        let store = HashmapAuthorizationCodeStore::new();
+        let code = AuthorizationCode::generate();
+        let grant = AuthorizationGrant {
+            client_id: "app".to_owned(),
+            redirect_uri: "https://app.example.com/callback".to_owned(),
+            email: Email::parse("test@example.com").unwrap(),
+            scope: "openid".to_owned(),
+            nonce: None,
+            code_challenge: PkceChallenge::from_verifier(&"a".repeat(43)),
+            expires_at: 0,
+        };
+
+        let _ = store.add_code(code.clone(), grant.clone()).await;
+
+        assert_eq!(store.take_code(&code).await, Ok(grant));
+        assert_eq!(
+            store.take_code(&code).await,
+            Err(AuthorizationCodeStoreError::CodeNotFound)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+use crate::domain::{MagicLink, MagicLinkId, MagicLinkStore, MagicLinkStoreError};
+use dashmap::DashMap;
+
+#[derive(Default)]
+pub struct HashmapMagicLinkStore {
+    links: DashMap<MagicLinkId, MagicLink>,
+}
+
+impl HashmapMagicLinkStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for HashmapMagicLinkStore {
+    async fn add_link(&self, id: MagicLinkId, link: MagicLink) -> Result<(), MagicLinkStoreError> {
+        self.links.insert(id, link);
+        Ok(())
+    }
+
+    async fn take_link(&self, id: &MagicLinkId) -> Result<MagicLink, MagicLinkStoreError> {
+        self.links.remove(id).map(|(_, link)| link).ok_or(MagicLinkStoreError::LinkNotFound)
+    }
+
+    async fn remove_expired_links(&self, now: i64) -> Result<(), MagicLinkStoreError> {
+        self.links.retain(|_, link| !link.is_expired(now));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Email;
+
+    fn link(expires_at: i64) -> MagicLink {
+        MagicLink {
+            email: Email::parse("test@example.com").unwrap(),
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn take_link_is_single_use() {
+        let store = HashmapMagicLinkStore::new();
+        let id = MagicLinkId::generate();
+        let _ = store.add_link(id.clone(), link(100)).await;
+
+        assert_eq!(store.take_link(&id).await, Ok(link(100)));
+        assert_eq!(store.take_link(&id).await, Err(MagicLinkStoreError::LinkNotFound));
+    }
+
+    #[tokio::test]
+    async fn remove_expired_links_keeps_live_ones() {
+        let store = HashmapMagicLinkStore::new();
+        let (expired, live) = (MagicLinkId::generate(), MagicLinkId::generate());
+        let _ = store.add_link(expired.clone(), link(100)).await;
+        let _ = store.add_link(live.clone(), link(200)).await;
+
+        let _ = store.remove_expired_links(100).await;
+
+        assert_eq!(store.take_link(&expired).await, Err(MagicLinkStoreError::LinkNotFound));
+        assert_eq!(store.take_link(&live).await, Ok(link(200)));
+    }
+}
@@ -0,0 +1,71 @@
+use crate::domain::{OidcClient, OidcClientStore, OidcClientStoreError};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+
+#[derive(Default)]
+pub struct HashmapOidcClientStore {
+    clients: DashMap<String, OidcClient>,
+}
+
+impl HashmapOidcClientStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl OidcClientStore for HashmapOidcClientStore {
+    async fn add_client(&self, client: OidcClient) -> Result<(), OidcClientStoreError> {
+        match self.clients.entry(client.client_id.clone()) {
+            Entry::Occupied(_) => Err(OidcClientStoreError::ClientAlreadyExists),
+            Entry::Vacant(entry) => {
+                entry.insert(client);
+                Ok(())
+            }
+        }
+    }
+
+    async fn get_client(&self, client_id: &str) -> Result<OidcClient, OidcClientStoreError> {
+        self.clients
+            .get(client_id)
+            .map(|client| client.clone())
+            .ok_or(OidcClientStoreError::ClientNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client() -> OidcClient {
+        OidcClient::new(
+            "app".to_owned(),
+            "App".to_owned(),
+            Some("secret"),
+            vec!["https://app.example.com/callback".to_owned()],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_client() {
+        let store = HashmapOidcClientStore::new();
+
+        assert_eq!(store.add_client(client()).await, Ok(()));
+        assert_eq!(store.get_client("app").await, Ok(client()));
+        assert_eq!(
+            store.get_client("other").await,
+            Err(OidcClientStoreError::ClientNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_duplicate_client() {
+        let store = HashmapOidcClientStore::new();
+        let _ = store.add_client(client()).await;
+
+        assert_eq!(
+            store.add_client(client()).await,
+            Err(OidcClientStoreError::ClientAlreadyExists)
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use crate::domain::{
+    write_private_file, ServiceAccount, ServiceAccountStore, ServiceAccountStoreError,
+};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Name of the file service accounts are saved to, next to the users.
+pub const SERVICE_ACCOUNTS_FILE_NAME: &str = "service_accounts.json";
+
+#[derive(Default)]
+pub struct HashmapServiceAccountStore {
+    accounts: DashMap<String, ServiceAccount>,
+    /// File the accounts are saved to after every change, if any.
+    path: Option<PathBuf>,
+    // Held across each change and its save, so that saves happen in the
+    // order of the changes.
+    save_lock: Mutex<()>,
+}
+
+impl HashmapServiceAccountStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the accounts saved at `path`, starting without any if the file
+    /// doesn't exist yet, and saves every change there from then on.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let accounts: Vec<ServiceAccount> = match fs::read(&path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("failed to read {}: {}", path.display(), e)),
+        };
+        Ok(Self {
+            accounts: accounts
+                .into_iter()
+                .map(|account| (account.client_id.clone(), account))
+                .collect(),
+            path: Some(path),
+            save_lock: Mutex::default(),
+        })
+    }
+
+    // The whole file is rewritten, as accounts only change when an admin
+    // changes them.
+    fn save(&self) -> Result<(), ServiceAccountStoreError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut accounts: Vec<ServiceAccount> =
+            self.accounts.iter().map(|account| account.clone()).collect();
+        accounts.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+        let contents = serde_json::to_vec_pretty(&accounts).expect("accounts always serialize");
+        write_private_file(path, &contents).map_err(|e| {
+            eprintln!("failed to save service accounts to {}: {}", path.display(), e);
+            ServiceAccountStoreError::UnexpectedError
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ServiceAccountStore for HashmapServiceAccountStore {
+    async fn add_account(&self, account: ServiceAccount) -> Result<(), ServiceAccountStoreError> {
+        let _save = self
+            .save_lock
+            .lock()
+            .map_err(|_| ServiceAccountStoreError::UnexpectedError)?;
+        let client_id = account.client_id.clone();
+        match self.accounts.entry(client_id.clone()) {
+            Entry::Occupied(_) => return Err(ServiceAccountStoreError::AccountAlreadyExists),
+            Entry::Vacant(entry) => {
+                entry.insert(account);
+            }
+        }
+        // A change that couldn't be saved is undone rather than lost on restart.
+        self.save().inspect_err(|_| {
+            self.accounts.remove(&client_id);
+        })
+    }
+
+    async fn get_account(&self, client_id: &str) -> Result<ServiceAccount, ServiceAccountStoreError> {
+        self.accounts
+            .get(client_id)
+            .map(|account| account.clone())
+            .ok_or(ServiceAccountStoreError::AccountNotFound)
+    }
+
+    async fn update_account(&self, account: ServiceAccount) -> Result<(), ServiceAccountStoreError> {
+        let _save = self
+            .save_lock
+            .lock()
+            .map_err(|_| ServiceAccountStoreError::UnexpectedError)?;
+        let previous = match self.accounts.get_mut(&account.client_id) {
+            Some(mut stored) => std::mem::replace(&mut *stored, account),
+            None => return Err(ServiceAccountStoreError::AccountNotFound),
+        };
+        self.save().inspect_err(|_| {
+            self.accounts.insert(previous.client_id.clone(), previous);
+        })
+    }
+
+    async fn delete_account(&self, client_id: &str) -> Result<(), ServiceAccountStoreError> {
+        let _save = self
+            .save_lock
+            .lock()
+            .map_err(|_| ServiceAccountStoreError::UnexpectedError)?;
+        let (client_id, account) = self
+            .accounts
+            .remove(client_id)
+            .ok_or(ServiceAccountStoreError::AccountNotFound)?;
+        self.save().inspect_err(|_| {
+            self.accounts.insert(client_id, account);
+        })
+    }
+
+    async fn list_accounts(&self) -> Result<Vec<ServiceAccount>, ServiceAccountStoreError> {
+        let mut accounts: Vec<ServiceAccount> =
+            self.accounts.iter().map(|account| account.clone()).collect();
+        accounts.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+        Ok(accounts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account() -> ServiceAccount {
+        ServiceAccount::new("Nightly report".to_owned(), vec!["reports:read".to_owned()], 1000).0
+    }
+
+    #[tokio::test]
+    async fn test_add_and_get_account() {
+        let store = HashmapServiceAccountStore::new();
+        let account = account();
+
+        assert_eq!(store.add_account(account.clone()).await, Ok(()));
+        assert_eq!(store.get_account(&account.client_id).await, Ok(account.clone()));
+        assert_eq!(
+            store.add_account(account).await,
+            Err(ServiceAccountStoreError::AccountAlreadyExists)
+        );
+        assert_eq!(
+            store.get_account("other").await,
+            Err(ServiceAccountStoreError::AccountNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete_account() {
+        let store = HashmapServiceAccountStore::new();
+        let mut account = account();
+        let _ = store.add_account(account.clone()).await;
+
+        account.rotate_secret(2000);
+        assert_eq!(store.update_account(account.clone()).await, Ok(()));
+        assert_eq!(store.get_account(&account.client_id).await, Ok(account.clone()));
+
+        assert_eq!(store.delete_account(&account.client_id).await, Ok(()));
+        assert_eq!(
+            store.update_account(account.clone()).await,
+            Err(ServiceAccountStoreError::AccountNotFound)
+        );
+        assert_eq!(
+            store.delete_account(&account.client_id).await,
+            Err(ServiceAccountStoreError::AccountNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_saved_accounts_survive_reopening() {
+        let path = std::env::temp_dir().join(format!("service-accounts-{}.json", uuid::Uuid::new_v4()));
+        let store = HashmapServiceAccountStore::open(&path).unwrap();
+        let mut rotated = account();
+        let deleted = account();
+        store.add_account(rotated.clone()).await.unwrap();
+        store.add_account(deleted.clone()).await.unwrap();
+        let secret = rotated.rotate_secret(2000);
+        store.update_account(rotated.clone()).await.unwrap();
+        store.delete_account(&deleted.client_id).await.unwrap();
+        drop(store);
+
+        let store = HashmapServiceAccountStore::open(&path).unwrap();
+        assert_eq!(store.list_accounts().await, Ok(vec![rotated.clone()]));
+        let account = store.get_account(&rotated.client_id).await.unwrap();
+        assert!(account.authenticate(secret.as_ref()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_open_rejects_files_it_cannot_parse() {
+        let path = std::env::temp_dir().join(format!("service-accounts-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "not json").unwrap();
+        assert!(HashmapServiceAccountStore::open(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_list_accounts_in_client_id_order() {
+        let store = HashmapServiceAccountStore::new();
+        for _ in 0..3 {
+            let _ = store.add_account(account()).await;
+        }
+
+        let accounts = store.list_accounts().await.unwrap();
+        assert_eq!(accounts.len(), 3);
+        assert!(accounts.windows(2).all(|w| w[0].client_id < w[1].client_id));
+    }
+}
@@ -0,0 +1,126 @@
+use crate::domain::{Email, Session, SessionId, SessionStore, SessionStoreError};
+use dashmap::DashMap;
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: DashMap<SessionId, Session>,
+}
+
+impl HashmapSessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn get_session(&self, id: &SessionId) -> Result<Session, SessionStoreError> {
+        self.sessions
+            .get(id)
+            .map(|session| session.clone())
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn touch_session(&self, id: &SessionId, now: i64) -> Result<(), SessionStoreError> {
+        let mut session = self
+            .sessions
+            .get_mut(id)
+            .ok_or(SessionStoreError::SessionNotFound)?;
+        session.last_seen_at = now;
+        Ok(())
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .iter()
+            .filter(|session| &session.email == email)
+            .map(|session| session.clone())
+            .collect();
+        sessions.sort_by_key(|session| session.created_at);
+        Ok(sessions)
+    }
+
+    async fn remove_session(&self, id: &SessionId) -> Result<(), SessionStoreError> {
+        self.sessions
+            .remove(id)
+            .map(|_| ())
+            .ok_or(SessionStoreError::SessionNotFound)
+    }
+
+    async fn remove_sessions(&self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| &session.email != email);
+        Ok(())
+    }
+
+    async fn remove_expired_sessions(&self, now: i64) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| !session.is_expired(now));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::ClientInfo;
+
+    fn session(email: &str, now: i64) -> Session {
+        Session::new(Email::parse(email).unwrap(), ClientInfo::default(), now, 600)
+    }
+
+    #[tokio::test]
+    async fn test_add_touch_and_remove_session() {
+        let store = HashmapSessionStore::new();
+        let session = session("test@example.com", 1_000);
+
+        store.add_session(session.clone()).await.unwrap();
+        store.touch_session(&session.id, 1_100).await.unwrap();
+        assert_eq!(store.get_session(&session.id).await.unwrap().last_seen_at, 1_100);
+
+        store.remove_session(&session.id).await.unwrap();
+        assert_eq!(
+            store.get_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(
+            store.remove_session(&session.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_listed_and_removed_per_user() {
+        let store = HashmapSessionStore::new();
+        let email = Email::parse("test@example.com").unwrap();
+        let first = session("test@example.com", 1_000);
+        let second = session("test@example.com", 1_001);
+        let other = session("other@example.com", 1_000);
+        for session in [second.clone(), first.clone(), other.clone()] {
+            store.add_session(session).await.unwrap();
+        }
+
+        assert_eq!(store.get_sessions(&email).await.unwrap(), vec![first, second]);
+
+        store.remove_sessions(&email).await.unwrap();
+        assert!(store.get_sessions(&email).await.unwrap().is_empty());
+        assert!(store.get_session(&other.id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_remove_expired_sessions() {
+        let store = HashmapSessionStore::new();
+        let old = session("test@example.com", 1_000);
+        let new = session("test@example.com", 1_500);
+        store.add_session(old.clone()).await.unwrap();
+        store.add_session(new.clone()).await.unwrap();
+
+        store.remove_expired_sessions(1_600).await.unwrap();
+        assert!(store.get_session(&old.id).await.is_err());
+        assert!(store.get_session(&new.id).await.is_ok());
+    }
+}
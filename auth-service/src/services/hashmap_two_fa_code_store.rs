@@ -0,0 +1,169 @@
+use crate::domain::{
+    Email, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, MAX_2FA_CODE_ATTEMPTS,
+};
+use dashmap::DashMap;
+
+struct StoredCode {
+    login_attempt_id: LoginAttemptId,
+    code: TwoFACode,
+    expires_at: i64,
+    failed_attempts: u32,
+}
+
+#[derive(Default)]
+pub struct HashmapTwoFACodeStore {
+    codes: DashMap<Email, StoredCode>,
+}
+
+impl HashmapTwoFACodeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl TwoFACodeStore for HashmapTwoFACodeStore {
+    async fn add_code(
+        &self,
+        email: Email,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+        expires_at: i64,
+    ) -> Result<(), TwoFACodeStoreError> {
+        self.codes.insert(
+            email,
+            StoredCode {
+                login_attempt_id,
+                code,
+                expires_at,
+                failed_attempts: 0,
+            },
+        );
+        Ok(())
+    }
+
+    async fn remove_code(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        self.codes.remove(email);
+        Ok(())
+    }
+
+    async fn get_code(
+        &self,
+        email: &Email,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        match self.codes.get(email) {
+            Some(entry) => Ok((entry.login_attempt_id.clone(), entry.code.clone())),
+            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        }
+    }
+
+    async fn record_failed_attempt(&self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        let mut entry = self
+            .codes
+            .get_mut(email)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)?;
+        entry.failed_attempts += 1;
+        if entry.failed_attempts >= MAX_2FA_CODE_ATTEMPTS {
+            drop(entry);
+            self.codes.remove(email);
+        }
+        Ok(())
+    }
+
+    async fn remove_expired_codes(&self, now: i64) -> Result<(), TwoFACodeStoreError> {
+        self.codes.retain(|_, entry| now < entry.expires_at);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPIRES_AT: i64 = 1_000;
+
+    #[tokio::test]
+    async fn add_and_get_code() {
+        let store = HashmapTwoFACodeStore::new();
+        let email = Email::parse("test@example.com").unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+
+        let result = store
+            .add_code(email.clone(), login_attempt_id.clone(), code.clone(), EXPIRES_AT)
+            .await;
+        assert!(result.is_ok());
+
+        let result = store.get_code(&email).await;
+        assert_eq!(result, Ok((login_attempt_id, code)));
+    }
+
+    #[tokio::test]
+    async fn add_code_replaces_previous_attempt() {
+        let store = HashmapTwoFACodeStore::new();
+        let email = Email::parse("test@example.com").unwrap();
+        let _ = store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default(), EXPIRES_AT)
+            .await;
+
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+        let _ = store
+            .add_code(email.clone(), login_attempt_id.clone(), code.clone(), EXPIRES_AT)
+            .await;
+
+        assert_eq!(store.get_code(&email).await, Ok((login_attempt_id, code)));
+    }
+
+    #[tokio::test]
+    async fn remove_code() {
+        let store = HashmapTwoFACodeStore::new();
+        let email = Email::parse("test@example.com").unwrap();
+        let _ = store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default(), EXPIRES_AT)
+            .await;
+
+        let result = store.remove_code(&email).await;
+        assert!(result.is_ok());
+
+        let result = store.get_code(&email).await;
+        assert_eq!(result.err(), Some(TwoFACodeStoreError::LoginAttemptIdNotFound));
+    }
+
+    #[tokio::test]
+    async fn failed_attempts_remove_code_at_limit() {
+        let store = HashmapTwoFACodeStore::new();
+        let email = Email::parse("test@example.com").unwrap();
+        let _ = store
+            .add_code(email.clone(), LoginAttemptId::default(), TwoFACode::default(), EXPIRES_AT)
+            .await;
+
+        for _ in 1..MAX_2FA_CODE_ATTEMPTS {
+            store.record_failed_attempt(&email).await.unwrap();
+        }
+        assert!(store.get_code(&email).await.is_ok());
+
+        store.record_failed_attempt(&email).await.unwrap();
+        assert_eq!(
+            store.get_code(&email).await.err(),
+            Some(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn remove_expired_codes() {
+        let store = HashmapTwoFACodeStore::new();
+        let old = Email::parse("old@example.com").unwrap();
+        let new = Email::parse("new@example.com").unwrap();
+        let _ = store
+            .add_code(old.clone(), LoginAttemptId::default(), TwoFACode::default(), EXPIRES_AT)
+            .await;
+        let _ = store
+            .add_code(new.clone(), LoginAttemptId::default(), TwoFACode::default(), EXPIRES_AT + 1)
+            .await;
+
+        store.remove_expired_codes(EXPIRES_AT).await.unwrap();
+        assert!(store.get_code(&old).await.is_err());
+        assert!(store.get_code(&new).await.is_ok());
+    }
+}
@@ -1,139 +1,258 @@
-use crate::domain::{Email, User, UserStore, UserStoreError};
-use std::collections::HashMap;
+use crate::domain::{
+    Email, User, UserChange, UserCursor, UserPage, UserQuery, UserStore, UserStoreError,
+};
+use crate::services::{UserStoreWal, UserStoreWalError};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
 
+/// Users in a sharded map, so requests for different users don't wait on
+/// each other, optionally persisted with a `UserStoreWal`.
+#[derive(Default)]
 pub struct HashmapUserStore {
-    users: HashMap<Email, User>,
+    users: DashMap<Email, User>,
+    // Only set when the store is persisted to disk.
+    wal: Option<Mutex<UserStoreWal>>,
+    // Changes hold this shared while they log and apply a record, so that a
+    // snapshot, which holds it exclusively, sees the log and the map agree.
+    snapshot_gate: RwLock<()>,
 }
 
 impl HashmapUserStore {
     pub fn new() -> Self {
-        Self {
-            users: HashMap::new(),
+        Self::default()
+    }
+
+    /// Loads the users persisted in `dir` and logs every change from then on,
+    /// compacting the log into a snapshot every `snapshot_interval` changes.
+    pub fn open(
+        dir: impl Into<PathBuf>,
+        snapshot_interval: usize,
+    ) -> Result<Self, UserStoreWalError> {
+        let (wal, users) = UserStoreWal::open(dir, snapshot_interval)?;
+        Ok(Self {
+            users: users
+                .into_iter()
+                .map(|user| (user.email.clone(), user))
+                .collect(),
+            wal: Some(Mutex::new(wal)),
+            snapshot_gate: RwLock::new(()),
+        })
+    }
+
+    // Callers hold the user's entry, so records for one user are logged in
+    // the order they're applied.
+    fn log_put(&self, user: &User) -> Result<(), UserStoreError> {
+        match &self.wal {
+            Some(wal) => wal
+                .lock()
+                .map_err(|_| UserStoreError::UnexpectedError)?
+                .append(user)
+                .map_err(|_| UserStoreError::UnexpectedError),
+            None => Ok(()),
+        }
+    }
+
+    fn log_delete(&self, email: &Email) -> Result<(), UserStoreError> {
+        match &self.wal {
+            Some(wal) => wal
+                .lock()
+                .map_err(|_| UserStoreError::UnexpectedError)?
+                .append_delete(email)
+                .map_err(|_| UserStoreError::UnexpectedError),
+            None => Ok(()),
+        }
+    }
+
+    fn snapshot_if_needed(&self) {
+        let Some(wal) = &self.wal else {
+            return;
+        };
+        if !wal.lock().is_ok_and(|wal| wal.needs_snapshot()) {
+            return;
+        }
+        let Ok(_gate) = self.snapshot_gate.write() else {
+            return;
+        };
+        let Ok(mut wal) = wal.lock() else {
+            return;
+        };
+        // Another change may have taken the snapshot while this one waited.
+        if !wal.needs_snapshot() {
+            return;
+        }
+        let users: Vec<User> = self.users.iter().map(|entry| entry.value().clone()).collect();
+        // The change is already durable in the log, so a failed snapshot
+        // only means a longer replay on the next start.
+        if let Err(e) = wal.snapshot(users.iter()) {
+            eprintln!("failed to snapshot user store: {:?}", e);
         }
     }
 }
 
 #[async_trait::async_trait]
 impl UserStore for HashmapUserStore {
-    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
-        if self.users.contains_key(&user.email) {
-            return Err(UserStoreError::UserAlreadyExists);
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        {
+            let _gate = self
+                .snapshot_gate
+                .read()
+                .map_err(|_| UserStoreError::UnexpectedError)?;
+            match self.users.entry(user.email.clone()) {
+                Entry::Occupied(_) => return Err(UserStoreError::UserAlreadyExists),
+                Entry::Vacant(entry) => {
+                    self.log_put(&user)?;
+                    entry.insert(user);
+                }
+            }
         }
-
-        self.users.insert(user.email.clone(), user);
+        self.snapshot_if_needed();
         Ok(())
     }
-    async fn get_user(&self, email: &Email) -> Result<&User, UserStoreError> {
-        let user = self.users.get(email);
-        match user {
-            Some(user) => Ok(user),
-            None => Err(UserStoreError::UserNotFound),
-        }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        self.users
+            .get(email)
+            .map(|user| user.clone())
+            .ok_or(UserStoreError::UserNotFound)
+    }
+
+    async fn update_user(&self, user: User) -> Result<(), UserStoreError> {
+        self.modify_user(
+            &user.email.clone(),
+            Box::new(move |stored| {
+                *stored = user;
+                Ok(())
+            }),
+        )
+        .await
+        .map(|_| ())
     }
 
-    async fn validate_user(&mut self, user: &User) -> Result<(), UserStoreError> {
-        let stored_user = self.get_user(&user.email).await;
-        match stored_user {
-            Ok(stored_user) => {
-                if stored_user.password.as_ref() == user.password.as_ref() {
-                    Ok(())
-                } else {
-                    Err(UserStoreError::InvalidCredentials)
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        {
+            let _gate = self
+                .snapshot_gate
+                .read()
+                .map_err(|_| UserStoreError::UnexpectedError)?;
+            match self.users.entry(email.clone()) {
+                Entry::Vacant(_) => return Err(UserStoreError::UserNotFound),
+                Entry::Occupied(entry) => {
+                    self.log_delete(email)?;
+                    entry.remove();
                 }
             }
-            Err(UserStoreError::UserNotFound) => Err(UserStoreError::UserNotFound),
-            Err(_) => Err(UserStoreError::UnexpectedError),
         }
+        self.snapshot_if_needed();
+        Ok(())
+    }
+
+    // The change is applied to a copy of the user, which only replaces the
+    // stored one once it's safely in the log.
+    async fn modify_user(&self, email: &Email, change: UserChange<'_>) -> Result<User, UserStoreError> {
+        let user = {
+            let _gate = self
+                .snapshot_gate
+                .read()
+                .map_err(|_| UserStoreError::UnexpectedError)?;
+            let mut entry = self.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+            let mut user = entry.clone();
+            change(&mut user)?;
+            self.log_put(&user)?;
+            *entry = user.clone();
+            user
+        };
+        self.snapshot_if_needed();
+        Ok(user)
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        let mut users: Vec<User> = self
+            .users
+            .iter()
+            .filter(|entry| query.matches(entry.value()))
+            .map(|entry| entry.value().clone())
+            .collect();
+        users.sort_by(|a, b| a.email.as_ref().cmp(b.email.as_ref()));
+
+        let total = users.len();
+        let mut users: Vec<User> = users
+            .into_iter()
+            .filter(|user| query.is_after_cursor(user))
+            .take(query.limit.saturating_add(1))
+            .collect();
+        // One more than asked for is fetched to tell whether there's another page.
+        let next_cursor = if users.len() > query.limit {
+            users.truncate(query.limit);
+            users.last().map(|user| UserCursor::after(&user.email))
+        } else {
+            None
+        };
+
+        Ok(UserPage {
+            users,
+            total,
+            next_cursor,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::Role;
+    use crate::services::user_store_conformance::check_user_store;
 
-    #[tokio::test]
-    async fn add_user() {
-        // Create a new store instance
-        let mut store = HashmapUserStore::new();
-
-        // Create a test user
-        let user = User::new(
-            "test@example.com".to_string(),
-            "test_Passw0rd!".to_string(),
-            false,
-        );
-        let mut result = store.add_user(user.clone()).await;
-        assert!(result.is_ok());
-
-        // Try to add the same user again
-        result = store.add_user(user).await;
-        assert_eq!(result.err(), Some(UserStoreError::UserAlreadyExists));
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_in_memory_store_conforms() {
+        check_user_store(HashmapUserStore::new).await;
     }
 
-    #[tokio::test]
-    async fn get_nonexistent_user_returns_error() {
-        let store = HashmapUserStore::new();
-        let email = Email::parse("test@example.com").unwrap();
-
-        let result = store.get_user(&email).await;
-        assert_eq!(result.err(), Some(UserStoreError::UserNotFound));
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_persisted_store_conforms() {
+        let dir = std::env::temp_dir().join(format!("user-store-{}", uuid::Uuid::new_v4()));
+        // A small interval so that snapshots are taken along the way.
+        check_user_store(|| {
+            HashmapUserStore::open(dir.join(uuid::Uuid::new_v4().to_string()), 3).unwrap()
+        })
+        .await;
+        std::fs::remove_dir_all(dir).unwrap();
     }
 
     #[tokio::test]
-    async fn validate_user_returns_user_not_found_err() {
-        let mut store = HashmapUserStore::new();
-
-        // Create a test user
-        let user = User::new(
-            "test@example.com".to_string(),
-            "test_Passw0rd!".to_string(),
-            false,
-        );
-
-        let result = store.validate_user(&user).await;
-        assert_eq!(result.err(), Some(UserStoreError::UserNotFound));
-    }
+    async fn test_persisted_store_survives_reopening() {
+        let dir = std::env::temp_dir().join(format!("user-store-{}", uuid::Uuid::new_v4()));
+        let email = Email::parse("test@example.com").unwrap();
 
-    #[tokio::test]
-    async fn validate_user_returns_invalid_credentials_err() {
-        let mut store = HashmapUserStore::new();
-
-        // Create a test user
-        let user = User::new(
-            "test@example.com".to_string(),
-            "test_Passw0rd!".to_string(),
-            false,
-        );
-
-        // Add the user to the store
-        let _ = store.add_user(user).await;
-
-        // Create a test user with incorrect password
-        let user = User::new(
-            "test@example.com".to_string(),
-            "test_wrong_Passw0rd!".to_string(),
-            false,
-        );
-
-        let result = store.validate_user(&user).await;
-        assert_eq!(result.err(), Some(UserStoreError::InvalidCredentials));
+        let store = HashmapUserStore::open(&dir, 2).unwrap();
+        let user = User::new(email.as_ref().to_owned(), "passworD123!".to_owned(), false);
+        store.add_user(user).await.unwrap();
+        store.record_failed_login(&email).await.unwrap();
+        store.set_roles(&email, vec![Role::admin()]).await.unwrap();
+        drop(store);
+
+        let store = HashmapUserStore::open(&dir, 2).unwrap();
+        let user = store.get_user(&email).await.unwrap();
+        assert_eq!(user.failed_login_attempts, 1);
+        assert_eq!(user.roles, vec![Role::admin()]);
+        std::fs::remove_dir_all(dir).unwrap();
     }
 
     #[tokio::test]
-    async fn validate_user_returns_ok() {
-        let mut store = HashmapUserStore::new();
-
-        // Create a test user
-        let user = User::new(
-            "test@example.com".to_string(),
-            "test_Passw0rd!".to_string(),
-            false,
-        );
+    async fn test_deleted_user_stays_deleted_after_reopening() {
+        let dir = std::env::temp_dir().join(format!("user-store-{}", uuid::Uuid::new_v4()));
+        let email = Email::parse("test@example.com").unwrap();
 
-        // Add the user to the store
-        let _ = store.add_user(user.clone()).await;
+        let store = HashmapUserStore::open(&dir, 100).unwrap();
+        let user = User::new(email.as_ref().to_owned(), "passworD123!".to_owned(), false);
+        store.add_user(user).await.unwrap();
+        store.delete_user(&email).await.unwrap();
+        assert_eq!(store.delete_user(&email).await, Err(UserStoreError::UserNotFound));
+        drop(store);
 
-        let result = store.validate_user(&user).await;
-        assert!(result.is_ok());
+        let store = HashmapUserStore::open(&dir, 100).unwrap();
+        assert_eq!(store.get_user(&email).await.err(), Some(UserStoreError::UserNotFound));
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,102 @@
+use crate::domain::{
+    Email, WebAuthnCeremony, WebAuthnChallenge, WebAuthnChallengeStore,
+    WebAuthnChallengeStoreError,
+};
+use dashmap::DashMap;
+
+#[derive(Default)]
+pub struct HashmapWebAuthnChallengeStore {
+    challenges: DashMap<(Email, WebAuthnCeremony), (WebAuthnChallenge, i64)>,
+}
+
+impl HashmapWebAuthnChallengeStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl WebAuthnChallengeStore for HashmapWebAuthnChallengeStore {
+    async fn add_challenge(
+        &self,
+        email: Email,
+        ceremony: WebAuthnCeremony,
+        challenge: WebAuthnChallenge,
+        expires_at: i64,
+    ) -> Result<(), WebAuthnChallengeStoreError> {
+        self.challenges.insert((email, ceremony), (challenge, expires_at));
+        Ok(())
+    }
+
+    async fn take_challenge(
+        &self,
+        email: &Email,
+        ceremony: WebAuthnCeremony,
+        now: i64,
+    ) -> Result<WebAuthnChallenge, WebAuthnChallengeStoreError> {
+        // Expired challenges are removed too, so they can't be retried later.
+        self.challenges
+            .remove(&(email.clone(), ceremony))
+            .filter(|(_, (_, expires_at))| now <= *expires_at)
+            .map(|(_, (challenge, _))| challenge)
+            .ok_or(WebAuthnChallengeStoreError::ChallengeNotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_000;
+
+    #[tokio::test]
+    async fn take_challenge_is_single_use() {
+        let store = HashmapWebAuthnChallengeStore::new();
+        let email = Email::parse("test@example.com").unwrap();
+        let challenge = WebAuthnChallenge::generate();
+
+        let _ = store
+            .add_challenge(email.clone(), WebAuthnCeremony::Registration, challenge.clone(), NOW)
+            .await;
+
+        let result = store.take_challenge(&email, WebAuthnCeremony::Registration, NOW).await;
+        assert_eq!(result, Ok(challenge));
+
+        let result = store.take_challenge(&email, WebAuthnCeremony::Registration, NOW).await;
+        assert_eq!(result, Err(WebAuthnChallengeStoreError::ChallengeNotFound));
+    }
+
+    #[tokio::test]
+    async fn challenges_are_kept_per_ceremony() {
+        let store = HashmapWebAuthnChallengeStore::new();
+        let email = Email::parse("test@example.com").unwrap();
+        let _ = store
+            .add_challenge(
+                email.clone(),
+                WebAuthnCeremony::Registration,
+                WebAuthnChallenge::generate(),
+                NOW,
+            )
+            .await;
+
+        let result = store.take_challenge(&email, WebAuthnCeremony::Authentication, NOW).await;
+        assert_eq!(result, Err(WebAuthnChallengeStoreError::ChallengeNotFound));
+    }
+
+    #[tokio::test]
+    async fn expired_challenges_are_not_returned() {
+        let store = HashmapWebAuthnChallengeStore::new();
+        let email = Email::parse("test@example.com").unwrap();
+        let _ = store
+            .add_challenge(
+                email.clone(),
+                WebAuthnCeremony::Authentication,
+                WebAuthnChallenge::generate(),
+                NOW,
+            )
+            .await;
+
+        let result = store.take_challenge(&email, WebAuthnCeremony::Authentication, NOW + 1).await;
+        assert_eq!(result, Err(WebAuthnChallengeStoreError::ChallengeNotFound));
+    }
+}
@@ -0,0 +1,148 @@
+use crate::domain::{BreachedPasswords, Password};
+use memmap2::Mmap;
+use sha1::{Digest, Sha1};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::path::Path;
+
+const HASH_LEN: usize = 40;
+
+/// Breached passwords from a Have I Been Pwned "Pwned Passwords" download:
+/// one `SHA1:COUNT` line per password, with the hash in uppercase hex and the
+/// lines sorted by hash.
+///
+/// The file is memory-mapped and binary searched, so lookups only touch a few
+/// pages of a dataset that's far too large to load. Nothing leaves the
+/// machine, unlike the k-anonymity range API the dataset is published for.
+pub struct HibpPasswordList {
+    // `None` when no dataset is configured.
+    map: Option<Mmap>,
+}
+
+impl HibpPasswordList {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        // SAFETY: the dataset is only ever replaced, never modified in place,
+        // so the mapped bytes don't change underneath us.
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| e.to_string())?;
+        if parse_line(first_line(&map)).is_none() {
+            return Err("not a Pwned Passwords file of SHA1:COUNT lines".to_owned());
+        }
+        Ok(Self { map: Some(map) })
+    }
+
+    /// A list without any passwords, for when no dataset is configured.
+    pub fn empty() -> Self {
+        Self { map: None }
+    }
+}
+
+impl BreachedPasswords for HibpPasswordList {
+    fn occurrences(&self, password: &Password) -> u64 {
+        let Some(map) = &self.map else {
+            return 0;
+        };
+        let hash = format!("{:X}", Sha1::digest(password.as_ref().as_bytes()));
+        find(map, hash.as_bytes()).unwrap_or(0)
+    }
+}
+
+fn first_line(data: &[u8]) -> &[u8] {
+    let end = data.iter().position(|&b| b == b'\n').unwrap_or(data.len());
+    &data[..end]
+}
+
+// Splits `HASH:COUNT`, allowing for the CRLF line endings the dataset ships with.
+fn parse_line(line: &[u8]) -> Option<(&[u8], u64)> {
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+    let (hash, count) = (line.get(..HASH_LEN)?, line.get(HASH_LEN..)?);
+    let count = std::str::from_utf8(count.strip_prefix(b":")?).ok()?;
+    Some((hash, count.parse().ok()?))
+}
+
+// Binary search over byte offsets: each probe lands somewhere in a line, and
+// is widened to that whole line before comparing, so the range `lo..hi`
+// always starts and ends on line boundaries.
+fn find(data: &[u8], hash: &[u8]) -> Option<u64> {
+    let (mut lo, mut hi) = (0, data.len());
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let start = data[lo..mid]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(lo, |i| lo + i + 1);
+        let end = data[mid..hi]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(hi, |i| mid + i);
+
+        let Some((line_hash, count)) = parse_line(&data[start..end]) else {
+            // Only a trailing blank line fails to parse in a valid file.
+            hi = start;
+            continue;
+        };
+        match line_hash.cmp(hash) {
+            Ordering::Equal => return Some(count),
+            Ordering::Less => lo = end + 1,
+            Ordering::Greater => hi = start,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BREACHED: [&str; 4] = ["P@ssw0rd1", "Passw0rd!", "Welcome1!", "Qwerty123!"];
+
+    fn sha1(password: &str) -> String {
+        format!("{:X}", Sha1::digest(password.as_bytes()))
+    }
+
+    fn write_dataset(line_ending: &str) -> std::path::PathBuf {
+        let mut lines: Vec<String> = BREACHED
+            .iter()
+            .enumerate()
+            .map(|(i, password)| format!("{}:{}", sha1(password), i + 1))
+            .collect();
+        // Unrelated hashes either side of and between the breached ones.
+        for filler in ["0000000000000000000000000000000000000000", "7", "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"] {
+            lines.push(format!("{:0<40}:1", filler));
+        }
+        lines.sort();
+        let path = std::env::temp_dir().join(format!("pwned-{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, lines.join(line_ending) + line_ending).unwrap();
+        path
+    }
+
+    fn occurrences(list: &HibpPasswordList, password: &str) -> u64 {
+        list.occurrences(&Password::parse(password).unwrap())
+    }
+
+    #[test]
+    fn test_finds_every_breached_password() {
+        for line_ending in ["\n", "\r\n"] {
+            let path = write_dataset(line_ending);
+            let list = HibpPasswordList::open(&path).unwrap();
+            for (i, password) in BREACHED.iter().enumerate() {
+                assert_eq!(occurrences(&list, password), i as u64 + 1, "{} should be found", password);
+            }
+            assert_eq!(occurrences(&list, "passworD123!"), 0);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_empty_list_finds_nothing() {
+        assert_eq!(occurrences(&HibpPasswordList::empty(), "P@ssw0rd1"), 0);
+    }
+
+    #[test]
+    fn test_open_rejects_other_files() {
+        let path = std::env::temp_dir().join(format!("pwned-{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "P@ssw0rd1\n").unwrap();
+        assert!(HibpPasswordList::open(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
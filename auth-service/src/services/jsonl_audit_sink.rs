@@ -0,0 +1,182 @@
+use crate::domain::{
+    verify_audit_chain, AuditChainError, AuditEntry, AuditEvent, AuditQuery, AuditSink,
+    AuditSinkError, AUDIT_GENESIS_HASH,
+};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Audit log kept as a file with one JSON entry per line.
+pub struct JsonlAuditSink {
+    path: PathBuf,
+    // Entries have to be chained one after the other, so appends take turns.
+    tail: Mutex<ChainTail>,
+}
+
+struct ChainTail {
+    file: File,
+    last_seq: u64,
+    last_hash: String,
+}
+
+impl JsonlAuditSink {
+    /// Opens the log at `path`, creating it if needed, and continues the
+    /// chain from its last entry.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, AuditSinkError> {
+        let path = path.into();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|_| AuditSinkError::UnexpectedError)?;
+
+        let (last_seq, last_hash) = match read_entries(&path)?.pop() {
+            Some(entry) => (entry.seq, entry.hash),
+            None => (0, AUDIT_GENESIS_HASH.to_owned()),
+        };
+
+        Ok(Self {
+            path,
+            tail: Mutex::new(ChainTail {
+                file,
+                last_seq,
+                last_hash,
+            }),
+        })
+    }
+
+    fn tail(&self) -> Result<MutexGuard<'_, ChainTail>, AuditSinkError> {
+        self.tail.lock().map_err(|_| AuditSinkError::UnexpectedError)
+    }
+}
+
+fn read_entries(path: &Path) -> Result<Vec<AuditEntry>, AuditSinkError> {
+    let file = File::open(path).map_err(|_| AuditSinkError::UnexpectedError)?;
+    let mut entries = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|_| AuditSinkError::UnexpectedError)?;
+        let entry = serde_json::from_str(&line)
+            .map_err(|_| AuditSinkError::Tampered(AuditChainError::Malformed { line: index + 1 }))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+#[async_trait::async_trait]
+impl AuditSink for JsonlAuditSink {
+    async fn record(
+        &self,
+        event: AuditEvent,
+        timestamp: i64,
+    ) -> Result<AuditEntry, AuditSinkError> {
+        let mut tail = self.tail()?;
+        let entry = AuditEntry::new(tail.last_seq + 1, timestamp, event, tail.last_hash.clone());
+        let mut line = serde_json::to_vec(&entry).map_err(|_| AuditSinkError::UnexpectedError)?;
+        line.push(b'\n');
+
+        // The entry only counts once it's on disk.
+        tail.file
+            .write_all(&line)
+            .and_then(|_| tail.file.sync_data())
+            .map_err(|_| AuditSinkError::UnexpectedError)?;
+
+        tail.last_seq = entry.seq;
+        tail.last_hash = entry.hash.clone();
+        Ok(entry)
+    }
+
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, AuditSinkError> {
+        // Holding the tail keeps a half-written entry from being read.
+        let mut entries = {
+            let _tail = self.tail()?;
+            read_entries(&self.path)?
+        };
+        entries.retain(|entry| query.matches(entry));
+        Ok(entries)
+    }
+
+    async fn verify(&self) -> Result<(), AuditSinkError> {
+        let entries = {
+            let _tail = self.tail()?;
+            read_entries(&self.path)?
+        };
+        verify_audit_chain(&entries).map_err(AuditSinkError::Tampered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AuditAction, AuditOutcome, ClientInfo};
+
+    fn temp_log_path() -> PathBuf {
+        std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()))
+    }
+
+    fn login(email: &str) -> AuditEvent {
+        AuditEvent::new(AuditAction::Login, AuditOutcome::Success, &ClientInfo::default())
+            .actor(email)
+    }
+
+    #[tokio::test]
+    async fn test_reopened_log_continues_the_chain() {
+        let path = temp_log_path();
+        let sink = JsonlAuditSink::open(&path).unwrap();
+        let first = sink.record(login("a@example.com"), 1_000).await.unwrap();
+        assert_eq!(first.seq, 1);
+        assert_eq!(first.prev_hash, AUDIT_GENESIS_HASH);
+        drop(sink);
+
+        let sink = JsonlAuditSink::open(&path).unwrap();
+        let second = sink.record(login("b@example.com"), 1_001).await.unwrap();
+        assert_eq!(second.seq, 2);
+        assert_eq!(second.prev_hash, first.hash);
+        assert_eq!(sink.verify().await, Ok(()));
+
+        let entries = sink.query(&AuditQuery::default()).await.unwrap();
+        assert_eq!(entries, [first, second]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_query_filters_entries() {
+        let path = temp_log_path();
+        let sink = JsonlAuditSink::open(&path).unwrap();
+        sink.record(login("a@example.com"), 1_000).await.unwrap();
+        sink.record(login("b@example.com"), 1_001).await.unwrap();
+        sink.record(login("a@example.com"), 1_002).await.unwrap();
+
+        let query = AuditQuery {
+            email: Some("a@example.com".to_owned()),
+            from: Some(1_001),
+            to: None,
+        };
+        let entries = sink.query(&query).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].seq, 3);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_edited_file_fails_verification() {
+        let path = temp_log_path();
+        let sink = JsonlAuditSink::open(&path).unwrap();
+        sink.record(login("a@example.com"), 1_000).await.unwrap();
+        sink.record(login("b@example.com"), 1_001).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, contents.replace("b@example.com", "c@example.com")).unwrap();
+        assert_eq!(
+            sink.verify().await,
+            Err(AuditSinkError::Tampered(AuditChainError::HashMismatch { seq: 2 }))
+        );
+
+        std::fs::write(&path, "not json\n").unwrap();
+        assert_eq!(
+            sink.verify().await,
+            Err(AuditSinkError::Tampered(AuditChainError::Malformed { line: 1 }))
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
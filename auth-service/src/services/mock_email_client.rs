@@ -0,0 +1,17 @@
+use crate::domain::{Email, EmailClient};
+
+/// Email client that logs messages instead of delivering them.
+pub struct MockEmailClient;
+
+#[async_trait::async_trait]
+impl EmailClient for MockEmailClient {
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<(), String> {
+        println!(
+            "Sending email to {} with subject: {} and content: {}",
+            recipient.as_ref(),
+            subject,
+            content
+        );
+        Ok(())
+    }
+}
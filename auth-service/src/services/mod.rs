@@ -1,3 +1,27 @@
+mod hashmap_authorization_code_store;
+mod hashmap_magic_link_store;
+mod hashmap_oidc_client_store;
+mod hashmap_service_account_store;
+mod hashmap_session_store;
+mod hashmap_two_fa_code_store;
 mod hashmap_user_store;
+mod hashmap_webauthn_challenge_store;
+mod hibp_password_list;
+mod jsonl_audit_sink;
+mod mock_email_client;
+#[cfg(test)]
+pub(crate) mod user_store_conformance;
+mod user_store_wal;
 
-pub use hashmap_user_store::*;
\ No newline at end of file
+pub use hashmap_authorization_code_store::*;
+pub use hashmap_magic_link_store::*;
+pub use hashmap_oidc_client_store::*;
+pub use hashmap_service_account_store::*;
+pub use hashmap_session_store::*;
+pub use hashmap_two_fa_code_store::*;
+pub use hashmap_user_store::*;
+pub use hashmap_webauthn_challenge_store::*;
+pub use hibp_password_list::*;
+pub use jsonl_audit_sink::*;
+pub use mock_email_client::*;
+pub use user_store_wal::*;
@@ -0,0 +1,363 @@
+//! Behaviour every `UserStore` implementation has to share. An implementation
+//! runs the whole suite from its own tests with `check_user_store`, giving it
+//! a way to make empty stores.
+
+use crate::domain::{
+    Email, PasskeyCredential, Password, PasswordHash, Permission, RecoveryCode, Role,
+    TotpCredential, TotpSecret, TwoFAMethod, User, UserCursor, UserQuery, UserStore,
+    UserStoreError, MAX_FAILED_LOGIN_ATTEMPTS,
+};
+use std::sync::Arc;
+
+pub async fn check_user_store<S: UserStore + 'static>(mut new_store: impl FnMut() -> S) {
+    add_user_rejects_duplicates(new_store()).await;
+    get_nonexistent_user_returns_error(new_store()).await;
+    get_user_returns_a_copy(new_store()).await;
+    update_user_replaces_stored_user(new_store()).await;
+    delete_user_removes_user(new_store()).await;
+    validate_user_checks_password(new_store()).await;
+    update_two_fa_changes_method(new_store()).await;
+    set_totp_credential_returns_user_not_found_err(new_store()).await;
+    set_recovery_codes_replaces_existing_codes(new_store()).await;
+    update_passkey_sign_count(new_store()).await;
+    failed_change_is_not_applied(new_store()).await;
+    failed_logins_lock_until_cleared(new_store()).await;
+    set_password_clears_required_reset(new_store()).await;
+    set_roles_and_permissions(new_store()).await;
+    reset_two_fa_removes_every_second_factor(new_store()).await;
+    list_users_filters(new_store()).await;
+    list_users_pages_with_cursor(new_store()).await;
+    concurrent_signups_are_all_added(new_store()).await;
+    concurrent_changes_are_not_lost(new_store()).await;
+}
+
+fn user(email: &str) -> User {
+    User::new(email.to_owned(), "test_Passw0rd!".to_owned(), false)
+}
+
+async fn add_user_rejects_duplicates(store: impl UserStore) {
+    let user = user("test@example.com");
+    assert_eq!(store.add_user(user.clone()).await, Ok(()));
+    assert_eq!(store.add_user(user).await, Err(UserStoreError::UserAlreadyExists));
+}
+
+async fn get_nonexistent_user_returns_error(store: impl UserStore) {
+    let email = Email::parse("test@example.com").unwrap();
+    assert_eq!(store.get_user(&email).await.err(), Some(UserStoreError::UserNotFound));
+}
+
+async fn get_user_returns_a_copy(store: impl UserStore) {
+    let user = user("test@example.com");
+    store.add_user(user.clone()).await.unwrap();
+
+    let mut copy = store.get_user(&user.email).await.unwrap();
+    copy.disabled = true;
+    assert!(!store.get_user(&user.email).await.unwrap().disabled);
+}
+
+async fn update_user_replaces_stored_user(store: impl UserStore) {
+    let mut user = user("test@example.com");
+    assert_eq!(
+        store.update_user(user.clone()).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    store.add_user(user.clone()).await.unwrap();
+
+    user.disabled = true;
+    user.roles = vec![Role::admin()];
+    store.update_user(user.clone()).await.unwrap();
+    let stored_user = store.get_user(&user.email).await.unwrap();
+    assert!(stored_user.disabled);
+    assert_eq!(stored_user.roles, user.roles);
+}
+
+async fn delete_user_removes_user(store: impl UserStore) {
+    let user = user("test@example.com");
+    store.add_user(user.clone()).await.unwrap();
+
+    assert_eq!(store.delete_user(&user.email).await, Ok(()));
+    assert_eq!(
+        store.get_user(&user.email).await.err(),
+        Some(UserStoreError::UserNotFound)
+    );
+    assert_eq!(
+        store.delete_user(&user.email).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    // The email is free to sign up again.
+    assert_eq!(store.add_user(user).await, Ok(()));
+}
+
+async fn validate_user_checks_password(store: impl UserStore) {
+    let user = user("test@example.com");
+    assert_eq!(
+        store.validate_user(&user.email, "test_Passw0rd!").await,
+        Err(UserStoreError::UserNotFound)
+    );
+    store.add_user(user.clone()).await.unwrap();
+
+    assert_eq!(
+        store.validate_user(&user.email, "test_wrong_Passw0rd!").await,
+        Err(UserStoreError::InvalidCredentials)
+    );
+    assert_eq!(store.validate_user(&user.email, "test_Passw0rd!").await, Ok(()));
+}
+
+async fn update_two_fa_changes_method(store: impl UserStore) {
+    let user = user("test@example.com");
+    store.add_user(user.clone()).await.unwrap();
+
+    let result = store.update_two_fa(&user.email, true, TwoFAMethod::Totp).await;
+    assert!(result.is_ok());
+
+    let stored_user = store.get_user(&user.email).await.unwrap();
+    assert!(stored_user.requires_2fa);
+    assert_eq!(stored_user.two_fa_method, TwoFAMethod::Totp);
+}
+
+async fn set_totp_credential_returns_user_not_found_err(store: impl UserStore) {
+    let email = Email::parse("test@example.com").unwrap();
+
+    let result = store.set_totp_credential(&email, None).await;
+    assert_eq!(result.err(), Some(UserStoreError::UserNotFound));
+}
+
+async fn set_recovery_codes_replaces_existing_codes(store: impl UserStore) {
+    let user = User::new("test@example.com".to_owned(), "test_Passw0rd!".to_owned(), true);
+    store.add_user(user.clone()).await.unwrap();
+
+    let (_, first_set) = RecoveryCode::generate_set();
+    store.set_recovery_codes(&user.email, first_set).await.unwrap();
+    let (_, second_set) = RecoveryCode::generate_set();
+    let result = store.set_recovery_codes(&user.email, second_set.clone()).await;
+    assert!(result.is_ok());
+
+    let stored_user = store.get_user(&user.email).await.unwrap();
+    assert_eq!(stored_user.recovery_codes, second_set);
+}
+
+async fn update_passkey_sign_count(store: impl UserStore) {
+    let user = user("test@example.com");
+    store.add_user(user.clone()).await.unwrap();
+    let passkey = PasskeyCredential {
+        id: b"credential-id".to_vec(),
+        public_key: Vec::new(),
+        sign_count: 0,
+    };
+    store.add_passkey(&user.email, passkey).await.unwrap();
+
+    let result = store
+        .update_passkey_sign_count(&user.email, b"credential-id", 7)
+        .await;
+    assert!(result.is_ok());
+    let stored_user = store.get_user(&user.email).await.unwrap();
+    assert_eq!(stored_user.passkeys[0].sign_count, 7);
+}
+
+async fn failed_change_is_not_applied(store: impl UserStore) {
+    let user = user("test@example.com");
+    store.add_user(user.clone()).await.unwrap();
+
+    let result = store.update_passkey_sign_count(&user.email, b"unknown", 1).await;
+    assert_eq!(result, Err(UserStoreError::InvalidCredentials));
+    assert!(store.get_user(&user.email).await.unwrap().passkeys.is_empty());
+}
+
+async fn failed_logins_lock_until_cleared(store: impl UserStore) {
+    let user = user("test@example.com");
+    store.add_user(user.clone()).await.unwrap();
+
+    for attempt in 1..=MAX_FAILED_LOGIN_ATTEMPTS {
+        assert_eq!(store.record_failed_login(&user.email).await, Ok(attempt));
+    }
+    assert!(store.get_user(&user.email).await.unwrap().is_locked());
+
+    store.clear_failed_logins(&user.email).await.unwrap();
+    assert!(!store.get_user(&user.email).await.unwrap().is_locked());
+}
+
+async fn set_password_clears_required_reset(store: impl UserStore) {
+    let user = user("test@example.com");
+    store.add_user(user.clone()).await.unwrap();
+    store.set_password_reset_required(&user.email, true).await.unwrap();
+
+    let password = Password::parse("new_Passw0rd!").unwrap();
+    let result = store.set_password(&user.email, PasswordHash::hash(&password)).await;
+    assert!(result.is_ok());
+
+    let stored_user = store.get_user(&user.email).await.unwrap();
+    assert!(stored_user.password_hash.verify("new_Passw0rd!"));
+    assert!(!stored_user.password_reset_required);
+}
+
+async fn set_roles_and_permissions(store: impl UserStore) {
+    let user = user("test@example.com");
+    store.add_user(user.clone()).await.unwrap();
+
+    let roles = vec![Role::admin()];
+    let permissions = vec![Permission::parse("reports:export").unwrap()];
+    assert!(store.set_roles(&user.email, roles.clone()).await.is_ok());
+    assert!(store
+        .set_permissions(&user.email, permissions.clone())
+        .await
+        .is_ok());
+
+    let stored_user = store.get_user(&user.email).await.unwrap();
+    assert_eq!(stored_user.roles, roles);
+    assert_eq!(stored_user.permissions, permissions);
+
+    let unknown = Email::parse("unknown@example.com").unwrap();
+    assert_eq!(
+        store.set_roles(&unknown, Vec::new()).await,
+        Err(UserStoreError::UserNotFound)
+    );
+}
+
+async fn reset_two_fa_removes_every_second_factor(store: impl UserStore) {
+    let user = User::new("test@example.com".to_owned(), "passworD123!".to_owned(), true);
+    let email = user.email.clone();
+    store.add_user(user).await.unwrap();
+    store
+        .set_totp_credential(&email, Some(TotpCredential::new(TotpSecret::generate())))
+        .await
+        .unwrap();
+    store.update_two_fa(&email, true, TwoFAMethod::Totp).await.unwrap();
+    store
+        .set_recovery_codes(&email, RecoveryCode::generate_set().1)
+        .await
+        .unwrap();
+
+    store.reset_two_fa(&email).await.unwrap();
+    let user = store.get_user(&email).await.unwrap();
+    assert!(!user.requires_2fa);
+    assert_eq!(user.two_fa_method, TwoFAMethod::Email);
+    assert!(user.totp.is_none());
+    assert!(user.recovery_codes.is_empty());
+    assert!(user.passkeys.is_empty());
+}
+
+fn emails(users: &[User]) -> Vec<&str> {
+    users.iter().map(|user| user.email.as_ref()).collect()
+}
+
+async fn list_users_filters(store: impl UserStore) {
+    for email in ["carol@example.com", "alice@example.com", "bob@test.com", "dave@example.com"] {
+        store.add_user(user(email)).await.unwrap();
+    }
+    let alice = Email::parse("alice@example.com").unwrap();
+    let bob = Email::parse("bob@test.com").unwrap();
+    store.set_roles(&alice, vec![Role::admin()]).await.unwrap();
+    store.set_disabled(&bob, true).await.unwrap();
+    store.update_two_fa(&bob, true, TwoFAMethod::Email).await.unwrap();
+
+    let query = UserQuery {
+        search: Some("EXAMPLE".to_owned()),
+        limit: 10,
+        ..UserQuery::default()
+    };
+    let page = store.list_users(&query).await.unwrap();
+    assert_eq!(page.total, 3);
+    assert_eq!(
+        emails(&page.users),
+        ["alice@example.com", "carol@example.com", "dave@example.com"]
+    );
+
+    let query = UserQuery {
+        role: Some(Role::admin()),
+        limit: 10,
+        ..UserQuery::default()
+    };
+    assert_eq!(emails(&store.list_users(&query).await.unwrap().users), ["alice@example.com"]);
+
+    let query = UserQuery {
+        disabled: Some(true),
+        requires_2fa: Some(true),
+        limit: 10,
+        ..UserQuery::default()
+    };
+    assert_eq!(emails(&store.list_users(&query).await.unwrap().users), ["bob@test.com"]);
+
+    let query = UserQuery {
+        disabled: Some(false),
+        limit: 10,
+        ..UserQuery::default()
+    };
+    assert_eq!(store.list_users(&query).await.unwrap().total, 3);
+}
+
+async fn list_users_pages_with_cursor(store: impl UserStore) {
+    for email in ["carol@example.com", "alice@example.com", "bob@test.com", "dave@example.com"] {
+        store.add_user(user(email)).await.unwrap();
+    }
+
+    let mut query = UserQuery {
+        limit: 3,
+        ..UserQuery::default()
+    };
+    let page = store.list_users(&query).await.unwrap();
+    assert_eq!(page.total, 4);
+    assert_eq!(
+        emails(&page.users),
+        ["alice@example.com", "bob@test.com", "carol@example.com"]
+    );
+
+    // Users added behind the cursor don't shift the next page.
+    store.add_user(user("aaron@example.com")).await.unwrap();
+    query.after = page.next_cursor;
+    let page = store.list_users(&query).await.unwrap();
+    assert_eq!(page.total, 5);
+    assert_eq!(emails(&page.users), ["dave@example.com"]);
+    assert_eq!(page.next_cursor, None);
+
+    // A cursor stays valid after its user is deleted.
+    let carol = Email::parse("carol@example.com").unwrap();
+    store.delete_user(&carol).await.unwrap();
+    query.after = Some(UserCursor::after(&carol));
+    let page = store.list_users(&query).await.unwrap();
+    assert_eq!(emails(&page.users), ["dave@example.com"]);
+}
+
+// Stores are shared between requests without a lock around them, so these
+// only pass if the store serializes conflicting changes itself.
+async fn concurrent_signups_are_all_added(store: impl UserStore + 'static) {
+    let store = Arc::new(store);
+    let tasks: Vec<_> = (0..64)
+        .map(|i| {
+            let store = store.clone();
+            tokio::spawn(async move {
+                let user = user(&format!("user{}@example.com", i));
+                store.add_user(user.clone()).await.unwrap();
+                // Only one of the racing signups for the same email may win.
+                store.add_user(user).await
+            })
+        })
+        .collect();
+    for task in tasks {
+        assert_eq!(task.await.unwrap(), Err(UserStoreError::UserAlreadyExists));
+    }
+
+    let page = store.list_users(&UserQuery { limit: 100, ..Default::default() }).await.unwrap();
+    assert_eq!(page.total, 64);
+}
+
+async fn concurrent_changes_are_not_lost(store: impl UserStore + 'static) {
+    let store = Arc::new(store);
+    let user = user("test@example.com");
+    store.add_user(user.clone()).await.unwrap();
+
+    let tasks: Vec<_> = (0..64)
+        .map(|_| {
+            let (store, email) = (store.clone(), user.email.clone());
+            tokio::spawn(async move { store.record_failed_login(&email).await.unwrap() })
+        })
+        .collect();
+    let mut counts = Vec::new();
+    for task in tasks {
+        counts.push(task.await.unwrap());
+    }
+    counts.sort();
+
+    // Every change saw the one before it.
+    assert_eq!(counts, (1..=64).collect::<Vec<u32>>());
+    assert_eq!(store.get_user(&user.email).await.unwrap().failed_login_attempts, 64);
+}
@@ -0,0 +1,488 @@
+use crate::domain::{Email, User};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const WAL_FILE_NAME: &str = "users.wal";
+const SNAPSHOT_FILE_NAME: &str = "users.snapshot";
+const LOCK_FILE_NAME: &str = "users.lock";
+// Payload length and CRC32 of the payload, both little endian.
+const FRAME_HEADER_LEN: usize = 8;
+
+#[derive(Debug)]
+pub enum UserStoreWalError {
+    Io(std::io::Error),
+    /// A record before the end of the file failed its checksum or couldn't be
+    /// parsed, so the file was damaged rather than cut short by a crash.
+    Corrupt { file: PathBuf, offset: usize },
+    /// Another process, or another store in this one, has the directory open.
+    Locked,
+    /// Two users were stored under emails that now normalize the same way,
+    /// such as ones differing only in case, so one would replace the other.
+    DuplicateEmail { first: String, second: String },
+}
+
+impl From<std::io::Error> for UserStoreWalError {
+    fn from(e: std::io::Error) -> Self {
+        UserStoreWalError::Io(e)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum WalRecord {
+    /// The user's full state after a change.
+    PutUser(User),
+    DeleteUser(Email),
+}
+
+// Emails are normalized as they're read, so the spelling a record was
+// written with is read separately to tell apart users it now conflates.
+#[derive(Deserialize)]
+struct StoredUser {
+    email: String,
+}
+
+#[derive(Deserialize)]
+enum StoredRecord {
+    PutUser(StoredUser),
+    DeleteUser(String),
+}
+
+impl StoredRecord {
+    fn into_email(self) -> String {
+        match self {
+            StoredRecord::PutUser(user) => user.email,
+            StoredRecord::DeleteUser(email) => email,
+        }
+    }
+}
+
+/// Write-ahead log and snapshot that make `HashmapUserStore` durable.
+///
+/// Every change is appended to the log and synced before it's applied. Once
+/// the log holds `snapshot_interval` records, the whole store is written to a
+/// new snapshot and the log starts over.
+pub struct UserStoreWal {
+    dir: PathBuf,
+    wal: File,
+    records_since_snapshot: usize,
+    snapshot_interval: usize,
+    // Exclusively locked while the log is open, so that two writers, such as
+    // the service and authctl, can't interleave their records. The lock goes
+    // with the file handle, so a crashed process doesn't leave it behind.
+    _lock: File,
+}
+
+impl UserStoreWal {
+    /// Opens the log in `dir`, creating it if needed, and returns it together
+    /// with the users recovered from the snapshot and log, ordered by email.
+    ///
+    /// Users stored before their emails were normalized as they are now are
+    /// written back to a new snapshot, unless two of them would collide.
+    pub fn open(
+        dir: impl Into<PathBuf>,
+        snapshot_interval: usize,
+    ) -> Result<(Self, Vec<User>), UserStoreWalError> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(LOCK_FILE_NAME))?;
+        match lock.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Err(UserStoreWalError::Locked),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+
+        // Keyed by the emails as they were stored, which is how they were
+        // told apart when they were written.
+        let mut stored: HashMap<String, User> =
+            read_snapshot(&dir.join(SNAPSHOT_FILE_NAME))?.into_iter().collect();
+        let wal_path = dir.join(WAL_FILE_NAME);
+        let records = replay_wal(&wal_path)?;
+        let records_since_snapshot = records.len();
+        // Replayed in order, so later records win over earlier ones and the snapshot.
+        for (email, record) in records {
+            match record {
+                WalRecord::PutUser(user) => {
+                    stored.insert(email, user);
+                }
+                WalRecord::DeleteUser(_) => {
+                    stored.remove(&email);
+                }
+            }
+        }
+        let needs_migration = stored
+            .iter()
+            .any(|(email, user)| email != user.email.as_ref());
+
+        let mut stored: Vec<(String, User)> = stored.into_iter().collect();
+        stored.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut users: HashMap<Email, (String, User)> = HashMap::new();
+        for (email, user) in stored {
+            if let Some((first, _)) = users.get(&user.email) {
+                return Err(UserStoreWalError::DuplicateEmail {
+                    first: first.clone(),
+                    second: email,
+                });
+            }
+            users.insert(user.email.clone(), (email, user));
+        }
+        let mut users: Vec<User> = users.into_values().map(|(_, user)| user).collect();
+        users.sort_by(|a, b| a.email.as_ref().cmp(b.email.as_ref()));
+
+        let wal = OpenOptions::new().create(true).append(true).open(&wal_path)?;
+        let mut log = Self {
+            dir,
+            wal,
+            records_since_snapshot,
+            snapshot_interval: snapshot_interval.max(1),
+            _lock: lock,
+        };
+        if needs_migration {
+            log.snapshot(users.iter())?;
+        }
+        Ok((log, users))
+    }
+
+    pub fn append(&mut self, user: &User) -> Result<(), UserStoreWalError> {
+        self.append_record(&WalRecord::PutUser(user.clone()))
+    }
+
+    pub fn append_delete(&mut self, email: &Email) -> Result<(), UserStoreWalError> {
+        self.append_record(&WalRecord::DeleteUser(email.clone()))
+    }
+
+    fn append_record(&mut self, record: &WalRecord) -> Result<(), UserStoreWalError> {
+        let payload = serde_json::to_vec(record).expect("records always serialize");
+        self.wal.write_all(&encode_frame(&payload))?;
+        self.wal.sync_data()?;
+        self.records_since_snapshot += 1;
+        Ok(())
+    }
+
+    pub fn needs_snapshot(&self) -> bool {
+        self.records_since_snapshot >= self.snapshot_interval
+    }
+
+    /// Replaces the snapshot with `users` and empties the log.
+    pub fn snapshot<'a>(
+        &mut self,
+        users: impl Iterator<Item = &'a User>,
+    ) -> Result<(), UserStoreWalError> {
+        let snapshot_path = self.dir.join(SNAPSHOT_FILE_NAME);
+        let temp_path = snapshot_path.with_extension("tmp");
+
+        let mut temp = File::create(&temp_path)?;
+        for user in users {
+            let payload = serde_json::to_vec(user).expect("users always serialize");
+            temp.write_all(&encode_frame(&payload))?;
+        }
+        temp.sync_all()?;
+        drop(temp);
+
+        // The rename is atomic, so readers see either the old snapshot or the
+        // complete new one. Replaying the log over the new snapshot is harmless
+        // if we crash before truncating it.
+        fs::rename(&temp_path, &snapshot_path)?;
+        File::open(&self.dir)?.sync_all()?;
+
+        self.wal.set_len(0)?;
+        self.wal.sync_all()?;
+        self.records_since_snapshot = 0;
+        Ok(())
+    }
+}
+
+fn encode_frame(payload: &[u8]) -> Vec<u8> {
+    let len = u32::try_from(payload.len()).expect("records are smaller than 4 GiB");
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + payload.len());
+    frame.extend_from_slice(&len.to_le_bytes());
+    frame.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Frames decoded from a file with their offsets, and where the last
+/// complete frame ends.
+struct Frames<'a> {
+    payloads: Vec<(usize, &'a [u8])>,
+    valid_len: usize,
+}
+
+// A frame that fails its checksum or runs past the end of the file is only
+// accepted as a torn write when it's the last one; anywhere else it's corruption.
+fn decode_frames<'a>(bytes: &'a [u8], path: &Path) -> Result<Frames<'a>, UserStoreWalError> {
+    let mut payloads = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let Some(header) = bytes.get(offset..offset + FRAME_HEADER_LEN) else {
+            break;
+        };
+        let len = u32::from_le_bytes(header[..4].try_into().expect("4 byte slice")) as usize;
+        let checksum = u32::from_le_bytes(header[4..].try_into().expect("4 byte slice"));
+        let end = offset + FRAME_HEADER_LEN + len;
+        let Some(payload) = bytes.get(offset + FRAME_HEADER_LEN..end) else {
+            break;
+        };
+        if crc32fast::hash(payload) != checksum {
+            if end == bytes.len() {
+                break;
+            }
+            return Err(UserStoreWalError::Corrupt {
+                file: path.to_owned(),
+                offset,
+            });
+        }
+        payloads.push((offset, payload));
+        offset = end;
+    }
+    Ok(Frames {
+        payloads,
+        valid_len: offset,
+    })
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, UserStoreWalError> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(bytes),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(
+    payload: &[u8],
+    path: &Path,
+    offset: usize,
+) -> Result<T, UserStoreWalError> {
+    serde_json::from_slice(payload).map_err(|_| UserStoreWalError::Corrupt {
+        file: path.to_owned(),
+        offset,
+    })
+}
+
+// Snapshots are renamed into place once complete, so unlike the log they
+// must never end in a partial frame.
+fn read_snapshot(path: &Path) -> Result<Vec<(String, User)>, UserStoreWalError> {
+    let bytes = read_file(path)?;
+    let frames = decode_frames(&bytes, path)?;
+    if frames.valid_len != bytes.len() {
+        return Err(UserStoreWalError::Corrupt {
+            file: path.to_owned(),
+            offset: frames.valid_len,
+        });
+    }
+    frames
+        .payloads
+        .iter()
+        .map(|(offset, payload)| {
+            let stored: StoredUser = parse(payload, path, *offset)?;
+            Ok((stored.email, parse(payload, path, *offset)?))
+        })
+        .collect()
+}
+
+// Reads the log's records with the emails they were written for, cutting
+// off a torn write at its end.
+fn replay_wal(path: &Path) -> Result<Vec<(String, WalRecord)>, UserStoreWalError> {
+    let bytes = read_file(path)?;
+    let frames = decode_frames(&bytes, path)?;
+    let records = frames
+        .payloads
+        .iter()
+        .map(|(offset, payload)| {
+            let stored: StoredRecord = parse(payload, path, *offset)?;
+            Ok((stored.into_email(), parse(payload, path, *offset)?))
+        })
+        .collect::<Result<Vec<_>, UserStoreWalError>>()?;
+
+    if frames.valid_len < bytes.len() {
+        let wal = OpenOptions::new().write(true).open(path)?;
+        wal.set_len(frames.valid_len as u64)?;
+        wal.sync_all()?;
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("user-store-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn user(email: &str) -> User {
+        User::new(email.to_owned(), "passworD123!".to_owned(), false)
+    }
+
+    fn emails(users: &[User]) -> Vec<&str> {
+        users.iter().map(|user| user.email.as_ref()).collect()
+    }
+
+    // Appends a record for `email` spelled as given, like one written before
+    // emails were normalized.
+    fn append_as_stored(dir: &Path, email: &str) {
+        let mut record = serde_json::to_value(WalRecord::PutUser(user("a@example.com"))).unwrap();
+        record["PutUser"]["email"] = email.into();
+        let payload = serde_json::to_vec(&record).unwrap();
+        let mut wal = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(WAL_FILE_NAME))
+            .unwrap();
+        wal.write_all(&encode_frame(&payload)).unwrap();
+    }
+
+    #[test]
+    fn test_replays_appended_records() {
+        let dir = temp_dir();
+        let (mut wal, users) = UserStoreWal::open(&dir, 100).unwrap();
+        assert!(users.is_empty());
+        wal.append(&user("a@example.com")).unwrap();
+        wal.append(&user("b@example.com")).unwrap();
+        drop(wal);
+
+        let (wal, users) = UserStoreWal::open(&dir, 100).unwrap();
+        assert_eq!(emails(&users), ["a@example.com", "b@example.com"]);
+        assert_eq!(wal.records_since_snapshot, 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_torn_tail_is_truncated() {
+        let dir = temp_dir();
+        let (mut wal, _) = UserStoreWal::open(&dir, 100).unwrap();
+        wal.append(&user("a@example.com")).unwrap();
+        wal.append(&user("b@example.com")).unwrap();
+        drop(wal);
+
+        let wal_path = dir.join(WAL_FILE_NAME);
+        let full_len = fs::metadata(&wal_path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&wal_path).unwrap();
+        file.set_len(full_len - 5).unwrap();
+        drop(file);
+
+        let (mut wal, users) = UserStoreWal::open(&dir, 100).unwrap();
+        assert_eq!(emails(&users), ["a@example.com"]);
+
+        // Appending after the truncation leaves a clean log.
+        wal.append(&user("c@example.com")).unwrap();
+        drop(wal);
+        let (_, users) = UserStoreWal::open(&dir, 100).unwrap();
+        assert_eq!(emails(&users), ["a@example.com", "c@example.com"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_corruption_before_the_tail_is_an_error() {
+        let dir = temp_dir();
+        let (mut wal, _) = UserStoreWal::open(&dir, 100).unwrap();
+        wal.append(&user("a@example.com")).unwrap();
+        wal.append(&user("b@example.com")).unwrap();
+        drop(wal);
+
+        let wal_path = dir.join(WAL_FILE_NAME);
+        let mut bytes = fs::read(&wal_path).unwrap();
+        bytes[FRAME_HEADER_LEN + 2] ^= 0xff;
+        fs::write(&wal_path, bytes).unwrap();
+
+        assert!(matches!(
+            UserStoreWal::open(&dir, 100),
+            Err(UserStoreWalError::Corrupt { offset: 0, .. })
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_deleted_users_are_not_recovered() {
+        let dir = temp_dir();
+        let (mut wal, _) = UserStoreWal::open(&dir, 2).unwrap();
+        let users = [user("a@example.com"), user("b@example.com")];
+        for user in &users {
+            wal.append(user).unwrap();
+        }
+        wal.snapshot(users.iter()).unwrap();
+        wal.append_delete(&users[0].email).unwrap();
+        drop(wal);
+
+        let (_, users) = UserStoreWal::open(&dir, 2).unwrap();
+        assert_eq!(emails(&users), ["b@example.com"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_users_whose_emails_now_collide_are_an_error() {
+        let dir = temp_dir();
+        fs::create_dir_all(&dir).unwrap();
+        append_as_stored(&dir, "Alice@example.com");
+        append_as_stored(&dir, "alice@example.com");
+
+        match UserStoreWal::open(&dir, 100) {
+            Err(UserStoreWalError::DuplicateEmail { first, second }) => {
+                assert_eq!(first, "Alice@example.com");
+                assert_eq!(second, "alice@example.com");
+            }
+            _ => panic!("expected a duplicate email error"),
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_users_stored_before_normalization_are_migrated() {
+        let dir = temp_dir();
+        fs::create_dir_all(&dir).unwrap();
+        append_as_stored(&dir, "Alice@example.com");
+
+        let (mut wal, users) = UserStoreWal::open(&dir, 100).unwrap();
+        assert_eq!(emails(&users), ["alice@example.com"]);
+        assert_eq!(fs::metadata(dir.join(WAL_FILE_NAME)).unwrap().len(), 0);
+
+        // Later changes to the user no longer look like a second one.
+        wal.append(&users[0]).unwrap();
+        drop(wal);
+        let (_, users) = UserStoreWal::open(&dir, 100).unwrap();
+        assert_eq!(emails(&users), ["alice@example.com"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_only_one_log_can_be_open_at_a_time() {
+        let dir = temp_dir();
+        let (wal, _) = UserStoreWal::open(&dir, 100).unwrap();
+        assert!(matches!(
+            UserStoreWal::open(&dir, 100),
+            Err(UserStoreWalError::Locked)
+        ));
+
+        drop(wal);
+        assert!(UserStoreWal::open(&dir, 100).is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_empties_the_log() {
+        let dir = temp_dir();
+        let (mut wal, _) = UserStoreWal::open(&dir, 2).unwrap();
+        let users = [user("a@example.com"), user("b@example.com")];
+        for user in &users {
+            wal.append(user).unwrap();
+        }
+        assert!(wal.needs_snapshot());
+        wal.snapshot(users.iter()).unwrap();
+        assert!(!wal.needs_snapshot());
+        assert_eq!(fs::metadata(dir.join(WAL_FILE_NAME)).unwrap().len(), 0);
+
+        wal.append(&user("c@example.com")).unwrap();
+        drop(wal);
+        let (wal, users) = UserStoreWal::open(&dir, 2).unwrap();
+        assert_eq!(emails(&users), ["a@example.com", "b@example.com", "c@example.com"]);
+        assert_eq!(wal.records_since_snapshot, 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,15 @@
+use crate::app_state::AppState;
+use crate::domain::AuditEvent;
+use chrono::Utc;
+
+// Append an event to the audit log. A failed write is reported but doesn't
+// fail the request, so an unavailable log can't lock everyone out.
+pub async fn record_audit_event(state: &AppState, event: AuditEvent) {
+    let result = state
+        .audit_sink
+        .record(event, Utc::now().timestamp())
+        .await;
+    if let Err(e) = result {
+        eprintln!("failed to record audit event: {:?}", e);
+    }
+}
@@ -0,0 +1,765 @@
+use crate::app_state::{AppState, KeyRingType};
+use crate::domain::{
+    AuthAPIError, BrowserBinding, ClientInfo, Email, KeyRing, KeyRotationPolicy, MagicLinkId,
+    ServiceAccount, Session, SessionId, SigningAlgorithm, SigningKey, User,
+};
+use crate::utils::constants::{
+    JWT_COOKIE_NAME, JWT_KEY_ROTATION_INTERVAL_SECONDS, JWT_SIGNING_ALGORITHM,
+    KEY_PUBLISH_AHEAD_SECONDS, KEY_ROTATION_CHECK_SECONDS, OIDC_ISSUER,
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use chrono::Utc;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+// This value determines how long the JWT auth token is valid for
+pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
+// Keeps sign-in link tokens from being mistaken for anything else signed by the key ring.
+const MAGIC_LINK_AUDIENCE: &str = "magic-link";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    /// The user's email, or the client id of a service account.
+    pub sub: String,
+    /// Id of the session the token was issued to. Service account tokens
+    /// have no session.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Effective permissions, including those implied by `roles`.
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    pub exp: usize,
+    #[serde(default)]
+    pub iat: usize,
+    /// The OpenID Connect client or service account an access token was
+    /// issued to; absent on the tokens of browser sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// Space separated scopes granted to an access token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+impl Claims {
+    pub fn session_id(&self) -> Option<SessionId> {
+        self.sid.clone().and_then(|sid| SessionId::parse(sid).ok())
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|r| r == role)
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+}
+
+/// Claims of the OpenID Connect ID token issued by the token endpoint.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    pub email: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+/// Claims of the token in an emailed sign-in link.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLinkClaims {
+    pub sub: String,
+    /// Id of the link, so that it can only be followed once.
+    pub jti: String,
+    pub aud: String,
+    pub exp: usize,
+    /// Digest of the browser binding, for links that only work in the
+    /// browser they were requested from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bnd: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum GenerateTokenError {
+    TokenError(jsonwebtoken::errors::Error),
+    UnexpectedError,
+}
+
+// Create cookie with a new JWT auth token
+pub fn generate_auth_cookie(
+    user: &User,
+    session_id: &SessionId,
+    key_ring: &KeyRing,
+) -> Result<Cookie<'static>, GenerateTokenError> {
+    let token = generate_auth_token(user, session_id, key_ring)?;
+    Ok(create_auth_cookie(token))
+}
+
+fn create_auth_cookie(token: String) -> Cookie<'static> {
+    Cookie::build((JWT_COOKIE_NAME, token))
+        .path("/") // apply cookie to all URLs on the server
+        .http_only(true) // prevent JavaScript from accessing the cookie
+        .same_site(SameSite::Lax) // send cookie with "same-site" requests, and with "cross-site" top-level navigations.
+        .build()
+}
+
+pub fn generate_auth_token(
+    user: &User,
+    session_id: &SessionId,
+    key_ring: &KeyRing,
+) -> Result<String, GenerateTokenError> {
+    encode_token(&session_claims(user, session_id)?, key_ring)
+}
+
+// Tied to the user's session like the auth token, so `/verify-token` accepts
+// both, but carries the client and scopes it was granted to instead of the
+// user's roles and permissions. Cookie authentication refuses it.
+pub fn generate_access_token(
+    user: &User,
+    session_id: &SessionId,
+    client_id: &str,
+    scope: &str,
+    key_ring: &KeyRing,
+) -> Result<String, GenerateTokenError> {
+    let claims = Claims {
+        roles: Vec::new(),
+        permissions: Vec::new(),
+        client_id: Some(client_id.to_owned()),
+        scope: Some(scope.to_owned()),
+        ..session_claims(user, session_id)?
+    };
+    encode_token(&claims, key_ring)
+}
+
+// Issued by the client credentials grant, for the scopes granted to the account.
+pub fn generate_service_account_token(
+    account: &ServiceAccount,
+    scope: &str,
+    key_ring: &KeyRing,
+) -> Result<String, GenerateTokenError> {
+    let claims = Claims {
+        sub: account.client_id.clone(),
+        sid: None,
+        roles: Vec::new(),
+        permissions: Vec::new(),
+        exp: expiry_timestamp()?,
+        iat: issued_at_timestamp()?,
+        client_id: Some(account.client_id.clone()),
+        scope: Some(scope.to_owned()),
+    };
+    encode_token(&claims, key_ring)
+}
+
+fn session_claims(user: &User, session_id: &SessionId) -> Result<Claims, GenerateTokenError> {
+    Ok(Claims {
+        sub: user.email.as_ref().to_owned(),
+        sid: Some(session_id.as_ref().to_owned()),
+        roles: user.roles.iter().map(|role| role.as_ref().to_owned()).collect(),
+        permissions: user
+            .effective_permissions()
+            .iter()
+            .map(|permission| permission.as_ref().to_owned())
+            .collect(),
+        exp: expiry_timestamp()?,
+        iat: issued_at_timestamp()?,
+        client_id: None,
+        scope: None,
+    })
+}
+
+pub fn generate_id_token(
+    email: &Email,
+    client_id: &str,
+    nonce: Option<String>,
+    key_ring: &KeyRing,
+) -> Result<String, GenerateTokenError> {
+    let claims = IdTokenClaims {
+        iss: OIDC_ISSUER.to_owned(),
+        sub: email.as_ref().to_owned(),
+        aud: client_id.to_owned(),
+        exp: expiry_timestamp()?,
+        iat: issued_at_timestamp()?,
+        email: email.as_ref().to_owned(),
+        nonce,
+    };
+
+    encode_token(&claims, key_ring)
+}
+
+pub fn generate_magic_link_token(
+    email: &Email,
+    id: &MagicLinkId,
+    binding: Option<&BrowserBinding>,
+    expires_at: i64,
+    key_ring: &KeyRing,
+) -> Result<String, GenerateTokenError> {
+    let claims = MagicLinkClaims {
+        sub: email.as_ref().to_owned(),
+        jti: id.as_ref().to_owned(),
+        aud: MAGIC_LINK_AUDIENCE.to_owned(),
+        exp: expires_at
+            .try_into()
+            .map_err(|_| GenerateTokenError::UnexpectedError)?,
+        bnd: binding.map(BrowserBinding::digest),
+    };
+
+    encode_token(&claims, key_ring)
+}
+
+fn issued_at_timestamp() -> Result<usize, GenerateTokenError> {
+    Utc::now()
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)
+}
+
+fn expiry_timestamp() -> Result<usize, GenerateTokenError> {
+    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
+        .ok_or(GenerateTokenError::UnexpectedError)?;
+
+    let exp = Utc::now()
+        .checked_add_signed(delta)
+        .ok_or(GenerateTokenError::UnexpectedError)?
+        .timestamp();
+
+    exp.try_into().map_err(|_| GenerateTokenError::UnexpectedError)
+}
+
+// Sign with the key ring's current key, naming it in the header so
+// verifiers can pick the matching key from the JWKS.
+fn encode_token<T: Serialize>(claims: &T, key_ring: &KeyRing) -> Result<String, GenerateTokenError> {
+    let key = key_ring
+        .signing_key(Utc::now().timestamp())
+        .ok_or(GenerateTokenError::UnexpectedError)?;
+
+    let mut header = Header::new(key.algorithm.jwt_algorithm());
+    header.kid = Some(key.kid.clone());
+
+    encode(&header, claims, key.encoding_key()).map_err(GenerateTokenError::TokenError)
+}
+
+// Check if JWT auth token is valid by verifying it against the key named in its header
+pub fn validate_token(token: &str, key_ring: &KeyRing) -> Result<Claims, jsonwebtoken::errors::Error> {
+    decode_token(token, key_ring, None)
+}
+
+pub fn validate_magic_link_token(
+    token: &str,
+    key_ring: &KeyRing,
+) -> Result<MagicLinkClaims, jsonwebtoken::errors::Error> {
+    decode_token(token, key_ring, Some(MAGIC_LINK_AUDIENCE))
+}
+
+fn decode_token<T: DeserializeOwned>(
+    token: &str,
+    key_ring: &KeyRing,
+    audience: Option<&str>,
+) -> Result<T, jsonwebtoken::errors::Error> {
+    let kid = decode_header(token)?.kid.ok_or(ErrorKind::InvalidToken)?;
+    let key = key_ring
+        .verification_key(&kid, Utc::now().timestamp())
+        .ok_or(ErrorKind::InvalidToken)?;
+
+    // The algorithm comes from the key, never from the token header.
+    let mut validation = Validation::new(key.algorithm.jwt_algorithm());
+    if let Some(audience) = audience {
+        validation.set_audience(&[audience]);
+    }
+    decode::<T>(token, key.decoding_key(), &validation).map(|data| data.claims)
+}
+
+// Record a new session for the user, lasting as long as the token issued for it
+pub async fn create_session(
+    state: &AppState,
+    email: &Email,
+    client: ClientInfo,
+) -> Result<SessionId, AuthAPIError> {
+    let now = Utc::now().timestamp();
+    let session = Session::new(email.clone(), client, now, TOKEN_TTL_SECONDS);
+    let session_id = session.id.clone();
+
+    let session_store = &state.session_store;
+    session_store
+        .remove_expired_sessions(now)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    session_store
+        .add_session(session)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(session_id)
+}
+
+// Check that the user may sign in, whichever method they authenticated with
+pub fn check_can_sign_in(user: &User) -> Result<(), AuthAPIError> {
+    if user.disabled {
+        Err(AuthAPIError::AccountDisabled)
+    } else if user.password_reset_required {
+        Err(AuthAPIError::PasswordResetRequired)
+    } else {
+        Ok(())
+    }
+}
+
+// Start a session and create the auth cookie that carries it
+pub async fn start_session(
+    state: &AppState,
+    user: &User,
+    client: ClientInfo,
+) -> Result<Cookie<'static>, AuthAPIError> {
+    check_can_sign_in(user)?;
+    let session_id = create_session(state, &user.email, client).await?;
+    generate_auth_cookie(user, &session_id, &*state.key_ring.read().await)
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// Validate a browser session's token and check that its session hasn't been
+// revoked. Tokens issued to a client are refused.
+pub async fn validate_session_token(token: &str, state: &AppState) -> Result<Claims, AuthAPIError> {
+    validate_session(token, state).await.map(|(claims, _)| claims)
+}
+
+// Like `validate_session_token`, also returning the session as it was
+// before this use of the token was recorded.
+pub async fn validate_session(
+    token: &str,
+    state: &AppState,
+) -> Result<(Claims, Session), AuthAPIError> {
+    let claims = validate_token(token, &*state.key_ring.read().await)
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    if claims.client_id.is_some() {
+        return Err(AuthAPIError::InvalidToken);
+    }
+    let session = check_session(&claims, state).await?;
+    Ok((claims, session))
+}
+
+// Validate a user's or a service account's token, for the routes other
+// services check the tokens they are sent with. Service account tokens are
+// revoked by deleting the account or rotating its secret.
+pub async fn validate_access_token(
+    token: &str,
+    state: &AppState,
+) -> Result<(Claims, Option<Session>), AuthAPIError> {
+    let claims = validate_token(token, &*state.key_ring.read().await)
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    if claims.sid.is_some() {
+        let session = check_session(&claims, state).await?;
+        return Ok((claims, Some(session)));
+    }
+
+    let account = state
+        .service_account_store
+        .get_account(&claims.sub)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    if claims.client_id.as_deref() != Some(account.client_id.as_str())
+        || !account.accepts_token_issued_at(claims.iat)
+    {
+        return Err(AuthAPIError::InvalidToken);
+    }
+    Ok((claims, None))
+}
+
+async fn check_session(claims: &Claims, state: &AppState) -> Result<Session, AuthAPIError> {
+    let session_id = claims.session_id().ok_or(AuthAPIError::InvalidToken)?;
+
+    let now = Utc::now().timestamp();
+    let session_store = &state.session_store;
+    let session = match session_store.get_session(&session_id).await {
+        Ok(session) if session.email.as_ref() == claims.sub && !session.is_expired(now) => session,
+        _ => return Err(AuthAPIError::InvalidToken),
+    };
+    session_store
+        .touch_session(&session_id, now)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(session)
+}
+
+// Resolve the claims of the request's JWT cookie
+pub async fn authenticated_session(
+    jar: &CookieJar,
+    state: &AppState,
+) -> Result<Claims, AuthAPIError> {
+    let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+    validate_session_token(cookie.value(), state).await
+}
+
+// Resolve the email of the user the request's JWT cookie was issued to
+pub async fn authenticated_email(jar: &CookieJar, state: &AppState) -> Result<Email, AuthAPIError> {
+    let claims = authenticated_session(jar, state).await?;
+    Email::parse(&claims.sub).map_err(|_| AuthAPIError::InvalidToken)
+}
+
+/// Builds the key ring for the configured algorithm and rotation schedule.
+/// With a `path`, keys are loaded from that file, which is created if it
+/// doesn't exist yet; without one they only live in memory.
+pub fn open_key_ring(path: Option<&str>, now: i64) -> Result<KeyRing, String> {
+    let algorithm = SigningAlgorithm::parse(&JWT_SIGNING_ALGORITHM)?;
+    let policy = KeyRotationPolicy {
+        rotation_interval: *JWT_KEY_ROTATION_INTERVAL_SECONDS,
+        publish_ahead: KEY_PUBLISH_AHEAD_SECONDS,
+        // Tokens signed just before a key is superseded stay valid this long.
+        retired_key_ttl: TOKEN_TTL_SECONDS,
+    };
+    match path {
+        Some(path) => KeyRing::open(path, algorithm, policy, now),
+        None => KeyRing::new(algorithm, policy, now),
+    }
+}
+
+// Runs for the lifetime of the server, advancing the key rotation schedule.
+pub async fn rotate_signing_keys(key_ring: KeyRingType) {
+    let mut interval =
+        tokio::time::interval(std::time::Duration::from_secs(KEY_ROTATION_CHECK_SECONDS));
+    loop {
+        interval.tick().await;
+        let now = Utc::now().timestamp();
+        let due = {
+            let key_ring = key_ring.read().await;
+            key_ring
+                .successor_due(now)
+                .map(|activates_at| (key_ring.algorithm(), activates_at))
+        };
+        // Tokens are signed and verified under the key ring's lock, so the
+        // successor is generated, slowly for RSA, before taking it.
+        let successor = match due {
+            Some((algorithm, activates_at)) => {
+                generate_signing_key(algorithm, activates_at).await.map(Some)
+            }
+            None => Ok(None),
+        };
+        let result = match successor {
+            Ok(successor) => key_ring.write().await.rotate_with(now, successor),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            eprintln!("failed to rotate signing keys: {}", e);
+        }
+    }
+}
+
+/// Generates a signing key on a blocking thread, as RSA keys take a while.
+pub async fn generate_signing_key(
+    algorithm: SigningAlgorithm,
+    activates_at: i64,
+) -> Result<SigningKey, String> {
+    tokio::task::spawn_blocking(move || SigningKey::generate(algorithm, activates_at))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        KeyRotationPolicy, Role, SigningAlgorithm, ADMIN_ROLE, USERS_WRITE_PERMISSION,
+    };
+    use crate::services::{
+        HashmapAuthorizationCodeStore, HashmapMagicLinkStore, HashmapOidcClientStore,
+        HashmapServiceAccountStore, HashmapSessionStore, HashmapTwoFACodeStore, HashmapUserStore,
+        HashmapWebAuthnChallengeStore, HibpPasswordList, JsonlAuditSink, MockEmailClient,
+    };
+    use jsonwebtoken::EncodingKey;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+
+    fn key_ring() -> KeyRing {
+        let policy = KeyRotationPolicy {
+            rotation_interval: 1000,
+            publish_ahead: 100,
+            retired_key_ttl: 50,
+        };
+        KeyRing::new(SigningAlgorithm::EdDSA, policy, Utc::now().timestamp()).unwrap()
+    }
+
+    fn user() -> User {
+        User::new("test@example.com".to_owned(), "passworD123!".to_owned(), false)
+    }
+
+    fn app_state() -> AppState {
+        AppState::new(
+            Arc::new(HashmapUserStore::new()),
+            Arc::new(HashmapTwoFACodeStore::new()),
+            Arc::new(HashmapWebAuthnChallengeStore::new()),
+            Arc::new(HashmapOidcClientStore::new()),
+            Arc::new(HashmapServiceAccountStore::new()),
+            Arc::new(HashmapAuthorizationCodeStore::new()),
+            Arc::new(HashmapMagicLinkStore::new()),
+            Arc::new(HashmapSessionStore::new()),
+            Arc::new(
+                JsonlAuditSink::open(
+                    std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4())),
+                )
+                .unwrap(),
+            ),
+            Arc::new(RwLock::new(key_ring())),
+            Arc::new(MockEmailClient),
+            Arc::new(HibpPasswordList::empty()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_generate_auth_cookie() {
+        let cookie = generate_auth_cookie(&user(), &SessionId::default(), &key_ring()).unwrap();
+        assert_eq!(cookie.name(), JWT_COOKIE_NAME);
+        assert_eq!(cookie.value().split('.').count(), 3);
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_valid_token() {
+        let key_ring = key_ring();
+        let token = generate_auth_token(&user(), &SessionId::default(), &key_ring).unwrap();
+        let result = validate_token(&token, &key_ring).unwrap();
+        assert_eq!(result.sub, "test@example.com");
+
+        let exp = Utc::now()
+            .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
+            .expect("valid timestamp")
+            .timestamp();
+
+        assert!(result.exp > exp as usize);
+    }
+
+    #[tokio::test]
+    async fn test_token_header_names_signing_key() {
+        let key_ring = key_ring();
+        let token = generate_auth_token(&user(), &SessionId::default(), &key_ring).unwrap();
+
+        let header = decode_header(&token).unwrap();
+        let key = key_ring.signing_key(Utc::now().timestamp()).unwrap();
+        assert_eq!(header.kid.as_ref(), Some(&key.kid));
+        assert_eq!(header.alg, jsonwebtoken::Algorithm::EdDSA);
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_with_invalid_token() {
+        let token = "invalid_token".to_owned();
+        let result = validate_token(&token, &key_ring());
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_from_other_key_ring() {
+        let token = generate_auth_token(&user(), &SessionId::default(), &key_ring()).unwrap();
+        assert!(validate_token(&token, &key_ring()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejects_hmac_token_with_known_kid() {
+        let key_ring = key_ring();
+        let key = key_ring.signing_key(Utc::now().timestamp()).unwrap();
+        let claims = Claims {
+            sub: "test@example.com".to_owned(),
+            sid: Some(SessionId::default().as_ref().to_owned()),
+            roles: Vec::new(),
+            permissions: Vec::new(),
+            exp: expiry_timestamp().unwrap(),
+            iat: issued_at_timestamp().unwrap(),
+            client_id: None,
+            scope: None,
+        };
+        let mut header = Header::new(jsonwebtoken::Algorithm::HS256);
+        header.kid = Some(key.kid.clone());
+        let token = encode(&header, &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+
+        assert!(validate_token(&token, &key_ring).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_id_token() {
+        let key_ring = key_ring();
+        let email = Email::parse("test@example.com").unwrap();
+        let token = generate_id_token(&email, "app", Some("nonce".to_owned()), &key_ring).unwrap();
+
+        let key = key_ring.signing_key(Utc::now().timestamp()).unwrap();
+        let mut validation = Validation::new(key.algorithm.jwt_algorithm());
+        validation.set_audience(&["app"]);
+        validation.set_issuer(&[OIDC_ISSUER.as_str()]);
+        let claims = decode::<IdTokenClaims>(&token, key.decoding_key(), &validation)
+            .unwrap()
+            .claims;
+
+        assert_eq!(claims.sub, "test@example.com");
+        assert_eq!(claims.email, "test@example.com");
+        assert_eq!(claims.nonce.as_deref(), Some("nonce"));
+        assert!(claims.exp > claims.iat);
+    }
+
+    #[tokio::test]
+    async fn test_authenticated_email() {
+        let state = app_state();
+        let email = Email::parse("test@example.com").unwrap();
+        let cookie = start_session(&state, &user(), ClientInfo::default()).await.unwrap();
+        let jar = CookieJar::new().add(cookie);
+        assert_eq!(authenticated_email(&jar, &state).await.ok(), Some(email));
+
+        let jar = CookieJar::new();
+        assert!(matches!(
+            authenticated_email(&jar, &state).await,
+            Err(AuthAPIError::MissingToken)
+        ));
+
+        let jar = CookieJar::new().add(Cookie::new(JWT_COOKIE_NAME, "invalid"));
+        assert!(matches!(
+            authenticated_email(&jar, &state).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_session_token_rejects_revoked_session() {
+        let state = app_state();
+        let email = Email::parse("test@example.com").unwrap();
+        let session_id = create_session(&state, &email, ClientInfo::default()).await.unwrap();
+        let token =
+            generate_auth_token(&user(), &session_id, &*state.key_ring.read().await).unwrap();
+        assert!(validate_session_token(&token, &state).await.is_ok());
+
+        state
+            .session_store
+            .remove_session(&session_id)
+            .await
+            .unwrap();
+        assert!(matches!(
+            validate_session_token(&token, &state).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_validate_session_token_rejects_unknown_session() {
+        let state = app_state();
+        let token =
+            generate_auth_token(&user(), &SessionId::default(), &*state.key_ring.read().await)
+                .unwrap();
+        assert!(matches!(
+            validate_session_token(&token, &state).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_token_carries_roles_and_permissions() {
+        let key_ring = key_ring();
+        let mut user = user();
+        user.roles = vec![Role::admin()];
+        let token = generate_auth_token(&user, &SessionId::default(), &key_ring).unwrap();
+
+        let claims = validate_token(&token, &key_ring).unwrap();
+        assert!(claims.has_role(ADMIN_ROLE));
+        assert!(claims.has_permission(USERS_WRITE_PERMISSION));
+        assert!(!claims.has_permission("reports:export"));
+    }
+
+    #[tokio::test]
+    async fn test_access_token_carries_client_and_scope() {
+        let key_ring = key_ring();
+        let token =
+            generate_access_token(&user(), &SessionId::default(), "app", "openid email", &key_ring)
+                .unwrap();
+
+        let claims = validate_token(&token, &key_ring).unwrap();
+        assert_eq!(claims.client_id.as_deref(), Some("app"));
+        assert_eq!(claims.scope.as_deref(), Some("openid email"));
+        assert!(claims.exp > claims.iat);
+        assert!(claims.roles.is_empty());
+        assert!(claims.permissions.is_empty());
+
+        let token = generate_auth_token(&user(), &SessionId::default(), &key_ring).unwrap();
+        let claims = validate_token(&token, &key_ring).unwrap();
+        assert_eq!(claims.client_id, None);
+        assert_eq!(claims.scope, None);
+    }
+
+    #[tokio::test]
+    async fn test_validate_session_token_rejects_access_tokens() {
+        let state = app_state();
+        let user = user();
+        let session_id = create_session(&state, &user.email, ClientInfo::default())
+            .await
+            .unwrap();
+        let token = generate_access_token(
+            &user,
+            &session_id,
+            "app",
+            "openid",
+            &*state.key_ring.read().await,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            validate_session_token(&token, &state).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+        assert!(validate_access_token(&token, &state).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_validate_access_token_accepts_service_account_tokens() {
+        let state = app_state();
+        let (mut account, _) =
+            ServiceAccount::new("Batch".to_owned(), vec!["reports:read".to_owned()], 0);
+        state.service_account_store.add_account(account.clone()).await.unwrap();
+        let token = generate_service_account_token(
+            &account,
+            "reports:read",
+            &*state.key_ring.read().await,
+        )
+        .unwrap();
+
+        let (claims, session) = validate_access_token(&token, &state).await.unwrap();
+        assert_eq!(claims.sub, account.client_id);
+        assert_eq!(claims.scope.as_deref(), Some("reports:read"));
+        assert!(session.is_none());
+        // They can't be used where a user's session is needed.
+        assert!(matches!(
+            validate_session_token(&token, &state).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+
+        account.rotate_secret(Utc::now().timestamp() + 1);
+        state.service_account_store.update_account(account.clone()).await.unwrap();
+        assert!(matches!(
+            validate_access_token(&token, &state).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+
+        state.service_account_store.delete_account(&account.client_id).await.unwrap();
+        assert!(matches!(
+            validate_access_token(&token, &state).await,
+            Err(AuthAPIError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_start_session_rejects_users_who_cannot_sign_in() {
+        let state = app_state();
+
+        let mut disabled = user();
+        disabled.disabled = true;
+        assert!(matches!(
+            start_session(&state, &disabled, ClientInfo::default()).await,
+            Err(AuthAPIError::AccountDisabled)
+        ));
+
+        let mut reset_required = user();
+        reset_required.password_reset_required = true;
+        assert!(matches!(
+            start_session(&state, &reset_required, ClientInfo::default()).await,
+            Err(AuthAPIError::PasswordResetRequired)
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::domain::{EmailPolicy, PasswordPolicy};
+use dotenvy::dotenv;
+use lazy_static::lazy_static;
+use std::env as std_env;
+
+lazy_static! {
+    pub static ref JWT_SIGNING_ALGORITHM: String =
+        env_or_default(env::JWT_SIGNING_ALGORITHM_ENV_VAR, "EdDSA");
+    pub static ref JWT_KEY_ROTATION_INTERVAL_SECONDS: i64 =
+        env_or_default(env::JWT_KEY_ROTATION_INTERVAL_ENV_VAR, "86400")
+            .parse()
+            .expect("JWT_KEY_ROTATION_INTERVAL_SECONDS must be a number of seconds.");
+    // Signing keys are only kept in memory unless this is set.
+    pub static ref JWT_KEYS_PATH: Option<String> = env_optional(env::JWT_KEYS_PATH_ENV_VAR);
+    pub static ref WEBAUTHN_RP_ID: String = env_or_default(env::WEBAUTHN_RP_ID_ENV_VAR, "localhost");
+    pub static ref WEBAUTHN_ORIGIN: String =
+        env_or_default(env::WEBAUTHN_ORIGIN_ENV_VAR, "http://localhost:3000");
+    pub static ref OIDC_ISSUER: String =
+        env_or_default(env::OIDC_ISSUER_ENV_VAR, "http://localhost:3000");
+    // Page that sign-in links point at; it hands the link's token back to the service.
+    pub static ref MAGIC_LINK_URL: String =
+        env_or_default(env::MAGIC_LINK_URL_ENV_VAR, "http://localhost:3000/");
+    // The gRPC API has no authentication of its own, so this should only be
+    // reachable from inside the cluster.
+    pub static ref GRPC_ADDRESS: String = env_or_default(env::GRPC_ADDRESS_ENV_VAR, "0.0.0.0:50051");
+    pub static ref AUDIT_LOG_PATH: String =
+        env_or_default(env::AUDIT_LOG_PATH_ENV_VAR, "audit.jsonl");
+    // Users are only kept in memory unless this is set.
+    pub static ref USER_STORE_DIR: Option<String> = env_optional(env::USER_STORE_DIR_ENV_VAR);
+    // Passwords are only checked against a breach dataset if this is set.
+    pub static ref BREACHED_PASSWORDS_PATH: Option<String> =
+        env_optional(env::BREACHED_PASSWORDS_PATH_ENV_VAR);
+    pub static ref USER_STORE_SNAPSHOT_INTERVAL: usize =
+        env_or_default(env::USER_STORE_SNAPSHOT_INTERVAL_ENV_VAR, "1000")
+            .parse()
+            .expect("USER_STORE_SNAPSHOT_INTERVAL must be a number of changes.");
+    pub static ref PASSWORD_POLICY: PasswordPolicy = password_policy();
+    pub static ref EMAIL_POLICY: EmailPolicy = email_policy();
+}
+
+fn password_policy() -> PasswordPolicy {
+    let default = PasswordPolicy::default();
+    let policy = PasswordPolicy {
+        min_length: env_optional(env::PASSWORD_MIN_LENGTH_ENV_VAR)
+            .map_or(default.min_length, |value| {
+                value.parse().expect("PASSWORD_MIN_LENGTH must be a number of characters.")
+            }),
+        max_length: env_optional(env::PASSWORD_MAX_LENGTH_ENV_VAR)
+            .map_or(default.max_length, |value| {
+                value.parse().expect("PASSWORD_MAX_LENGTH must be a number of characters.")
+            }),
+        composition_rules: env_optional(env::PASSWORD_COMPOSITION_RULES_ENV_VAR)
+            .map_or(default.composition_rules, |value| {
+                value.parse().expect("PASSWORD_COMPOSITION_RULES must be true or false.")
+            }),
+        min_strength: env_optional(env::PASSWORD_MIN_STRENGTH_ENV_VAR)
+            .map_or(default.min_strength, |value| {
+                value.parse().expect("PASSWORD_MIN_STRENGTH must be a score from 0 to 4.")
+            }),
+    };
+    assert!(
+        (1..=policy.max_length).contains(&policy.min_length),
+        "PASSWORD_MIN_LENGTH must be at least 1 and at most PASSWORD_MAX_LENGTH."
+    );
+    assert!(policy.min_strength <= 4, "PASSWORD_MIN_STRENGTH must be a score from 0 to 4.");
+    policy
+}
+
+fn email_policy() -> EmailPolicy {
+    let default = EmailPolicy::default();
+    let domains = |name: &str| {
+        EmailPolicy::parse_domains(&env_or_default(name, ""))
+            .unwrap_or_else(|e| panic!("{} must be a comma-separated list of domains: {}", name, e))
+    };
+    EmailPolicy {
+        fold_local_part_case: env_optional(env::EMAIL_FOLD_LOCAL_PART_CASE_ENV_VAR)
+            .map_or(default.fold_local_part_case, |value| {
+                value.parse().expect("EMAIL_FOLD_LOCAL_PART_CASE must be true or false.")
+            }),
+        allowed_domains: domains(env::EMAIL_ALLOWED_DOMAINS_ENV_VAR),
+        denied_domains: domains(env::EMAIL_DENIED_DOMAINS_ENV_VAR),
+        block_disposable: env_optional(env::EMAIL_BLOCK_DISPOSABLE_ENV_VAR)
+            .map_or(default.block_disposable, |value| {
+                value.parse().expect("EMAIL_BLOCK_DISPOSABLE must be true or false.")
+            }),
+    }
+}
+
+fn env_or_default(name: &str, default: &str) -> String {
+    env_optional(name).unwrap_or_else(|| default.to_owned())
+}
+
+fn env_optional(name: &str) -> Option<String> {
+    dotenv().ok();
+    std_env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+pub mod env {
+    pub const JWT_SIGNING_ALGORITHM_ENV_VAR: &str = "JWT_SIGNING_ALGORITHM";
+    pub const JWT_KEY_ROTATION_INTERVAL_ENV_VAR: &str = "JWT_KEY_ROTATION_INTERVAL_SECONDS";
+    pub const JWT_KEYS_PATH_ENV_VAR: &str = "JWT_KEYS_PATH";
+    pub const WEBAUTHN_RP_ID_ENV_VAR: &str = "WEBAUTHN_RP_ID";
+    pub const WEBAUTHN_ORIGIN_ENV_VAR: &str = "WEBAUTHN_ORIGIN";
+    pub const OIDC_ISSUER_ENV_VAR: &str = "OIDC_ISSUER";
+    pub const OIDC_CLIENTS_FILE_ENV_VAR: &str = "OIDC_CLIENTS_FILE";
+    pub const MAGIC_LINK_URL_ENV_VAR: &str = "MAGIC_LINK_URL";
+    pub const GRPC_ADDRESS_ENV_VAR: &str = "GRPC_ADDRESS";
+    pub const AUDIT_LOG_PATH_ENV_VAR: &str = "AUDIT_LOG_PATH";
+    pub const USER_STORE_DIR_ENV_VAR: &str = "USER_STORE_DIR";
+    pub const USER_STORE_SNAPSHOT_INTERVAL_ENV_VAR: &str = "USER_STORE_SNAPSHOT_INTERVAL";
+    pub const BREACHED_PASSWORDS_PATH_ENV_VAR: &str = "BREACHED_PASSWORDS_PATH";
+    pub const PASSWORD_MIN_LENGTH_ENV_VAR: &str = "PASSWORD_MIN_LENGTH";
+    pub const PASSWORD_MAX_LENGTH_ENV_VAR: &str = "PASSWORD_MAX_LENGTH";
+    pub const PASSWORD_COMPOSITION_RULES_ENV_VAR: &str = "PASSWORD_COMPOSITION_RULES";
+    pub const PASSWORD_MIN_STRENGTH_ENV_VAR: &str = "PASSWORD_MIN_STRENGTH";
+    pub const EMAIL_FOLD_LOCAL_PART_CASE_ENV_VAR: &str = "EMAIL_FOLD_LOCAL_PART_CASE";
+    pub const EMAIL_ALLOWED_DOMAINS_ENV_VAR: &str = "EMAIL_ALLOWED_DOMAINS";
+    pub const EMAIL_DENIED_DOMAINS_ENV_VAR: &str = "EMAIL_DENIED_DOMAINS";
+    pub const EMAIL_BLOCK_DISPOSABLE_ENV_VAR: &str = "EMAIL_BLOCK_DISPOSABLE";
+}
+
+pub const JWT_COOKIE_NAME: &str = "jwt";
+// Verifiers may cache the JWKS for up to `JWKS_MAX_AGE_SECONDS`, so new
+// signing keys are published well ahead of their first use.
+pub const JWKS_MAX_AGE_SECONDS: u64 = 300;
+pub const KEY_PUBLISH_AHEAD_SECONDS: i64 = 3600;
+pub const KEY_ROTATION_CHECK_SECONDS: u64 = 60;
+pub const TOTP_ISSUER: &str = "Live Bootcamp";
+pub const WEBAUTHN_RP_NAME: &str = "Live Bootcamp";
+pub const WEBAUTHN_TIMEOUT_MS: u64 = 60_000;
+// How long a login has to pass its second factor.
+pub const TWO_FA_CODE_TTL_SECONDS: i64 = 600;
+// Authorization codes are redeemed by the client's backend straight after
+// the redirect, so they only need to live long enough for that round trip.
+pub const AUTHORIZATION_CODE_TTL_SECONDS: i64 = 60;
+// Long enough for an email to arrive, short enough that an old inbox is no use.
+pub const MAGIC_LINK_TTL_SECONDS: i64 = 900;
+pub const MAGIC_LINK_BINDING_COOKIE_NAME: &str = "magic_link_binding";
+pub const ADMIN_USERS_DEFAULT_PAGE_SIZE: usize = 20;
+pub const ADMIN_USERS_MAX_PAGE_SIZE: usize = 100;
+// Exports are streamed, fetching this many users from the store at a time.
+pub const USER_EXPORT_PAGE_SIZE: usize = 500;
@@ -0,0 +1,96 @@
+use crate::app_state::AppState;
+use crate::domain::{
+    AuthAPIError, ADMIN_ROLE, AUDIT_READ_PERMISSION, USERS_READ_PERMISSION, USERS_WRITE_PERMISSION,
+};
+use crate::utils::auth::{authenticated_session, Claims};
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum_extra::extract::CookieJar;
+use std::marker::PhantomData;
+
+/// Claims of a request whose token and session are valid.
+pub struct Authenticated(pub Claims);
+
+#[async_trait]
+impl FromRequestParts<AppState> for Authenticated {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+        authenticated_session(&jar, state).await.map(Authenticated)
+    }
+}
+
+pub trait RequiredRole {
+    const ROLE: &'static str;
+}
+
+pub trait RequiredPermission {
+    const PERMISSION: &'static str;
+}
+
+/// Rejects requests whose token doesn't carry the role `R::ROLE`.
+pub struct RequireRole<R>(pub Claims, pub PhantomData<R>);
+
+#[async_trait]
+impl<R: RequiredRole> FromRequestParts<AppState> for RequireRole<R> {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Authenticated(claims) = Authenticated::from_request_parts(parts, state).await?;
+        if !claims.has_role(R::ROLE) {
+            return Err(AuthAPIError::Forbidden);
+        }
+        Ok(RequireRole(claims, PhantomData))
+    }
+}
+
+/// Rejects requests whose token doesn't carry the permission `P::PERMISSION`.
+pub struct RequirePermission<P>(pub Claims, pub PhantomData<P>);
+
+#[async_trait]
+impl<P: RequiredPermission> FromRequestParts<AppState> for RequirePermission<P> {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Authenticated(claims) = Authenticated::from_request_parts(parts, state).await?;
+        if !claims.has_permission(P::PERMISSION) {
+            return Err(AuthAPIError::Forbidden);
+        }
+        Ok(RequirePermission(claims, PhantomData))
+    }
+}
+
+pub struct Admin;
+
+impl RequiredRole for Admin {
+    const ROLE: &'static str = ADMIN_ROLE;
+}
+
+pub struct UsersRead;
+
+impl RequiredPermission for UsersRead {
+    const PERMISSION: &'static str = USERS_READ_PERMISSION;
+}
+
+pub struct UsersWrite;
+
+impl RequiredPermission for UsersWrite {
+    const PERMISSION: &'static str = USERS_WRITE_PERMISSION;
+}
+
+pub struct AuditRead;
+
+impl RequiredPermission for AuditRead {
+    const PERMISSION: &'static str = AUDIT_READ_PERMISSION;
+}
@@ -0,0 +1,4 @@
+pub mod audit;
+pub mod auth;
+pub mod constants;
+pub mod guards;
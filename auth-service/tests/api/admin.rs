@@ -0,0 +1,399 @@
+use crate::contract::SendChecked;
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::domain::{
+    Email, Permission, RecoveryCode, Role, TotpCredential, TotpSecret, TwoFAMethod, User,
+    MAX_FAILED_LOGIN_ATTEMPTS, USERS_READ_PERMISSION, USERS_WRITE_PERMISSION,
+};
+use auth_service::routes::{AdminUserResponse, ErrorResponse, ListUsersResponse};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+const PASSWORD: &str = "passworD123!";
+
+async fn login_as_admin(app: &TestApp) -> String {
+    login_with(app, vec![Role::admin()], Vec::new()).await
+}
+
+async fn login_with(app: &TestApp, roles: Vec<Role>, permissions: Vec<Permission>) -> String {
+    let email = get_random_email();
+    let mut user = User::new(email.clone(), PASSWORD.to_owned(), false);
+    user.roles = roles;
+    user.permissions = permissions;
+    app.user_store
+        .add_user(user)
+        .await
+        .expect("Failed to add user");
+    login(app, &email).await;
+    email
+}
+
+async fn login(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": PASSWORD,
+    }))
+    .await
+}
+
+// Signs a user up without touching the test app's cookie jar.
+async fn signup(app: &TestApp, email: &str) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn admin_user(app: &TestApp, email: &str) -> AdminUserResponse {
+    let response = app.get_admin_user(email).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<AdminUserResponse>()
+        .await
+        .expect("Could not deserialize response body to AdminUserResponse")
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.get_admin_users(&()).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_403_for_non_admin() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+    login(&app, &email).await;
+
+    assert_eq!(app.get_admin_users(&()).await.status().as_u16(), 403);
+    assert_eq!(app.get_admin_user(&email).await.status().as_u16(), 403);
+    assert_eq!(
+        app.post_admin_user_action(&email, "unlock").await.status().as_u16(),
+        403
+    );
+}
+
+#[tokio::test]
+async fn should_list_users_with_search_and_pagination() {
+    let app = TestApp::new().await;
+    login_as_admin(&app).await;
+    for name in ["alice", "bob", "carol"] {
+        signup(&app, &format!("{}@search.test", name)).await;
+    }
+
+    let response = app
+        .get_admin_users(&[("search", "SEARCH.TEST"), ("perPage", "2")])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<ListUsersResponse>()
+        .await
+        .expect("Could not deserialize response body to ListUsersResponse");
+    assert_eq!(body.total, 3);
+    assert_eq!(body.per_page, 2);
+    let cursor = body.next_cursor.expect("No cursor for the next page");
+
+    let response = app
+        .get_admin_users(&[("search", "SEARCH.TEST"), ("perPage", "2"), ("cursor", &cursor)])
+        .await;
+    let body = response
+        .json::<ListUsersResponse>()
+        .await
+        .expect("Could not deserialize response body to ListUsersResponse");
+    let emails: Vec<&str> = body.users.iter().map(|user| user.email.as_str()).collect();
+    assert_eq!(emails, ["carol@search.test"]);
+    assert_eq!(body.next_cursor, None);
+
+    let response = app.get_admin_users(&[("cursor", "not a cursor")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_filter_listed_users() {
+    let app = TestApp::new().await;
+    login_as_admin(&app).await;
+    for name in ["alice", "bob"] {
+        signup(&app, &format!("{}@filter.test", name)).await;
+    }
+    let response = app.post_admin_user_action("bob@filter.test", "disable").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .get_admin_users(&[("search", "filter.test"), ("disabled", "true")])
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<ListUsersResponse>()
+        .await
+        .expect("Could not deserialize response body to ListUsersResponse");
+    let emails: Vec<&str> = body.users.iter().map(|user| user.email.as_str()).collect();
+    assert_eq!(emails, ["bob@filter.test"]);
+
+    let response = app.get_admin_users(&[("role", "admin")]).await;
+    let body = response
+        .json::<ListUsersResponse>()
+        .await
+        .expect("Could not deserialize response body to ListUsersResponse");
+    assert!(body.users.iter().all(|user| user.roles.contains(&Role::admin())));
+    assert_eq!(body.total, 1);
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_user() {
+    let app = TestApp::new().await;
+    login_as_admin(&app).await;
+
+    for email in [get_random_email(), "not-an-email".to_owned()] {
+        let response = app.get_admin_user(&email).await;
+        assert_eq!(response.status().as_u16(), 404, "User '{}' should not be found", email);
+        let response = app.post_admin_user_action(&email, "disable").await;
+        assert_eq!(response.status().as_u16(), 404, "User '{}' should not be found", email);
+    }
+}
+
+#[tokio::test]
+async fn should_disable_and_enable_user() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+    let token = login(&app, &email)
+        .await
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    login_as_admin(&app).await;
+
+    let response = app.post_admin_user_action(&email, "disable").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(admin_user(&app, &email).await.disabled);
+
+    // Existing sessions are revoked and new logins refused.
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/login", &app.address))
+        .json(&serde_json::json!({ "email": email, "password": PASSWORD }))
+        .send_checked()
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let response = app.post_admin_user_action(&email, "enable").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!admin_user(&app, &email).await.disabled);
+}
+
+#[tokio::test]
+async fn should_unlock_user_after_failed_logins() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+
+    for _ in 0..MAX_FAILED_LOGIN_ATTEMPTS {
+        let response = app
+            .post_login(&serde_json::json!({
+                "email": email,
+                "password": "wrongPassword1!",
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    // Even the right password is refused while the account is locked.
+    assert_eq!(login(&app, &email).await.status().as_u16(), 423);
+
+    login_as_admin(&app).await;
+    let user = admin_user(&app, &email).await;
+    assert!(user.locked);
+    assert_eq!(user.failed_login_attempts, MAX_FAILED_LOGIN_ATTEMPTS);
+
+    let response = app.post_admin_user_action(&email, "unlock").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(!admin_user(&app, &email).await.locked);
+    assert_eq!(login(&app, &email).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_force_password_reset() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+    login_as_admin(&app).await;
+
+    let response = app
+        .post_admin_user_action(&email, "force-password-reset")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(admin_user(&app, &email).await.password_reset_required);
+
+    assert_eq!(login(&app, &email).await.status().as_u16(), 403);
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+            "newPassword": "newPassworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "newPassworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_toggle_requires_2fa() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+    login_as_admin(&app).await;
+
+    let response = app
+        .put_admin_user_2fa(&email, &serde_json::json!({ "requires2FA": true }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(admin_user(&app, &email).await.requires_2fa);
+
+    assert_eq!(login(&app, &email).await.status().as_u16(), 206);
+}
+
+#[tokio::test]
+async fn should_remove_second_factors_when_turning_2fa_off() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+    let parsed_email = Email::parse(&email).unwrap();
+    let user_store = &app.user_store;
+    user_store
+        .set_totp_credential(&parsed_email, Some(TotpCredential::new(TotpSecret::generate())))
+        .await
+        .unwrap();
+    user_store
+        .set_recovery_codes(&parsed_email, RecoveryCode::generate_set().1)
+        .await
+        .unwrap();
+    user_store
+        .update_two_fa(&parsed_email, true, TwoFAMethod::Totp)
+        .await
+        .unwrap();
+    login_as_admin(&app).await;
+
+    let response = app
+        .put_admin_user_2fa(&email, &serde_json::json!({ "requires2FA": false }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user = user_store.get_user(&parsed_email).await.unwrap();
+    assert!(!user.requires_2fa);
+    assert!(user.totp.is_none());
+    assert!(user.recovery_codes.is_empty());
+}
+
+#[tokio::test]
+async fn should_allow_read_only_permission_to_list_but_not_modify() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+    login_with(
+        &app,
+        Vec::new(),
+        vec![Permission::parse(USERS_READ_PERMISSION).unwrap()],
+    )
+    .await;
+
+    assert_eq!(app.get_admin_users(&()).await.status().as_u16(), 200);
+    assert_eq!(app.get_admin_user(&email).await.status().as_u16(), 200);
+    assert_eq!(
+        app.post_admin_user_action(&email, "disable").await.status().as_u16(),
+        403
+    );
+}
+
+#[tokio::test]
+async fn should_set_roles_and_revoke_sessions() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+    let token = login(&app, &email)
+        .await
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    login_as_admin(&app).await;
+
+    let response = app
+        .put_admin_user_roles(
+            &email,
+            &serde_json::json!({ "roles": ["premium"], "permissions": ["reports:export"] }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let user = response
+        .json::<AdminUserResponse>()
+        .await
+        .expect("Could not deserialize response body to AdminUserResponse");
+    assert_eq!(user.roles, vec![Role::parse("premium").unwrap()]);
+    assert_eq!(user.permissions, vec![Permission::parse("reports:export").unwrap()]);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_not_let_users_write_permission_grant_admin() {
+    let app = TestApp::new().await;
+    let caller = login_with(
+        &app,
+        Vec::new(),
+        vec![Permission::parse(USERS_WRITE_PERMISSION).unwrap()],
+    )
+    .await;
+
+    let response = app
+        .put_admin_user_roles(&caller, &serde_json::json!({ "roles": ["admin"] }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    let user = app
+        .user_store
+        .get_user(&Email::parse(&caller).unwrap())
+        .await
+        .unwrap();
+    assert!(user.roles.is_empty());
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_role() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup(&app, &email).await;
+    login_as_admin(&app).await;
+
+    let response = app
+        .put_admin_user_roles(&email, &serde_json::json!({ "roles": ["Not A Role"] }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let body = response
+        .json::<ErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to ErrorResponse");
+    assert_eq!(body.error, "Invalid input: invalid role 'Not A Role'");
+}
@@ -0,0 +1,219 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::domain::{
+    AuditAction, AuditEntry, AuditOutcome, Permission, Role, User, USERS_READ_PERMISSION,
+};
+use auth_service::routes::{AuditLogResponse, AuditVerifyResponse};
+
+const PASSWORD: &str = "passworD123!";
+
+async fn add_user(app: &TestApp, roles: Vec<Role>, permissions: Vec<Permission>) -> String {
+    let email = get_random_email();
+    let mut user = User::new(email.clone(), PASSWORD.to_owned(), false);
+    user.roles = roles;
+    user.permissions = permissions;
+    app.user_store
+        .add_user(user)
+        .await
+        .expect("Failed to add user");
+    email
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": password,
+    }))
+    .await
+}
+
+async fn audit_log<Query: serde::Serialize>(app: &TestApp, query: &Query) -> Vec<AuditEntry> {
+    let response = app.get_admin_audit(query).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<AuditLogResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditLogResponse")
+        .entries
+}
+
+fn actions(entries: &[AuditEntry]) -> Vec<(AuditAction, AuditOutcome)> {
+    entries
+        .iter()
+        .map(|entry| (entry.event.action, entry.event.outcome))
+        .collect()
+}
+
+#[tokio::test]
+async fn should_record_signup_login_and_logout() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    assert_eq!(login(&app, &email, "wrongPassword1!").await.status().as_u16(), 401);
+    assert_eq!(login(&app, &email, PASSWORD).await.status().as_u16(), 200);
+    assert_eq!(app.delete_logout().await.status().as_u16(), 200);
+
+    let admin = add_user(&app, vec![Role::admin()], Vec::new()).await;
+    login(&app, &admin, PASSWORD).await;
+
+    let entries = audit_log(&app, &[("email", email.as_str())]).await;
+    assert_eq!(
+        actions(&entries),
+        [
+            (AuditAction::Signup, AuditOutcome::Success),
+            (AuditAction::Login, AuditOutcome::Failure),
+            (AuditAction::Login, AuditOutcome::Success),
+            (AuditAction::Logout, AuditOutcome::Success),
+        ]
+    );
+    assert_eq!(entries[1].event.detail.as_deref(), Some("IncorrectCredentials"));
+    for entry in &entries {
+        assert_eq!(entry.event.actor.as_deref(), Some(email.as_str()));
+        assert_eq!(entry.event.ip_address.as_deref(), Some("127.0.0.1"));
+    }
+}
+
+#[tokio::test]
+async fn should_record_2fa_challenge_and_verification() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": PASSWORD,
+        "requires2FA": true
+    }))
+    .await;
+    let response = login(&app, &email, PASSWORD).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let body = response
+        .json::<serde_json::Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body["loginAttemptId"],
+            "2FACode": "000000",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let admin = add_user(&app, vec![Role::admin()], Vec::new()).await;
+    login(&app, &admin, PASSWORD).await;
+
+    let entries = audit_log(&app, &[("email", email.as_str())]).await;
+    assert_eq!(
+        actions(&entries),
+        [
+            (AuditAction::Signup, AuditOutcome::Success),
+            (AuditAction::TwoFAChallenge, AuditOutcome::Success),
+            (AuditAction::TwoFAVerification, AuditOutcome::Failure),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn should_record_admin_actions_including_refused_ones() {
+    let app = TestApp::new().await;
+    let user = add_user(&app, Vec::new(), Vec::new()).await;
+    login(&app, &user, PASSWORD).await;
+    assert_eq!(
+        app.post_admin_user_action(&user, "unlock").await.status().as_u16(),
+        403
+    );
+
+    let admin = add_user(&app, vec![Role::admin()], Vec::new()).await;
+    login(&app, &admin, PASSWORD).await;
+    assert_eq!(
+        app.post_admin_user_action(&user, "disable").await.status().as_u16(),
+        200
+    );
+
+    let entries: Vec<AuditEntry> = audit_log(&app, &[("email", user.as_str())])
+        .await
+        .into_iter()
+        .filter(|entry| entry.event.action == AuditAction::AdminAction)
+        .collect();
+    assert_eq!(entries.len(), 2);
+
+    assert_eq!(entries[0].event.outcome, AuditOutcome::Failure);
+    assert_eq!(entries[0].event.actor.as_deref(), Some(user.as_str()));
+    assert_eq!(
+        entries[0].event.detail.as_deref(),
+        Some("POST /admin/users/:email/unlock (403 Forbidden)")
+    );
+
+    assert_eq!(entries[1].event.outcome, AuditOutcome::Success);
+    assert_eq!(entries[1].event.actor.as_deref(), Some(admin.as_str()));
+    assert_eq!(entries[1].event.subject.as_deref(), Some(user.as_str()));
+}
+
+#[tokio::test]
+async fn should_filter_by_time_range() {
+    let app = TestApp::new().await;
+    let admin = add_user(&app, vec![Role::admin()], Vec::new()).await;
+    login(&app, &admin, PASSWORD).await;
+
+    let now = chrono::Utc::now().timestamp();
+    let entries = audit_log(&app, &[("from", now - 60), ("to", now + 60)]).await;
+    assert!(!entries.is_empty());
+
+    let entries = audit_log(&app, &[("from", now + 60)]).await;
+    assert!(entries.is_empty());
+}
+
+#[tokio::test]
+async fn should_return_403_without_audit_permission() {
+    let app = TestApp::new().await;
+    let email = add_user(
+        &app,
+        Vec::new(),
+        vec![Permission::parse(USERS_READ_PERMISSION).unwrap()],
+    )
+    .await;
+    login(&app, &email, PASSWORD).await;
+
+    assert_eq!(app.get_admin_audit(&()).await.status().as_u16(), 403);
+    assert_eq!(app.get_admin_audit_verify().await.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_detect_tampering() {
+    let app = TestApp::new().await;
+    let user = add_user(&app, Vec::new(), Vec::new()).await;
+    login(&app, &user, PASSWORD).await;
+    let admin = add_user(&app, vec![Role::admin()], Vec::new()).await;
+    login(&app, &admin, PASSWORD).await;
+
+    let response = app.get_admin_audit_verify().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response
+        .json::<AuditVerifyResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditVerifyResponse");
+    assert!(body.valid);
+
+    let contents = std::fs::read_to_string(&app.audit_log_path).expect("Failed to read audit log");
+    std::fs::write(&app.audit_log_path, contents.replacen("success", "failure", 1))
+        .expect("Failed to write audit log");
+
+    let body = app
+        .get_admin_audit_verify()
+        .await
+        .json::<AuditVerifyResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditVerifyResponse");
+    assert!(!body.valid);
+    assert_eq!(
+        body.error.as_deref(),
+        Some("entry 1 was modified after it was written")
+    );
+}
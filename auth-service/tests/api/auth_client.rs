@@ -0,0 +1,122 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_client::{AuthClient, AuthError, LoginOutcome};
+use auth_service::domain::{AuthAPIError, Email, EmailError};
+
+const PASSWORD: &str = "passworD123!";
+
+fn client(app: &TestApp) -> AuthClient {
+    AuthClient::new(reqwest::Client::new(), app.address.clone())
+}
+
+#[tokio::test]
+async fn should_sign_up_log_in_and_out() {
+    let app = TestApp::new().await;
+    let client = client(&app);
+    let email = get_random_email();
+
+    let response = client.signup(&email, PASSWORD, false).await.unwrap();
+    assert_eq!(response.message, "User created successfully!");
+    assert_eq!(response.recovery_codes, None);
+
+    let LoginOutcome::Authenticated(token) = client.login(&email, PASSWORD).await.unwrap() else {
+        panic!("expected to be signed in without 2FA");
+    };
+    let verified = client.verify_token(token.as_ref()).await.unwrap();
+    assert_eq!(verified.sub, email);
+    assert_eq!(verified.client_id, None);
+
+    client.logout(token.as_ref()).await.unwrap();
+    assert!(matches!(
+        client.verify_token(token.as_ref()).await,
+        Err(AuthError::InvalidToken)
+    ));
+}
+
+#[tokio::test]
+async fn should_complete_2fa_login() {
+    let app = TestApp::new().await;
+    let client = client(&app);
+    let email = get_random_email();
+    let response = client.signup(&email, PASSWORD, true).await.unwrap();
+    let recovery_codes = response.recovery_codes.expect("No recovery codes issued");
+
+    let LoginOutcome::TwoFactorRequired(challenge) = client.login(&email, PASSWORD).await.unwrap()
+    else {
+        panic!("expected a 2FA challenge");
+    };
+    let (_, code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(&email).unwrap())
+        .await
+        .expect("No 2FA code stored for login attempt");
+    let verified = client
+        .verify_2fa(&email, &challenge.login_attempt_id, code.as_ref())
+        .await
+        .unwrap();
+    assert_eq!(verified.remaining_recovery_codes, None);
+    client.verify_token(verified.token.as_ref()).await.unwrap();
+
+    // A recovery code stands in for the emailed code.
+    let LoginOutcome::TwoFactorRequired(challenge) = client.login(&email, PASSWORD).await.unwrap()
+    else {
+        panic!("expected a 2FA challenge");
+    };
+    let verified = client
+        .verify_2fa(&email, &challenge.login_attempt_id, &recovery_codes[0])
+        .await
+        .unwrap();
+    assert_eq!(verified.remaining_recovery_codes, Some(recovery_codes.len() - 1));
+}
+
+#[tokio::test]
+async fn should_return_typed_errors() {
+    let app = TestApp::new().await;
+    let client = client(&app);
+    let email = get_random_email();
+    client.signup(&email, PASSWORD, false).await.unwrap();
+
+    assert!(matches!(
+        client.signup(&email, PASSWORD, false).await,
+        Err(AuthError::UserAlreadyExists)
+    ));
+    assert!(matches!(
+        client.signup(&get_random_email(), "password", false).await,
+        Err(AuthError::InvalidPassword(_))
+    ));
+    assert!(matches!(
+        client.login(&email, "wrongPassword123!").await,
+        Err(AuthError::IncorrectCredentials)
+    ));
+    assert!(matches!(
+        client.login("not an email", PASSWORD).await,
+        Err(AuthError::InvalidCredentials)
+    ));
+    assert!(matches!(
+        client.verify_2fa(&email, "not an id", "123456").await,
+        Err(AuthError::InvalidCredentials)
+    ));
+    assert!(matches!(
+        client.logout("not a token").await,
+        Err(AuthError::InvalidToken)
+    ));
+    assert!(matches!(
+        client.signup("someone@mailinator.com", PASSWORD, false).await,
+        Err(AuthError::EmailNotAllowed(_))
+    ));
+
+    // Allowed and denied domains come from the environment, so the refusal is
+    // served by a stand-in that renders it the way the service does.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    let router = axum::Router::new().route(
+        "/signup",
+        axum::routing::post(|| async { AuthAPIError::EmailNotAllowed(EmailError::DomainNotAllowed) }),
+    );
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    let client = AuthClient::new(reqwest::Client::new(), address);
+    assert!(matches!(
+        client.signup(&get_random_email(), PASSWORD, false).await,
+        Err(AuthError::EmailNotAllowed(_))
+    ));
+}
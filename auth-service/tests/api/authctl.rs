@@ -0,0 +1,248 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::domain::{Email, Role, TwoFAMethod, User, UserStore, UserStoreError};
+use auth_service::routes::KeyRotationResponse;
+use auth_service::services::HashmapUserStore;
+use auth_service::utils::auth::open_key_ring;
+use reqwest::cookie::CookieStore;
+use std::path::{Path, PathBuf};
+use std::process::{Output, Stdio};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+const PASSWORD: &str = "passworD123!";
+
+struct Paths {
+    store_dir: PathBuf,
+    audit_log: PathBuf,
+}
+
+impl Paths {
+    fn new() -> Self {
+        let id = uuid::Uuid::new_v4();
+        Self {
+            store_dir: std::env::temp_dir().join(format!("authctl-users-{}", id)),
+            audit_log: std::env::temp_dir().join(format!("authctl-audit-{}.jsonl", id)),
+        }
+    }
+}
+
+// Runs asynchronously so that the test's own server keeps serving meanwhile.
+async fn authctl(paths: &Paths, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_authctl"))
+        .args(args)
+        .env("USER_STORE_DIR", &paths.store_dir)
+        .env("AUDIT_LOG_PATH", &paths.audit_log)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run authctl");
+    let mut input = child.stdin.take().unwrap();
+    input.write_all(stdin.as_bytes()).await.unwrap();
+    drop(input);
+    child.wait_with_output().await.expect("Failed to run authctl")
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "authctl failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+async fn load_user(store_dir: &Path, email: &str) -> Result<User, UserStoreError> {
+    let store = HashmapUserStore::open(store_dir, 1000).expect("Failed to open user store");
+    let email = Email::parse(email).unwrap();
+    store.get_user(&email).await
+}
+
+#[tokio::test]
+async fn should_manage_users_in_the_store() {
+    let paths = Paths::new();
+    let email = get_random_email();
+
+    let output = authctl(
+        &paths,
+        &["user", "create", &email, "--role", "admin", "--requires-2fa"],
+        &format!("{}\n", PASSWORD),
+    )
+    .await;
+    assert_success(&output);
+    let user = load_user(&paths.store_dir, &email).await.unwrap();
+    assert_eq!(user.roles, vec![Role::admin()]);
+    assert!(user.requires_2fa);
+    assert!(user.password_hash.verify(PASSWORD));
+
+    let output = authctl(&paths, &["user", "list"], "").await;
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains(&email));
+
+    assert_success(&authctl(&paths, &["user", "reset-2fa", &email], "").await);
+    let new_password = "newPassworD456!";
+    let output = authctl(
+        &paths,
+        &["user", "reset-password", &email, "--require-change"],
+        &format!("{}\n", new_password),
+    )
+    .await;
+    assert_success(&output);
+    assert_success(&authctl(&paths, &["user", "disable", &email], "").await);
+
+    let user = load_user(&paths.store_dir, &email).await.unwrap();
+    assert!(!user.requires_2fa);
+    assert_eq!(user.two_fa_method, TwoFAMethod::Email);
+    assert!(user.password_hash.verify(new_password));
+    assert!(user.password_reset_required);
+    assert!(user.disabled);
+
+    assert_success(&authctl(&paths, &["user", "delete", &email], "").await);
+    assert_eq!(
+        load_user(&paths.store_dir, &email).await.err(),
+        Some(UserStoreError::UserNotFound)
+    );
+
+    // Every change was audited, and the log verifies.
+    let output = authctl(&paths, &["audit", "verify"], "").await;
+    assert_success(&output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "audit log is intact (5 entries)"
+    );
+
+    std::fs::remove_dir_all(&paths.store_dir).unwrap();
+    std::fs::remove_file(&paths.audit_log).unwrap();
+}
+
+#[tokio::test]
+async fn should_reject_invalid_input() {
+    let paths = Paths::new();
+    let email = get_random_email();
+
+    let output = authctl(&paths, &["user", "create", &email], "short\n").await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("password must be"));
+
+    let output = authctl(&paths, &["user", "delete", &email], "").await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("UserNotFound"));
+}
+
+#[tokio::test]
+async fn should_detect_tampered_audit_log() {
+    let paths = Paths::new();
+    let email = get_random_email();
+    let output = authctl(&paths, &["user", "create", &email], &format!("{}\n", PASSWORD)).await;
+    assert_success(&output);
+
+    let contents = std::fs::read_to_string(&paths.audit_log).unwrap();
+    std::fs::write(&paths.audit_log, contents.replace("user create", "user delete")).unwrap();
+
+    let output = authctl(&paths, &["audit", "verify"], "").await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("entry 1 was modified"));
+
+    std::fs::remove_dir_all(&paths.store_dir).unwrap();
+    std::fs::remove_file(&paths.audit_log).unwrap();
+}
+
+#[tokio::test]
+async fn should_refuse_a_store_the_service_has_open() {
+    let paths = Paths::new();
+    let store = HashmapUserStore::open(&paths.store_dir, 1000).expect("Failed to open user store");
+
+    let output = authctl(&paths, &["user", "list"], "").await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is in use by the auth service"));
+
+    drop(store);
+    assert_success(&authctl(&paths, &["user", "list"], "").await);
+    std::fs::remove_dir_all(&paths.store_dir).unwrap();
+}
+
+#[tokio::test]
+async fn should_generate_keys_for_the_service_to_load() {
+    let paths = Paths::new();
+    let keys_path = std::env::temp_dir().join(format!("authctl-keys-{}.json", uuid::Uuid::new_v4()));
+    let keys_path = keys_path.to_str().unwrap();
+
+    let output = authctl(&paths, &["keys", "generate", "--path", keys_path], "").await;
+    assert_success(&output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let kid = stdout.trim().rsplit(' ').next().unwrap();
+
+    let now = chrono::Utc::now().timestamp();
+    let key_ring = open_key_ring(Some(keys_path), now).expect("Failed to load generated keys");
+    assert_eq!(key_ring.signing_key(now).unwrap().kid, kid);
+
+    // Existing keys are never replaced.
+    let output = authctl(&paths, &["keys", "generate", "--path", keys_path], "").await;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("already exists"));
+    std::fs::remove_file(keys_path).unwrap();
+}
+
+#[tokio::test]
+async fn should_rotate_keys_through_the_admin_api() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let mut user = User::new(email.clone(), PASSWORD.to_owned(), false);
+    user.roles = vec![Role::admin()];
+    app.user_store.add_user(user).await.unwrap();
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let cookies = app
+        .cookie_jar
+        .cookies(&app.address.parse().unwrap())
+        .expect("Login should set the auth cookie");
+    let token = cookies.to_str().unwrap().trim_start_matches("jwt=");
+
+    let paths = Paths::new();
+    let output = authctl(
+        &paths,
+        &["keys", "--url", &app.address, "rotate", "--token", token],
+        "",
+    )
+    .await;
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("published key "));
+
+    let output = authctl(&paths, &["keys", "--url", &app.address, "list"], "").await;
+    assert_success(&output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+
+    // Rotating again before the new key signs doesn't publish a third.
+    let response = app.post_admin_keys_rotate().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<KeyRotationResponse>()
+        .await
+        .expect("Could not deserialize response body to KeyRotationResponse");
+    let output = authctl(&paths, &["keys", "--url", &app.address, "list"], "").await;
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+}
+
+#[tokio::test]
+async fn should_return_403_when_non_admin_rotates_keys() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": PASSWORD,
+        "requires2FA": false
+    }))
+    .await;
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": PASSWORD,
+    }))
+    .await;
+
+    assert_eq!(app.post_admin_keys_rotate().await.status().as_u16(), 403);
+}
@@ -0,0 +1,120 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::{TestApp, BREACHED_PASSWORDS};
+use auth_service::routes::ErrorResponse;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    token
+}
+
+// localhost:3000/change-password
+#[tokio::test]
+async fn should_return_400_if_new_password_invalid() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+            "newPassword": "short",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_400_if_new_password_is_breached() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+            "newPassword": BREACHED_PASSWORDS[0],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Password has appeared in a data breach, choose another".to_owned()
+    );
+}
+
+#[tokio::test]
+async fn should_return_401_if_current_password_incorrect() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    signup_and_login(&app, &email).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "email": email,
+            "password": "wrongPassworD123!",
+            "newPassword": "newPassworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_change_password_and_revoke_sessions() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let token = signup_and_login(&app, &email).await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+            "newPassword": "newPassworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "newPassworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,196 @@
+//! Checks responses against the documented API in `api_schema.yml`, so that a
+//! route can't answer with a status, header or body the schema doesn't describe.
+
+use lazy_static::lazy_static;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde_json::{json, Value};
+
+// The login page's assets and the API documentation itself aren't part of the API.
+const UNDOCUMENTED_PATHS: [&str; 3] = ["/", "/openapi.json", "/swagger-ui/"];
+
+// Headers the routes set on purpose, which must be documented whenever they are
+// sent. Transport headers such as `content-length` and `date` are left out.
+const CONTRACT_HEADERS: [&str; 5] = [
+    "cache-control",
+    "location",
+    "pragma",
+    "set-cookie",
+    "www-authenticate",
+];
+
+lazy_static! {
+    static ref SCHEMA: Value = {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/api_schema.yml");
+        let yaml = std::fs::read_to_string(path).expect("Could not read api_schema.yml");
+        serde_yaml::from_str(&yaml).expect("Could not parse api_schema.yml")
+    };
+}
+
+/// Sends requests whose responses must match `api_schema.yml`.
+pub trait SendChecked {
+    /// Sends the request, panicking if the response isn't documented.
+    async fn send_checked(self) -> Response;
+}
+
+impl SendChecked for RequestBuilder {
+    async fn send_checked(self) -> Response {
+        let (client, request) = self.build_split();
+        let request = request.expect("Failed to build request.");
+        let method = request.method().clone();
+        let path = request.url().path().to_owned();
+        let response = client
+            .execute(request)
+            .await
+            .expect("Failed to execute request.");
+        check_response(&method, &path, response).await
+    }
+}
+
+// Reads the whole response to check it, then rebuilds it for the test to read.
+async fn check_response(method: &Method, path: &str, response: Response) -> Response {
+    let status = response.status();
+    let version = response.version();
+    let headers = response.headers().clone();
+    let body = response
+        .bytes()
+        .await
+        .expect("Failed to read response body.");
+
+    if !UNDOCUMENTED_PATHS.contains(&path) {
+        if let Err(e) = check(method, path, status, &headers, &body) {
+            panic!(
+                "{} {} answered {}, which doesn't match api_schema.yml: {}",
+                method, path, status, e
+            );
+        }
+    }
+
+    let mut rebuilt = http::Response::new(body);
+    *rebuilt.status_mut() = status;
+    *rebuilt.version_mut() = version;
+    *rebuilt.headers_mut() = headers;
+    Response::from(rebuilt)
+}
+
+fn check(
+    method: &Method,
+    path: &str,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), String> {
+    let operation = find_operation(method, path).ok_or("the route isn't documented")?;
+    let response = operation["responses"]
+        .get(status.as_str())
+        .ok_or("the status isn't documented")?;
+    check_headers(response, headers)?;
+    check_body(response, headers, body)
+}
+
+// Literal segments win over parameters, so `/admin/users/export` isn't taken
+// for `/admin/users/{email}`.
+fn find_operation(method: &Method, path: &str) -> Option<&'static Value> {
+    let segments: Vec<&str> = path.split('/').collect();
+    SCHEMA["paths"]
+        .as_object()?
+        .iter()
+        .filter_map(|(template, item)| {
+            let template: Vec<&str> = template.split('/').collect();
+            if template.len() != segments.len() {
+                return None;
+            }
+            let mut parameters = 0;
+            for (expected, actual) in template.iter().zip(&segments) {
+                if expected.starts_with('{') && expected.ends_with('}') {
+                    if actual.is_empty() {
+                        return None;
+                    }
+                    parameters += 1;
+                } else if expected != actual {
+                    return None;
+                }
+            }
+            Some((parameters, item))
+        })
+        .min_by_key(|(parameters, _)| *parameters)
+        .and_then(|(_, item)| item.get(method.as_str().to_lowercase()))
+}
+
+fn check_headers(response: &Value, headers: &HeaderMap) -> Result<(), String> {
+    let documented = response["headers"].as_object();
+    let documented_header = |name: &str| {
+        documented.and_then(|documented| {
+            documented
+                .iter()
+                .find(|(documented, _)| documented.eq_ignore_ascii_case(name))
+                .map(|(_, header)| header)
+        })
+    };
+
+    for name in CONTRACT_HEADERS {
+        if headers.contains_key(name) && documented_header(name).is_none() {
+            return Err(format!("the {} header isn't documented", name));
+        }
+    }
+
+    for (name, value) in headers {
+        let Some(header) = documented_header(name.as_str()) else {
+            continue;
+        };
+        let value = value
+            .to_str()
+            .map_err(|_| format!("the {} header isn't text", name))?;
+        validate(&header["schema"], &Value::String(value.to_owned()))
+            .map_err(|e| format!("the {} header {}", name, e))?;
+    }
+    Ok(())
+}
+
+fn check_body(response: &Value, headers: &HeaderMap, body: &[u8]) -> Result<(), String> {
+    if body.is_empty() {
+        return Ok(());
+    }
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .ok_or("the body has no content type")?;
+    let media_type = content_type.split(';').next().unwrap_or_default().trim();
+    let content = response["content"]
+        .as_object()
+        .ok_or("the body isn't documented")?;
+    let media = content
+        .get(media_type)
+        .ok_or_else(|| format!("the {} content type isn't documented", media_type))?;
+
+    let instance = if media_type == "application/json" {
+        serde_json::from_slice(body).map_err(|e| format!("the body isn't JSON: {}", e))?
+    } else {
+        Value::String(String::from_utf8_lossy(body).into_owned())
+    };
+    validate(&media["schema"], &instance).map_err(|e| format!("the body {}", e))
+}
+
+// Schemas refer to `#/components/schemas/...`, so the components are carried
+// along to resolve them.
+fn validate(schema: &Value, instance: &Value) -> Result<(), String> {
+    let schema = json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "components": SCHEMA["components"],
+        "allOf": [schema],
+    });
+    let validator = jsonschema::options()
+        .should_validate_formats(true)
+        .build(&schema)
+        .map_err(|e| format!("has an invalid schema: {}", e))?;
+
+    let errors: Vec<String> = validator
+        .iter_errors(instance)
+        .map(|e| format!("{} at '{}'", e, e.instance_path()))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("breaks the schema: {}", errors.join("; ")))
+    }
+}
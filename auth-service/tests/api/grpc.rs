@@ -0,0 +1,234 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::grpc::proto::auth_service_client::AuthServiceClient;
+use auth_service::grpc::proto::{
+    GetUserRequest, IntrospectTokenRequest, TwoFaMethod, VerifyTokenRequest,
+};
+use auth_service::domain::ServiceAccount;
+use auth_service::grpc::{TOKENS_INTROSPECT_SCOPE, USERS_READ_SCOPE};
+use auth_service::utils::constants::OIDC_ISSUER;
+use auth_service::utils::auth::generate_service_account_token;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+
+async fn grpc_client(app: &TestApp) -> AuthServiceClient<Channel> {
+    AuthServiceClient::connect(app.grpc_address.clone())
+        .await
+        .expect("Failed to connect to gRPC server")
+}
+
+async fn service_account_token(app: &TestApp, scope: &str) -> String {
+    let (account, _) = ServiceAccount::new(
+        "Mesh".to_owned(),
+        vec![scope.to_owned()],
+        chrono::Utc::now().timestamp(),
+    );
+    app.service_account_store
+        .add_account(account.clone())
+        .await
+        .expect("Failed to add service account");
+    generate_service_account_token(&account, scope, &*app.key_ring.read().await)
+        .expect("Failed to generate token")
+}
+
+fn authorized<T>(message: T, token: &str) -> Request<T> {
+    let mut request = Request::new(message);
+    request.metadata_mut().insert(
+        "authorization",
+        format!("Bearer {}", token).parse().unwrap(),
+    );
+    request
+}
+
+async fn login(app: &TestApp, email: &str) -> String {
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    auth_cookie.value().to_owned()
+}
+
+#[tokio::test]
+async fn verify_token_should_accept_session_tokens_until_logout() {
+    let app = TestApp::new().await;
+    let mut client = grpc_client(&app).await;
+    let email = get_random_email();
+    let token = login(&app, &email).await;
+
+    let request = VerifyTokenRequest { token: token.clone() };
+    let response = client.verify_token(request.clone()).await.unwrap().into_inner();
+    assert_eq!(response.sub, email);
+    assert_eq!(response.client_id, "");
+    assert_eq!(response.scope, "");
+
+    assert_eq!(app.delete_logout().await.status().as_u16(), 200);
+    let status = client.verify_token(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = client
+        .verify_token(VerifyTokenRequest { token: "invalid".to_owned() })
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn verify_token_should_accept_service_account_tokens_until_deleted() {
+    let app = TestApp::new().await;
+    let mut client = grpc_client(&app).await;
+    let token = service_account_token(&app, "reports:read").await;
+
+    let request = VerifyTokenRequest { token: token.clone() };
+    let response = client.verify_token(request.clone()).await.unwrap().into_inner();
+    let accounts = app.service_account_store.list_accounts().await.unwrap();
+    assert_eq!(response.sub, accounts[0].client_id);
+    assert_eq!(response.client_id, accounts[0].client_id);
+    assert_eq!(response.scope, "reports:read");
+
+    app.service_account_store
+        .delete_account(&accounts[0].client_id)
+        .await
+        .unwrap();
+    let status = client.verify_token(request).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn introspect_token_should_describe_active_tokens() {
+    let app = TestApp::new().await;
+    let mut client = grpc_client(&app).await;
+    let email = get_random_email();
+    let token = login(&app, &email).await;
+    let caller_token = service_account_token(&app, TOKENS_INTROSPECT_SCOPE).await;
+
+    let response = client
+        .introspect_token(authorized(IntrospectTokenRequest { token }, &caller_token))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.active);
+    assert_eq!(response.sub, email);
+    assert_eq!(response.iss, OIDC_ISSUER.trim_end_matches('/'));
+    assert!(!response.session.expect("Session tokens have a session").id.is_empty());
+    assert!(response.exp > chrono::Utc::now().timestamp());
+
+    // Service account tokens are active too, without a session.
+    let response = client
+        .introspect_token(authorized(
+            IntrospectTokenRequest { token: caller_token.clone() },
+            &caller_token,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(response.active);
+    assert_eq!(response.scope, TOKENS_INTROSPECT_SCOPE);
+    assert!(!response.client_id.is_empty());
+    assert!(response.session.is_none());
+
+    let response = client
+        .introspect_token(authorized(
+            IntrospectTokenRequest { token: "invalid".to_owned() },
+            &caller_token,
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert!(!response.active);
+    assert!(response.sub.is_empty());
+}
+
+#[tokio::test]
+async fn introspect_token_should_require_a_service_account_with_the_scope() {
+    let app = TestApp::new().await;
+    let mut client = grpc_client(&app).await;
+    let token = login(&app, &get_random_email()).await;
+    let request = || IntrospectTokenRequest { token: token.clone() };
+
+    let status = client.introspect_token(request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = client
+        .introspect_token(authorized(request(), &token))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let caller_token = service_account_token(&app, USERS_READ_SCOPE).await;
+    let status = client
+        .introspect_token(authorized(request(), &caller_token))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn get_user_should_return_user_or_not_found() {
+    let app = TestApp::new().await;
+    let mut client = grpc_client(&app).await;
+    let email = get_random_email();
+    login(&app, &email).await;
+    let token = service_account_token(&app, USERS_READ_SCOPE).await;
+
+    let user = client
+        .get_user(authorized(GetUserRequest { email: email.clone() }, &token))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(user.email, email);
+    assert!(!user.requires_2fa);
+    assert_eq!(user.two_fa_method(), TwoFaMethod::Email);
+    assert!(!user.disabled);
+
+    let status = client
+        .get_user(authorized(GetUserRequest { email: get_random_email() }, &token))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::NotFound);
+
+    let status = client
+        .get_user(authorized(GetUserRequest { email: "not an email".to_owned() }, &token))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn get_user_should_require_a_service_account_with_users_read() {
+    let app = TestApp::new().await;
+    let mut client = grpc_client(&app).await;
+    let email = get_random_email();
+    let session_token = login(&app, &email).await;
+    let request = || GetUserRequest { email: email.clone() };
+
+    let status = client.get_user(request()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = client.get_user(authorized(request(), "invalid")).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let status = client
+        .get_user(authorized(request(), &session_token))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let token = service_account_token(&app, "reports:read").await;
+    let status = client.get_user(authorized(request(), &token)).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
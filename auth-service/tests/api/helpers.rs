@@ -1,55 +1,211 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use auth_service::app_state::AppState;
+use auth_service::app_state::{
+    AppState, KeyRingType, OidcClientStoreType, ServiceAccountStoreType, TwoFACodeStoreType,
+    UserStoreType,
+};
+use auth_service::domain::{Email, EmailClient, KeyRing, KeyRotationPolicy, SigningAlgorithm};
+use auth_service::utils::auth::TOKEN_TTL_SECONDS;
 use auth_service::Application;
-use auth_service::services::HashmapUserStore;
+use auth_service::services::{
+    HashmapAuthorizationCodeStore, HashmapMagicLinkStore, HashmapOidcClientStore,
+    HashmapServiceAccountStore, HashmapSessionStore,
+    HashmapTwoFACodeStore,
+    HashmapUserStore, HashmapWebAuthnChallengeStore, HibpPasswordList, JsonlAuditSink,
+};
+use crate::contract::SendChecked;
+use reqwest::cookie::Jar;
+use sha1::{Digest, Sha1};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Passwords that pass the password rules but are in the test breach dataset.
+pub const BREACHED_PASSWORDS: [&str; 2] = ["P@ssw0rd1", "Welcome1!"];
+
+// A Pwned Passwords style file: `SHA1:COUNT` lines sorted by hash.
+fn write_breached_passwords() -> PathBuf {
+    let mut lines: Vec<String> = BREACHED_PASSWORDS
+        .iter()
+        .map(|password| format!("{:X}:42", Sha1::digest(password.as_bytes())))
+        .collect();
+    lines.sort();
+    let path = std::env::temp_dir().join(format!("pwned-{}.txt", uuid::Uuid::new_v4()));
+    std::fs::write(&path, lines.join("\n") + "\n").expect("Failed to write breached passwords");
+    path
+}
+
+/// An email the app sent.
+#[derive(Debug, Clone)]
+pub struct SentEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub content: String,
+}
+
+/// Email client that keeps what it sends, so tests can read the emails.
+#[derive(Default)]
+pub struct RecordingEmailClient {
+    sent: Mutex<Vec<SentEmail>>,
+}
+
+#[async_trait::async_trait]
+impl EmailClient for RecordingEmailClient {
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<(), String> {
+        self.sent.lock().unwrap().push(SentEmail {
+            recipient: recipient.as_ref().to_owned(),
+            subject: subject.to_owned(),
+            content: content.to_owned(),
+        });
+        Ok(())
+    }
+}
 
 pub struct TestApp {
     pub address: String,
+    pub grpc_address: String,
+    pub cookie_jar: Arc<Jar>,
+    pub user_store: UserStoreType,
+    pub two_fa_code_store: TwoFACodeStoreType,
+    pub oidc_client_store: OidcClientStoreType,
+    pub service_account_store: ServiceAccountStoreType,
+    pub key_ring: KeyRingType,
+    pub audit_log_path: PathBuf,
+    pub email_client: Arc<RecordingEmailClient>,
     pub http_client: reqwest::Client,
 }
 
 const TEST_SERVER_HOST: &str = "127.0.0.1:0";
 
-
 impl TestApp {
     pub async fn new() -> Self {
-        let user_store = Arc::new(RwLock::new(HashmapUserStore::new()));
-        let app_state = AppState { user_store };
-        let app = Application::build(app_state, TEST_SERVER_HOST)
+        Self::with_user_store(Arc::new(HashmapUserStore::new())).await
+    }
+
+    pub async fn with_user_store(user_store: UserStoreType) -> Self {
+        let two_fa_code_store: TwoFACodeStoreType = Arc::new(HashmapTwoFACodeStore::new());
+        let webauthn_challenge_store = Arc::new(HashmapWebAuthnChallengeStore::new());
+        let oidc_client_store: OidcClientStoreType = Arc::new(HashmapOidcClientStore::new());
+        let service_account_store: ServiceAccountStoreType =
+            Arc::new(HashmapServiceAccountStore::new());
+        let authorization_code_store = Arc::new(HashmapAuthorizationCodeStore::new());
+        let magic_link_store = Arc::new(HashmapMagicLinkStore::new());
+        let session_store = Arc::new(HashmapSessionStore::new());
+        let audit_log_path =
+            std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()));
+        let audit_sink = Arc::new(
+            JsonlAuditSink::open(audit_log_path.clone()).expect("Failed to open audit log"),
+        );
+        let key_ring: KeyRingType = Arc::new(RwLock::new(
+            KeyRing::new(
+                SigningAlgorithm::EdDSA,
+                KeyRotationPolicy {
+                    rotation_interval: 86_400,
+                    publish_ahead: 3_600,
+                    retired_key_ttl: TOKEN_TTL_SECONDS,
+                },
+                chrono::Utc::now().timestamp(),
+            )
+            .expect("Failed to create signing keys"),
+        ));
+        let email_client = Arc::new(RecordingEmailClient::default());
+        let breached_passwords = Arc::new(
+            HibpPasswordList::open(write_breached_passwords())
+                .expect("Failed to open breached passwords"),
+        );
+        let app_state = AppState::new(
+            user_store.clone(),
+            two_fa_code_store.clone(),
+            webauthn_challenge_store,
+            oidc_client_store.clone(),
+            service_account_store.clone(),
+            authorization_code_store,
+            magic_link_store,
+            session_store,
+            audit_sink,
+            key_ring.clone(),
+            email_client.clone(),
+            breached_passwords,
+        );
+        let app = Application::build(app_state, TEST_SERVER_HOST, TEST_SERVER_HOST)
             .await
             .expect("Failed to build app");
 
         let address = format!("http://{}", app.address.clone());
+        let grpc_address = format!("http://{}", app.grpc_address.clone());
 
         // Run the auth service in a separate async task
         // to avoid blocking the main test thread.
         #[allow(clippy::let_underscore_future)]
         let _ = tokio::spawn(app.run());
 
+        let cookie_jar = Arc::new(Jar::default());
         let http_client = reqwest::Client::builder()
             .timeout(std::time::Duration::from_secs(10))
+            .cookie_provider(cookie_jar.clone())
+            // Redirects are asserted on rather than followed, since the OIDC
+            // flow redirects to client URIs that aren't being served.
+            .redirect(reqwest::redirect::Policy::none())
             .build().expect("Failed to build reqwest client");
 
 
         // Create new `TestApp` instance and return it
-        Self { address, http_client }
+        Self {
+            address,
+            grpc_address,
+            cookie_jar,
+            user_store,
+            two_fa_code_store,
+            oidc_client_store,
+            service_account_store,
+            key_ring,
+            audit_log_path,
+            email_client,
+            http_client,
+        }
+    }
+
+    pub fn sent_emails(&self) -> Vec<SentEmail> {
+        self.email_client.sent.lock().unwrap().clone()
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
-            .send()
+            .get(format!("{}/", &self.address))
+            .send_checked()
             .await
-            .expect("Failed to execute request.")
     }
 
-    pub async fn post_login(&self) -> reqwest::Response {
+    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
         self.http_client
-            .post(&format!("{}/login", &self.address))
-            .send()
+            .post(format!("{}/login", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_magic_link<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_magic_link_verify<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link/verify", &self.address))
+            .json(body)
+            .send_checked()
             .await
-            .expect("Failed to execute request.")
     }
 
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
@@ -57,34 +213,375 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
-            .send()
+            .send_checked()
             .await
-            .expect("Failed to execute request.")
     }
 
     pub async fn delete_logout(&self) -> reqwest::Response {
         self.http_client
-            .delete(&format!("{}/logout", &self.address))
-            .send()
+            .delete(format!("{}/logout", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_verify_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-token", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
+            .send_checked()
+            .await
+    }
+
+    pub async fn delete_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/change-password", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_admin_users<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/admin/users", &self.address))
+            .query(query)
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_admin_user(&self, email: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/users/{}", &self.address, email))
+            .send_checked()
+            .await
+    }
+
+    /// Posts one of the admin account actions, e.g. `disable` or `unlock`.
+    pub async fn post_admin_user_action(&self, email: &str, action: &str) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin/users/{}/{}", &self.address, email, action))
+            .send_checked()
+            .await
+    }
+
+    pub async fn put_admin_user_roles<Body>(&self, email: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .put(format!("{}/admin/users/{}/roles", &self.address, email))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_admin_users_export(&self, query: &[(&str, &str)]) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/users/export", &self.address))
+            .query(query)
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_admin_users_import(&self, format: &str, body: String) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin/users/import", &self.address))
+            .query(&[("format", format)])
+            .body(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_admin_audit<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/admin/audit", &self.address))
+            .query(query)
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_admin_audit_verify(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/audit/verify", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_admin_keys_rotate(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/admin/keys/rotate", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_admin_service_accounts(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/service-accounts", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_admin_service_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/service-accounts", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_admin_service_account_rotate_secret(
+        &self,
+        client_id: &str,
+    ) -> reqwest::Response {
+        self.http_client
+            .post(format!(
+                "{}/admin/service-accounts/{}/rotate-secret",
+                &self.address, client_id
+            ))
+            .send_checked()
+            .await
+    }
+
+    pub async fn delete_admin_service_account(&self, client_id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/admin/service-accounts/{}", &self.address, client_id))
+            .send_checked()
+            .await
+    }
+
+    pub async fn put_admin_user_2fa<Body>(&self, email: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .put(format!("{}/admin/users/{}/2fa", &self.address, email))
+            .json(body)
+            .send_checked()
             .await
-            .expect("Failed to execute request.")
     }
 
-    pub async fn post_verify_token(&self) -> reqwest::Response {
+    pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
         self.http_client
-            .post(&format!("{}/verify-token", &self.address))
-            .send()
+            .post(format!("{}/verify-2fa", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn put_2fa_method<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .put(format!("{}/2fa/method", &self.address))
+            .json(body)
+            .send_checked()
             .await
-            .expect("Failed to execute request.")
     }
 
-    pub async fn post_verify_2fa(&self) -> reqwest::Response {
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
-            .send()
+            .post(format!("{}/2fa/totp/enroll", &self.address))
+            .send_checked()
             .await
-            .expect("Failed to execute request.")
     }
-}
\ No newline at end of file
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/2fa/recovery-codes", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/recovery-codes", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_webauthn_register_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/webauthn/register/start", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_webauthn_register_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/register/finish", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_webauthn_login_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/start", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_webauthn_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/login/finish", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_webauthn_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/webauthn/verify-2fa", &self.address))
+            .json(body)
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/openid-configuration", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_openapi(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/openapi.json", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_swagger_ui(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/swagger-ui/", &self.address))
+            .send_checked()
+            .await
+    }
+
+    pub async fn get_authorize<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/authorize", &self.address))
+            .query(query)
+            .send_checked()
+            .await
+    }
+
+    pub async fn post_token<Form>(
+        &self,
+        form: &Form,
+        basic_auth: Option<(&str, &str)>,
+    ) -> reqwest::Response
+    where
+        Form: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/token", &self.address))
+            .form(form);
+        if let Some((client_id, client_secret)) = basic_auth {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+        request.send_checked().await
+    }
+
+    pub async fn post_introspect<Form>(
+        &self,
+        form: &Form,
+        basic_auth: Option<(&str, &str)>,
+    ) -> reqwest::Response
+    where
+        Form: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/introspect", &self.address))
+            .form(form);
+        if let Some((client_id, client_secret)) = basic_auth {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+        request.send_checked().await
+    }
+
+    pub async fn get_userinfo(&self, access_token: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/userinfo", &self.address))
+            .bearer_auth(access_token)
+            .send_checked()
+            .await
+    }
+}
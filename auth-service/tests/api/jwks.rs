@@ -0,0 +1,98 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::utils::auth::Claims;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+
+async fn get_jwks(app: &TestApp) -> JwkSet {
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet")
+}
+
+async fn login(app: &TestApp) -> String {
+    let random_email = get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    auth_cookie.value().to_owned()
+}
+
+// localhost:3000/.well-known/jwks.json
+#[tokio::test]
+async fn should_publish_public_keys_only() {
+    let app = TestApp::new().await;
+
+    let response = app.get_jwks().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .headers()
+        .get(reqwest::header::CACHE_CONTROL)
+        .is_some());
+
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    let keys = body["keys"].as_array().expect("No keys in JWKS");
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0]["use"], "sig");
+    assert!(keys[0]["kid"].is_string());
+    assert!(keys[0].get("d").is_none(), "Private key material published");
+}
+
+#[tokio::test]
+async fn auth_token_should_verify_against_published_key() {
+    let app = TestApp::new().await;
+    let token = login(&app).await;
+
+    let header = decode_header(&token).unwrap();
+    let kid = header.kid.expect("No kid in token header");
+    let jwks = get_jwks(&app).await;
+    let jwk = jwks.find(&kid).expect("Signing key not published");
+
+    let claims = decode::<Claims>(
+        &token,
+        &DecodingKey::from_jwk(jwk).unwrap(),
+        &Validation::new(header.alg),
+    );
+    assert!(claims.is_ok());
+}
+
+#[tokio::test]
+async fn next_key_should_be_published_before_it_signs() {
+    let app = TestApp::new().await;
+    let now = chrono::Utc::now().timestamp();
+
+    // Jump to shortly before the scheduled rotation.
+    app.key_ring.write().await.rotate(now + 86_400 - 3_600).unwrap();
+
+    let jwks = get_jwks(&app).await;
+    assert_eq!(jwks.keys.len(), 2);
+
+    // The current key keeps signing until the new one activates.
+    let token = login(&app).await;
+    let kid = decode_header(&token).unwrap().kid.unwrap();
+    assert_eq!(jwks.keys[0].common.key_id.as_ref(), Some(&kid));
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
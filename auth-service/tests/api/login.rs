@@ -1,10 +1,202 @@
+use crate::get_random_email::get_random_email;
 use crate::helpers::TestApp;
+use auth_service::domain::{Email, Password, PasswordHash, PasswordPolicy};
+use auth_service::routes::{ErrorResponse, TwoFactorAuthResponse};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
 
 // localhost:3000/login
 #[tokio::test]
-async fn login_returns_ok_status() {
+async fn should_return_422_if_malformed_credentials() {
     let app = TestApp::new().await;
+    let random_email = get_random_email();
 
-    let response = app.post_login().await;
+    let test_cases = [
+        serde_json::json!({
+            "password": "password123",
+        }),
+        serde_json::json!({
+            "email": random_email,
+        }),
+        serde_json::json!({}),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_login(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let test_cases = [
+        serde_json::json!({
+            "email": "invalid_email",
+            "password": "passworD123!",
+        }),
+        serde_json::json!({
+            "email": random_email,
+            "password": "",
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_login(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid credentials".to_owned()
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_credentials() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    });
+    app.post_signup(&signup_body).await;
+
+    let test_cases = [
+        serde_json::json!({
+            "email": random_email,
+            "password": "wrongPassw0rd!",
+        }),
+        serde_json::json!({
+            "email": get_random_email(),
+            "password": "passworD123!",
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_login(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_not_apply_the_password_policy_at_login() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // The user chose a password back when the minimum length was lower, and
+    // it has since been raised past it.
+    let lax_policy = PasswordPolicy {
+        min_length: 4,
+        ..PasswordPolicy::default()
+    };
+    let password = Password::parse_with("Pw1!", &lax_policy).unwrap();
+    assert!(Password::parse("Pw1!").is_err());
+    let email = Email::parse(&random_email).unwrap();
+    app.user_store
+        .update_password_hash(&email, PasswordHash::hash(&password))
+        .await
+        .unwrap();
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "Pw1!",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+    });
+    let response = app.post_login(&login_body).await;
     assert_eq!(response.status().as_u16(), 200);
-}
\ No newline at end of file
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[tokio::test]
+async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let signup_body = serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": true
+    });
+    let response = app.post_signup(&signup_body).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+    });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(json_body.message, "2FA required".to_owned());
+
+    let email = Email::parse(&random_email).unwrap();
+    let (login_attempt_id, _) = app
+        .two_fa_code_store
+        .get_code(&email)
+        .await
+        .expect("No 2FA code stored for login attempt");
+    assert_eq!(json_body.login_attempt_id, login_attempt_id.as_ref());
+}
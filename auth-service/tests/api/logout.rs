@@ -1,10 +1,97 @@
+use crate::get_random_email::get_random_email;
 use crate::helpers::TestApp;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use reqwest::Url;
 
 // localhost:3000/logout
 #[tokio::test]
-async fn logout_returns_ok_status() {
+async fn should_return_400_if_jwt_cookie_missing() {
     let app = TestApp::new().await;
 
+    let response = app.delete_logout().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let app = TestApp::new().await;
+
+    // add invalid cookie
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}=invalid; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME
+        ),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+
+    let response = app.delete_logout().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_200_if_valid_jwt_cookie() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    app.post_signup(&serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+
+    // The cookie has been cleared so logging out again fails.
+    let response = app.delete_logout().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_revoke_token_on_logout() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    app.post_signup(&serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "passworD123!",
+        }))
+        .await;
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
     let response = app.delete_logout().await;
     assert_eq!(response.status().as_u16(), 200);
-}
\ No newline at end of file
+
+    // The token is still signed and unexpired, but its session is gone.
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,160 @@
+use crate::contract::SendChecked;
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::routes::{ErrorResponse, MagicLinkResponse, TwoFactorAuthResponse};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool) {
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "passworD123!",
+        "requires2FA": requires_2fa
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+}
+
+// The token from the link in the last email sent.
+fn magic_link_token(app: &TestApp) -> String {
+    let email = app.sent_emails().pop().expect("No email was sent");
+    let link = email
+        .content
+        .split_whitespace()
+        .find(|word| word.starts_with("http"))
+        .expect("Email has no link");
+    let link = url::Url::parse(link).expect("Invalid link");
+    link.query_pairs()
+        .find(|(name, _)| name == "magicLinkToken")
+        .map(|(_, token)| token.into_owned())
+        .expect("Link has no token")
+}
+
+#[tokio::test]
+async fn should_sign_in_with_emailed_link() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email, false).await;
+
+    let response = app
+        .post_magic_link(&serde_json::json!({ "email": random_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let email = app.sent_emails().pop().expect("No email was sent");
+    assert_eq!(email.recipient, random_email);
+
+    let response = app
+        .post_magic_link_verify(&serde_json::json!({ "token": magic_link_token(&app) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[tokio::test]
+async fn should_return_401_if_link_is_followed_twice() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email, false).await;
+
+    app.post_magic_link(&serde_json::json!({ "email": random_email }))
+        .await;
+    let body = serde_json::json!({ "token": magic_link_token(&app) });
+    assert_eq!(app.post_magic_link_verify(&body).await.status().as_u16(), 200);
+
+    let response = app.post_magic_link_verify(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid auth token".to_owned()
+    );
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_is_invalid() {
+    let app = TestApp::new().await;
+
+    for token in ["", "invalid_token"] {
+        let response = app
+            .post_magic_link_verify(&serde_json::json!({ "token": token }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401, "Failed for token: {:?}", token);
+    }
+}
+
+#[tokio::test]
+async fn should_return_206_if_user_requires_2fa() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email, true).await;
+
+    app.post_magic_link(&serde_json::json!({ "email": random_email }))
+        .await;
+    let response = app
+        .post_magic_link_verify(&serde_json::json!({ "token": magic_link_token(&app) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    assert_eq!(json_body.message, "2FA required".to_owned());
+    // The 2FA code follows the link into the same inbox.
+    assert_eq!(app.sent_emails().last().unwrap().subject, "2FA Code");
+}
+
+#[tokio::test]
+async fn should_only_accept_bound_link_in_requesting_browser() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup(&app, &random_email, false).await;
+
+    app.post_magic_link(&serde_json::json!({ "email": random_email, "sameBrowser": true }))
+        .await;
+    let body = serde_json::json!({ "token": magic_link_token(&app) });
+
+    // A client without the binding cookie stands in for another browser.
+    let response = reqwest::Client::new()
+        .post(format!("{}/login/magic-link/verify", &app.address))
+        .json(&body)
+        .send_checked()
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+
+    // The failed attempt doesn't use the link up.
+    assert_eq!(app.post_magic_link_verify(&body).await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_not_reveal_whether_account_exists() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_magic_link(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<MagicLinkResponse>()
+        .await
+        .expect("Could not deserialize response body to MagicLinkResponse");
+    assert!(app.sent_emails().is_empty());
+}
+
+#[tokio::test]
+async fn should_return_400_if_email_is_invalid() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_magic_link(&serde_json::json!({ "email": "invalid_email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -1,8 +1,26 @@
 mod helpers;
+mod contract;
+mod admin;
+mod audit;
+mod auth_client;
+mod authctl;
+mod change_password;
+mod grpc;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod oidc;
+mod openapi;
+mod recovery_codes;
 mod root;
+mod service_accounts;
+mod sessions;
 mod signup;
+mod software_authenticator;
+mod totp;
+mod user_import;
 mod verify_2fa;
 mod verify_token;
-mod get_random_email;
\ No newline at end of file
+mod webauthn;
+mod get_random_email;
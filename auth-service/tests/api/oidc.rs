@@ -0,0 +1,573 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::domain::{Email, OidcClient, PkceChallenge};
+use auth_service::routes::{
+    IntrospectionResponse, OAuthErrorResponse, OpenIdConfiguration, TokenResponse,
+    UserInfoResponse,
+};
+use auth_service::utils::auth::IdTokenClaims;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
+use reqwest::cookie::CookieStore;
+use std::collections::HashMap;
+use url::Url;
+
+const CLIENT_ID: &str = "app-service";
+const CLIENT_SECRET: &str = "app-service-secret";
+const PUBLIC_CLIENT_ID: &str = "dashboard";
+const REDIRECT_URI: &str = "http://localhost:8000/callback";
+const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+
+async fn register_clients(app: &TestApp) {
+    let store = &app.oidc_client_store;
+    store
+        .add_client(OidcClient::new(
+            CLIENT_ID.to_owned(),
+            "App service".to_owned(),
+            Some(CLIENT_SECRET),
+            vec![REDIRECT_URI.to_owned()],
+        ))
+        .await
+        .unwrap();
+    store
+        .add_client(OidcClient::new(
+            PUBLIC_CLIENT_ID.to_owned(),
+            "Dashboard".to_owned(),
+            None,
+            vec![REDIRECT_URI.to_owned()],
+        ))
+        .await
+        .unwrap();
+}
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+fn authorize_query(client_id: &str) -> HashMap<&'static str, String> {
+    HashMap::from([
+        ("response_type", "code".to_owned()),
+        ("client_id", client_id.to_owned()),
+        ("redirect_uri", REDIRECT_URI.to_owned()),
+        ("scope", "openid email".to_owned()),
+        ("state", "af0ifjsldkj".to_owned()),
+        ("nonce", "n-0S6_WzA2Mj".to_owned()),
+        (
+            "code_challenge",
+            PkceChallenge::from_verifier(CODE_VERIFIER).as_ref().to_owned(),
+        ),
+        ("code_challenge_method", "S256".to_owned()),
+    ])
+}
+
+fn redirect_location(response: &reqwest::Response) -> Url {
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .expect("No Location header")
+        .to_str()
+        .unwrap();
+    Url::parse(location)
+        .or_else(|_| Url::parse("http://auth.local").unwrap().join(location))
+        .unwrap()
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+async fn authorization_code(app: &TestApp, client_id: &str) -> String {
+    let response = app.get_authorize(&authorize_query(client_id)).await;
+    let location = redirect_location(&response);
+
+    assert!(location.as_str().starts_with(REDIRECT_URI));
+    assert_eq!(query_param(&location, "state").as_deref(), Some("af0ifjsldkj"));
+    query_param(&location, "code").expect("No code in redirect")
+}
+
+fn token_form(code: &str, verifier: &str) -> HashMap<&'static str, String> {
+    HashMap::from([
+        ("grant_type", "authorization_code".to_owned()),
+        ("code", code.to_owned()),
+        ("redirect_uri", REDIRECT_URI.to_owned()),
+        ("code_verifier", verifier.to_owned()),
+    ])
+}
+
+async fn access_token(app: &TestApp) -> String {
+    let code = authorization_code(app, CLIENT_ID).await;
+    let response = app
+        .post_token(&token_form(&code, CODE_VERIFIER), Some((CLIENT_ID, CLIENT_SECRET)))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .access_token
+}
+
+async fn introspect(app: &TestApp, token: &str) -> IntrospectionResponse {
+    let response = app
+        .post_introspect(
+            &HashMap::from([("token", token)]),
+            Some((CLIENT_ID, CLIENT_SECRET)),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse")
+}
+
+async fn oauth_error(response: reqwest::Response) -> String {
+    response
+        .json::<OAuthErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthErrorResponse")
+        .error
+}
+
+// localhost:3000/.well-known/openid-configuration
+#[tokio::test]
+async fn should_serve_discovery_document() {
+    let app = TestApp::new().await;
+
+    let response = app.get_openid_configuration().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let configuration = response
+        .json::<OpenIdConfiguration>()
+        .await
+        .expect("Could not deserialize response body to OpenIdConfiguration");
+    assert_eq!(
+        configuration.authorization_endpoint,
+        format!("{}/authorize", configuration.issuer)
+    );
+    assert_eq!(configuration.token_endpoint, format!("{}/token", configuration.issuer));
+    assert_eq!(
+        configuration.introspection_endpoint,
+        format!("{}/introspect", configuration.issuer)
+    );
+    assert_eq!(configuration.code_challenge_methods_supported, vec!["S256"]);
+    assert_eq!(
+        configuration.jwks_uri,
+        format!("{}/.well-known/jwks.json", configuration.issuer)
+    );
+    assert_eq!(configuration.id_token_signing_alg_values_supported, vec!["EdDSA"]);
+}
+
+// localhost:3000/authorize
+#[tokio::test]
+async fn authorize_should_not_redirect_to_unregistered_uris() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+
+    let response = app.get_authorize(&authorize_query("unknown")).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(oauth_error(response).await, "invalid_client");
+
+    let mut query = authorize_query(CLIENT_ID);
+    query.insert("redirect_uri", "https://evil.example.com/callback".to_owned());
+    let response = app.get_authorize(&query).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_request");
+}
+
+#[tokio::test]
+async fn authorize_should_redirect_errors_to_client() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let mut without_pkce = authorize_query(CLIENT_ID);
+    without_pkce.remove("code_challenge");
+    let mut plain_pkce = authorize_query(CLIENT_ID);
+    plain_pkce.insert("code_challenge_method", "plain".to_owned());
+    let mut without_openid = authorize_query(CLIENT_ID);
+    without_openid.insert("scope", "email".to_owned());
+    let mut token_response = authorize_query(CLIENT_ID);
+    token_response.insert("response_type", "token".to_owned());
+
+    let test_cases = [
+        (without_pkce, "invalid_request"),
+        (plain_pkce, "invalid_request"),
+        (without_openid, "invalid_scope"),
+        (token_response, "unsupported_response_type"),
+    ];
+
+    for (query, error) in test_cases.iter() {
+        let location = redirect_location(&app.get_authorize(query).await);
+        assert!(location.as_str().starts_with(REDIRECT_URI));
+        assert_eq!(query_param(&location, "error").as_deref(), Some(*error));
+        assert_eq!(query_param(&location, "state").as_deref(), Some("af0ifjsldkj"));
+    }
+}
+
+#[tokio::test]
+async fn authorize_should_send_anonymous_users_to_login_page() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+
+    let location = redirect_location(&app.get_authorize(&authorize_query(CLIENT_ID)).await);
+    assert_eq!(location.path(), "/");
+    let next = query_param(&location, "next").expect("No next parameter");
+    assert!(next.starts_with("/authorize?"));
+
+    let mut query = authorize_query(CLIENT_ID);
+    query.insert("prompt", "none".to_owned());
+    let location = redirect_location(&app.get_authorize(&query).await);
+    assert!(location.as_str().starts_with(REDIRECT_URI));
+    assert_eq!(query_param(&location, "error").as_deref(), Some("login_required"));
+}
+
+// localhost:3000/token
+#[tokio::test]
+async fn should_complete_authorization_code_flow() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let code = authorization_code(&app, CLIENT_ID).await;
+    let response = app
+        .post_token(&token_form(&code, CODE_VERIFIER), Some((CLIENT_ID, CLIENT_SECRET)))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get(reqwest::header::CACHE_CONTROL).unwrap(),
+        "no-store"
+    );
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.scope, "openid email");
+
+    // Relying parties verify ID tokens against the published keys.
+    let jwks = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+    let id_token = tokens.id_token.expect("No ID token issued");
+    let header = decode_header(&id_token).unwrap();
+    let jwk = jwks.find(&header.kid.unwrap()).expect("Signing key not published");
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[CLIENT_ID]);
+    let id_token = decode::<IdTokenClaims>(
+        &id_token,
+        &DecodingKey::from_jwk(jwk).unwrap(),
+        &validation,
+    )
+    .expect("ID token is not valid")
+    .claims;
+    assert_eq!(id_token.email, random_email);
+    assert_eq!(id_token.nonce.as_deref(), Some("n-0S6_WzA2Mj"));
+
+    let response = app.get_userinfo(&tokens.access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let userinfo = response
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+    assert_eq!(userinfo.email.as_deref(), Some(random_email.as_str()));
+}
+
+#[tokio::test]
+async fn access_token_should_not_authenticate_as_session_cookie() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    signup_and_login(&app, &get_random_email()).await;
+    let access_token = access_token(&app).await;
+
+    app.cookie_jar.add_cookie_str(
+        &format!("{}={}; HttpOnly; SameSite=Lax; Path=/", JWT_COOKIE_NAME, access_token),
+        &Url::parse(&app.address).expect("Failed to parse URL"),
+    );
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn token_should_reject_reused_code() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let code = authorization_code(&app, CLIENT_ID).await;
+    let form = token_form(&code, CODE_VERIFIER);
+
+    let response = app.post_token(&form, Some((CLIENT_ID, CLIENT_SECRET))).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_token(&form, Some((CLIENT_ID, CLIENT_SECRET))).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_grant");
+}
+
+#[tokio::test]
+async fn token_should_reject_wrong_code_verifier() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let code = authorization_code(&app, CLIENT_ID).await;
+    let form = token_form(&code, &"a".repeat(43));
+
+    let response = app.post_token(&form, Some((CLIENT_ID, CLIENT_SECRET))).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_grant");
+}
+
+#[tokio::test]
+async fn token_should_authenticate_clients() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let code = authorization_code(&app, CLIENT_ID).await;
+    let form = token_form(&code, CODE_VERIFIER);
+    let response = app.post_token(&form, Some((CLIENT_ID, "wrong-secret"))).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(oauth_error(response).await, "invalid_client");
+
+    // A code issued to one client can't be redeemed by another.
+    let mut form = form;
+    form.insert("client_id", PUBLIC_CLIENT_ID.to_owned());
+    let response = app.post_token(&form, None).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_grant");
+}
+
+#[tokio::test]
+async fn public_client_should_redeem_code_with_pkce_only() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let code = authorization_code(&app, PUBLIC_CLIENT_ID).await;
+    let mut form = token_form(&code, CODE_VERIFIER);
+    form.insert("client_id", PUBLIC_CLIENT_ID.to_owned());
+
+    let response = app.post_token(&form, None).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn authorize_should_accept_session_from_2fa_login() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    let random_email = get_random_email();
+
+    app.post_signup(&serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": true
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    // Until the second factor is verified there is no session to authorize.
+    let location = redirect_location(&app.get_authorize(&authorize_query(CLIENT_ID)).await);
+    assert_eq!(location.path(), "/");
+
+    let (login_attempt_id, code) = app
+        .two_fa_code_store
+        .get_code(&Email::parse(&random_email).unwrap())
+        .await
+        .unwrap();
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    authorization_code(&app, CLIENT_ID).await;
+}
+
+// localhost:3000/userinfo
+#[tokio::test]
+async fn userinfo_should_return_401_for_invalid_token() {
+    let app = TestApp::new().await;
+
+    let response = app.get_userinfo("invalid").await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(response
+        .headers()
+        .get(reqwest::header::WWW_AUTHENTICATE)
+        .is_some());
+}
+
+#[tokio::test]
+async fn userinfo_should_return_401_for_session_token() {
+    let app = TestApp::new().await;
+    signup_and_login(&app, &get_random_email()).await;
+    let cookies = app
+        .cookie_jar
+        .cookies(&Url::parse(&app.address).expect("Failed to parse URL"))
+        .expect("No cookies stored");
+    let session_token = cookies
+        .to_str()
+        .unwrap()
+        .split("; ")
+        .find_map(|cookie| cookie.strip_prefix(&format!("{}=", JWT_COOKIE_NAME)))
+        .expect("No auth cookie found")
+        .to_owned();
+
+    let response = app.get_userinfo(&session_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn userinfo_should_omit_email_without_email_scope() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let mut query = authorize_query(CLIENT_ID);
+    query.insert("scope", "openid".to_owned());
+    let response = app.get_authorize(&query).await;
+    let code = query_param(&redirect_location(&response), "code").expect("No code in redirect");
+    let response = app
+        .post_token(&token_form(&code, CODE_VERIFIER), Some((CLIENT_ID, CLIENT_SECRET)))
+        .await;
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert_eq!(tokens.scope, "openid");
+
+    let response = app.get_userinfo(&tokens.access_token).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let userinfo = response
+        .json::<UserInfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserInfoResponse");
+    assert_eq!(userinfo.email, None);
+}
+
+// localhost:3000/introspect
+#[tokio::test]
+async fn introspect_should_describe_active_access_token() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let access_token = access_token(&app).await;
+
+    let introspection = introspect(&app, &access_token).await;
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_deref(), Some(random_email.as_str()));
+    assert_eq!(introspection.client_id.as_deref(), Some(CLIENT_ID));
+    assert_eq!(introspection.scope.as_deref(), Some("openid email"));
+    assert_eq!(introspection.token_type.as_deref(), Some("Bearer"));
+    assert!(introspection.exp.unwrap() > introspection.iat.unwrap());
+    let session = introspection.session.expect("No session in introspection");
+    assert!(session.expires_at > session.created_at);
+}
+
+#[tokio::test]
+async fn introspect_should_report_revoked_and_invalid_tokens_as_inactive() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    signup_and_login(&app, &get_random_email()).await;
+    let access_token = access_token(&app).await;
+
+    let session = introspect(&app, &access_token)
+        .await
+        .session
+        .expect("No session in introspection");
+    let response = app.delete_session(&session.id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let inactive = IntrospectionResponse::default();
+    assert_eq!(introspect(&app, &access_token).await, inactive);
+    assert_eq!(introspect(&app, "invalid").await, inactive);
+}
+
+#[tokio::test]
+async fn introspect_should_only_answer_confidential_clients() {
+    let app = TestApp::new().await;
+    register_clients(&app).await;
+    signup_and_login(&app, &get_random_email()).await;
+    let access_token = access_token(&app).await;
+    let form = HashMap::from([("token", access_token.as_str())]);
+
+    let response = app.post_introspect(&form, None).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(oauth_error(response).await, "invalid_client");
+
+    let response = app
+        .post_introspect(&form, Some((CLIENT_ID, "wrong-secret")))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(oauth_error(response).await, "invalid_client");
+
+    let mut form = form;
+    form.insert("client_id", PUBLIC_CLIENT_ID);
+    let response = app.post_introspect(&form, None).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(oauth_error(response).await, "invalid_client");
+
+    let response = app
+        .post_introspect(&HashMap::<&str, &str>::new(), Some((CLIENT_ID, CLIENT_SECRET)))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_request");
+}
+
+#[tokio::test]
+async fn basic_credentials_should_be_form_urldecoded() {
+    let app = TestApp::new().await;
+    app.oidc_client_store
+        .add_client(OidcClient::new(
+            "reports:api".to_owned(),
+            "Reports".to_owned(),
+            Some("s3cret: with+special%chars"),
+            vec![REDIRECT_URI.to_owned()],
+        ))
+        .await
+        .unwrap();
+    let form = HashMap::from([("token", "invalid")]);
+
+    let response = app
+        .post_introspect(&form, Some(("reports%3Aapi", "s3cret%3A+with%2Bspecial%25chars")))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Undecoded, the secret is a different one.
+    let response = app
+        .post_introspect(&form, Some(("reports%3Aapi", "s3cret: with+special%chars")))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
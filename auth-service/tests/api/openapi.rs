@@ -0,0 +1,58 @@
+use crate::helpers::TestApp;
+use auth_service::routes::ApiDoc;
+use utoipa::OpenApi;
+
+const SCHEMA_HEADER: &str = "\
+# Generated from the route types, don't edit by hand. To update it after changing
+# a route, run `UPDATE_API_SCHEMA=1 cargo test --test api openapi`.
+# Visualize schema at: https://editor.swagger.io/ or /swagger-ui on a running service.
+";
+
+fn generated_schema() -> String {
+    let yaml = ApiDoc::openapi()
+        .to_yaml()
+        .expect("Could not serialize the OpenAPI document");
+    format!("{}{}", SCHEMA_HEADER, yaml)
+}
+
+#[test]
+fn checked_in_schema_matches_the_routes() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/api_schema.yml");
+    let generated = generated_schema();
+
+    if std::env::var_os("UPDATE_API_SCHEMA").is_some() {
+        std::fs::write(path, &generated).expect("Could not write api_schema.yml");
+        return;
+    }
+
+    let checked_in = std::fs::read_to_string(path).expect("Could not read api_schema.yml");
+    assert!(
+        checked_in == generated,
+        "api_schema.yml is out of date, run `UPDATE_API_SCHEMA=1 cargo test --test api openapi`"
+    );
+}
+
+#[tokio::test]
+async fn openapi_json_serves_the_generated_document() {
+    let app = TestApp::new().await;
+
+    let response = app.get_openapi().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let document = response
+        .json::<serde_json::Value>()
+        .await
+        .expect("Could not deserialize response body to JSON");
+    assert_eq!(document, serde_json::to_value(ApiDoc::openapi()).unwrap());
+}
+
+#[tokio::test]
+async fn swagger_ui_is_served() {
+    let app = TestApp::new().await;
+
+    let response = app.get_swagger_ui().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let content_type = response.headers().get("content-type").unwrap();
+    assert!(content_type.to_str().unwrap().starts_with("text/html"));
+}
@@ -0,0 +1,242 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::domain::{
+    Email, User, UserChange, UserPage, UserQuery, UserStore, UserStoreError, RECOVERY_CODE_COUNT,
+};
+use auth_service::services::HashmapUserStore;
+use futures_util::future::join_all;
+use std::sync::Arc;
+use std::time::Duration;
+use auth_service::routes::{
+    RecoveryCodeLoginResponse, RecoveryCodesResponse, RecoveryCodesStatusResponse,
+    SignupResponse, TwoFactorAuthResponse,
+};
+
+async fn signup_with_2fa(app: &TestApp, email: &str) -> Vec<String> {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes
+        .expect("No recovery codes issued")
+}
+
+async fn verify_with_code(app: &TestApp, email: &str, code: &str) -> reqwest::Response {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    app.post_verify_2fa(&serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    }))
+    .await
+}
+
+// localhost:3000/verify-2fa
+#[tokio::test]
+async fn recovery_code_can_replace_2fa_code_once() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    let codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = verify_with_code(&app, &random_email, &codes[0].to_uppercase()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<RecoveryCodeLoginResponse>()
+            .await
+            .expect("Could not deserialize response body to RecoveryCodeLoginResponse")
+            .remaining_recovery_codes,
+        RECOVERY_CODE_COUNT - 1
+    );
+
+    let response = verify_with_code(&app, &random_email, &codes[0]).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+// Takes a while to read users, so that requests which read a user and then
+// write it back overlap.
+#[derive(Default)]
+struct SlowReadUserStore(HashmapUserStore);
+
+#[async_trait::async_trait]
+impl UserStore for SlowReadUserStore {
+    async fn add_user(&self, user: User) -> Result<(), UserStoreError> {
+        self.0.add_user(user).await
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        self.0.get_user(email).await
+    }
+
+    async fn update_user(&self, user: User) -> Result<(), UserStoreError> {
+        self.0.update_user(user).await
+    }
+
+    async fn delete_user(&self, email: &Email) -> Result<(), UserStoreError> {
+        self.0.delete_user(email).await
+    }
+
+    async fn list_users(&self, query: &UserQuery) -> Result<UserPage, UserStoreError> {
+        self.0.list_users(query).await
+    }
+
+    async fn modify_user(&self, email: &Email, change: UserChange<'_>) -> Result<User, UserStoreError> {
+        self.0.modify_user(email, change).await
+    }
+}
+
+#[tokio::test]
+async fn recovery_code_redeemed_concurrently_only_succeeds_once() {
+    let app = TestApp::with_user_store(Arc::new(SlowReadUserStore::default())).await;
+    let random_email = get_random_email();
+    let codes = signup_with_2fa(&app, &random_email).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "passworD123!",
+        }))
+        .await;
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": codes[0],
+    });
+
+    let responses = join_all((0..8).map(|_| app.post_verify_2fa(&body))).await;
+    let statuses: Vec<u16> = responses.iter().map(|r| r.status().as_u16()).collect();
+    assert_eq!(statuses.iter().filter(|&&status| status == 200).count(), 1, "{:?}", statuses);
+    assert!(statuses.iter().all(|&status| status == 200 || status == 401), "{:?}", statuses);
+}
+
+// localhost:3000/2fa/recovery-codes
+#[tokio::test]
+async fn status_should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.get_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn status_should_return_remaining_count() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    let codes = signup_with_2fa(&app, &random_email).await;
+    verify_with_code(&app, &random_email, &codes[3]).await;
+
+    let response = app.get_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<RecoveryCodesStatusResponse>()
+            .await
+            .expect("Could not deserialize response body to RecoveryCodesStatusResponse"),
+        RecoveryCodesStatusResponse {
+            remaining: RECOVERY_CODE_COUNT - 1,
+            total: RECOVERY_CODE_COUNT,
+        }
+    );
+}
+
+#[tokio::test]
+async fn regenerate_should_invalidate_previous_codes() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    let old_codes = signup_with_2fa(&app, &random_email).await;
+    verify_with_code(&app, &random_email, &old_codes[0]).await;
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let new_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+    assert_eq!(new_codes.len(), RECOVERY_CODE_COUNT);
+
+    let response = verify_with_code(&app, &random_email, &old_codes[1]).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = verify_with_code(&app, &random_email, &new_codes[1]).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn regenerate_should_return_400_if_2fa_disabled() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    app.post_login(&serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+    }))
+    .await;
+
+    let response = app.post_recovery_codes().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+// localhost:3000/2fa/method
+#[tokio::test]
+async fn enabling_2fa_issues_recovery_codes() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    app.post_login(&serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+    }))
+    .await;
+
+    let response = app
+        .put_2fa_method(&serde_json::json!({ "requires2FA": true, "method": "email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+    assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+
+    let response = verify_with_code(&app, &random_email, &codes[0]).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -0,0 +1,266 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::domain::{OidcClient, Role, User};
+use auth_service::routes::{
+    IntrospectionResponse, OAuthErrorResponse, ServiceAccountCredentialsResponse,
+    ServiceAccountsResponse, TokenResponse, VerifyTokenResponse,
+};
+use std::collections::HashMap;
+
+const PASSWORD: &str = "passworD123!";
+
+async fn login_as(app: &TestApp, roles: Vec<Role>) {
+    let email = get_random_email();
+    let mut user = User::new(email.clone(), PASSWORD.to_owned(), false);
+    user.roles = roles;
+    app.user_store
+        .add_user(user)
+        .await
+        .expect("Failed to add user");
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn credentials(response: reqwest::Response) -> ServiceAccountCredentialsResponse {
+    response
+        .json::<ServiceAccountCredentialsResponse>()
+        .await
+        .expect("Could not deserialize response body to ServiceAccountCredentialsResponse")
+}
+
+async fn create_account(app: &TestApp) -> ServiceAccountCredentialsResponse {
+    let response = app
+        .post_admin_service_account(&serde_json::json!({
+            "name": "Nightly report",
+            "scopes": ["reports:read", "reports:write"],
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    credentials(response).await
+}
+
+async fn request_token(
+    app: &TestApp,
+    client_id: &str,
+    client_secret: &str,
+    scope: Option<&str>,
+) -> reqwest::Response {
+    let mut form = HashMap::from([("grant_type", "client_credentials")]);
+    if let Some(scope) = scope {
+        form.insert("scope", scope);
+    }
+    app.post_token(&form, Some((client_id, client_secret))).await
+}
+
+async fn access_token(app: &TestApp, credentials: &ServiceAccountCredentialsResponse) -> TokenResponse {
+    let response = request_token(
+        app,
+        &credentials.service_account.client_id,
+        &credentials.client_secret,
+        None,
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+}
+
+async fn oauth_error(response: reqwest::Response) -> String {
+    response
+        .json::<OAuthErrorResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthErrorResponse")
+        .error
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+// localhost:3000/admin/service-accounts
+#[tokio::test]
+async fn should_return_403_for_non_admin() {
+    let app = TestApp::new().await;
+    login_as(&app, Vec::new()).await;
+
+    assert_eq!(app.get_admin_service_accounts().await.status().as_u16(), 403);
+    let response = app
+        .post_admin_service_account(&serde_json::json!({ "name": "Batch", "scopes": [] }))
+        .await;
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn should_create_and_list_service_accounts() {
+    let app = TestApp::new().await;
+    login_as(&app, vec![Role::admin()]).await;
+
+    let created = create_account(&app).await;
+    assert_eq!(created.service_account.name, "Nightly report");
+    assert!(!created.client_secret.is_empty());
+
+    let response = app.get_admin_service_accounts().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let accounts = response
+        .json::<ServiceAccountsResponse>()
+        .await
+        .expect("Could not deserialize response body to ServiceAccountsResponse");
+    assert_eq!(accounts.service_accounts, vec![created.service_account]);
+
+    for body in [
+        serde_json::json!({ "name": " ", "scopes": [] }),
+        serde_json::json!({ "name": "Batch", "scopes": ["two words"] }),
+    ] {
+        let response = app.post_admin_service_account(&body).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", body);
+    }
+}
+
+// localhost:3000/token
+#[tokio::test]
+async fn should_issue_scoped_tokens_that_verify_token_accepts() {
+    let app = TestApp::new().await;
+    login_as(&app, vec![Role::admin()]).await;
+    let created = create_account(&app).await;
+    let client_id = &created.service_account.client_id;
+
+    let tokens = access_token(&app, &created).await;
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.scope, "reports:read reports:write");
+    assert_eq!(tokens.id_token, None);
+    assert_eq!(verify_token_status(&app, &tokens.access_token).await, 200);
+
+    let response = request_token(&app, client_id, &created.client_secret, Some("reports:read")).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+    assert_eq!(tokens.scope, "reports:read");
+    let verified = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse");
+    assert_eq!(
+        verified,
+        VerifyTokenResponse {
+            sub: client_id.clone(),
+            client_id: Some(client_id.clone()),
+            scope: Some("reports:read".to_owned()),
+        }
+    );
+
+    let response = request_token(&app, client_id, &created.client_secret, Some("users:write")).await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(oauth_error(response).await, "invalid_scope");
+
+    let response = request_token(&app, client_id, "wrong-secret", None).await;
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(oauth_error(response).await, "invalid_client");
+}
+
+#[tokio::test]
+async fn service_account_tokens_should_not_open_user_routes() {
+    let app = TestApp::new().await;
+    login_as(&app, vec![Role::admin()]).await;
+    let created = create_account(&app).await;
+    let tokens = access_token(&app, &created).await;
+
+    let response = app.get_userinfo(&tokens.access_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+// localhost:3000/introspect
+#[tokio::test]
+async fn introspect_should_describe_service_account_tokens() {
+    let app = TestApp::new().await;
+    login_as(&app, vec![Role::admin()]).await;
+    app.oidc_client_store
+        .add_client(OidcClient::new(
+            "reports".to_owned(),
+            "Reports".to_owned(),
+            Some("reports-secret"),
+            Vec::new(),
+        ))
+        .await
+        .unwrap();
+    let created = create_account(&app).await;
+    let tokens = access_token(&app, &created).await;
+
+    let response = app
+        .post_introspect(
+            &HashMap::from([("token", tokens.access_token.as_str())]),
+            Some(("reports", "reports-secret")),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let introspection = response
+        .json::<IntrospectionResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectionResponse");
+    assert!(introspection.active);
+    assert_eq!(introspection.sub.as_ref(), Some(&created.service_account.client_id));
+    assert_eq!(introspection.client_id.as_ref(), Some(&created.service_account.client_id));
+    assert_eq!(introspection.scope.as_deref(), Some("reports:read reports:write"));
+    assert_eq!(introspection.session, None);
+}
+
+// localhost:3000/admin/service-accounts/{client_id}/rotate-secret
+#[tokio::test]
+async fn rotating_the_secret_should_revoke_old_secret_and_tokens() {
+    let app = TestApp::new().await;
+    login_as(&app, vec![Role::admin()]).await;
+    let created = create_account(&app).await;
+    let client_id = &created.service_account.client_id;
+    let old_token = access_token(&app, &created).await.access_token;
+
+    // Rotation is recorded in whole seconds, and only tokens issued before
+    // the second it happens in are revoked.
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let response = app.post_admin_service_account_rotate_secret(client_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let rotated = credentials(response).await;
+    assert_ne!(rotated.client_secret, created.client_secret);
+
+    assert_eq!(verify_token_status(&app, &old_token).await, 401);
+    let response = request_token(&app, client_id, &created.client_secret, None).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let new_token = access_token(&app, &rotated).await.access_token;
+    assert_eq!(verify_token_status(&app, &new_token).await, 200);
+
+    let response = app.post_admin_service_account_rotate_secret("unknown").await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+// localhost:3000/admin/service-accounts/{client_id}
+#[tokio::test]
+async fn deleting_an_account_should_revoke_its_tokens() {
+    let app = TestApp::new().await;
+    login_as(&app, vec![Role::admin()]).await;
+    let created = create_account(&app).await;
+    let client_id = &created.service_account.client_id;
+    let token = access_token(&app, &created).await.access_token;
+
+    let response = app.delete_admin_service_account(client_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+    let response = request_token(&app, client_id, &created.client_secret, None).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.delete_admin_service_account(client_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
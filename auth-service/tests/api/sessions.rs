@@ -0,0 +1,183 @@
+use crate::contract::SendChecked;
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::routes::SessionsResponse;
+use auth_service::utils::{auth::validate_token, constants::JWT_COOKIE_NAME};
+
+async fn signup_and_login(app: &TestApp, email: &str) -> String {
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    auth_token(&response)
+}
+
+// Logs in from a client that doesn't share the test app's cookie jar.
+async fn login_elsewhere(app: &TestApp, email: &str) -> String {
+    let response = reqwest::Client::builder()
+        .user_agent("Other device")
+        .build()
+        .expect("Failed to build reqwest client")
+        .post(format!("{}/login", &app.address))
+        .json(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .send_checked()
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    auth_token(&response)
+}
+
+fn auth_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned()
+}
+
+async fn list_sessions(app: &TestApp) -> SessionsResponse {
+    let response = app.get_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<SessionsResponse>()
+        .await
+        .expect("Could not deserialize response body to SessionsResponse")
+}
+
+async fn verify_token_status(app: &TestApp, token: &str) -> u16 {
+    app.post_verify_token(&serde_json::json!({ "token": token }))
+        .await
+        .status()
+        .as_u16()
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    assert_eq!(app.get_sessions().await.status().as_u16(), 400);
+    assert_eq!(app.delete_sessions().await.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_list_sessions_of_the_user() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    signup_and_login(&app, &email).await;
+    login_elsewhere(&app, &email).await;
+    login_elsewhere(&app, &email).await;
+
+    // Sessions of other users aren't listed.
+    let other_email = get_random_email();
+    app.post_signup(&serde_json::json!({
+        "email": other_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    login_elsewhere(&app, &other_email).await;
+
+    let sessions = list_sessions(&app).await.sessions;
+    assert_eq!(sessions.len(), 3);
+    assert_eq!(sessions.iter().filter(|session| session.current).count(), 1);
+
+    let current = sessions.iter().find(|session| session.current).unwrap();
+    assert_eq!(current.ip_address.as_deref(), Some("127.0.0.1"));
+    let other = sessions.iter().find(|session| !session.current).unwrap();
+    assert_eq!(other.user_agent.as_deref(), Some("Other device"));
+    assert!(other.last_seen_at >= other.created_at);
+}
+
+#[tokio::test]
+async fn should_revoke_another_session() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let token = signup_and_login(&app, &email).await;
+    let other_token = login_elsewhere(&app, &email).await;
+    let other_id = list_sessions(&app)
+        .await
+        .sessions
+        .into_iter()
+        .find(|session| !session.current)
+        .unwrap()
+        .id;
+
+    let response = app.delete_session(&other_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(verify_token_status(&app, &other_token).await, 401);
+    assert_eq!(verify_token_status(&app, &token).await, 200);
+    assert_eq!(list_sessions(&app).await.sessions.len(), 1);
+}
+
+#[tokio::test]
+async fn should_clear_cookie_when_revoking_current_session() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let token = signup_and_login(&app, &email).await;
+    let current_id = list_sessions(&app).await.sessions[0].id.clone();
+
+    let response = app.delete_session(&current_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(auth_token(&response).is_empty());
+
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+}
+
+#[tokio::test]
+async fn should_return_404_for_unknown_or_foreign_session() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let other_email = get_random_email();
+
+    app.post_signup(&serde_json::json!({
+        "email": other_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let other_token = login_elsewhere(&app, &other_email).await;
+    signup_and_login(&app, &email).await;
+
+    let foreign_id = validate_token(&other_token, &*app.key_ring.read().await)
+        .expect("Token should be valid")
+        .sid
+        .expect("No session id in token");
+    for id in ["not-a-uuid", "0b7a3f0e-3b6c-4a53-9f5e-0d8a9a7b1c2d", foreign_id.as_str()] {
+        let response = app.delete_session(id).await;
+        assert_eq!(response.status().as_u16(), 404, "Session '{}' should not be found", id);
+    }
+
+    assert_eq!(verify_token_status(&app, &other_token).await, 200);
+}
+
+#[tokio::test]
+async fn should_log_out_everywhere() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let token = signup_and_login(&app, &email).await;
+    let other_token = login_elsewhere(&app, &email).await;
+
+    let response = app.delete_sessions().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(auth_token(&response).is_empty());
+
+    assert_eq!(verify_token_status(&app, &token).await, 401);
+    assert_eq!(verify_token_status(&app, &other_token).await, 401);
+}
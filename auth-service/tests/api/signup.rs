@@ -1,5 +1,6 @@
 use crate::get_random_email::get_random_email;
-use crate::helpers::TestApp;
+use crate::helpers::{TestApp, BREACHED_PASSWORDS};
+use auth_service::domain::{Email, RECOVERY_CODE_COUNT};
 use auth_service::routes::{ErrorResponse, SignupResponse};
 
 // localhost:3000/signup
@@ -52,8 +53,34 @@ async fn signup_returns_created_status_when_inputs_are_valid() {
         test_case
     );
 
+    let response = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to UserBody");
+
+    assert_eq!(response.message, "User created successfully!".to_owned());
+    assert_eq!(
+        response.recovery_codes.map(|codes| codes.len()),
+        Some(RECOVERY_CODE_COUNT)
+    );
+}
+
+#[tokio::test]
+async fn signup_without_2fa_does_not_issue_recovery_codes() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let test_case = serde_json::json!({
+        "email": random_email,
+        "password": "test_Passw0rd!",
+        "requires2FA": false
+    });
+    let response = app.post_signup(&test_case).await;
+    assert_eq!(response.status().as_u16(), 201);
+
     let expected_response = SignupResponse {
         message: "User created successfully!".to_owned(),
+        recovery_codes: None,
     };
 
     assert_eq!(
@@ -65,17 +92,33 @@ async fn signup_returns_created_status_when_inputs_are_valid() {
     );
 }
 
+#[tokio::test]
+async fn signup_never_grants_roles() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "test_Passw0rd!",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let user = app
+        .user_store
+        .get_user(&Email::parse(&email).unwrap())
+        .await
+        .expect("User should exist");
+    assert!(user.roles.is_empty());
+}
+
 #[tokio::test]
 async fn should_return_400_if_invalid_input() {
     let app = TestApp::new().await;
-    let random_email = get_random_email();
 
     let test_cases = [
-        serde_json::json!({
-            "email": random_email,
-            "password": "pass",
-            "requires2FA": true
-        }),
         serde_json::json!({
             "email": "invalid_email",
             "password": "password123",
@@ -108,6 +151,63 @@ async fn should_return_400_if_invalid_input() {
     }
 }
 
+#[tokio::test]
+async fn should_return_400_with_reason_if_password_breaks_policy() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let test_cases = [
+        ("pass", "Password must be at least 8 characters long"),
+        (email.as_str(), "Password must not be your email address"),
+        (
+            "password1",
+            "Password is too easy to guess, avoid common passwords and words",
+        ),
+    ];
+    for (password, error) in test_cases {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": password,
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for {}", password);
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            error
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_return_400_if_password_is_breached() {
+    let app = TestApp::new().await;
+
+    for password in BREACHED_PASSWORDS {
+        let response = app
+            .post_signup(&serde_json::json!({
+                "email": get_random_email(),
+                "password": password,
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for {}", password);
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Password has appeared in a data breach, choose another".to_owned()
+        );
+    }
+}
+
 #[tokio::test]
 async fn should_return_409_if_email_already_exists() {
     let app = TestApp::new().await;
@@ -131,3 +231,55 @@ async fn should_return_409_if_email_already_exists() {
         "User already exists".to_owned()
     );
 }
+
+#[tokio::test]
+async fn should_treat_emails_differing_in_case_as_one_account() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": format!("  {}", random_email.to_uppercase()),
+            "password": "passworD123!",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": random_email,
+            "password": "passworD123!",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_for_disposable_email() {
+    let app = TestApp::new().await;
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": "someone@mailinator.com",
+            "password": "passworD123!",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Disposable email addresses are not allowed, use a permanent one"
+    );
+}
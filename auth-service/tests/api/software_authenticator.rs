@@ -0,0 +1,128 @@
+use auth_service::domain::COSE_ALG_ES256;
+use auth_service::routes::{PublicKeyCredentialCreationOptions, PublicKeyCredentialRequestOptions};
+use auth_service::utils::constants::{WEBAUTHN_ORIGIN, WEBAUTHN_RP_ID};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ciborium::value::Value;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_USER_VERIFIED: u8 = 0x04;
+const FLAG_ATTESTED_CREDENTIAL_DATA: u8 = 0x40;
+
+/// In-memory stand-in for a platform authenticator, producing the same
+/// payloads a browser would send back from `navigator.credentials`.
+pub struct SoftwareAuthenticator {
+    key: SigningKey,
+    credential_id: Vec<u8>,
+    pub sign_count: u32,
+    /// Whether assertions report that the user was verified, not just present.
+    pub verifies_user: bool,
+}
+
+impl SoftwareAuthenticator {
+    pub fn new() -> Self {
+        Self {
+            key: SigningKey::random(&mut OsRng),
+            credential_id: uuid::Uuid::new_v4().as_bytes().to_vec(),
+            sign_count: 0,
+            verifies_user: true,
+        }
+    }
+
+    pub fn create(&self, options: &PublicKeyCredentialCreationOptions) -> serde_json::Value {
+        assert_eq!(options.rp.id, *WEBAUTHN_RP_ID);
+        assert!(options.pub_key_cred_params.iter().any(|p| p.alg == COSE_ALG_ES256));
+
+        let client_data = client_data("webauthn.create", &options.challenge);
+
+        let point = self.key.verifying_key().to_encoded_point(false);
+        let cose_key = Value::Map(vec![
+            (Value::from(1), Value::from(2)),
+            (Value::from(3), Value::from(COSE_ALG_ES256)),
+            (Value::from(-1), Value::from(1)),
+            (Value::from(-2), Value::Bytes(point.x().unwrap().to_vec())),
+            (Value::from(-3), Value::Bytes(point.y().unwrap().to_vec())),
+        ]);
+        let mut auth_data = authenticator_data(
+            FLAG_USER_PRESENT | FLAG_ATTESTED_CREDENTIAL_DATA,
+            self.sign_count,
+        );
+        auth_data.extend_from_slice(&[0u8; 16]);
+        auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+        auth_data.extend_from_slice(&self.credential_id);
+        ciborium::into_writer(&cose_key, &mut auth_data).unwrap();
+
+        let attestation = Value::Map(vec![
+            (Value::from("fmt"), Value::from("none")),
+            (Value::from("attStmt"), Value::Map(vec![])),
+            (Value::from("authData"), Value::Bytes(auth_data)),
+        ]);
+        let mut attestation_object = Vec::new();
+        ciborium::into_writer(&attestation, &mut attestation_object).unwrap();
+
+        serde_json::json!({
+            "id": self.credential_id_base64(),
+            "rawId": self.credential_id_base64(),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                "attestationObject": URL_SAFE_NO_PAD.encode(attestation_object),
+            },
+        })
+    }
+
+    pub fn get(&mut self, options: &PublicKeyCredentialRequestOptions) -> serde_json::Value {
+        assert_eq!(options.rp_id, *WEBAUTHN_RP_ID);
+        assert!(options
+            .allow_credentials
+            .iter()
+            .any(|credential| credential.id == self.credential_id_base64()));
+
+        self.sign_count += 1;
+        let client_data = client_data("webauthn.get", &options.challenge);
+        let flags = if self.verifies_user {
+            FLAG_USER_PRESENT | FLAG_USER_VERIFIED
+        } else {
+            FLAG_USER_PRESENT
+        };
+        let auth_data = authenticator_data(flags, self.sign_count);
+
+        let mut signed = auth_data.clone();
+        signed.extend_from_slice(&Sha256::digest(&client_data));
+        let signature: Signature = self.key.sign(&signed);
+
+        serde_json::json!({
+            "id": self.credential_id_base64(),
+            "rawId": self.credential_id_base64(),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": URL_SAFE_NO_PAD.encode(client_data),
+                "authenticatorData": URL_SAFE_NO_PAD.encode(auth_data),
+                "signature": URL_SAFE_NO_PAD.encode(signature.to_der().as_bytes()),
+            },
+        })
+    }
+
+    fn credential_id_base64(&self) -> String {
+        URL_SAFE_NO_PAD.encode(&self.credential_id)
+    }
+}
+
+fn client_data(ceremony: &str, challenge: &str) -> Vec<u8> {
+    serde_json::json!({
+        "type": ceremony,
+        "challenge": challenge,
+        "origin": *WEBAUTHN_ORIGIN,
+    })
+    .to_string()
+    .into_bytes()
+}
+
+fn authenticator_data(flags: u8, sign_count: u32) -> Vec<u8> {
+    let mut data = Sha256::digest(WEBAUTHN_RP_ID.as_bytes()).to_vec();
+    data.push(flags);
+    data.extend_from_slice(&sign_count.to_be_bytes());
+    data
+}
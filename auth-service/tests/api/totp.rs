@@ -0,0 +1,176 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::domain::{Email, TotpCredential, TotpSecret, TwoFAMethod};
+use auth_service::routes::{TotpEnrollResponse, TwoFactorAuthResponse};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+const TOTP_STEP_SECONDS: u64 = 30;
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn enroll(app: &TestApp) -> TotpCredential {
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<TotpEnrollResponse>()
+        .await
+        .expect("Could not deserialize response body to TotpEnrollResponse");
+    TotpCredential::new(TotpSecret::parse(&body.secret).expect("Invalid TOTP secret"))
+}
+
+fn code_at(credential: &TotpCredential, email: &str, offset_steps: u64) -> String {
+    let now = chrono::Utc::now().timestamp() as u64;
+    credential
+        .code_at(&Email::parse(email).unwrap(), now + offset_steps * TOTP_STEP_SECONDS)
+        .unwrap()
+}
+
+// localhost:3000/2fa/totp/enroll
+#[tokio::test]
+async fn enroll_should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn enroll_should_return_otpauth_uri_and_qr_code() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<TotpEnrollResponse>()
+        .await
+        .expect("Could not deserialize response body to TotpEnrollResponse");
+    assert!(body.otpauth_uri.starts_with("otpauth://totp/"));
+    assert!(body.otpauth_uri.contains(&body.secret));
+
+    let png = STANDARD.decode(&body.qr_code_png).expect("QR code is not base64");
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+    // Enrollment only takes effect once confirmed.
+    let email = Email::parse(&random_email).unwrap();
+    let user_store = &app.user_store;
+    let user = user_store.get_user(&email).await.unwrap();
+    assert!(!user.requires_2fa);
+    assert_eq!(user.two_fa_method, TwoFAMethod::Email);
+}
+
+// localhost:3000/2fa/totp/confirm
+#[tokio::test]
+async fn confirm_should_return_400_if_not_enrolled() {
+    let app = TestApp::new().await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "123456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_incorrect_code() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let credential = enroll(&app).await;
+
+    let code = code_at(&credential, &random_email, 5);
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": code }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn confirmed_totp_is_required_at_login_and_rejects_replayed_codes() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let credential = enroll(&app).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": code_at(&credential, &random_email, 0) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Re-enrolling would replace the secret that is now in use.
+    let response = app.post_totp_enroll().await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    // The code used to confirm has been spent, so log in with the next one.
+    let code = code_at(&credential, &random_email, 1);
+    for expected_status in [200, 401] {
+        let response = app
+            .post_login(&serde_json::json!({
+                "email": random_email,
+                "password": "passworD123!",
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 206);
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id;
+
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": random_email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": code,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), expected_status);
+    }
+}
+
+// localhost:3000/2fa/method
+#[tokio::test]
+async fn method_should_return_400_if_totp_not_enrolled() {
+    let app = TestApp::new().await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app
+        .put_2fa_method(&serde_json::json!({ "requires2FA": true, "method": "totp" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn method_should_update_user_selection() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+
+    let response = app
+        .put_2fa_method(&serde_json::json!({ "requires2FA": true, "method": "email" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email = Email::parse(&random_email).unwrap();
+    let user_store = &app.user_store;
+    let user = user_store.get_user(&email).await.unwrap();
+    assert!(user.requires_2fa);
+    assert_eq!(user.two_fa_method, TwoFAMethod::Email);
+}
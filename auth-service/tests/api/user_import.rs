@@ -0,0 +1,329 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use auth_service::domain::{
+    Email, Permission, PasswordHashAlgorithm, Role, TotpCredential, TotpSecret, TwoFAMethod, User,
+    UserExport, USERS_READ_PERMISSION, USERS_WRITE_PERMISSION,
+};
+use auth_service::utils::constants::USER_EXPORT_PAGE_SIZE;
+use auth_service::routes::{SkippedUser, UserImportResponse};
+use pbkdf2::password_hash::{rand_core::OsRng, PasswordHasher, SaltString};
+
+const PASSWORD: &str = "passworD123!";
+
+async fn login_as_new_user(app: &TestApp, roles: Vec<Role>, permissions: Vec<Permission>) {
+    let email = get_random_email();
+    let mut user = User::new(email.clone(), PASSWORD.to_owned(), false);
+    user.roles = roles;
+    user.permissions = permissions;
+    app.user_store
+        .add_user(user)
+        .await
+        .expect("Failed to add user");
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn import(app: &TestApp, format: &str, body: String) -> UserImportResponse {
+    let response = app.post_admin_users_import(format, body).await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<UserImportResponse>()
+        .await
+        .expect("Could not deserialize response body to UserImportResponse")
+}
+
+async fn password_hash(app: &TestApp, email: &str) -> String {
+    let email = Email::parse(email).unwrap();
+    app.user_store
+        .get_user(&email)
+        .await
+        .expect("User should exist")
+        .password_hash
+        .as_ref()
+        .to_owned()
+}
+
+#[tokio::test]
+async fn should_import_bcrypt_user_who_is_rehashed_on_login() {
+    let app = TestApp::new().await;
+    login_as_new_user(&app, vec![Role::admin()], Vec::new()).await;
+
+    let email = get_random_email();
+    let body = serde_json::json!({
+        "version": 1,
+        "users": [{
+            "email": email,
+            "passwordAlgorithm": "bcrypt",
+            "passwordHash": bcrypt::hash(PASSWORD, 4).unwrap(),
+            "roles": ["premium"],
+        }]
+    });
+    let response = import(&app, "json", body.to_string()).await;
+    assert_eq!(response.imported, 1);
+    assert!(response.skipped.is_empty());
+    assert!(password_hash(&app, &email).await.starts_with("$2b$"));
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(password_hash(&app, &email).await.starts_with("$argon2id$"));
+
+    // The new hash still accepts the same password.
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_import_pbkdf2_user_from_csv() {
+    let app = TestApp::new().await;
+    login_as_new_user(&app, vec![Role::admin()], Vec::new()).await;
+
+    let email = get_random_email();
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = pbkdf2::Pbkdf2
+        .hash_password_customized(
+            PASSWORD.as_bytes(),
+            None,
+            None,
+            pbkdf2::Params {
+                rounds: 1_000,
+                output_length: 32,
+            },
+            &salt,
+        )
+        .unwrap();
+    let body = format!(
+        "version,email,passwordAlgorithm,passwordHash,requires2FA,twoFAMethod,totpSecret,roles,permissions,disabled\n\
+         1,{},pbkdf2,\"{}\",false,email,,,reports:export,false\n",
+        email, hash
+    );
+    let response = import(&app, "csv", body).await;
+    assert_eq!(response.imported, 1);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(password_hash(&app, &email).await.starts_with("$argon2id$"));
+}
+
+#[tokio::test]
+async fn should_skip_existing_and_invalid_users() {
+    let app = TestApp::new().await;
+    login_as_new_user(&app, vec![Role::admin()], Vec::new()).await;
+
+    let response = app.get_admin_users_export(&[("format", "json")]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let mut export = response
+        .json::<UserExport>()
+        .await
+        .expect("Could not deserialize response body to UserExport");
+    assert_eq!(export.users.len(), 1);
+    assert_eq!(export.users[0].password_algorithm, PasswordHashAlgorithm::Argon2);
+    let existing = export.users[0].email.clone();
+
+    let mut invalid = export.users[0].clone();
+    invalid.email = get_random_email();
+    invalid.password_hash = "$1$md5$notsupported".to_owned();
+    export.users.push(invalid.clone());
+
+    let response = import(&app, "json", serde_json::to_string(&export).unwrap()).await;
+    assert_eq!(response.imported, 0);
+    assert_eq!(
+        response.skipped,
+        [
+            SkippedUser {
+                email: existing,
+                reason: "user already exists".to_owned(),
+            },
+            SkippedUser {
+                email: invalid.email,
+                reason: "unsupported password hash".to_owned(),
+            },
+        ]
+    );
+}
+
+#[tokio::test]
+async fn should_round_trip_csv_export() {
+    let app = TestApp::new().await;
+    login_as_new_user(&app, vec![Role::admin()], Vec::new()).await;
+
+    let response = app.get_admin_users_export(&[("format", "csv")]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"].to_str().unwrap(),
+        "text/csv"
+    );
+    let body = response.text().await.expect("Failed to read response body");
+    assert!(body.starts_with("version,email,passwordAlgorithm,passwordHash,"));
+
+    let response = import(&app, "csv", body).await;
+    assert_eq!(response.imported, 0);
+    assert_eq!(response.skipped.len(), 1);
+}
+
+#[tokio::test]
+async fn should_return_400_for_malformed_or_unsupported_imports() {
+    let app = TestApp::new().await;
+    login_as_new_user(&app, vec![Role::admin()], Vec::new()).await;
+
+    let bodies = [
+        ("json", "not json".to_owned()),
+        ("json", serde_json::json!({"version": 2, "users": []}).to_string()),
+        ("csv", "version,email\n1,user@example.com\n".to_owned()),
+    ];
+    for (format, body) in bodies {
+        let response = app.post_admin_users_import(format, body.clone()).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for {} body: {}",
+            format,
+            body
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_return_403_without_users_write_permission() {
+    let app = TestApp::new().await;
+    login_as_new_user(
+        &app,
+        Vec::new(),
+        vec![Permission::parse(USERS_READ_PERMISSION).unwrap()],
+    )
+    .await;
+
+    assert_eq!(
+        app.get_admin_users_export(&[]).await.status().as_u16(),
+        403
+    );
+    let body = serde_json::json!({"version": 1, "users": []}).to_string();
+    assert_eq!(
+        app.post_admin_users_import("json", body).await.status().as_u16(),
+        403
+    );
+}
+
+#[tokio::test]
+async fn should_return_403_for_export_and_import_without_admin_role() {
+    let app = TestApp::new().await;
+    login_as_new_user(
+        &app,
+        Vec::new(),
+        vec![Permission::parse(USERS_WRITE_PERMISSION).unwrap()],
+    )
+    .await;
+
+    let email = get_random_email();
+    let body = serde_json::json!({
+        "version": 1,
+        "users": [{
+            "email": email,
+            "passwordAlgorithm": "argon2",
+            "passwordHash": "$argon2id$v=19$m=15000,t=2,p=1$c2FsdHNhbHQ$aGFzaGhhc2hoYXNo",
+            "roles": ["admin"],
+        }],
+    })
+    .to_string();
+    assert_eq!(
+        app.get_admin_users_export(&[]).await.status().as_u16(),
+        403
+    );
+    let response = app.post_admin_users_import("json", body).await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(app
+        .user_store
+        .get_user(&Email::parse(&email).unwrap())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn should_export_totp_secrets_only_when_asked() {
+    let app = TestApp::new().await;
+    login_as_new_user(&app, vec![Role::admin()], Vec::new()).await;
+
+    let email = get_random_email();
+    let secret = TotpSecret::generate();
+    let mut user = User::new(email.clone(), PASSWORD.to_owned(), true);
+    user.two_fa_method = TwoFAMethod::Totp;
+    user.totp = Some(TotpCredential {
+        confirmed: true,
+        ..TotpCredential::new(secret.clone())
+    });
+    app.user_store
+        .add_user(user)
+        .await
+        .expect("Failed to add user");
+
+    for (include, expected_secret, expected_method) in [
+        ("false", None, TwoFAMethod::Email),
+        ("true", Some(secret.as_ref().to_owned()), TwoFAMethod::Totp),
+    ] {
+        let response = app
+            .get_admin_users_export(&[("includeTotpSecrets", include)])
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+        let export = response
+            .json::<UserExport>()
+            .await
+            .expect("Could not deserialize response body to UserExport");
+        let user = export
+            .users
+            .into_iter()
+            .find(|user| user.email == email)
+            .expect("User should be exported");
+        assert_eq!(user.totp_secret, expected_secret);
+        assert_eq!(user.two_fa_method, expected_method);
+    }
+}
+
+#[tokio::test]
+async fn should_export_users_across_store_pages() {
+    let app = TestApp::new().await;
+    login_as_new_user(&app, vec![Role::admin()], Vec::new()).await;
+
+    let user = User::new(get_random_email(), PASSWORD.to_owned(), false);
+    for _ in 0..USER_EXPORT_PAGE_SIZE {
+        let user = User {
+            email: Email::parse(&get_random_email()).unwrap(),
+            ..user.clone()
+        };
+        app.user_store
+            .add_user(user)
+            .await
+            .expect("Failed to add user");
+    }
+
+    let response = app.get_admin_users_export(&[("format", "json")]).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let export = response
+        .json::<UserExport>()
+        .await
+        .expect("Could not deserialize response body to UserExport");
+    assert_eq!(export.users.len(), USER_EXPORT_PAGE_SIZE + 1);
+
+    let response = app.get_admin_users_export(&[("format", "csv")]).await;
+    let body = response.text().await.expect("Failed to read response body");
+    assert_eq!(body.lines().count(), USER_EXPORT_PAGE_SIZE + 2);
+    assert_eq!(body.lines().filter(|line| line.starts_with("version,")).count(), 1);
+}
@@ -1,10 +1,213 @@
+use crate::get_random_email::get_random_email;
 use crate::helpers::TestApp;
+use auth_service::domain::{Email, LoginAttemptId, TwoFACode, MAX_2FA_CODE_ATTEMPTS};
+use auth_service::routes::TwoFactorAuthResponse;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+async fn start_2fa_login(app: &TestApp, email: &str) -> (LoginAttemptId, TwoFACode) {
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "passworD123!",
+        "requires2FA": true
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+
+    app.two_fa_code_store
+        .get_code(&Email::parse(email).unwrap())
+        .await
+        .expect("No 2FA code stored for login attempt")
+}
 
 // localhost:3000/verify-2fa
 #[tokio::test]
-async fn verify_2fa_returns_ok_status() {
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let test_cases = [
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": "2fd8c5a6-7b8c-4c6e-9d6a-3b1b0d3c8f59",
+        }),
+        serde_json::json!({
+            "email": random_email,
+            "2FACode": "123456",
+        }),
+        serde_json::json!({
+            "loginAttemptId": "2fd8c5a6-7b8c-4c6e-9d6a-3b1b0d3c8f59",
+            "2FACode": "123456",
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_verify_2fa(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
     let app = TestApp::new().await;
+    let random_email = get_random_email();
+    let login_attempt_id = LoginAttemptId::default();
+
+    let test_cases = [
+        serde_json::json!({
+            "email": "invalid_email",
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": "123456",
+        }),
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": "invalid",
+            "2FACode": "123456",
+        }),
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": "12ab",
+        }),
+    ];
 
-    let response = app.post_verify_2fa().await;
+    for test_case in test_cases.iter() {
+        let response = app.post_verify_2fa(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_credentials() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    let (login_attempt_id, code) = start_2fa_login(&app, &random_email).await;
+    let wrong_code = if code.as_ref() == "000000" { "111111" } else { "000000" };
+
+    let test_cases = [
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": LoginAttemptId::default().as_ref(),
+            "2FACode": code.as_ref(),
+        }),
+        serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": wrong_code,
+        }),
+        serde_json::json!({
+            "email": get_random_email(),
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": code.as_ref(),
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_verify_2fa(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_return_200_if_correct_code() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    let (login_attempt_id, code) = start_2fa_login(&app, &random_email).await;
+
+    let body = serde_json::json!({
+        "email": random_email,
+        "loginAttemptId": login_attempt_id.as_ref(),
+        "2FACode": code.as_ref(),
+    });
+    let response = app.post_verify_2fa(&body).await;
     assert_eq!(response.status().as_u16(), 200);
-}
\ No newline at end of file
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+
+    // A code can only be used once.
+    let response = app.post_verify_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_drop_login_attempt_after_too_many_wrong_codes() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    let (login_attempt_id, code) = start_2fa_login(&app, &random_email).await;
+    let wrong_code = if code.as_ref() == "000000" { "111111" } else { "000000" };
+
+    for _ in 0..MAX_2FA_CODE_ATTEMPTS {
+        let response = app
+            .post_verify_2fa(&serde_json::json!({
+                "email": random_email,
+                "loginAttemptId": login_attempt_id.as_ref(),
+                "2FACode": wrong_code,
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_code_expired() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    let (login_attempt_id, code) = start_2fa_login(&app, &random_email).await;
+
+    let expired = chrono::Utc::now().timestamp() - 1;
+    app.two_fa_code_store
+        .add_code(
+            Email::parse(&random_email).unwrap(),
+            login_attempt_id.clone(),
+            code.clone(),
+            expired,
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": code.as_ref(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
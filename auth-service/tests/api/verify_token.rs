@@ -1,10 +1,64 @@
+use crate::get_random_email::get_random_email;
 use crate::helpers::TestApp;
+use auth_service::routes::VerifyTokenResponse;
+use auth_service::utils::constants::JWT_COOKIE_NAME;
 
 // localhost:3000/verify-token
 #[tokio::test]
-async fn verify_token_returns_ok_status() {
+async fn should_return_422_if_malformed_input() {
     let app = TestApp::new().await;
 
-    let response = app.post_verify_token().await;
+    let response = app.post_verify_token(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn should_return_200_valid_token() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    app.post_signup(&serde_json::json!({
+        "email": random_email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "passworD123!",
+        }))
+        .await;
+    let token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
     assert_eq!(response.status().as_u16(), 200);
-}
\ No newline at end of file
+    assert_eq!(
+        response
+            .json::<VerifyTokenResponse>()
+            .await
+            .expect("Could not deserialize response body to VerifyTokenResponse"),
+        VerifyTokenResponse {
+            sub: random_email,
+            client_id: None,
+            scope: None,
+        }
+    );
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_token() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": "invalid" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
@@ -0,0 +1,247 @@
+use crate::get_random_email::get_random_email;
+use crate::helpers::TestApp;
+use crate::software_authenticator::SoftwareAuthenticator;
+use auth_service::routes::{
+    AuthenticationOptionsResponse, PublicKeyCredentialRequestOptions, RegistrationOptionsResponse,
+    TwoFactorAuthResponse,
+};
+use auth_service::utils::constants::JWT_COOKIE_NAME;
+
+async fn signup_and_login(app: &TestApp, email: &str) {
+    app.post_signup(&serde_json::json!({
+        "email": email,
+        "password": "passworD123!",
+        "requires2FA": false
+    }))
+    .await;
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn register(app: &TestApp, authenticator: &SoftwareAuthenticator) -> reqwest::Response {
+    let response = app.post_webauthn_register_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+    let options = response
+        .json::<RegistrationOptionsResponse>()
+        .await
+        .expect("Could not deserialize response body to RegistrationOptionsResponse");
+
+    app.post_webauthn_register_finish(&authenticator.create(&options.public_key))
+        .await
+}
+
+async fn passwordless_login(
+    app: &TestApp,
+    email: &str,
+    authenticator: &mut SoftwareAuthenticator,
+) -> reqwest::Response {
+    let response = app
+        .post_webauthn_login_start(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let options = response
+        .json::<AuthenticationOptionsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuthenticationOptionsResponse");
+
+    app.post_webauthn_login_finish(&serde_json::json!({
+        "email": email,
+        "credential": authenticator.get(&options.public_key),
+    }))
+    .await
+}
+
+// localhost:3000/webauthn/register
+#[tokio::test]
+async fn register_should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_webauthn_register_start().await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn register_should_store_passkey() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let authenticator = SoftwareAuthenticator::new();
+
+    let response = register(&app, &authenticator).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // The same authenticator can't be registered twice.
+    let response = register(&app, &authenticator).await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn register_finish_should_return_401_without_challenge() {
+    let app = TestApp::new().await;
+    signup_and_login(&app, &get_random_email()).await;
+    let authenticator = SoftwareAuthenticator::new();
+
+    let response = app.post_webauthn_register_start().await;
+    let options = response
+        .json::<RegistrationOptionsResponse>()
+        .await
+        .expect("Could not deserialize response body to RegistrationOptionsResponse");
+    let credential = authenticator.create(&options.public_key);
+
+    let response = app.post_webauthn_register_finish(&credential).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // The challenge was consumed by the first attempt.
+    let response = app.post_webauthn_register_finish(&credential).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+// localhost:3000/webauthn/login
+async fn login_start_options(app: &TestApp, email: &str) -> PublicKeyCredentialRequestOptions {
+    let response = app
+        .post_webauthn_login_start(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    response
+        .json::<AuthenticationOptionsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuthenticationOptionsResponse")
+        .public_key
+}
+
+#[tokio::test]
+async fn login_start_should_not_reveal_whether_user_has_passkeys() {
+    let app = TestApp::new().await;
+    let without_passkeys = get_random_email();
+    signup_and_login(&app, &without_passkeys).await;
+    let with_passkey = get_random_email();
+    signup_and_login(&app, &with_passkey).await;
+    register(&app, &SoftwareAuthenticator::new()).await;
+
+    let unknown = login_start_options(&app, &get_random_email()).await;
+    let without_passkeys = login_start_options(&app, &without_passkeys).await;
+    let with_passkey = login_start_options(&app, &with_passkey).await;
+
+    for options in [&unknown, &without_passkeys, &with_passkey] {
+        assert_eq!(options.allow_credentials.len(), 1);
+        assert_eq!(options.user_verification, "required");
+    }
+    assert_eq!(
+        unknown.allow_credentials[0].id.len(),
+        with_passkey.allow_credentials[0].id.len()
+    );
+}
+
+#[tokio::test]
+async fn login_start_should_return_same_credentials_for_unknown_email() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+
+    let first = login_start_options(&app, &random_email).await;
+    let second = login_start_options(&app, &random_email).await;
+    assert_eq!(first.allow_credentials, second.allow_credentials);
+    assert_ne!(first.challenge, second.challenge);
+}
+
+#[tokio::test]
+async fn passwordless_login_should_set_auth_cookie() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator).await;
+
+    let response = passwordless_login(&app, &random_email, &mut authenticator).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    assert!(!auth_cookie.value().is_empty());
+}
+
+#[tokio::test]
+async fn passwordless_login_should_reject_cloned_authenticator() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator).await;
+
+    let response = passwordless_login(&app, &random_email, &mut authenticator).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Replaying the same counter value looks like a cloned authenticator.
+    authenticator.sign_count -= 1;
+    let response = passwordless_login(&app, &random_email, &mut authenticator).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn passwordless_login_should_require_user_verification() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator).await;
+
+    authenticator.verifies_user = false;
+    let response = passwordless_login(&app, &random_email, &mut authenticator).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+// localhost:3000/webauthn/verify-2fa
+#[tokio::test]
+async fn passkey_can_be_used_as_second_factor() {
+    let app = TestApp::new().await;
+    let random_email = get_random_email();
+    signup_and_login(&app, &random_email).await;
+    let mut authenticator = SoftwareAuthenticator::new();
+    register(&app, &authenticator).await;
+    // After the password, presence is enough for the second factor.
+    authenticator.verifies_user = false;
+
+    let response = app
+        .put_2fa_method(&serde_json::json!({ "requires2FA": true, "method": "webauthn" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": random_email,
+            "password": "passworD123!",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    let options = body.public_key.expect("No passkey challenge in 2FA response");
+
+    let response = app
+        .post_webauthn_verify_2fa(&serde_json::json!({
+            "email": random_email,
+            "loginAttemptId": body.login_attempt_id,
+            "credential": authenticator.get(&options),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn method_should_return_400_if_no_passkey_registered() {
+    let app = TestApp::new().await;
+    signup_and_login(&app, &get_random_email()).await;
+
+    let response = app
+        .put_2fa_method(&serde_json::json!({ "requires2FA": true, "method": "webauthn" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
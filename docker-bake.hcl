@@ -3,7 +3,8 @@ group "default" {
 }
 
 target "app-service" {
-  context = "./app-service"
+  context = "."
+  dockerfile = "app-service/Dockerfile"
   tags = []
 }
 